@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+/// Per-file quality search: each file is encoded at a few distances until
+/// its ssimulacra2 score against the source lands in a target window.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdaptiveQuality {
+    /// Lowest acceptable ssimulacra2 score; around 70 is high quality, 90
+    /// visually lossless.
+    pub min_score: f32,
+    /// Scores above this waste bits; the search tries a larger distance.
+    pub max_score: f32,
+    /// The distances the search stays within.
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Encodes per file, at most.
+    pub max_attempts: u8,
+}
+
+impl Default for AdaptiveQuality {
+    fn default() -> Self {
+        Self { min_score: 80.0, max_score: 85.0, min_distance: 0.3, max_distance: 6.0, max_attempts: 4 }
+    }
+}
+
+impl AdaptiveQuality {
+    /// Checks the window and bounds make sense.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=100.0).contains(&self.min_score) || !(self.min_score..=100.0).contains(&self.max_score) {
+            return Err("The adaptive quality score window must lie within 0–100, low end first".to_string());
+        }
+        if !(0.01..=25.0).contains(&self.min_distance) || !(self.min_distance..=25.0).contains(&self.max_distance) {
+            return Err("The adaptive quality distance range must lie within 0.01–25, low end first".to_string());
+        }
+        if !(1..=10).contains(&self.max_attempts) {
+            return Err("Adaptive quality needs 1 to 10 attempts per file".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// One encode the search tried.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Candidate {
+    pub distance: f32,
+    pub score: f32,
+}
+
+/// Closer distances than this count as the same.
+const SAME_DISTANCE: f32 = 0.01;
+
+/// How far the distance moves while the target is only on one side.
+const STEP: f32 = 1.6;
+
+/// The search for one file. Scores fall as the distance grows, so it steps
+/// towards the window until it has candidates on both sides, then bisects.
+pub struct DistanceSearch<'a> {
+    target: &'a AdaptiveQuality,
+    initial: f32,
+    tried: Vec<Candidate>,
+}
+
+impl<'a> DistanceSearch<'a> {
+    /// A search starting at `initial`, clamped to the target's bounds.
+    pub fn new(target: &'a AdaptiveQuality, initial: f32) -> Self {
+        Self { target, initial: initial.clamp(target.min_distance, target.max_distance), tried: Vec::new() }
+    }
+
+    pub fn record(&mut self, candidate: Candidate) {
+        self.tried.push(candidate);
+    }
+
+    pub fn attempts(&self) -> usize {
+        self.tried.len()
+    }
+
+    /// The distance to try next, or `None` once a score is in the window,
+    /// the attempts are used up or there is nowhere left to go.
+    pub fn next_distance(&self) -> Option<f32> {
+        let target = self.target;
+        let Some(last) = self.tried.last() else {
+            return Some(self.initial);
+        };
+        if self.tried.len() >= target.max_attempts as usize || self.in_window(last) {
+            return None;
+        }
+
+        // The largest distance that scored too well and the smallest that
+        // scored too badly bracket the window
+        let too_good = self.tried.iter().filter(|c| c.score > target.max_score).map(|c| c.distance).reduce(f32::max);
+        let too_bad = self.tried.iter().filter(|c| c.score < target.min_score).map(|c| c.distance).reduce(f32::min);
+        let next = match (too_good, too_bad) {
+            (Some(low), Some(high)) => (low * high).sqrt(),
+            (Some(low), None) => (low * STEP).min(target.max_distance),
+            (None, Some(high)) => (high / STEP).max(target.min_distance),
+            (None, None) => return None,
+        };
+        let tried_already = self.tried.iter().any(|c| (c.distance - next).abs() < SAME_DISTANCE);
+        (!tried_already).then_some(next)
+    }
+
+    /// The candidate to keep: the largest distance in the window, or else
+    /// the largest still scoring at least `min_score`, or else the best
+    /// scoring one.
+    pub fn best(&self) -> Option<Candidate> {
+        let largest = |a: &&Candidate, b: &&Candidate| a.distance.total_cmp(&b.distance);
+        self.tried
+            .iter()
+            .filter(|c| self.in_window(c))
+            .max_by(largest)
+            .or_else(|| self.tried.iter().filter(|c| c.score >= self.target.min_score).max_by(largest))
+            .or_else(|| self.tried.iter().max_by(|a, b| a.score.total_cmp(&b.score)))
+            .copied()
+    }
+
+    fn in_window(&self, candidate: &Candidate) -> bool {
+        (self.target.min_score..=self.target.max_score).contains(&candidate.score)
+    }
+}
+
+/// The score in ssimulacra2's output: the last number it prints.
+pub fn parse_ssimulacra2_score(output: &str) -> Option<f32> {
+    output.split_whitespace().rev().find_map(|word| word.parse::<f32>().ok())
+}
@@ -0,0 +1,716 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Cursor};
+use std::path::Path;
+
+use gif::{DisposalMethod, Encoder, Frame, Repeat};
+use image::codecs::png::PngDecoder;
+use image::{AnimationDecoder, RgbaImage};
+
+/// Pixels with less alpha than this become the GIF's transparent color.
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// Pixels sampled across all frames to build the palette.
+const PALETTE_SAMPLES: usize = 1 << 18;
+
+/// Browsers play GIF delays below 20 ms as 100 ms, so shorter ones are
+/// raised to this (in GIF's 10 ms units).
+const MIN_DELAY_CS: u16 = 2;
+
+/// The longest frame delay an override may set; APNG stores delays as a
+/// 16-bit fraction of a second.
+pub const MAX_FRAME_DELAY_MS: u32 = 65535;
+
+/// Timing forced onto animated outputs instead of the source's own.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AnimationTiming {
+    /// Every frame's delay, 1–65535 ms.
+    pub frame_delay_ms: Option<u32>,
+    /// How many times the animation plays; 0 is forever.
+    pub loop_count: Option<u16>,
+}
+
+impl AnimationTiming {
+    pub fn is_set(&self) -> bool {
+        self.frame_delay_ms.is_some() || self.loop_count.is_some()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self.frame_delay_ms {
+            Some(delay) if !(1..=MAX_FRAME_DELAY_MS).contains(&delay) => {
+                Err(format!("Frame delay must be between 1 and {} ms", MAX_FRAME_DELAY_MS))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Frame count and timing of an animation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationInfo {
+    pub frames: usize,
+    pub delays_ms: Vec<u32>,
+    /// How many times it plays; 0 is forever.
+    pub plays: u32,
+}
+
+impl AnimationInfo {
+    /// E.g. `42 frames @ 33 ms, loop ∞`.
+    pub fn describe(&self) -> String {
+        format!("{} frames @ {}", self.frames, self.describe_timing())
+    }
+
+    /// E.g. `33 ms, loop ∞` or `20–100 ms, plays 3×`.
+    pub fn describe_timing(&self) -> String {
+        let min = self.delays_ms.iter().min().copied().unwrap_or(0);
+        let max = self.delays_ms.iter().max().copied().unwrap_or(0);
+        let delay = if min == max { format!("{} ms", min) } else { format!("{}–{} ms", min, max) };
+        let plays = match self.plays {
+            0 => "loop ∞".to_string(),
+            1 => "plays once".to_string(),
+            plays => format!("plays {}×", plays),
+        };
+        format!("{}, {}", delay, plays)
+    }
+
+    fn same_timing(&self, other: &AnimationInfo) -> bool {
+        self.delays_ms == other.delays_ms && self.plays == other.plays
+    }
+
+    /// A log line for an animation written with `timing`, noting when it
+    /// replaced different source timing.
+    pub fn describe_override(&self, source: &AnimationInfo) -> String {
+        if self.same_timing(source) {
+            self.describe()
+        } else {
+            format!("{} (overridden; the source had {})", self.describe(), source.describe_timing())
+        }
+    }
+}
+
+/// How a GIF is written.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GifOptions {
+    /// Palette size, 2–256, including the transparent color if needed.
+    pub max_colors: u16,
+    /// Floyd–Steinberg dithering; smoother gradients, larger files.
+    pub dither: bool,
+    pub timing: AnimationTiming,
+}
+
+/// What [`write_gif`] produced.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GifSummary {
+    /// The GIF as written.
+    pub written: AnimationInfo,
+    /// The source's own timing, before any override.
+    pub source: AnimationInfo,
+    /// Some frame delay wasn't a multiple of 10 ms, or was too short for
+    /// browsers to honor, and was adjusted.
+    pub delays_adjusted: bool,
+}
+
+/// A frame read from the PNG djxl wrote, with its delay in milliseconds.
+struct SourceFrame {
+    image: RgbaImage,
+    delay_ms: f64,
+}
+
+/// Writes the image in `png` (an APNG for animations, which djxl writes for
+/// animated JXLs) to `output` as a GIF. A still image gives a single frame.
+///
+/// All frames share one palette, so colors don't flicker between frames.
+/// Delays are rounded to 10 ms keeping the total duration, and the APNG's
+/// play count carries over as the GIF's loop count unless `options.timing`
+/// overrides them. `on_frame(done, total)` is called after each frame.
+pub fn write_gif(
+    png: &Path,
+    output: &Path,
+    options: &GifOptions,
+    mut on_frame: impl FnMut(usize, usize),
+) -> Result<GifSummary, String> {
+    let data = std::fs::read(png).map_err(|e| format!("Failed to read decoded image: {}", e))?;
+    let (mut frames, plays) = read_frames(&data).map_err(|e| format!("Failed to read decoded image: {}", e))?;
+    let source = frames_info(&frames, plays);
+    if let (Some(delay), true) = (options.timing.frame_delay_ms, frames.len() > 1) {
+        frames.iter_mut().for_each(|frame| frame.delay_ms = f64::from(delay));
+    }
+    let plays = options.timing.loop_count.map_or(plays, u32::from);
+    let (width, height) = frames[0].image.dimensions();
+    let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+        return Err(format!("{}×{} is too large for a GIF (65535 pixels at most)", width, height));
+    };
+
+    let has_transparency = frames
+        .iter()
+        .any(|frame| frame.image.pixels().any(|p| p.0[3] < ALPHA_THRESHOLD));
+    let colors = usize::from(options.max_colors.clamp(2, 256)) - usize::from(has_transparency);
+    let palette = median_cut(&frames, colors.max(1));
+    let transparent = has_transparency.then_some(palette.len() as u8);
+    let mut palette_bytes: Vec<u8> = palette.iter().flatten().copied().collect();
+    if has_transparency {
+        palette_bytes.extend([0, 0, 0]);
+    }
+
+    let (delays, delays_adjusted) = gif_delays(&frames);
+
+    let file = File::create(output).map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+    let write_error = |e: gif::EncodingError| format!("Failed to write {}: {}", output.display(), e);
+    let mut encoder = Encoder::new(BufWriter::new(file), width, height, &palette_bytes).map_err(write_error)?;
+    if frames.len() > 1 {
+        // APNG counts plays, GIF counts repeats; one play needs no extension
+        match plays {
+            0 => encoder.set_repeat(Repeat::Infinite).map_err(write_error)?,
+            1 => {}
+            plays => encoder
+                .set_repeat(Repeat::Finite(u16::try_from(plays - 1).unwrap_or(u16::MAX)))
+                .map_err(write_error)?,
+        }
+    }
+
+    let mut lookup = PaletteLookup::new(&palette);
+    for (idx, (frame, delay)) in frames.iter().zip(&delays).enumerate() {
+        let indices = map_frame(&frame.image, &mut lookup, transparent, options.dither);
+        let frame = Frame {
+            width,
+            height,
+            delay: *delay,
+            // Every frame is a full composited canvas, so nothing of the
+            // previous one may show through its transparent pixels
+            dispose: DisposalMethod::Background,
+            transparent,
+            buffer: Cow::Owned(indices),
+            ..Default::default()
+        };
+        encoder.write_frame(&frame).map_err(write_error)?;
+        on_frame(idx + 1, frames.len());
+    }
+
+    let written = AnimationInfo {
+        frames: frames.len(),
+        delays_ms: delays.iter().map(|&cs| u32::from(cs) * 10).collect(),
+        plays,
+    };
+    Ok(GifSummary { written, source, delays_adjusted })
+}
+
+fn frames_info(frames: &[SourceFrame], plays: u32) -> AnimationInfo {
+    AnimationInfo {
+        frames: frames.len(),
+        delays_ms: frames.iter().map(|frame| frame.delay_ms.round() as u32).collect(),
+        plays,
+    }
+}
+
+/// The frames of a PNG or APNG, and the APNG's play count (0 is forever).
+fn read_frames(data: &[u8]) -> image::ImageResult<(Vec<SourceFrame>, u32)> {
+    let decoder = PngDecoder::new(Cursor::new(data))?;
+    if !decoder.is_apng()? {
+        let image = image::load_from_memory_with_format(data, image::ImageFormat::Png)?.to_rgba8();
+        return Ok((vec![SourceFrame { image, delay_ms: 0.0 }], 1));
+    }
+
+    let frames = decoder
+        .apng()?
+        .into_frames()
+        .collect_frames()?
+        .into_iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay_ms = f64::from(numer) / f64::from(denom.max(1));
+            SourceFrame { image: frame.into_buffer(), delay_ms }
+        })
+        .collect();
+    Ok((frames, apng_plays(data).unwrap_or(0)))
+}
+
+/// `num_plays` from the APNG's `acTL` chunk.
+fn apng_plays(data: &[u8]) -> Option<u32> {
+    let mut pos = 8;
+    while let Some(header) = data.get(pos..pos + 8) {
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        if &header[4..8] == b"acTL" {
+            let plays = data.get(pos + 12..pos + 16)?;
+            return Some(u32::from_be_bytes([plays[0], plays[1], plays[2], plays[3]]));
+        }
+        if &header[4..8] == b"IDAT" {
+            return None;
+        }
+        pos += 12 + len;
+    }
+    None
+}
+
+/// Frame delays in GIF's 10 ms units. Each frame ends at its source end
+/// time rounded, so rounding errors don't add up over long animations.
+fn gif_delays(frames: &[SourceFrame]) -> (Vec<u16>, bool) {
+    if frames.len() == 1 {
+        return (vec![0], false);
+    }
+    let mut delays = Vec::with_capacity(frames.len());
+    let mut adjusted = false;
+    let (mut source_end, mut gif_end) = (0.0, 0u64);
+    for frame in frames {
+        source_end += frame.delay_ms / 10.0;
+        let end = (source_end.round() as u64).max(gif_end + u64::from(MIN_DELAY_CS));
+        let delay = u16::try_from(end - gif_end).unwrap_or(u16::MAX);
+        adjusted |= f64::from(delay) * 10.0 != frame.delay_ms;
+        delays.push(delay);
+        gif_end = end;
+    }
+    (delays, adjusted)
+}
+
+/// Up to `colors` colors representing the opaque pixels of all frames, by
+/// repeatedly splitting the box of colors with the widest channel range at
+/// its median.
+fn median_cut(frames: &[SourceFrame], colors: usize) -> Vec<[u8; 3]> {
+    let total: usize = frames.iter().map(|frame| frame.image.len() / 4).sum();
+    let step = total.div_ceil(PALETTE_SAMPLES).max(1);
+    let samples: Vec<[u8; 3]> = frames
+        .iter()
+        .flat_map(|frame| frame.image.pixels())
+        .step_by(step)
+        .filter(|p| p.0[3] >= ALPHA_THRESHOLD)
+        .map(|p| [p.0[0], p.0[1], p.0[2]])
+        .collect();
+    if samples.is_empty() {
+        return vec![[0, 0, 0]];
+    }
+
+    let range = |colors: &[[u8; 3]]| -> (usize, u8) {
+        (0..3)
+            .map(|channel| {
+                let (min, max) = colors
+                    .iter()
+                    .fold((u8::MAX, 0), |(min, max), c| (min.min(c[channel]), max.max(c[channel])));
+                (channel, max - min)
+            })
+            .max_by_key(|&(_, range)| range)
+            .unwrap_or((0, 0))
+    };
+
+    let mut boxes = vec![samples];
+    while boxes.len() < colors {
+        let Some((idx, channel)) = boxes
+            .iter()
+            .enumerate()
+            .map(|(idx, colors)| (idx, range(colors)))
+            .filter(|(_, (_, range))| *range > 0)
+            .max_by_key(|(idx, (_, range))| (*range, boxes[*idx].len()))
+            .map(|(idx, (channel, _))| (idx, channel))
+        else {
+            break;
+        };
+        let mut colors = boxes.swap_remove(idx);
+        colors.sort_unstable_by_key(|c| c[channel]);
+        let upper = colors.split_off(colors.len() / 2);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .map(|colors| {
+            let mut sum = [0u64; 3];
+            for c in colors {
+                for channel in 0..3 {
+                    sum[channel] += u64::from(c[channel]);
+                }
+            }
+            sum.map(|s| (s / colors.len() as u64) as u8)
+        })
+        .collect()
+}
+
+/// Nearest palette entries, computed on first use for each 6-bit-per-channel
+/// cell.
+struct PaletteLookup<'a> {
+    palette: &'a [[u8; 3]],
+    cells: Vec<u16>,
+}
+
+impl<'a> PaletteLookup<'a> {
+    const UNSET: u16 = u16::MAX;
+
+    fn new(palette: &'a [[u8; 3]]) -> Self {
+        Self { palette, cells: vec![Self::UNSET; 1 << 18] }
+    }
+
+    fn nearest(&mut self, color: [i32; 3]) -> u8 {
+        let [r, g, b] = color.map(|c| c.clamp(0, 255) as usize >> 2);
+        let cell = (r << 12) | (g << 6) | b;
+        if self.cells[cell] == Self::UNSET {
+            // The cell's center stands in for every color in it
+            let center = [r, g, b].map(|c| (c << 2) as i32 + 2);
+            let (idx, _) = self
+                .palette
+                .iter()
+                .enumerate()
+                .map(|(idx, p)| {
+                    let d: i32 = (0..3).map(|i| (i32::from(p[i]) - center[i]).pow(2)).sum();
+                    (idx, d)
+                })
+                .min_by_key(|&(_, d)| d)
+                .unwrap_or((0, 0));
+            self.cells[cell] = idx as u16;
+        }
+        self.cells[cell] as u8
+    }
+}
+
+/// Palette indices for one frame, optionally with Floyd–Steinberg dithering.
+fn map_frame(image: &RgbaImage, lookup: &mut PaletteLookup, transparent: Option<u8>, dither: bool) -> Vec<u8> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut indices = Vec::with_capacity(width * height);
+    // Accumulated error for this row and the next, in 1/16ths
+    let mut errors = vec![[0i32; 3]; width + 2];
+    let mut next_errors = vec![[0i32; 3]; width + 2];
+
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = image.get_pixel(x as u32, y as u32).0;
+            if let Some(transparent) = transparent.filter(|_| pixel[3] < ALPHA_THRESHOLD) {
+                indices.push(transparent);
+                continue;
+            }
+            let color = [0, 1, 2].map(|i| i32::from(pixel[i]) + errors[x + 1][i] / 16);
+            let idx = lookup.nearest(color);
+            indices.push(idx);
+
+            if dither {
+                let chosen = lookup.palette[usize::from(idx)];
+                for i in 0..3 {
+                    let error = color[i].clamp(0, 255) - i32::from(chosen[i]);
+                    errors[x + 2][i] += error * 7;
+                    next_errors[x][i] += error * 3;
+                    next_errors[x + 1][i] += error * 5;
+                    next_errors[x + 2][i] += error;
+                }
+            }
+        }
+        std::mem::swap(&mut errors, &mut next_errors);
+        next_errors.iter_mut().for_each(|e| *e = [0; 3]);
+    }
+    indices
+}
+
+/// The number of frames in an animated GIF or APNG; `None` for stills and
+/// other formats. Only headers are read and nothing is kept: a PNG up to
+/// its image data, a GIF block by block.
+pub fn frame_count(mut reader: impl BufRead) -> Option<usize> {
+    let mut signature = [0u8; 8];
+    reader.read_exact(&mut signature[..6]).ok()?;
+    let frames = if &signature[..6] == b"GIF87a" || &signature[..6] == b"GIF89a" {
+        gif_frame_count(&mut reader)
+    } else {
+        reader.read_exact(&mut signature[6..]).ok()?;
+        if &signature != b"\x89PNG\r\n\x1a\n" {
+            return None;
+        }
+        apng_frame_count(&mut reader)
+    };
+    frames.ok().filter(|&frames| frames > 1)
+}
+
+/// The frame count an APNG's acTL chunk declares; 0 for a plain PNG.
+fn apng_frame_count(reader: &mut impl BufRead) -> io::Result<usize> {
+    loop {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        match &header[4..] {
+            b"acTL" => {
+                let mut frames = [0u8; 4];
+                reader.read_exact(&mut frames)?;
+                return Ok(u32::from_be_bytes(frames) as usize);
+            }
+            // acTL has to come before the image data
+            b"IDAT" | b"IEND" => return Ok(0),
+            _ => {
+                let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+                skip(reader, u64::from(len) + 4)?;
+            }
+        }
+    }
+}
+
+/// The number of image descriptors in a GIF.
+fn gif_frame_count(reader: &mut impl BufRead) -> io::Result<usize> {
+    let mut screen = [0u8; 7];
+    reader.read_exact(&mut screen)?;
+    skip_color_table(reader, screen[4])?;
+
+    let mut frames = 0;
+    loop {
+        match read_byte(reader)? {
+            0x3B => return Ok(frames),
+            0x21 => {
+                read_byte(reader)?; // label
+                skip_sub_blocks(reader)?;
+            }
+            0x2C => {
+                frames += 1;
+                let mut descriptor = [0u8; 9];
+                reader.read_exact(&mut descriptor)?;
+                skip_color_table(reader, descriptor[8])?;
+                read_byte(reader)?; // LZW minimum code size
+                skip_sub_blocks(reader)?;
+            }
+            other => {
+                let message = format!("Unexpected GIF block 0x{:02X}", other);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+        }
+    }
+}
+
+fn read_byte(reader: &mut impl BufRead) -> io::Result<u8> {
+    let mut byte = [0u8];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// Skips the color table a GIF block's `flags` say follows it.
+fn skip_color_table(reader: &mut impl BufRead, flags: u8) -> io::Result<()> {
+    if flags & 0x80 != 0 {
+        skip(reader, 3 << ((flags & 0x07) + 1))?;
+    }
+    Ok(())
+}
+
+/// Skips GIF sub-blocks up to and including the zero length that ends them.
+fn skip_sub_blocks(reader: &mut impl BufRead) -> io::Result<()> {
+    loop {
+        match read_byte(reader)? {
+            0 => return Ok(()),
+            len => skip(reader, u64::from(len))?,
+        }
+    }
+}
+
+/// Moves past `count` bytes without copying them out of the reader's buffer.
+fn skip(reader: &mut impl BufRead, mut count: u64) -> io::Result<()> {
+    while count > 0 {
+        let available = reader.fill_buf()?.len();
+        if available == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let step = available.min(usize::try_from(count).unwrap_or(usize::MAX));
+        reader.consume(step);
+        count -= step as u64;
+    }
+    Ok(())
+}
+
+/// Reads a frame number from a line of tool output such as
+/// `Encoding frame 12/500`, `frame 12 of 500` or `Frame #12`, with the total
+/// when the line has one.
+pub fn parse_frame_line(line: &str) -> Option<(usize, Option<usize>)> {
+    let lower = line.to_lowercase();
+    let rest = &lower[lower.find("frame")? + "frame".len()..];
+    let rest = rest.trim_start_matches([' ', '#', ':']);
+    let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    let frame = rest[..digits].parse().ok()?;
+    let rest = rest[digits..].trim_start();
+    let total = rest
+        .strip_prefix('/')
+        .or_else(|| rest.strip_prefix("of"))
+        .map(str::trim_start)
+        .and_then(|rest| {
+            let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            rest[..digits].parse().ok()
+        });
+    Some((frame, total))
+}
+
+/// An animation with its timing replaced.
+#[derive(Clone, Debug)]
+pub struct Retimed {
+    pub data: Vec<u8>,
+    pub before: AnimationInfo,
+    pub after: AnimationInfo,
+}
+
+/// Applies `timing` to an animated GIF or APNG by rewriting its frame
+/// delays and loop count, leaving the image data alone. `None` when `data`
+/// isn't an animation.
+pub fn retime(data: &[u8], timing: &AnimationTiming) -> Result<Option<Retimed>, String> {
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        retime_gif(data, timing)
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        retime_apng(data, timing)
+    } else {
+        Ok(None)
+    }
+}
+
+fn retime_apng(data: &[u8], timing: &AnimationTiming) -> Result<Option<Retimed>, String> {
+    let truncated = || "Truncated PNG".to_string();
+    let mut out = data[..8].to_vec();
+    let (mut before_delays, mut after_delays) = (Vec::new(), Vec::new());
+    let (mut before_plays, mut after_plays) = (None, 0);
+    let mut pos = 8;
+    while pos < data.len() {
+        let header = data.get(pos..pos + 8).ok_or_else(truncated)?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let chunk = data.get(pos..pos + 12 + len).ok_or_else(truncated)?;
+        let mut body = chunk[4..8 + len].to_vec();
+
+        match &body[..4] {
+            b"acTL" if len >= 8 => {
+                let plays = u32::from_be_bytes([body[8], body[9], body[10], body[11]]);
+                before_plays = Some(plays);
+                after_plays = timing.loop_count.map_or(plays, u32::from);
+                body[8..12].copy_from_slice(&after_plays.to_be_bytes());
+            }
+            b"fcTL" if len >= 26 => {
+                let numer = u16::from_be_bytes([body[24], body[25]]);
+                let denom = match u16::from_be_bytes([body[26], body[27]]) {
+                    0 => 100,
+                    denom => denom,
+                };
+                let delay = (f64::from(numer) * 1000.0 / f64::from(denom)).round() as u32;
+                before_delays.push(delay);
+                if let Some(delay) = timing.frame_delay_ms {
+                    let delay = delay.min(MAX_FRAME_DELAY_MS) as u16;
+                    body[24..26].copy_from_slice(&delay.to_be_bytes());
+                    body[26..28].copy_from_slice(&1000u16.to_be_bytes());
+                }
+                after_delays.push(timing.frame_delay_ms.unwrap_or(delay));
+            }
+            _ => {}
+        }
+
+        out.extend(&chunk[..4]);
+        out.extend(&body);
+        out.extend(crc32fast::hash(&body).to_be_bytes());
+        pos += 12 + len;
+    }
+
+    let Some(before_plays) = before_plays.filter(|_| before_delays.len() > 1) else {
+        return Ok(None);
+    };
+    let frames = before_delays.len();
+    Ok(Some(Retimed {
+        data: out,
+        before: AnimationInfo { frames, delays_ms: before_delays, plays: before_plays },
+        after: AnimationInfo { frames, delays_ms: after_delays, plays: after_plays },
+    }))
+}
+
+fn retime_gif(data: &[u8], timing: &AnimationTiming) -> Result<Option<Retimed>, String> {
+    let truncated = || "Truncated GIF".to_string();
+    let byte = |pos: usize| data.get(pos).copied().ok_or_else(truncated);
+    // Sub-blocks run until a zero length; returns the position after it
+    let skip_sub_blocks = |mut pos: usize| -> Result<usize, String> {
+        loop {
+            let len = usize::from(byte(pos)?);
+            pos += 1 + len;
+            if len == 0 {
+                return Ok(pos);
+            }
+        }
+    };
+
+    let flags = byte(10)?;
+    let mut pos = 13;
+    if flags & 0x80 != 0 {
+        pos += 3 << ((flags & 0x07) + 1);
+    }
+    let mut out = data.get(..pos).ok_or_else(truncated)?.to_vec();
+    let delay_cs = timing.frame_delay_ms.map(|ms| ((ms + 5) / 10).clamp(u32::from(MIN_DELAY_CS), 65535) as u16);
+
+    // GIF stores repeats after the first play; 0 is forever. Written here
+    // for a loop count override, and the source's own block dropped below.
+    let loop_block = |plays: u16| -> Vec<u8> {
+        let mut block = vec![0x21, 0xFF, 11];
+        block.extend(b"NETSCAPE2.0");
+        block.extend([3, 1]);
+        block.extend(plays.saturating_sub(1).to_le_bytes());
+        block.push(0);
+        block
+    };
+    match timing.loop_count {
+        Some(1) | None => {}
+        Some(plays) => out.extend(loop_block(plays)),
+    }
+
+    let (mut before_delays, mut after_delays) = (Vec::new(), Vec::new());
+    let mut source_repeats = None;
+    let mut pending_delay = None;
+    loop {
+        match byte(pos)? {
+            0x3B => {
+                out.push(0x3B);
+                break;
+            }
+            0x21 if byte(pos + 1)? == 0xF9 => {
+                // Graphic control extension: the next image's delay
+                let end = skip_sub_blocks(pos + 2)?;
+                let mut block = data[pos..end].to_vec();
+                if block.len() >= 8 {
+                    pending_delay = Some(u16::from_le_bytes([block[4], block[5]]));
+                    if let Some(delay) = delay_cs {
+                        block[4..6].copy_from_slice(&delay.to_le_bytes());
+                    }
+                }
+                out.extend(block);
+                pos = end;
+            }
+            0x21 if byte(pos + 1)? == 0xFF && data.get(pos + 3..pos + 14) == Some(b"NETSCAPE2.0") => {
+                let end = skip_sub_blocks(pos + 2)?;
+                if let Some(&[3, 1, lo, hi]) = data.get(pos + 14..pos + 18) {
+                    source_repeats = Some(u16::from_le_bytes([lo, hi]));
+                }
+                if timing.loop_count.is_none() {
+                    out.extend(&data[pos..end]);
+                }
+                pos = end;
+            }
+            0x21 => {
+                let end = skip_sub_blocks(pos + 2)?;
+                out.extend(&data[pos..end]);
+                pos = end;
+            }
+            0x2C => {
+                let source_delay = pending_delay.take();
+                if let (None, Some(delay)) = (source_delay, delay_cs) {
+                    // No control block to rewrite, so add one
+                    out.extend([0x21, 0xF9, 4, 0]);
+                    out.extend(delay.to_le_bytes());
+                    out.extend([0, 0]);
+                }
+                let source_delay = u32::from(source_delay.unwrap_or(0)) * 10;
+                before_delays.push(source_delay);
+                after_delays.push(delay_cs.map_or(source_delay, |cs| u32::from(cs) * 10));
+
+                let flags = byte(pos + 9)?;
+                let mut end = pos + 10;
+                if flags & 0x80 != 0 {
+                    end += 3 << ((flags & 0x07) + 1);
+                }
+                // LZW minimum code size, then the image data
+                let end = skip_sub_blocks(end + 1)?;
+                out.extend(data.get(pos..end).ok_or_else(truncated)?);
+                pos = end;
+            }
+            other => return Err(format!("Unexpected GIF block 0x{:02X} at byte {}", other, pos)),
+        }
+    }
+
+    if before_delays.len() < 2 {
+        return Ok(None);
+    }
+    // No loop block plays once; otherwise repeats + 1, with 0 forever
+    let before_plays = source_repeats.map_or(1, |repeats| if repeats == 0 { 0 } else { u32::from(repeats) + 1 });
+    let frames = before_delays.len();
+    Ok(Some(Retimed {
+        data: out,
+        before: AnimationInfo { frames, delays_ms: before_delays, plays: before_plays },
+        after: AnimationInfo {
+            frames,
+            delays_ms: after_delays,
+            plays: timing.loop_count.map_or(before_plays, u32::from),
+        },
+    }))
+}
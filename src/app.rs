@@ -1,32 +1,166 @@
-use std::path::PathBuf;
-use std::sync::mpsc::{channel, Receiver};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use chrono::{DateTime, Local};
 use egui::{Color32, RichText, Slider, ScrollArea};
 
-use crate::engine::ConversionEngine;
-use crate::types::{ConversionSettings, DecodeSettings, DecodeItem, LogEntry, OutputFormat, ProgressMessage};
+use jxl_converter::adaptive::AdaptiveQuality;
+use jxl_converter::animation::MAX_FRAME_DELAY_MS;
+use jxl_converter::breakdown::{losing_animation, BatchBreakdown};
+use jxl_converter::command::{describe_changes, join_args, parse_cjxl_command};
+use jxl_converter::compare::{compare_files, CompareTools, Comparison, HEATMAP_GAIN};
+use jxl_converter::conflicts::{check_settings, Conflict, Control, Severity};
+use jxl_converter::download::url_of;
+use jxl_converter::flatten::jxl_is_animated;
+use jxl_converter::engine::{is_jxl_file, is_supported_image, SUPPORTED_IMAGE_EXTENSIONS};
+use jxl_converter::journal::{find_unfinished, JournalBatch, Resume, UnfinishedBatch};
+use jxl_converter::filelist::{import_list, write_list, ImportMessage, ListEntry, ListTarget};
+use jxl_converter::paste::{parse_path_list, save_clipboard_image};
+use jxl_converter::paths::{check_output_dir, expand_path, path_key, plan_output_path, OutputDirStatus, OutputPlan};
+use jxl_converter::process::affinity_supported;
+use jxl_converter::script::{write_encode_script, Shell};
+use jxl_converter::tools::DiscoveredTools;
+use jxl_converter::summary::{format_table, BatchSummary};
+use jxl_converter::sidecar::{SidecarSettings, SIDECAR_FILE_NAME};
+use jxl_converter::verify::Verification;
+use jxl_converter::types::{
+    distance_from_quality, format_env_overrides, parse_env_overrides, OverwritePolicy, DEFAULT_BATCH_HOOK_TIMEOUT_SECS,
+    DEFAULT_CONFIRM_BATCH_BYTES, DEFAULT_CONFIRM_BATCH_FILES,
+};
+use jxl_converter::{
+    ConversionEngine, ConversionSettings, DecodeItem, DecodeSettings, OutputFormat, ProgressMessage, ToolSettings,
+};
+
+use crate::config::{AppConfig, Profile};
+use crate::power::{PowerMonitor, PowerSettings, PowerState};
+use crate::reveal::{open_with_default_app, reveal_in_file_manager};
+use crate::stats::{group_thousands, BatchKind, LifetimeStats};
+use crate::schedule::{arm_timer, format_countdown, resolve_start_time};
+use crate::throttle::{os_idle_time, Throttle, ThrottleSettings, ThrottleState};
+
+/// How often user idle time is checked while a batch runs.
+const THROTTLE_SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+/// Why the EXR format is greyed out.
+const EXR_UNSUPPORTED: &str = "Needs a djxl built with OpenEXR";
 
 pub struct JxlConverterApp {
+    /// cjxl/djxl found at startup or by the last re-scan.
+    tools: DiscoveredTools,
     engine: ConversionEngine,
+    config: AppConfig,
     
     // Encode tab
     settings: ConversionSettings,
     input_paths: Vec<PathBuf>,
+    selected_inputs: HashSet<PathBuf>,
+    selection_anchor: Option<PathBuf>,
+    /// Clipboard images saved to the temp folder, deleted once neither the
+    /// list nor a running batch refers to them.
+    pasted_images: HashSet<PathBuf>,
+    /// The pasted images the running batch reads.
+    batch_pasted_images: Vec<PathBuf>,
+    /// Inputs the last batch left when its output budget ran out.
+    remaining_inputs: Vec<PathBuf>,
+    command_preview: Option<CommandPreview>,
     
     // Decode tab
     decode_settings: DecodeSettings,
     decode_items: Vec<DecodeItem>,
     
     // Shared conversion state
+    tool_settings: ToolSettings,
     is_converting: bool,
     cancel_flag: Arc<AtomicBool>,
     progress_rx: Option<Receiver<ProgressMessage>>,
     current_progress: usize,
     total_files: usize,
     current_file: String,
+    /// Sub-progress through the current file's frames, and their count if
+    /// known.
+    current_frame: Option<(usize, Option<usize>)>,
+    pause_flag: Arc<AtomicBool>,
+    /// Reconstructed JPEGs this batch: identical, different, no original.
+    verification_counts: (usize, usize, usize),
+    
+    // Power source
+    power_monitor: PowerMonitor,
+    power_state: PowerState,
+    power_settings: PowerSettings,
+    
+    // Backing off while the user is working
+    throttle: Throttle,
+    throttle_settings: ThrottleSettings,
+    file_delay_ms: Arc<AtomicU64>,
+    /// The running batch's cool-down between files, and when the current
+    /// one ends.
+    batch_cool_down: Duration,
+    cooling_until: Option<Instant>,
+    last_input: Instant,
+    throttle_sampled: Instant,
+    
+    // Scheduled start
+    schedule_enabled: bool,
+    schedule_hour: u32,
+    schedule_minute: u32,
+    schedule_tomorrow: bool,
+    armed_start: Option<ArmedStart>,
+    
+    // List import running in the background
+    import_rx: Option<(ListTarget, Receiver<ImportMessage>)>,
+    import_progress: (usize, usize),
+    export_dialog_open: bool,
+    export_expanded: bool,
+    
+    // Profiles
+    profile_manager_open: bool,
+    new_profile_name: String,
+    renaming_profile: Option<(String, String)>,
+    window_title: String,
+    settings_window_open: bool,
+    env_text: String,
+    /// `settings.extra_args` as typed, which may not parse while being edited.
+    extra_args_text: String,
+    /// The text in the "Paste Command" dialog while it is open.
+    paste_command: Option<String>,
+    /// The "Test on one file" run, while its panel is open.
+    test_run: Option<TestRun>,
+    compare: CompareState,
+    /// Journals of batches a crash cut short, offered for resuming one at
+    /// a time.
+    unfinished_batches: Vec<UnfinishedBatch>,
+    /// The batch being resumed, handed to the next batch's engine.
+    pending_resume: Option<Resume>,
+    /// Soft conflicts waiting for "Start Anyway" before the batch starts.
+    pending_conflicts: Option<Vec<Conflict>>,
+    /// Animated JXLs about to be decoded to a still format, waiting for a
+    /// fix or "Decode Anyway".
+    pending_animation_loss: Option<Vec<PathBuf>>,
+    /// Whether each JXL in the decode list is animated, probed once.
+    animated_jxls: HashMap<PathBuf, bool>,
+    /// A batch over the size thresholds, waiting for "Start" or "Dry run
+    /// instead".
+    pending_large_batch: Option<BatchBreakdown>,
+    /// Whether the batch being run only lists its outputs.
+    dry_run: bool,
+    /// The running batch's totals, tallied from its messages.
+    batch_summary: BatchSummary,
+    batch_kind: BatchKind,
+    /// All-time counters, saved as each batch ends.
+    stats: LifetimeStats,
+    about_open: bool,
+    /// A missing output folder waiting for "Create and Start".
+    pending_create_dir: Option<PathBuf>,
+    /// The output Directory fields as typed, before expansion.
+    output_dir_text: String,
+    decode_output_dir_text: String,
+    /// Whether the next batch runs the configured hooks; unticking it skips
+    /// them without losing the commands.
+    run_hooks: bool,
     
     // UI state
     active_tab: AppTab,
@@ -34,242 +168,2996 @@ pub struct JxlConverterApp {
     scroll_to_bottom: bool,
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Debug)]
+pub enum LogEntry {
+    Info(String),
+    Success(String, Option<PathBuf>),
+    Error(String),
+    Warning(String),
+    /// A line of a table, shown in a monospace font.
+    Table(String),
+}
+
+/// The first JPEG and the first other supported image in `inputs`, looking
+/// inside folders only as far as needed to find both.
+fn find_preview_samples(inputs: &[PathBuf], recursive: bool) -> (Option<PathBuf>, Option<PathBuf>) {
+    fn consider(samples: &mut (Option<PathBuf>, Option<PathBuf>), path: &Path) {
+        if !is_supported_image(path) {
+            return;
+        }
+        let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let slot = if ext == "jpg" || ext == "jpeg" { &mut samples.0 } else { &mut samples.1 };
+        if slot.is_none() {
+            *slot = Some(path.to_path_buf());
+        }
+    }
+
+    let mut samples = (None, None);
+
+    // Loose files first; they are cheap to check
+    for input in inputs.iter().filter(|p| !p.is_dir() && url_of(p).is_none()) {
+        consider(&mut samples, input);
+    }
+    for folder in inputs.iter().filter(|p| p.is_dir()) {
+        let walker = walkdir::WalkDir::new(folder).max_depth(if recursive { usize::MAX } else { 1 });
+        for entry in walker.into_iter().filter_map(|e| e.ok()) {
+            if samples.0.is_some() && samples.1.is_some() {
+                return samples;
+            }
+            if entry.file_type().is_file() {
+                consider(&mut samples, entry.path());
+            }
+        }
+    }
+
+    samples
+}
+
+/// A warning sign after a control involved in any of `conflicts`, red for
+/// hard ones, explaining them on hover.
+fn conflict_marker(ui: &mut egui::Ui, conflicts: &[Conflict], control: Control) {
+    let relevant: Vec<&Conflict> = conflicts.iter().filter(|c| c.controls.contains(&control)).collect();
+    let Some(worst) = relevant.iter().map(|c| c.severity).max() else {
+        return;
+    };
+    let color = if worst == Severity::Hard { Color32::LIGHT_RED } else { Color32::YELLOW };
+    let text = relevant.iter().map(|c| c.message).collect::<Vec<_>>().join("\n");
+    ui.label(RichText::new("⚠").color(color)).on_hover_text(text);
+}
+
+/// What happens to existing outputs, and whether a cancelled batch removes
+/// what it wrote. Transactional batches can't overwrite, since a rolled
+/// back file couldn't be brought back, so ticking it switches Overwrite to
+/// Skip.
+fn overwrite_controls(ui: &mut egui::Ui, id: &str, overwrite: &mut OverwritePolicy, transactional: &mut bool) {
+    if *transactional && *overwrite == OverwritePolicy::Overwrite {
+        *overwrite = OverwritePolicy::Skip;
+    }
+    ui.horizontal(|ui| {
+        ui.label("Existing outputs:");
+        egui::ComboBox::from_id_salt(id).selected_text(overwrite.name()).show_ui(ui, |ui| {
+            for &policy in OverwritePolicy::all() {
+                let allowed = !(*transactional && policy == OverwritePolicy::Overwrite);
+                ui.add_enabled_ui(allowed, |ui| ui.selectable_value(overwrite, policy, policy.name()));
+            }
+        });
+    });
+    ui.checkbox(transactional, "Roll back if cancelled")
+        .on_hover_text("Delete every file and folder the batch created when it is cancelled, leaving the output folder as it was");
+}
+
+/// The output Directory row. Typed text is expanded (`~`, environment
+/// variables) into `dir` as it changes; when `dir` changes elsewhere, e.g.
+/// through Browse or a profile, the text follows unless it is being edited.
+/// Folders that can't be used are outlined and explained in red.
+fn output_dir_field(ui: &mut egui::Ui, text: &mut String, dir: &mut PathBuf) {
+    let status = check_output_dir(dir);
+    let invalid = match &status {
+        OutputDirStatus::Invalid(message) if !text.trim().is_empty() => Some(message.clone()),
+        _ => None,
+    };
+
+    ui.horizontal(|ui| {
+        ui.label("Directory:");
+        let response = ui.add(
+            egui::TextEdit::singleline(text)
+                .hint_text("Paste or type a folder; ~ and $VARS are expanded")
+                .desired_width(ui.available_width() - 80.0),
+        );
+        if response.changed() {
+            *dir = expand_path(text);
+        } else if !response.has_focus() && expand_path(text) != *dir {
+            *text = dir.display().to_string();
+        }
+        if invalid.is_some() {
+            ui.painter().rect_stroke(response.rect, 2.0, egui::Stroke::new(1.5, Color32::from_rgb(255, 100, 100)));
+        }
+
+        if ui.button("Browse").clicked() {
+            if let Some(folder) = rfd::FileDialog::new()
+                .set_title("Select Output Directory")
+                .pick_folder()
+            {
+                *text = folder.display().to_string();
+                *dir = folder;
+            }
+        }
+    });
+
+    if let Some(message) = invalid {
+        ui.label(RichText::new(message).small().color(Color32::from_rgb(255, 100, 100)));
+    } else if status == OutputDirStatus::Missing {
+        ui.label(RichText::new("Doesn't exist yet; it can be created when the batch starts").small().color(Color32::GRAY));
+    }
+}
+
+/// The adaptive quality toggle and, while it is on, the score window and
+/// distance range it searches within.
+fn adaptive_quality_controls(ui: &mut egui::Ui, adaptive: &mut Option<AdaptiveQuality>, lossless: bool) {
+    ui.add_enabled_ui(!lossless, |ui| {
+        let mut enabled = adaptive.is_some();
+        if ui
+            .checkbox(&mut enabled, "Adaptive quality")
+            .on_hover_text("Encode each file at a few distances and keep the smallest that scores in the window (needs ssimulacra2)")
+            .changed()
+        {
+            *adaptive = enabled.then(AdaptiveQuality::default);
+        }
+        let Some(adaptive) = adaptive else { return };
+        ui.horizontal(|ui| {
+            ui.label("ssimulacra2 score:");
+            ui.add(egui::DragValue::new(&mut adaptive.min_score).range(0.0..=100.0).speed(0.5));
+            ui.label("to");
+            ui.add(egui::DragValue::new(&mut adaptive.max_score).range(0.0..=100.0).speed(0.5));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Distance:");
+            ui.add(egui::DragValue::new(&mut adaptive.min_distance).range(0.01..=25.0).speed(0.05));
+            ui.label("to");
+            ui.add(egui::DragValue::new(&mut adaptive.max_distance).range(0.01..=25.0).speed(0.05));
+            ui.label("Attempts:");
+            ui.add(egui::DragValue::new(&mut adaptive.max_attempts).range(1..=10));
+        });
+    });
+}
+
+/// Frame delay and loop count overrides for animations, each off unless
+/// ticked.
+fn animation_timing_controls(ui: &mut egui::Ui, frame_delay_ms: &mut Option<u32>, loop_count: &mut Option<u16>) {
+    ui.horizontal(|ui| {
+        let mut override_delay = frame_delay_ms.is_some();
+        if ui
+            .checkbox(&mut override_delay, "Frame delay:")
+            .on_hover_text("Give every frame of an animation this delay")
+            .changed()
+        {
+            *frame_delay_ms = override_delay.then_some(33);
+        }
+        if let Some(delay) = frame_delay_ms {
+            ui.add(egui::DragValue::new(delay).range(1..=MAX_FRAME_DELAY_MS).suffix(" ms"));
+            ui.label(RichText::new(format!("≈ {:.1} fps", 1000.0 / f64::from(*delay))).small().color(Color32::GRAY));
+        }
+    });
+    ui.horizontal(|ui| {
+        let mut override_loops = loop_count.is_some();
+        if ui
+            .checkbox(&mut override_loops, "Plays:")
+            .on_hover_text("How many times an animation plays; 0 loops forever")
+            .changed()
+        {
+            *loop_count = override_loops.then_some(0);
+        }
+        if let Some(plays) = loop_count {
+            ui.add(egui::DragValue::new(plays).custom_formatter(|n, _| {
+                if n == 0.0 { "∞".to_string() } else { format!("{}×", n) }
+            }));
+        }
+    });
+}
+
+/// A preview command in a read-only box, with a button to copy it.
+fn show_preview_command(ui: &mut egui::Ui, preview: &PreviewCommand) {
+    let text = match &preview.note {
+        Some(note) => format!("{}  # {}", preview.command, note),
+        None => preview.command.clone(),
+    };
+    ui.horizontal(|ui| {
+        if ui.small_button("📋").on_hover_text("Copy command").clicked() {
+            ui.ctx().copy_text(preview.command.clone());
+        }
+        ui.add(
+            egui::TextEdit::multiline(&mut text.as_str())
+                .font(egui::TextStyle::Monospace)
+                .desired_width(f32::INFINITY)
+                .desired_rows(1)
+                .interactive(false)
+                .frame(true),
+        );
+    });
+}
+
+/// The command preview for the current inputs and settings, rebuilt only
+/// when one of them changes.
+struct CommandPreview {
+    inputs: Vec<PathBuf>,
+    recursive: bool,
+    settings: ConversionSettings,
+    tool_settings: ToolSettings,
+    /// The first JPEG and the first other image among the inputs.
+    samples: (Option<PathBuf>, Option<PathBuf>),
+    jpeg: PreviewCommand,
+    other: PreviewCommand,
+}
+
+/// One line of the command preview.
+struct PreviewCommand {
+    /// What the copy button puts on the clipboard.
+    command: String,
+    /// Shown after the command but not copied, e.g. the distance a quality
+    /// maps to.
+    note: Option<String>,
+}
+
+/// A one-file trial of the current encode settings. It converts into its
+/// own temp folder, so the configured output folder is never touched.
+struct TestRun {
+    input: PathBuf,
+    dir: PathBuf,
+    command: PreviewCommand,
+    started: Instant,
+    cancel_flag: Arc<AtomicBool>,
+    progress_rx: Option<Receiver<ProgressMessage>>,
+    outcome: Option<Result<TestOutcome, String>>,
+}
+
+struct TestOutcome {
+    output: PathBuf,
+    input_bytes: u64,
+    output_bytes: u64,
+    duration: Duration,
+}
+
+impl TestRun {
+    /// Deletes the temp folder. A run still going is cancelled and deletes
+    /// whatever it wrote once it stops.
+    fn discard(self) {
+        self.cancel_flag.store(true, Ordering::Relaxed);
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// The Compare tab: two files and what comparing them found.
+#[derive(Default)]
+struct CompareState {
+    a: Option<PathBuf>,
+    b: Option<PathBuf>,
+    run: Option<CompareRun>,
+    result: Option<Result<Comparison, String>>,
+    heatmap: Option<egui::TextureHandle>,
+    show_heatmap: bool,
+}
+
+impl CompareState {
+    /// Fills A, then B; later files replace B.
+    fn add(&mut self, path: PathBuf) {
+        if self.a.is_none() {
+            self.a = Some(path);
+        } else {
+            self.b = Some(path);
+        }
+    }
+}
+
+/// A comparison running on its own thread. JXLs are decoded into a temp
+/// folder, which the thread deletes when it is done.
+struct CompareRun {
+    cancel_flag: Arc<AtomicBool>,
+    rx: Receiver<CompareMessage>,
+    /// Steps done, the total and what is happening now.
+    step: (usize, usize, String),
+}
+
+enum CompareMessage {
+    Step(usize, usize, String),
+    Done(Result<Comparison, String>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
 enum AppTab {
     Encode,
     Decode,
+    Compare,
+}
+
+/// A batch waiting for its scheduled start time.
+struct ArmedStart {
+    at: DateTime<Local>,
+    tab: AppTab,
+    cancel: Arc<AtomicBool>,
+    /// Set once the start was held back because of battery power.
+    deferred: bool,
 }
 
 impl JxlConverterApp {
-    pub fn new() -> Self {
-        let engine = ConversionEngine::new();
+    pub fn new(ctx: &egui::Context) -> Self {
+        let (config, config_error) = AppConfig::load();
+        let (stats, stats_error) = LifetimeStats::load();
+        let tools = DiscoveredTools::discover_with(&config.tools);
+        let engine = ConversionEngine::from_tools(&tools);
+        let power_monitor = PowerMonitor::start(ctx.clone());
         
         let mut app = Self {
+            tools,
             engine,
-            settings: ConversionSettings::default(),
+            settings: config.settings.clone(),
             input_paths: Vec::new(),
-            decode_settings: DecodeSettings::default(),
+            selected_inputs: HashSet::new(),
+            selection_anchor: None,
+            pasted_images: HashSet::new(),
+            batch_pasted_images: Vec::new(),
+            remaining_inputs: Vec::new(),
+            command_preview: None,
+            decode_settings: config.decode_settings.clone(),
             decode_items: Vec::new(),
+            tool_settings: config.tools.clone(),
             is_converting: false,
             cancel_flag: Arc::new(AtomicBool::new(false)),
             progress_rx: None,
             current_progress: 0,
             total_files: 0,
+            verification_counts: (0, 0, 0),
             current_file: String::new(),
+            current_frame: None,
+            pause_flag: Arc::new(AtomicBool::new(false)),
+            power_state: power_monitor.state(),
+            power_monitor,
+            power_settings: config.power.clone(),
+            throttle: Throttle::default(),
+            throttle_settings: config.throttle.clone(),
+            file_delay_ms: Arc::new(AtomicU64::new(0)),
+            batch_cool_down: Duration::ZERO,
+            cooling_until: None,
+            last_input: Instant::now(),
+            throttle_sampled: Instant::now(),
+            schedule_enabled: false,
+            schedule_hour: 23,
+            schedule_minute: 0,
+            schedule_tomorrow: false,
+            armed_start: None,
+            import_rx: None,
+            import_progress: (0, 0),
+            export_dialog_open: false,
+            export_expanded: false,
+            config,
+            profile_manager_open: false,
+            new_profile_name: String::new(),
+            renaming_profile: None,
+            window_title: String::new(),
+            settings_window_open: false,
+            env_text: String::new(),
+            extra_args_text: String::new(),
+            paste_command: None,
+            test_run: None,
+            compare: CompareState::default(),
+            unfinished_batches: AppConfig::journal_dir().map(|dir| find_unfinished(&dir)).unwrap_or_default(),
+            pending_resume: None,
+            pending_conflicts: None,
+            pending_animation_loss: None,
+            animated_jxls: HashMap::new(),
+            pending_large_batch: None,
+            dry_run: false,
+            batch_summary: BatchSummary::default(),
+            batch_kind: BatchKind::Encode,
+            stats,
+            about_open: false,
+            pending_create_dir: None,
+            // Filled in from the settings by output_dir_field
+            output_dir_text: String::new(),
+            decode_output_dir_text: String::new(),
+            run_hooks: true,
             active_tab: AppTab::Encode,
             log_entries: Vec::new(),
             scroll_to_bottom: false,
         };
 
-        // Check if cjxl is available
-        if let Some(error) = app.engine.get_error() {
-            app.log_entries.push(LogEntry::Error(error));
-        } else {
-            app.log_entries.push(LogEntry::Info("cjxl found and ready.".to_string()));
-        }
+        app.env_text = format_env_overrides(&app.tool_settings.env_overrides);
 
-        // Check if djxl is available
-        if let Some(error) = app.engine.get_decode_error() {
+        if let Some(error) = config_error {
             app.log_entries.push(LogEntry::Error(error));
-        } else {
-            app.log_entries.push(LogEntry::Info("djxl found and ready.".to_string()));
+        }
+        if let Some(error) = stats_error {
+            app.log_entries.push(LogEntry::Warning(error));
         }
 
+        app.log_tool_status();
         app
     }
 
-    fn add_log(&mut self, entry: LogEntry) {
-        self.log_entries.push(entry);
-        self.scroll_to_bottom = true;
-    }
-
-    fn start_conversion(&mut self) {
-        if !self.engine.is_available() {
-            self.add_log(LogEntry::Error("cjxl is not available.".to_string()));
-            return;
+    fn log_tool_status(&mut self) {
+        let chosen = [
+            ("cjxl", &self.tool_settings.cjxl_path, &self.tools.cjxl),
+            ("djxl", &self.tool_settings.djxl_path, &self.tools.djxl),
+        ];
+        let mut missing = Vec::new();
+        for (name, chosen, selected) in chosen {
+            if let Some(chosen) = chosen {
+                if selected.as_ref().map(|tool| &tool.path) != Some(chosen) {
+                    missing.push(format!("The selected {} ({}) is no longer available.", name, chosen.display()));
+                }
+            }
+        }
+        for warning in missing {
+            self.add_log(LogEntry::Warning(warning));
         }
 
-        if self.input_paths.is_empty() {
-            self.add_log(LogEntry::Warning("No input files or folders selected.".to_string()));
-            return;
+        match (&self.tools.cjxl, self.engine.get_error()) {
+            (Some(cjxl), None) => self.add_log(LogEntry::Info(format!("cjxl found and ready: {}", cjxl.describe()))),
+            (_, error) => self.add_log(LogEntry::Error(error.unwrap_or_default())),
         }
+        match (&self.tools.djxl, self.engine.get_decode_error()) {
+            (Some(djxl), None) => self.add_log(LogEntry::Info(format!("djxl found and ready: {}", djxl.describe()))),
+            (_, error) => self.add_log(LogEntry::Error(error.unwrap_or_default())),
+        }
+    }
 
-        if self.settings.output_dir.as_os_str().is_empty() {
-            self.add_log(LogEntry::Warning("No output directory selected.".to_string()));
+    /// Switches to the cjxl/djxl chosen in the settings, among those already found.
+    fn apply_tool_choice(&mut self) {
+        self.tools.select(&self.tool_settings);
+        self.engine = ConversionEngine::from_tools(&self.tools);
+    }
+
+    fn add_tool_location(&mut self) {
+        let Some(folder) = rfd::FileDialog::new()
+            .set_title("Select Folder Containing cjxl/djxl")
+            .pick_folder()
+        else {
             return;
+        };
+
+        if !self.tool_settings.extra_tool_paths.contains(&folder) {
+            self.tool_settings.extra_tool_paths.push(folder.clone());
         }
+        self.tools = DiscoveredTools::discover_with(&self.tool_settings);
+        self.engine = ConversionEngine::from_tools(&self.tools);
 
-        self.is_converting = true;
-        self.cancel_flag.store(false, Ordering::Relaxed);
-        self.current_progress = 0;
-        self.total_files = 0;
-        self.current_file.clear();
+        let found: Vec<String> = self
+            .tools
+            .cjxl_candidates
+            .iter()
+            .chain(&self.tools.djxl_candidates)
+            .filter(|tool| tool.path.starts_with(&folder))
+            .map(|tool| {
+                let name = tool.path.file_name().unwrap_or_default().to_string_lossy();
+                format!("{} {}", name, tool.describe())
+            })
+            .collect();
+        if found.is_empty() {
+            self.add_log(LogEntry::Warning(format!("No cjxl or djxl found in {}", folder.display())));
+        } else {
+            self.add_log(LogEntry::Info(format!("Added tool location: {}", found.join(", "))));
+        }
+    }
 
-        let (tx, rx) = channel();
-        self.progress_rx = Some(rx);
+    /// Looks for cjxl and djxl again, e.g. after installing them while the
+    /// app is open. Running batches keep the tools they started with.
+    fn rescan_tools(&mut self) {
+        self.tools = DiscoveredTools::discover_with(&self.tool_settings);
+        self.engine = ConversionEngine::from_tools(&self.tools);
+        self.add_log(LogEntry::Info("Re-scanned for tools.".to_string()));
+        self.log_tool_status();
+    }
 
-        let engine = ConversionEngine::new();
-        let input_paths = self.input_paths.clone();
-        let settings = self.settings.clone();
-        let cancel_flag = Arc::clone(&self.cancel_flag);
+    fn add_log(&mut self, entry: LogEntry) {
+        self.log_entries.push(entry);
+        self.scroll_to_bottom = true;
+    }
 
-        thread::spawn(move || {
-            engine.convert_batch(input_paths, settings, tx, cancel_flag);
-        });
+    fn save_config(&mut self) {
+        self.config.settings = self.settings.clone();
+        self.config.decode_settings = self.decode_settings.clone();
+        self.config.tools = self.tool_settings.clone();
+        self.config.power = self.power_settings.clone();
+        self.config.throttle = self.throttle_settings.clone();
+        if let Err(e) = self.config.save() {
+            self.add_log(LogEntry::Error(e));
+        }
+    }
 
-        self.add_log(LogEntry::Info("Conversion started...".to_string()));
+    fn active_profile(&self) -> Option<&Profile> {
+        self.config
+            .active_profile
+            .as_deref()
+            .and_then(|name| self.config.profile(name))
     }
 
-    fn cancel_conversion(&mut self) {
-        self.cancel_flag.store(true, Ordering::Relaxed);
-        self.add_log(LogEntry::Warning("Cancelling conversion...".to_string()));
+    /// Whether the current settings differ from the active profile's snapshot.
+    fn is_profile_dirty(&self) -> bool {
+        self.active_profile().is_some_and(|profile| {
+            profile.settings != self.settings || profile.decode_settings != self.decode_settings
+        })
     }
 
-    fn start_decode(&mut self) {
-        if !self.engine.is_decode_available() {
-            self.add_log(LogEntry::Error("djxl is not available.".to_string()));
+    fn apply_profile(&mut self, name: &str) {
+        let Some(profile) = self.config.profile(name).cloned() else {
             return;
-        }
+        };
 
-        if self.decode_items.is_empty() {
-            self.add_log(LogEntry::Warning("No JXL files selected.".to_string()));
-            return;
-        }
+        self.settings = profile.settings;
+        self.decode_settings = profile.decode_settings;
+        self.config.active_profile = Some(profile.name.clone());
+        self.save_config();
+        self.add_log(LogEntry::Info(format!("Switched to profile \"{}\".", profile.name)));
+    }
 
-        if self.decode_settings.output_dir.as_os_str().is_empty() {
-            self.add_log(LogEntry::Warning("No output directory selected.".to_string()));
+    fn save_active_profile(&mut self) {
+        let settings = self.settings.clone();
+        let decode_settings = self.decode_settings.clone();
+        let Some(name) = self.config.active_profile.clone() else {
             return;
-        }
-
-        self.is_converting = true;
-        self.cancel_flag.store(false, Ordering::Relaxed);
-        self.current_progress = 0;
-        self.total_files = 0;
-        self.current_file.clear();
+        };
 
-        let (tx, rx) = channel();
-        self.progress_rx = Some(rx);
+        if let Some(profile) = self.config.profiles.iter_mut().find(|p| p.name == name) {
+            profile.settings = settings;
+            profile.decode_settings = decode_settings;
+        }
+        self.save_config();
+        self.add_log(LogEntry::Info(format!("Saved profile \"{}\".", name)));
+    }
 
-        let engine = ConversionEngine::new();
-        let decode_items = self.decode_items.clone();
-        let settings = self.decode_settings.clone();
-        let cancel_flag = Arc::clone(&self.cancel_flag);
+    fn render_profile_bar(&mut self, ui: &mut egui::Ui) {
+        let dirty = self.is_profile_dirty();
+        let selected_text = match &self.config.active_profile {
+            Some(name) if dirty => format!("{}*", name),
+            Some(name) => name.clone(),
+            None => "(none)".to_string(),
+        };
 
-        thread::spawn(move || {
-            engine.decode_batch(decode_items, settings, tx, cancel_flag);
-        });
+        ui.horizontal(|ui| {
+            ui.label("Profile:");
 
-        self.add_log(LogEntry::Info("Decoding started...".to_string()));
-    }
+            let mut switch_to = None;
+            egui::ComboBox::from_id_salt("profile_switcher")
+                .selected_text(selected_text)
+                .width(180.0)
+                .show_ui(ui, |ui| {
+                    for profile in &self.config.profiles {
+                        let is_active = self.config.active_profile.as_deref() == Some(profile.name.as_str());
+                        if ui.selectable_label(is_active, &profile.name).clicked() {
+                            switch_to = Some(profile.name.clone());
+                        }
+                    }
+                    if self.config.profiles.is_empty() {
+                        ui.label(RichText::new("No profiles yet").italics().color(Color32::GRAY));
+                    }
+                    ui.separator();
+                    if ui.button("Manage…").clicked() {
+                        self.profile_manager_open = true;
+                    }
+                });
 
-    fn process_progress_messages(&mut self) {
-        // Collect all messages first to avoid borrow checker issues
-        let mut messages = Vec::new();
-        if let Some(rx) = &self.progress_rx {
-            while let Ok(msg) = rx.try_recv() {
-                messages.push(msg);
+            if let Some(name) = switch_to {
+                self.apply_profile(&name);
             }
-        }
 
-        // Process collected messages
-        for msg in messages {
-            match msg {
-                ProgressMessage::Started { total } => {
-                    self.total_files = total;
-                    self.add_log(LogEntry::Info(format!("Processing {} file(s)...", total)));
-                }
-                ProgressMessage::Progress { current, total, file } => {
-                    self.current_progress = current;
-                    self.total_files = total;
-                    self.current_file = file;
+            if dirty {
+                if ui.button("Save").on_hover_text("Store the current settings in this profile").clicked() {
+                    self.save_active_profile();
                 }
-                ProgressMessage::Success { file } => {
-                    self.add_log(LogEntry::Success(format!("✓ {}", file)));
+                if ui.button("Revert").on_hover_text("Discard changes since the profile was applied").clicked() {
+                    if let Some(name) = self.config.active_profile.clone() {
+                        self.apply_profile(&name);
+                    }
                 }
-                ProgressMessage::Error { file, error } => {
-                    self.add_log(LogEntry::Error(format!("✗ {}: {}", file, error)));
+            }
+
+            if ui.button("Manage…").clicked() {
+                self.profile_manager_open = true;
+            }
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("⚙ Settings").clicked() {
+                    self.settings_window_open = true;
                 }
-                ProgressMessage::Skipped { file, reason } => {
-                    self.add_log(LogEntry::Warning(format!("⊘ {}: {}", file, reason)));
+                if ui.button("ℹ About").clicked() {
+                    self.about_open = true;
                 }
-                ProgressMessage::Completed => {
-                    self.is_converting = false;
-                    self.progress_rx = None;
-                    self.current_file.clear();
-                    self.add_log(LogEntry::Info("Conversion completed.".to_string()));
+            });
+        });
+    }
+
+    fn render_about_window(&mut self, ctx: &egui::Context) {
+        if !self.about_open {
+            return;
+        }
+
+        let mut open = true;
+        let mut reset = false;
+        let stats = &self.stats;
+        egui::Window::new("About")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(RichText::new(format!("JPEG XL Converter {}", env!("CARGO_PKG_VERSION"))).strong());
+                ui.add_space(8.0);
+                ui.separator();
+                ui.label(RichText::new("Statistics").strong());
+                ui.add_space(3.0);
+                ui.label(format!("All time: {}", stats.encode.describe()));
+                egui::Grid::new("stats_grid").num_columns(2).show(ui, |ui| {
+                    let hours = |seconds: f64| format!("{:.1} h", seconds / 3600.0);
+                    ui.label("Encode batches:");
+                    ui.label(format!("{}, {} encoding", group_thousands(stats.encode.batches), hours(stats.encode.seconds)));
+                    ui.end_row();
+                    ui.label("Failed files:");
+                    ui.label(group_thousands(stats.encode.failed_files + stats.decode.failed_files));
+                    ui.end_row();
+                    ui.label("Decode batches:");
+                    ui.label(format!("{}: {}", group_thousands(stats.decode.batches), stats.decode.describe()));
+                    ui.end_row();
+                    ui.label("Failed batches:");
+                    ui.label(format!(
+                        "{}, {} failed files",
+                        group_thousands(stats.failed.batches),
+                        group_thousands(stats.failed.failed_files)
+                    ));
+                    ui.end_row();
+                });
+                if let Some(since) = &stats.since {
+                    ui.label(RichText::new(format!("Counting since {}", since)).small().color(Color32::GRAY));
                 }
-                ProgressMessage::Cancelled => {
-                    self.is_converting = false;
-                    self.progress_rx = None;
-                    self.current_file.clear();
-                    self.add_log(LogEntry::Warning("Conversion cancelled.".to_string()));
+                ui.add_space(5.0);
+                if ui.button("Reset statistics").clicked() {
+                    reset = true;
                 }
+            });
+
+        if reset {
+            self.stats.reset();
+            match self.stats.save() {
+                Ok(()) => self.add_log(LogEntry::Info("Statistics reset.".to_string())),
+                Err(e) => self.add_log(LogEntry::Error(e)),
             }
         }
+        self.about_open = open;
     }
 
-    fn render_input_section(&mut self, ui: &mut egui::Ui) {
-        ui.heading("Input");
-        ui.add_space(5.0);
-
-        // Drop area
-        let drop_area = ui.allocate_response(
-            egui::vec2(ui.available_width(), 100.0),
-            egui::Sense::click(),
-        );
-
-        ui.painter().rect_filled(
-            drop_area.rect,
-            4.0,
-            if drop_area.hovered() {
-                Color32::from_rgb(60, 60, 80)
-            } else {
-                Color32::from_rgb(40, 40, 60)
-            },
-        );
+    fn render_settings_window(&mut self, ctx: &egui::Context) {
+        if !self.settings_window_open {
+            return;
+        }
 
-        ui.painter().rect_stroke(
-            drop_area.rect,
-            4.0,
-            egui::Stroke::new(2.0, Color32::from_rgb(100, 100, 120)),
-        );
+        let before = self.tool_settings.clone();
+        let power_before = self.power_settings.clone();
+        let throttle_before = self.throttle_settings.clone();
+        let mut open = true;
+        let mut add_location = false;
+
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(380.0)
+            .show(ctx, |ui| {
+                ui.label(RichText::new("Tools").strong());
+                ui.add_space(3.0);
+                let mut remove_location = None;
+                egui::Grid::new("tool_choice").num_columns(2).show(ui, |ui| {
+                    let choices = [
+                        ("cjxl", &mut self.tool_settings.cjxl_path, &self.tools.cjxl, &self.tools.cjxl_candidates),
+                        ("djxl", &mut self.tool_settings.djxl_path, &self.tools.djxl, &self.tools.djxl_candidates),
+                    ];
+                    for (name, chosen, selected, candidates) in choices {
+                        ui.label(format!("{}:", name));
+                        let text = selected.as_ref().map_or("not found".to_string(), |tool| tool.describe());
+                        egui::ComboBox::from_id_salt(name).selected_text(text).width(280.0).show_ui(ui, |ui| {
+                            for tool in candidates {
+                                let is_selected = selected.as_ref() == Some(tool);
+                                if ui.selectable_label(is_selected, tool.describe()).clicked() {
+                                    *chosen = Some(tool.path.clone());
+                                }
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+                for (index, location) in self.tool_settings.extra_tool_paths.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("✖").on_hover_text("Stop looking here").clicked() {
+                            remove_location = Some(index);
+                        }
+                        ui.label(RichText::new(location.display().to_string()).small());
+                    });
+                }
+                if ui.button("➕ Add Tool Location…").clicked() {
+                    add_location = true;
+                }
+                if let Some(index) = remove_location {
+                    self.tool_settings.extra_tool_paths.remove(index);
+                }
+                ui.label(
+                    RichText::new("Found in the tools folder, on PATH and in the locations above.")
+                        .small()
+                        .color(Color32::GRAY),
+                );
+
+                ui.add_space(8.0);
+                ui.separator();
+                ui.label(RichText::new("Tool Output").strong());
+                ui.add_space(3.0);
+                ui.horizontal(|ui| {
+                    ui.label("Verbosity:");
+                    ui.add(Slider::new(&mut self.tool_settings.tool_verbosity, 0..=3));
+                });
+                ui.label(
+                    RichText::new("Adds that many -v flags to cjxl/djxl and copies their output into the log. 0 keeps the tools quiet.")
+                        .small()
+                        .color(Color32::GRAY),
+                );
+
+                ui.add_space(8.0);
+                ui.separator();
+                ui.label(RichText::new("Environment Variables").strong());
+                ui.add_space(3.0);
+                let response = ui.add(
+                    egui::TextEdit::multiline(&mut self.env_text)
+                        .font(egui::TextStyle::Monospace)
+                        .hint_text("KEY=VALUE, one per line")
+                        .desired_rows(3)
+                        .desired_width(f32::INFINITY),
+                );
+                let (overrides, errors) = parse_env_overrides(&self.env_text);
+                if response.changed() {
+                    self.tool_settings.env_overrides = overrides;
+                }
+                for error in &errors {
+                    ui.label(RichText::new(error).small().color(Color32::from_rgb(255, 100, 100)));
+                }
+                ui.label(
+                    RichText::new("Set on every cjxl/djxl run. Values of keys containing TOKEN, KEY or SECRET are masked in the log.")
+                        .small()
+                        .color(Color32::GRAY),
+                );
+
+                ui.add_space(8.0);
+                ui.separator();
+                ui.label(RichText::new("Resource Limits").strong());
+                ui.add_space(3.0);
+                ui.horizontal(|ui| {
+                    let mut limit_enabled = self.tool_settings.max_child_memory_mb.is_some();
+                    if ui.checkbox(&mut limit_enabled, "Kill tools using more than").changed() {
+                        self.tool_settings.max_child_memory_mb = limit_enabled.then_some(8192);
+                    }
+                    if let Some(limit) = &mut self.tool_settings.max_child_memory_mb {
+                        ui.add(egui::DragValue::new(limit).range(64..=1_048_576).speed(64).suffix(" MB"));
+                    }
+                });
+
+                let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+                ui.horizontal(|ui| {
+                    let mut limit_enabled = self.tool_settings.cpu_limit.is_some();
+                    if ui.checkbox(&mut limit_enabled, "Limit tools to").changed() {
+                        self.tool_settings.cpu_limit = limit_enabled.then_some((cores / 2).max(1));
+                    }
+                    if let Some(limit) = &mut self.tool_settings.cpu_limit {
+                        *limit = (*limit).clamp(1, cores);
+                        ui.add(egui::DragValue::new(limit).range(1..=cores).suffix(format!(" of {} cores", cores)));
+                    }
+                });
+                if self.tool_settings.cpu_limit.is_some() && !affinity_supported() {
+                    ui.label(
+                        RichText::new("Core pinning isn't available on this platform; the tools' own --num_threads is used instead.")
+                            .small()
+                            .color(Color32::GRAY),
+                    );
+                }
+
+                ui.horizontal(|ui| {
+                    let mut delay_enabled = self.tool_settings.inter_file_delay_ms.is_some();
+                    if ui.checkbox(&mut delay_enabled, "Cool down between files for").changed() {
+                        self.tool_settings.inter_file_delay_ms = delay_enabled.then_some(3000);
+                    }
+                    if let Some(delay) = &mut self.tool_settings.inter_file_delay_ms {
+                        let mut secs = *delay as f64 / 1000.0;
+                        if ui.add(egui::DragValue::new(&mut secs).range(0.1..=3600.0).speed(0.1).suffix(" s")).changed() {
+                            *delay = (secs * 1000.0).round() as u64;
+                        }
+                    }
+                });
+                ui.label(
+                    RichText::new("Idling between files keeps a machine that throttles when hot at full speed over a long batch.")
+                        .small()
+                        .color(Color32::GRAY),
+                );
+
+                ui.add_space(8.0);
+                ui.separator();
+                ui.label(RichText::new("Unfinished Files").strong());
+                ui.add_space(3.0);
+                ui.horizontal(|ui| {
+                    let mut wait_enabled = self.tool_settings.stable_wait_secs.is_some();
+                    if ui.checkbox(&mut wait_enabled, "Skip files still being written; watch for").changed() {
+                        self.tool_settings.stable_wait_secs = wait_enabled.then_some(2);
+                    }
+                    if let Some(secs) = &mut self.tool_settings.stable_wait_secs {
+                        ui.add(egui::DragValue::new(secs).range(1..=60).suffix(" s"));
+                    }
+                });
+                ui.label(
+                    RichText::new("Files modified that recently are checked again after the wait and skipped if they changed. Partial downloads (.part, .crdownload, .tmp, …) are always skipped in folders.")
+                        .small()
+                        .color(Color32::GRAY),
+                );
+
+                ui.add_space(8.0);
+                ui.separator();
+                ui.label(RichText::new("Large Batches").strong());
+                ui.add_space(3.0);
+                ui.horizontal(|ui| {
+                    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+                    ui.label("Ask before batches of more than");
+                    let mut files = self.tool_settings.confirm_batch_files.unwrap_or(DEFAULT_CONFIRM_BATCH_FILES);
+                    if ui.add(egui::DragValue::new(&mut files).range(1..=10_000_000).speed(100).suffix(" files")).changed() {
+                        self.tool_settings.confirm_batch_files = Some(files);
+                    }
+                    ui.label("or");
+                    let bytes = self.tool_settings.confirm_batch_bytes.unwrap_or(DEFAULT_CONFIRM_BATCH_BYTES);
+                    let mut gb = bytes as f64 / GB;
+                    if ui.add(egui::DragValue::new(&mut gb).range(0.1..=1_000_000.0).speed(1.0).suffix(" GB")).changed() {
+                        self.tool_settings.confirm_batch_bytes = Some((gb * GB) as u64);
+                    }
+                });
+
+                ui.add_space(8.0);
+                ui.separator();
+                ui.label(RichText::new("Hooks").strong());
+                ui.add_space(3.0);
+                let tool_settings = &mut self.tool_settings;
+                egui::Grid::new("hooks_grid").num_columns(2).show(ui, |ui| {
+                    let hooks = [
+                        ("Before each batch:", &mut tool_settings.pre_batch_hook, "mount /mnt/photos"),
+                        ("After each file:", &mut tool_settings.post_hook, "exiftool -TagsFromFile {input} {output}"),
+                        ("After each batch:", &mut tool_settings.post_batch_hook, "notify-send \"$JXL_STATUS\""),
+                    ];
+                    for (label, hook, hint) in hooks {
+                        ui.label(label);
+                        ui.add(egui::TextEdit::singleline(hook).hint_text(hint).desired_width(260.0));
+                        ui.end_row();
+                    }
+                });
+                ui.checkbox(&mut tool_settings.post_hook_always, "Run the file hook after failed files too");
+                ui.horizontal(|ui| {
+                    ui.label("Stop batch hooks after");
+                    let mut timeout = tool_settings.batch_hook_timeout_secs.unwrap_or(DEFAULT_BATCH_HOOK_TIMEOUT_SECS);
+                    if ui.add(egui::DragValue::new(&mut timeout).range(1..=86_400).suffix(" s")).changed() {
+                        tool_settings.batch_hook_timeout_secs = Some(timeout);
+                    }
+                });
+                ui.label(
+                    RichText::new("Hooks run through the system shell. The file hook gets {input}, {output} and {status} (success or error) filled in, and a non-zero exit is only a warning. A failing batch-start hook stops the batch; the batch-end hook sees JXL_CONVERTED, JXL_FAILED, JXL_SKIPPED, JXL_BYTES_SAVED and JXL_STATUS (completed or cancelled).")
+                        .small()
+                        .color(Color32::GRAY),
+                );
+
+                ui.add_space(8.0);
+                ui.separator();
+                ui.label(RichText::new("Notifications").strong());
+                ui.add_space(3.0);
+                egui::Grid::new("notify_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Notify URL:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.tool_settings.notify_url)
+                            .hint_text("https://dashboard.example.com/hooks/jxl")
+                            .desired_width(260.0),
+                    );
+                    ui.end_row();
+                    ui.label("Bearer token:");
+                    ui.add(egui::TextEdit::singleline(&mut self.tool_settings.notify_token).password(true).desired_width(260.0));
+                    ui.end_row();
+                    ui.label("Batch label:");
+                    ui.add(egui::TextEdit::singleline(&mut self.tool_settings.batch_label).desired_width(260.0));
+                    ui.end_row();
+                });
+                ui.label(
+                    RichText::new("When a batch ends, its summary is POSTed as JSON, the same as the CLI's --progress=json summary line. Failed attempts are retried twice and then logged as a warning.")
+                        .small()
+                        .color(Color32::GRAY),
+                );
+
+                ui.add_space(8.0);
+                ui.separator();
+                ui.label(RichText::new("Background Mode").strong());
+                ui.add_space(3.0);
+                ui.checkbox(&mut self.throttle_settings.enabled, "Be nice while I'm working");
+                ui.add_enabled_ui(self.throttle_settings.enabled, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Wait between files:");
+                        ui.add(egui::DragValue::new(&mut self.throttle_settings.delay_ms).range(0..=60_000).speed(100).suffix(" ms"));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Full speed after");
+                        ui.add(egui::DragValue::new(&mut self.throttle_settings.idle_minutes).range(1..=120).suffix(" min"));
+                        ui.label("idle");
+                    });
+                });
+                ui.label(
+                    RichText::new("Slows a running batch down while you use the keyboard or mouse.")
+                        .small()
+                        .color(Color32::GRAY),
+                );
+
+                // Desktops have no battery, so there's nothing to configure
+                if self.power_state != PowerState::Unknown {
+                    ui.add_space(8.0);
+                    ui.separator();
+                    ui.label(RichText::new("Power").strong());
+                    ui.add_space(3.0);
+                    ui.checkbox(&mut self.power_settings.pause_on_battery, "Pause conversion while on battery");
+                    ui.checkbox(
+                        &mut self.power_settings.defer_scheduled_on_battery,
+                        "Don't start scheduled batches on battery",
+                    );
+                }
+            });
+
+        if add_location {
+            self.add_tool_location();
+        } else if self.tool_settings.extra_tool_paths != before.extra_tool_paths {
+            self.rescan_tools();
+        } else if self.tool_settings.cjxl_path != before.cjxl_path || self.tool_settings.djxl_path != before.djxl_path {
+            self.apply_tool_choice();
+        }
+
+        if self.tool_settings != before
+            || self.power_settings != power_before
+            || self.throttle_settings != throttle_before
+        {
+            self.save_config();
+        }
+
+        if !open {
+            self.settings_window_open = false;
+        }
+    }
+
+    fn render_profile_manager(&mut self, ctx: &egui::Context) {
+        if !self.profile_manager_open {
+            return;
+        }
+
+        let mut open = true;
+        let mut changed = false;
+        let mut delete = None;
+        let mut rename_done = None;
+        let mut export = None;
+
+        egui::Window::new("Manage Profiles")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_profile_name)
+                            .hint_text("New profile name")
+                            .desired_width(200.0),
+                    );
+                    let name = self.new_profile_name.trim().to_string();
+                    if ui
+                        .add_enabled(!name.is_empty(), egui::Button::new("Create from current"))
+                        .clicked()
+                    {
+                        let name = self.config.unique_profile_name(&name);
+                        self.config.profiles.push(Profile {
+                            name: name.clone(),
+                            settings: self.settings.clone(),
+                            decode_settings: self.decode_settings.clone(),
+                        });
+                        self.config.active_profile = Some(name);
+                        self.new_profile_name.clear();
+                        changed = true;
+                    }
+                });
+
+                ui.add_space(5.0);
+                ui.separator();
+
+                for profile in &self.config.profiles {
+                    ui.horizontal(|ui| {
+                        match &mut self.renaming_profile {
+                            Some((original, buffer)) if *original == profile.name => {
+                                let response = ui.text_edit_singleline(buffer);
+                                if ui.button("✔").clicked()
+                                    || (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)))
+                                {
+                                    rename_done = Some((original.clone(), buffer.trim().to_string()));
+                                }
+                            }
+                            _ => {
+                                ui.label(&profile.name);
+                                if ui.small_button("Rename").clicked() {
+                                    self.renaming_profile = Some((profile.name.clone(), profile.name.clone()));
+                                }
+                            }
+                        }
+
+                        if ui.small_button("Export…").clicked() {
+                            export = Some(profile.clone());
+                        }
+                        if ui.small_button("🗑").on_hover_text("Delete profile").clicked() {
+                            delete = Some(profile.name.clone());
+                        }
+                    });
+                }
+
+                ui.add_space(5.0);
+                ui.separator();
+
+                if ui.button("Import profile…").clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .set_title("Import Profile")
+                        .add_filter("Profile", &["json"])
+                        .pick_file()
+                    {
+                        match Profile::import_json(&path) {
+                            Ok(mut profile) => {
+                                profile.name = self.config.unique_profile_name(&profile.name);
+                                self.log_entries.push(LogEntry::Info(format!(
+                                    "Imported profile \"{}\".",
+                                    profile.name
+                                )));
+                                self.config.profiles.push(profile);
+                                changed = true;
+                            }
+                            Err(e) => self.log_entries.push(LogEntry::Error(e)),
+                        }
+                    }
+                }
+            });
+
+        if let Some((original, new_name)) = rename_done {
+            self.renaming_profile = None;
+            if !new_name.is_empty() && new_name != original {
+                let new_name = self.config.unique_profile_name(&new_name);
+                if let Some(profile) = self.config.profiles.iter_mut().find(|p| p.name == original) {
+                    profile.name = new_name.clone();
+                }
+                if self.config.active_profile.as_deref() == Some(original.as_str()) {
+                    self.config.active_profile = Some(new_name);
+                }
+                changed = true;
+            }
+        }
+
+        if let Some(name) = delete {
+            self.config.profiles.retain(|p| p.name != name);
+            if self.config.active_profile.as_deref() == Some(name.as_str()) {
+                self.config.active_profile = None;
+            }
+            changed = true;
+        }
+
+        if let Some(profile) = export {
+            if let Some(path) = rfd::FileDialog::new()
+                .set_title("Export Profile")
+                .add_filter("Profile", &["json"])
+                .set_file_name(format!("{}.json", profile.name))
+                .save_file()
+            {
+                match profile.export_json(&path) {
+                    Ok(()) => self.add_log(LogEntry::Info(format!("Exported profile to {}", path.display()))),
+                    Err(e) => self.add_log(LogEntry::Error(e)),
+                }
+            }
+        }
+
+        if changed {
+            self.save_config();
+        }
+
+        if !open {
+            self.profile_manager_open = false;
+            self.renaming_profile = None;
+        }
+    }
+
+    fn update_window_title(&mut self, ctx: &egui::Context) {
+        let title = match &self.config.active_profile {
+            Some(name) => format!(
+                "JPEG XL Converter — {}{}",
+                name,
+                if self.is_profile_dirty() { "*" } else { "" }
+            ),
+            None => "JPEG XL Converter".to_string(),
+        };
+
+        if title != self.window_title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.window_title = title;
+        }
+    }
+
+    /// Rejects malformed environment lines before a batch starts and logs
+    /// the overrides that will apply.
+    fn check_env_overrides(&mut self) -> bool {
+        let (_, errors) = parse_env_overrides(&self.env_text);
+        if !errors.is_empty() {
+            self.add_log(LogEntry::Error(format!(
+                "Invalid environment variables in Settings: {}",
+                errors.join("; ")
+            )));
+            return false;
+        }
+
+        if !self.tool_settings.env_overrides.is_empty() {
+            let description = self.tool_settings.describe_env();
+            self.add_log(LogEntry::Info(format!("Tool environment: {}", description)));
+        }
+        true
+    }
+
+    /// The tool settings for a batch about to start, without the hooks if
+    /// they were turned off for it.
+    fn batch_tool_settings(&self) -> ToolSettings {
+        let mut tool_settings = self.tool_settings.clone();
+        if !self.run_hooks {
+            tool_settings.post_hook.clear();
+            tool_settings.pre_batch_hook.clear();
+            tool_settings.post_batch_hook.clear();
+        }
+        tool_settings
+    }
+
+    fn start_conversion(&mut self) {
+        if !self.engine.is_available() {
+            self.add_log(LogEntry::Error("cjxl is not available.".to_string()));
+            return;
+        }
+
+        if self.input_paths.is_empty() {
+            self.add_log(LogEntry::Warning("No input files or folders selected.".to_string()));
+            return;
+        }
+
+        if self.settings.output_dir.as_os_str().is_empty() {
+            self.add_log(LogEntry::Warning("No output directory selected.".to_string()));
+            return;
+        }
+
+        if !self.check_env_overrides() {
+            return;
+        }
+
+        if let Err(error) = self.engine.validate_settings(&self.settings) {
+            self.add_log(LogEntry::Error(error));
+            return;
+        }
+
+        self.is_converting = true;
+        self.cancel_flag.store(false, Ordering::Relaxed);
+        self.current_progress = 0;
+        self.total_files = 0;
+        self.current_file.clear();
+        self.verification_counts = (0, 0, 0);
+        self.remaining_inputs.clear();
+        self.batch_summary = BatchSummary::begin();
+
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+
+        let engine = ConversionEngine::from_tools(&self.tools)
+            .with_tool_settings(self.batch_tool_settings())
+            .with_pause_flag(Arc::clone(&self.pause_flag))
+            .with_file_delay(Arc::clone(&self.file_delay_ms))
+            .with_resume(self.pending_resume.take().unwrap_or_default())
+            .with_dry_run(self.dry_run);
+        let engine = match AppConfig::journal_dir() {
+            Some(dir) => engine.with_journal_dir(dir),
+            None => engine,
+        };
+        let input_paths = self.input_paths.clone();
+        let settings = self.settings.clone();
+        let cancel_flag = Arc::clone(&self.cancel_flag);
+        self.batch_pasted_images = input_paths
+            .iter()
+            .filter(|p| self.pasted_images.contains(*p))
+            .cloned()
+            .collect();
+
+        thread::spawn(move || {
+            engine.convert_batch(input_paths, settings, tx, cancel_flag);
+        });
+
+        self.add_log(LogEntry::Info("Conversion started...".to_string()));
+    }
+
+    fn start_or_arm(&mut self, ctx: &egui::Context) {
+        if self.schedule_enabled {
+            self.arm_schedule(ctx);
+        } else {
+            self.start_tab(self.active_tab);
+        }
+    }
+
+    /// Checks the active tab's output folder when Start is pressed: one that
+    /// can't be used is logged, a missing one opens an offer to create it.
+    /// Returns whether to go ahead now.
+    fn confirm_output_dir(&mut self) -> bool {
+        let dir = match self.active_tab {
+            AppTab::Encode => self.settings.output_dir.clone(),
+            AppTab::Decode => self.decode_settings.output_dir.clone(),
+            AppTab::Compare => return false,
+        };
+        match check_output_dir(&dir) {
+            OutputDirStatus::Ready => true,
+            OutputDirStatus::Missing => {
+                self.pending_create_dir = Some(dir);
+                false
+            }
+            OutputDirStatus::Invalid(message) => {
+                self.add_log(LogEntry::Error(message));
+                false
+            }
+        }
+    }
+
+    fn render_create_dir_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dir) = &self.pending_create_dir else {
+            return;
+        };
+
+        let mut open = true;
+        let mut create = false;
+        let mut close = false;
+
+        egui::Window::new("Create Output Folder")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("{} doesn't exist yet.", dir.display()));
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Create and Start").clicked() {
+                        create = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if create {
+            let dir = self.pending_create_dir.take().unwrap_or_default();
+            match std::fs::create_dir_all(&dir) {
+                Ok(()) => {
+                    self.add_log(LogEntry::Info(format!("Created {}", dir.display())));
+                    if self.confirm_conflicts() && self.confirm_animation_loss() && self.confirm_large_batch() {
+                        self.start_or_arm(ctx);
+                    }
+                }
+                Err(e) => self.add_log(LogEntry::Error(format!("Failed to create {}: {}", dir.display(), e))),
+            }
+        } else if close || !open {
+            self.pending_create_dir = None;
+        }
+    }
+
+    /// Checks the encode settings when Start is pressed. Hard conflicts are
+    /// logged and stop the batch; soft ones open a confirmation first.
+    /// Returns whether to go ahead now.
+    fn confirm_conflicts(&mut self) -> bool {
+        if self.active_tab != AppTab::Encode {
+            return true;
+        }
+
+        let conflicts = check_settings(&self.settings);
+        let hard: Vec<&Conflict> = conflicts.iter().filter(|c| c.severity == Severity::Hard).collect();
+        if !hard.is_empty() {
+            for conflict in hard {
+                self.add_log(LogEntry::Error(conflict.message.to_string()));
+            }
+            return false;
+        }
+        if conflicts.is_empty() {
+            return true;
+        }
+        self.pending_conflicts = Some(conflicts);
+        false
+    }
+
+    fn render_conflicts_dialog(&mut self, ctx: &egui::Context) {
+        let Some(conflicts) = &self.pending_conflicts else {
+            return;
+        };
+
+        let mut open = true;
+        let mut start = false;
+        let mut close = false;
+
+        egui::Window::new("Check Settings")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Some settings won't do what they look like:");
+                ui.add_space(3.0);
+                for conflict in conflicts {
+                    ui.label(format!("⚠ {}", conflict.message));
+                }
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Start Anyway").clicked() {
+                        start = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if start {
+            self.pending_conflicts = None;
+            if self.confirm_animation_loss() && self.confirm_large_batch() {
+                self.start_or_arm(ctx);
+            }
+        } else if close || !open {
+            self.pending_conflicts = None;
+        }
+    }
+
+    /// Checks the decode list for animated JXLs going to a format that keeps
+    /// only the first frame; any open a confirmation with a fix first.
+    /// Returns whether to go ahead now.
+    fn confirm_animation_loss(&mut self) -> bool {
+        if self.active_tab != AppTab::Decode {
+            return true;
+        }
+
+        let losing: Vec<PathBuf> = losing_animation(&self.decode_items, &self.decode_settings)
+            .into_iter()
+            .map(Path::to_path_buf)
+            .collect();
+        if losing.is_empty() {
+            return true;
+        }
+        self.pending_animation_loss = Some(losing);
+        false
+    }
+
+    fn render_animation_loss_dialog(&mut self, ctx: &egui::Context) {
+        let Some(losing) = &self.pending_animation_loss else {
+            return;
+        };
+
+        let mut open = true;
+        let mut switch_to = None;
+        let mut start = false;
+        let mut close = false;
+
+        egui::Window::new("Animated Files")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} animated file(s) will lose all but the first frame. Switch them to APNG or GIF?",
+                    losing.len()
+                ));
+                ui.add_space(3.0);
+                for path in losing.iter().take(5) {
+                    ui.label(RichText::new(path.display().to_string()).small().color(Color32::GRAY));
+                }
+                if losing.len() > 5 {
+                    ui.label(RichText::new(format!("…and {} more", losing.len() - 5)).small().color(Color32::GRAY));
+                }
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Switch to APNG").on_hover_text("Decode them to PNG, which keeps every frame").clicked() {
+                        switch_to = Some(OutputFormat::Png);
+                    }
+                    if ui.button("Switch to GIF").clicked() {
+                        switch_to = Some(OutputFormat::Gif);
+                    }
+                    if ui.button("Decode Anyway").clicked() {
+                        start = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if let Some(format) = switch_to {
+            let losing = self.pending_animation_loss.take().unwrap_or_default();
+            for item in self.decode_items.iter_mut().filter(|item| losing.contains(&item.path)) {
+                item.output_formats.retain(|f| f.supports_animation());
+                if !item.output_formats.contains(&format) {
+                    item.output_formats.push(format);
+                }
+            }
+            self.add_log(LogEntry::Info(format!(
+                "Switched {} animated file(s) to {}",
+                losing.len(),
+                format.name()
+            )));
+            if self.confirm_large_batch() {
+                self.start_or_arm(ctx);
+            }
+        } else if start {
+            self.pending_animation_loss = None;
+            if self.confirm_large_batch() {
+                self.start_or_arm(ctx);
+            }
+        } else if close || !open {
+            self.pending_animation_loss = None;
+        }
+    }
+
+    /// Counts the active tab's inputs when Start is pressed; a batch over
+    /// the thresholds in the settings opens a breakdown to confirm first.
+    /// Returns whether to go ahead now.
+    fn confirm_large_batch(&mut self) -> bool {
+        let breakdown = match self.active_tab {
+            AppTab::Encode => {
+                let (mut files, paths): (Vec<PathBuf>, Vec<PathBuf>) =
+                    self.input_paths.iter().cloned().partition(|p| url_of(p).is_some());
+                let scan = self.engine.expand_paths(&paths, &self.settings.scan_options());
+                files.extend(scan.files.into_iter().filter(|p| is_supported_image(p)));
+                BatchBreakdown::of_files(&files)
+            }
+            AppTab::Decode => BatchBreakdown::of_decode(&self.decode_items, &self.decode_settings),
+            AppTab::Compare => return true,
+        };
+        if !breakdown.needs_confirmation(&self.tool_settings) {
+            return true;
+        }
+        self.pending_large_batch = Some(breakdown);
+        false
+    }
+
+    fn render_large_batch_dialog(&mut self, ctx: &egui::Context) {
+        let Some(breakdown) = &self.pending_large_batch else {
+            return;
+        };
+
+        let mut open = true;
+        let mut start = false;
+        let mut dry_run = false;
+        let mut close = false;
+
+        let destination = match self.active_tab {
+            AppTab::Encode => &self.settings.output_dir,
+            AppTab::Decode => &self.decode_settings.output_dir,
+            AppTab::Compare => return,
+        };
+        egui::Window::new("Large Batch")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("This batch is larger than usual:");
+                ui.add_space(3.0);
+                for line in breakdown.describe(destination) {
+                    ui.label(RichText::new(line).monospace());
+                }
+                if let Some(estimate) = self.config.throughput.and_then(|rate| breakdown.estimate(rate)) {
+                    ui.label(format!("Estimated time: about {}, at the speed of recent batches", format_countdown(estimate)));
+                }
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Start").clicked() {
+                        start = true;
+                    }
+                    if ui.button("Dry run instead").on_hover_text("List each file's output without converting anything").clicked() {
+                        dry_run = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if start {
+            self.pending_large_batch = None;
+            self.start_or_arm(ctx);
+        } else if dry_run {
+            self.pending_large_batch = None;
+            self.start_batch(self.active_tab, true);
+        } else if close || !open {
+            self.pending_large_batch = None;
+        }
+    }
+
+    fn render_resume_dialog(&mut self, ctx: &egui::Context) {
+        if self.is_converting {
+            return;
+        }
+        let Some(batch) = self.unfinished_batches.first() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut resume = false;
+        let mut discard = false;
+        let mut later = false;
+
+        egui::Window::new("Resume Previous Batch")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let (kind, count) = match &batch.batch {
+                    JournalBatch::Encode { inputs, .. } => ("Encode", inputs.len()),
+                    JournalBatch::Decode { items, .. } => ("Decode", items.len()),
+                };
+                match batch.remaining() {
+                    Some(remaining) => ui.label(format!("Resume previous batch ({} files remaining)?", remaining)),
+                    None => ui.label("Resume previous batch?"),
+                };
+                ui.label(
+                    RichText::new(format!("{} of {} input(s), started {}.", kind, count, batch.started_at))
+                        .weak(),
+                );
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Resume").clicked() {
+                        resume = true;
+                    }
+                    if ui.button("Discard").clicked() {
+                        discard = true;
+                    }
+                    if ui.button("Not now").clicked() {
+                        later = true;
+                    }
+                });
+            });
+
+        if resume {
+            let batch = self.unfinished_batches.remove(0);
+            self.resume_batch(batch);
+        } else if discard {
+            self.unfinished_batches.remove(0).discard();
+        } else if later || !open {
+            // The journal stays, so it's offered again next launch
+            self.unfinished_batches.remove(0);
+        }
+    }
+
+    /// Loads an unfinished batch's settings and inputs and starts it again,
+    /// leaving out what it already finished.
+    fn resume_batch(&mut self, batch: UnfinishedBatch) {
+        self.pending_resume = Some(batch.resume());
+        let tab = match batch.batch {
+            JournalBatch::Encode { inputs, settings } => {
+                self.settings = settings;
+                self.input_paths = inputs;
+                self.selected_inputs.clear();
+                AppTab::Encode
+            }
+            JournalBatch::Decode { items, settings } => {
+                self.decode_settings = settings;
+                self.decode_items = items;
+                AppTab::Decode
+            }
+        };
+        self.active_tab = tab;
+        self.add_log(LogEntry::Info(format!("Resuming the batch started {}.", batch.started_at)));
+        self.start_tab(tab);
+        // A batch that didn't start leaves its journal for next time
+        self.pending_resume = None;
+    }
+
+    fn start_tab(&mut self, tab: AppTab) {
+        self.start_batch(tab, false);
+    }
+
+    /// Starts `tab`'s batch; a dry run only lists each file's output.
+    fn start_batch(&mut self, tab: AppTab, dry_run: bool) {
+        self.batch_kind = match tab {
+            AppTab::Encode => BatchKind::Encode,
+            AppTab::Decode => BatchKind::Decode,
+            AppTab::Compare => return,
+        };
+        self.dry_run = dry_run;
+        self.cooling_until = None;
+        self.batch_cool_down = match self.tool_settings.inter_file_delay_ms {
+            Some(ms) if !dry_run => Duration::from_millis(ms),
+            _ => Duration::ZERO,
+        };
+        match tab {
+            AppTab::Encode => self.start_conversion(),
+            AppTab::Decode => self.start_decode(),
+            AppTab::Compare => {}
+        }
+    }
+
+    fn arm_schedule(&mut self, ctx: &egui::Context) {
+        let days_ahead = u64::from(self.schedule_tomorrow);
+        let at = match resolve_start_time(self.schedule_hour, self.schedule_minute, days_ahead, Local::now()) {
+            Ok(at) => at,
+            Err(e) => {
+                self.add_log(LogEntry::Error(format!("Can't schedule batch: {}", e)));
+                return;
+            }
+        };
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        arm_timer(at, ctx.clone(), Arc::clone(&cancel));
+        self.armed_start = Some(ArmedStart { at, tab: self.active_tab, cancel, deferred: false });
+        self.add_log(LogEntry::Info(format!("Batch scheduled to start at {}.", at.format("%Y-%m-%d %H:%M"))));
+    }
+
+    fn disarm_schedule(&mut self) {
+        if let Some(armed) = self.armed_start.take() {
+            armed.cancel.store(true, Ordering::Relaxed);
+            self.add_log(LogEntry::Warning("Scheduled start cancelled.".to_string()));
+        }
+    }
+
+    /// Launches the armed batch once its time has come, exactly as if Start
+    /// had been clicked on its tab.
+    fn check_scheduled_start(&mut self) {
+        let due = self.armed_start.as_ref().is_some_and(|armed| Local::now() >= armed.at);
+        if !due {
+            return;
+        }
+
+        if self.power_settings.defer_scheduled_on_battery && self.power_state == PowerState::Battery {
+            if let Some(armed) = self.armed_start.as_mut().filter(|armed| !armed.deferred) {
+                armed.deferred = true;
+                self.add_log(LogEntry::Warning(
+                    "Scheduled start reached, but running on battery. Waiting for AC power.".to_string(),
+                ));
+            }
+            return;
+        }
+
+        let Some(armed) = self.armed_start.take() else {
+            return;
+        };
+        armed.cancel.store(true, Ordering::Relaxed);
+        self.add_log(LogEntry::Info("Scheduled start time reached.".to_string()));
+        self.start_tab(armed.tab);
+    }
+
+    /// Follows power source changes, pausing a running batch on battery
+    /// when asked to and resuming it once AC power returns.
+    fn check_power_state(&mut self) {
+        let state = self.power_monitor.state();
+        if state != self.power_state {
+            match state {
+                PowerState::Battery => self.add_log(LogEntry::Warning("Running on battery power.".to_string())),
+                PowerState::Ac if self.power_state == PowerState::Battery => {
+                    self.add_log(LogEntry::Info("AC power restored.".to_string()))
+                }
+                _ => {}
+            }
+            self.power_state = state;
+        }
+
+        let pause = self.is_converting
+            && self.power_settings.pause_on_battery
+            && self.power_state == PowerState::Battery;
+        if pause != self.pause_flag.swap(pause, Ordering::Relaxed) && self.is_converting {
+            if pause {
+                self.add_log(LogEntry::Warning("Pausing after the current file until AC power returns.".to_string()));
+            } else {
+                self.add_log(LogEntry::Info("Resuming conversion.".to_string()));
+            }
+        }
+    }
+
+    /// Tracks user activity while a batch runs and slows the batch down
+    /// when the user is working.
+    fn check_throttle(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| !i.events.is_empty() || i.pointer.is_moving()) {
+            self.last_input = Instant::now();
+        }
+
+        if !self.is_converting {
+            self.throttle = Throttle::default();
+            self.file_delay_ms.store(0, Ordering::Relaxed);
+            return;
+        }
+
+        if self.throttle_sampled.elapsed() < THROTTLE_SAMPLE_INTERVAL {
+            return;
+        }
+        self.throttle_sampled = Instant::now();
+
+        // Our own window counts even where the OS can't report system-wide idle time
+        let window_idle = self.last_input.elapsed();
+        let idle = os_idle_time().map_or(window_idle, |os_idle| os_idle.min(window_idle));
+
+        match self.throttle.update(idle, &self.throttle_settings) {
+            Some(ThrottleState::Throttled) => self.add_log(LogEntry::Info(format!(
+                "You're active: throttling to 1 job with {:.1} s between files.",
+                self.throttle_settings.delay_ms as f64 / 1000.0
+            ))),
+            Some(ThrottleState::Full) if self.throttle_settings.enabled => {
+                self.add_log(LogEntry::Info("Idle again: running at full speed.".to_string()))
+            }
+            _ => {}
+        }
+
+        let delay = self.throttle.delay(&self.throttle_settings);
+        self.file_delay_ms.store(delay.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn cancel_conversion(&mut self) {
+        self.cancel_flag.store(true, Ordering::Relaxed);
+        self.add_log(LogEntry::Warning("Cancelling conversion...".to_string()));
+    }
+
+    fn start_decode(&mut self) {
+        if !self.engine.is_decode_available() {
+            self.add_log(LogEntry::Error("djxl is not available.".to_string()));
+            return;
+        }
+
+        if self.decode_items.is_empty() {
+            self.add_log(LogEntry::Warning("No JXL files selected.".to_string()));
+            return;
+        }
+
+        if self.decode_settings.output_dir.as_os_str().is_empty() {
+            self.add_log(LogEntry::Warning("No output directory selected.".to_string()));
+            return;
+        }
+
+        if !self.check_env_overrides() {
+            return;
+        }
+
+        if let Err(error) = self.engine.validate_decode(&self.decode_items, &self.decode_settings) {
+            self.add_log(LogEntry::Error(error));
+            return;
+        }
+
+        self.is_converting = true;
+        self.cancel_flag.store(false, Ordering::Relaxed);
+        self.current_progress = 0;
+        self.total_files = 0;
+        self.current_file.clear();
+        self.verification_counts = (0, 0, 0);
+        self.batch_summary = BatchSummary::begin();
+
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+
+        let engine = ConversionEngine::from_tools(&self.tools)
+            .with_tool_settings(self.batch_tool_settings())
+            .with_pause_flag(Arc::clone(&self.pause_flag))
+            .with_file_delay(Arc::clone(&self.file_delay_ms))
+            .with_resume(self.pending_resume.take().unwrap_or_default())
+            .with_dry_run(self.dry_run);
+        let engine = match AppConfig::journal_dir() {
+            Some(dir) => engine.with_journal_dir(dir),
+            None => engine,
+        };
+        let decode_items = self.decode_items.clone();
+        let settings = self.decode_settings.clone();
+        let cancel_flag = Arc::clone(&self.cancel_flag);
+
+        thread::spawn(move || {
+            engine.decode_batch(decode_items, settings, tx, cancel_flag);
+        });
+
+        self.add_log(LogEntry::Info("Decoding started...".to_string()));
+    }
+
+    fn process_progress_messages(&mut self) {
+        // Collect all messages first to avoid borrow checker issues
+        let mut messages = Vec::new();
+        let mut disconnected = false;
+        if let Some(rx) = &self.progress_rx {
+            loop {
+                match rx.try_recv() {
+                    Ok(msg) => messages.push(msg),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        disconnected = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Process collected messages
+        for msg in messages {
+            self.batch_summary.record(&msg);
+            self.track_cool_down(&msg);
+            match msg {
+                ProgressMessage::Started { total } => {
+                    self.total_files = total;
+                    self.add_log(LogEntry::Info(format!("Processing {} file(s)...", total)));
+                }
+                ProgressMessage::Progress { current, total, file } => {
+                    self.current_progress = current;
+                    self.total_files = total;
+                    self.current_file = file;
+                    self.current_frame = None;
+                }
+                ProgressMessage::FrameProgress { frame, total, .. } => {
+                    self.current_frame = Some((frame, total));
+                }
+                ProgressMessage::Success { file, output, .. } => {
+                    self.add_log(LogEntry::Success(format!("✓ {}", file), Some(output)));
+                }
+                ProgressMessage::Error { file, error } => {
+                    self.add_log(LogEntry::Error(format!("✗ {}: {}", file, error)));
+                }
+                ProgressMessage::Skipped { file, reason } => {
+                    self.add_log(LogEntry::Warning(format!("⊘ {}: {}", file, reason)));
+                }
+                ProgressMessage::Verified { file, verification } => {
+                    let counts = &mut self.verification_counts;
+                    match verification {
+                        Verification::Match { .. } => counts.0 += 1,
+                        Verification::Mismatch { .. } => counts.1 += 1,
+                        Verification::Unverified { .. } => counts.2 += 1,
+                    }
+                    let line = format!("{}: {}", file, verification.describe());
+                    if verification.is_problem() {
+                        self.add_log(LogEntry::Warning(line));
+                    } else {
+                        self.add_log(LogEntry::Info(line));
+                    }
+                }
+                ProgressMessage::Adapted { file, distance, score, attempts } => {
+                    self.add_log(LogEntry::Info(format!(
+                        "{}: d {:.2}, ssimulacra2 {:.1} after {} attempt(s)",
+                        file, distance, score, attempts
+                    )));
+                }
+                ProgressMessage::Info { message } => {
+                    self.add_log(LogEntry::Info(message));
+                }
+                ProgressMessage::Warning { message } => {
+                    self.add_log(LogEntry::Warning(message));
+                }
+                ProgressMessage::Completed if self.dry_run => {
+                    self.is_converting = false;
+                    self.current_file.clear();
+                    self.batch_pasted_images.clear();
+                    self.add_log(LogEntry::Info("Dry run completed; nothing was converted.".to_string()));
+                }
+                ProgressMessage::Completed => {
+                    self.is_converting = false;
+                    self.current_file.clear();
+                    self.add_log(LogEntry::Info("Conversion completed.".to_string()));
+                    self.log_format_table();
+                    self.record_throughput();
+                    self.record_lifetime_stats();
+                    // Pasted images have served their purpose once encoded
+                    let converted: HashSet<PathBuf> = self.batch_pasted_images.drain(..).collect();
+                    if !converted.is_empty() {
+                        self.input_paths.retain(|p| !converted.contains(p));
+                        self.prune_input_selection();
+                    }
+                    let (verified, mismatched, unverified) = self.verification_counts;
+                    if mismatched > 0 {
+                        self.add_log(LogEntry::Warning(format!(
+                            "Reconstructed JPEGs: {} verified, {} mismatched, {} unverified",
+                            verified, mismatched, unverified
+                        )));
+                    } else if verified + unverified > 0 {
+                        self.add_log(LogEntry::Info(format!(
+                            "Reconstructed JPEGs: {} verified, {} unverified",
+                            verified, unverified
+                        )));
+                    }
+                }
+                ProgressMessage::Stopped { reason, remaining } => {
+                    self.is_converting = false;
+                    self.current_file.clear();
+                    self.add_log(LogEntry::Warning(format!(
+                        "Conversion stopped: {}; {} file(s) left unconverted.",
+                        reason,
+                        remaining.len()
+                    )));
+                    self.log_format_table();
+                    self.record_lifetime_stats();
+                    // Pasted images that were reached are done with
+                    let left: HashSet<PathBuf> = remaining.into_iter().map(PathBuf::from).collect();
+                    let converted: HashSet<PathBuf> =
+                        self.batch_pasted_images.drain(..).filter(|p| !left.contains(p)).collect();
+                    if !converted.is_empty() {
+                        self.input_paths.retain(|p| !converted.contains(p));
+                        self.prune_input_selection();
+                    }
+                    self.remaining_inputs = left.into_iter().collect();
+                    self.remaining_inputs.sort();
+                }
+                ProgressMessage::Cancelled => {
+                    self.is_converting = false;
+                    self.current_file.clear();
+                    self.add_log(LogEntry::Warning("Conversion cancelled.".to_string()));
+                    self.log_format_table();
+                    self.record_lifetime_stats();
+                    // Pasted images stay in the list for another try
+                    self.batch_pasted_images.clear();
+                    self.discard_unused_pasted_images();
+                }
+            }
+        }
+
+        // The post-batch hook may still log after Completed, so the channel
+        // is kept until the batch thread drops it. A batch that never
+        // started (e.g. a failed pre-batch hook) ends here too.
+        if disconnected {
+            self.progress_rx = None;
+            if self.is_converting {
+                self.is_converting = false;
+                self.current_file.clear();
+                self.batch_summary.end();
+                self.record_lifetime_stats();
+                self.batch_pasted_images.clear();
+                self.discard_unused_pasted_images();
+            }
+        }
+    }
+
+    /// Logs the finished batch's outcomes per input format, when it reached
+    /// any files.
+    fn log_format_table(&mut self) {
+        if self.batch_summary.formats.is_empty() {
+            return;
+        }
+        for line in format_table(&self.batch_summary.formats) {
+            self.add_log(LogEntry::Table(line));
+        }
+    }
+
+    /// Notes when the engine is idling between files: from a file's
+    /// outcome until the next one starts, if there is one.
+    fn track_cool_down(&mut self, msg: &ProgressMessage) {
+        match msg {
+            ProgressMessage::Success { .. } | ProgressMessage::Skipped { .. } | ProgressMessage::Error { .. }
+                if !self.batch_cool_down.is_zero() && self.current_progress < self.total_files =>
+            {
+                self.cooling_until = Some(Instant::now() + self.batch_cool_down);
+            }
+            ProgressMessage::Progress { .. }
+            | ProgressMessage::Completed
+            | ProgressMessage::Stopped { .. }
+            | ProgressMessage::Cancelled => self.cooling_until = None,
+            _ => {}
+        }
+    }
+
+    /// Adds the finished batch to the all-time counters and saves them.
+    /// Dry runs convert nothing, so they aren't counted.
+    fn record_lifetime_stats(&mut self) {
+        if self.dry_run {
+            return;
+        }
+        self.stats.record(self.batch_kind, &self.batch_summary);
+        if let Err(e) = self.stats.save() {
+            self.add_log(LogEntry::Warning(e));
+        }
+    }
+
+    /// Folds the finished batch's speed into [`AppConfig::throughput`].
+    /// Batches too short to time well are left out.
+    fn record_throughput(&mut self) {
+        let elapsed = self.batch_summary.duration_secs.unwrap_or_default();
+        if elapsed < 5.0 || self.batch_summary.bytes_in == 0 {
+            return;
+        }
+        let rate = self.batch_summary.bytes_in as f64 / elapsed;
+        self.config.throughput = Some(self.config.throughput.map_or(rate, |previous| (previous + rate) / 2.0));
+    }
+
+    fn render_input_section(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Input");
+        ui.add_space(5.0);
+
+        // Drop area
+        let drop_area = ui.allocate_response(
+            egui::vec2(ui.available_width(), 100.0),
+            egui::Sense::click(),
+        );
+
+        ui.painter().rect_filled(
+            drop_area.rect,
+            4.0,
+            if drop_area.hovered() {
+                Color32::from_rgb(60, 60, 80)
+            } else {
+                Color32::from_rgb(40, 40, 60)
+            },
+        );
+
+        ui.painter().rect_stroke(
+            drop_area.rect,
+            4.0,
+            egui::Stroke::new(2.0, Color32::from_rgb(100, 100, 120)),
+        );
 
         let text = if self.input_paths.is_empty() {
             "Drop files or folders here\nor use the buttons below"
         } else {
-            &format!("{} item(s) selected", self.input_paths.len())
+            &format!("{} item(s) added", self.input_paths.len())
+        };
+
+        ui.put(
+            drop_area.rect,
+            egui::Label::new(RichText::new(text).size(14.0).color(Color32::LIGHT_GRAY)),
+        );
+
+        // Handle drag and drop
+        ui.ctx().input(|i| {
+            if !i.raw.dropped_files.is_empty() {
+                for file in &i.raw.dropped_files {
+                    if let Some(path) = &file.path {
+                        if !self.input_paths.contains(path) {
+                            self.input_paths.push(path.clone());
+                        }
+                    }
+                }
+            }
+        });
+
+        ui.add_space(10.0);
+
+        ui.horizontal_wrapped(|ui| {
+            if ui.button("📁 Add Files").clicked() {
+                if let Some(files) = rfd::FileDialog::new()
+                    .set_title("Select Image Files")
+                    .add_filter("Images", SUPPORTED_IMAGE_EXTENSIONS)
+                    .pick_files()
+                {
+                    let mut known = self.input_keys();
+                    for file in files {
+                        if known.insert(path_key(&file)) {
+                            self.input_paths.push(file);
+                        }
+                    }
+                }
+            }
+
+            if ui.button("📂 Add Folder").clicked() {
+                if let Some(folder) = rfd::FileDialog::new()
+                    .set_title("Select Folder")
+                    .pick_folder()
+                {
+                    if !self.input_keys().contains(&path_key(&folder)) {
+                        self.input_paths.push(folder);
+                    }
+                }
+            }
+
+            if ui.button("📋 Paste").on_hover_text("Add file paths or http(s) URLs from the clipboard (Ctrl+V)").clicked() {
+                self.paste_paths_from_clipboard();
+            }
+
+            if ui.button("🖼 Paste Image").on_hover_text("Encode the image on the clipboard (Alt+V)").clicked() {
+                self.paste_image_from_clipboard();
+            }
+
+            if ui.add_enabled(self.import_rx.is_none(), egui::Button::new("📄 Import list…")).clicked() {
+                self.start_list_import(ListTarget::Encode);
+            }
+
+            if ui.add_enabled(!self.input_paths.is_empty(), egui::Button::new("💾 Export list…")).clicked() {
+                self.export_dialog_open = true;
+            }
+
+            if ui.button("Clear").clicked() {
+                self.input_paths.clear();
+                self.prune_input_selection();
+            }
+        });
+
+        self.render_import_progress(ui, ListTarget::Encode);
+
+        if !self.input_paths.is_empty() {
+            ui.add_space(5.0);
+            self.render_input_list(ui);
+        }
+
+        ui.add_space(5.0);
+        ui.checkbox(&mut self.settings.recursive, "Recursive (scan subfolders)");
+        ui.checkbox(&mut self.settings.respect_ignore_files, "Skip .nomedia and .jxlignore folders")
+            .on_hover_text("Leave out folders containing a .nomedia file and paths matching patterns in a .jxlignore file");
+        if self.input_paths.iter().any(|p| url_of(p).is_some()) {
+            ui.checkbox(&mut self.settings.keep_downloads, "Keep downloaded images")
+                .on_hover_text("Leave images downloaded from URLs in the temp folder instead of deleting them after the batch");
+        }
+    }
+
+    fn render_input_list(&mut self, ui: &mut egui::Ui) {
+        let mut clicked = None;
+        let mut reveal_request = None;
+
+        let row_height = ui.spacing().interact_size.y;
+        ScrollArea::vertical()
+            .id_salt("input_list")
+            .max_height(150.0)
+            .auto_shrink([false, true])
+            .show_rows(ui, row_height, self.input_paths.len(), |ui, row_range| {
+                for idx in row_range {
+                    let path = &self.input_paths[idx];
+                    let icon = if path.is_dir() { "📂" } else { "🖼" };
+                    let name = path
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_else(|| path.display().to_string());
+                    let selected = self.selected_inputs.contains(path);
+
+                    let response = ui
+                        .selectable_label(selected, format!("{} {}", icon, name))
+                        .on_hover_text(path.display().to_string());
+
+                    if response.clicked() {
+                        clicked = Some(idx);
+                    }
+
+                    response.context_menu(|ui| {
+                        if ui.button("Reveal in file manager").clicked() {
+                            reveal_request = Some(path.clone());
+                            ui.close_menu();
+                        }
+                    });
+                }
+            });
+
+        if let Some(idx) = clicked {
+            let modifiers = ui.input(|i| i.modifiers);
+            self.select_input(idx, modifiers);
+        }
+
+        if let Some(path) = reveal_request {
+            self.reveal_path(&path);
+        }
+
+        // Delete removes the selection unless a text field is being edited
+        let delete_pressed = !ui.ctx().wants_keyboard_input()
+            && ui.input(|i| i.key_pressed(egui::Key::Delete));
+        if delete_pressed {
+            self.remove_selected_inputs();
+        }
+
+        ui.add_space(3.0);
+        ui.label(
+            RichText::new(format!(
+                "{} of {} selected",
+                self.selected_inputs.len(),
+                self.input_paths.len()
+            ))
+            .small()
+            .color(Color32::GRAY),
+        );
+
+        ui.horizontal_wrapped(|ui| {
+            let has_selection = !self.selected_inputs.is_empty();
+
+            if ui.add_enabled(has_selection, egui::Button::new("Remove selected")).clicked() {
+                self.remove_selected_inputs();
+            }
+
+            if ui.add_enabled(has_selection, egui::Button::new("Keep only selected")).clicked() {
+                let selected = &self.selected_inputs;
+                self.input_paths.retain(|p| selected.contains(p));
+                self.prune_input_selection();
+            }
+
+            if ui.button("Clear folders").clicked() {
+                self.input_paths.retain(|p| !p.is_dir());
+                self.prune_input_selection();
+            }
+
+            if ui.button("Clear files").clicked() {
+                self.input_paths.retain(|p| p.is_dir());
+                self.prune_input_selection();
+            }
+        });
+    }
+
+    /// Applies a click on the input list: plain click selects one item,
+    /// ctrl/cmd toggles, and shift selects the range from the anchor.
+    fn select_input(&mut self, idx: usize, modifiers: egui::Modifiers) {
+        let path = self.input_paths[idx].clone();
+
+        let anchor_idx = self
+            .selection_anchor
+            .as_ref()
+            .and_then(|anchor| self.input_paths.iter().position(|p| p == anchor));
+
+        if modifiers.shift {
+            if let Some(anchor_idx) = anchor_idx {
+                let (start, end) = if anchor_idx <= idx { (anchor_idx, idx) } else { (idx, anchor_idx) };
+                if !modifiers.command {
+                    self.selected_inputs.clear();
+                }
+                self.selected_inputs.extend(self.input_paths[start..=end].iter().cloned());
+                return;
+            }
+        }
+
+        if modifiers.command {
+            if !self.selected_inputs.remove(&path) {
+                self.selected_inputs.insert(path.clone());
+            }
+        } else {
+            self.selected_inputs.clear();
+            self.selected_inputs.insert(path.clone());
+        }
+        self.selection_anchor = Some(path);
+    }
+
+    fn remove_selected_inputs(&mut self) {
+        if self.selected_inputs.is_empty() {
+            return;
+        }
+
+        let selected = &self.selected_inputs;
+        self.input_paths.retain(|p| !selected.contains(p));
+        self.prune_input_selection();
+    }
+
+    /// Drops selection entries that no longer refer to an item in the list.
+    fn prune_input_selection(&mut self) {
+        let remaining: HashSet<&PathBuf> = self.input_paths.iter().collect();
+        self.selected_inputs.retain(|p| remaining.contains(p));
+        if let Some(anchor) = &self.selection_anchor {
+            if !remaining.contains(anchor) {
+                self.selection_anchor = None;
+            }
+        }
+        self.discard_unused_pasted_images();
+    }
+
+    /// Deletes pasted images that were removed from the list, unless the
+    /// running batch still has to read them.
+    fn discard_unused_pasted_images(&mut self) {
+        let listed: HashSet<&PathBuf> = self.input_paths.iter().chain(&self.batch_pasted_images).collect();
+        self.pasted_images.retain(|path| {
+            listed.contains(path) || {
+                let _ = std::fs::remove_file(path);
+                false
+            }
+        });
+    }
+
+    fn render_output_section(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Output");
+        ui.add_space(5.0);
+
+        output_dir_field(ui, &mut self.output_dir_text, &mut self.settings.output_dir);
+
+        ui.add_space(5.0);
+        ui.checkbox(&mut self.settings.keep_structure, "Keep input folder structure");
+        overwrite_controls(ui, "encode_overwrite", &mut self.settings.overwrite, &mut self.settings.transactional);
+
+        ui.horizontal(|ui| {
+            const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+            let mut limited = self.settings.output_budget_bytes.is_some();
+            if ui
+                .checkbox(&mut limited, "Stop at")
+                .on_hover_text("Stop before the outputs add up to more than this, e.g. to fill a memory card")
+                .changed()
+            {
+                self.settings.output_budget_bytes = limited.then_some(32 * GB as u64);
+            }
+            if let Some(budget) = &mut self.settings.output_budget_bytes {
+                let mut gb = *budget as f64 / GB;
+                if ui.add(egui::DragValue::new(&mut gb).range(0.01..=100_000.0).speed(0.1).suffix(" GB")).changed() {
+                    *budget = (gb * GB) as u64;
+                }
+                ui.label("of output");
+            }
+        });
+    }
+
+    fn render_options_section(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Conversion Options");
+        ui.add_space(5.0);
+
+        let conflicts = check_settings(&self.settings);
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.lossless, "Lossless (all formats)");
+            conflict_marker(ui, &conflicts, Control::Lossless);
+        });
+        ui.add_space(3.0);
+        
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.jpeg_lossless, "JPEG Lossless");
+            ui.label(RichText::new("(uses --lossless_jpeg=1)").small().color(Color32::GRAY));
+            conflict_marker(ui, &conflicts, Control::JpegLossless);
+        });
+        ui.add_space(5.0);
+
+        ui.horizontal(|ui| {
+            ui.label("Quality:");
+            ui.add_enabled(
+                !self.settings.lossless,
+                Slider::new(&mut self.settings.quality, 1..=100),
+            );
+            if !self.settings.lossless {
+                let distance = distance_from_quality(f32::from(self.settings.quality));
+                ui.label(RichText::new(format!("≈ d {:.2}", distance)).small().color(Color32::GRAY))
+                    .on_hover_text("The butteraugli distance cjxl derives from this quality; 1.0 is visually lossless");
+            }
+            conflict_marker(ui, &conflicts, Control::Quality);
+        });
+        adaptive_quality_controls(ui, &mut self.settings.adaptive_quality, self.settings.lossless);
+
+        ui.add_space(5.0);
+
+        ui.horizontal(|ui| {
+            ui.label("Effort:");
+            let max_effort = if self.engine.capabilities().supports_effort_10 { 10 } else { 9 };
+            ui.add(Slider::new(&mut self.settings.effort, 1..=max_effort)).on_hover_text(if max_effort == 10 {
+                "10 is very slow and only worth it for archiving"
+            } else {
+                "Effort 10 needs cjxl 0.10 or newer"
+            });
+            conflict_marker(ui, &conflicts, Control::Effort);
+        });
+
+        ui.add_space(5.0);
+
+        ui.horizontal(|ui| {
+            let mut override_bitdepth = self.settings.override_bitdepth.is_some();
+            if ui
+                .checkbox(&mut override_bitdepth, "Override bit depth:")
+                .on_hover_text("Record this many bits per sample in the JXL header, e.g. 10 for 10-bit data in 16-bit PNGs. Losslessly transcoded JPEGs keep their own depth")
+                .changed()
+            {
+                self.settings.override_bitdepth = override_bitdepth.then_some(10);
+            }
+            if let Some(bits) = &mut self.settings.override_bitdepth {
+                ui.add(egui::DragValue::new(bits).range(1..=32).suffix(" bits"));
+            }
+            conflict_marker(ui, &conflicts, Control::BitDepth);
+        });
+
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            ui.label("Animated GIF/APNG inputs:");
+            conflict_marker(ui, &conflicts, Control::Animation);
+        });
+        animation_timing_controls(ui, &mut self.settings.frame_delay_ms, &mut self.settings.loop_count);
+
+        ui.add_space(5.0);
+
+        ui.horizontal(|ui| {
+            ui.label("Extra arguments:");
+            let response = ui
+                .add(egui::TextEdit::singleline(&mut self.extra_args_text).hint_text("e.g. --photon_noise_iso=800"))
+                .on_hover_text("Passed to cjxl as-is, after the options above");
+            let parsed = shlex::split(&self.extra_args_text);
+            if response.changed() {
+                if let Some(args) = parsed {
+                    self.settings.extra_args = args;
+                }
+            } else if !response.has_focus() && parsed.as_ref() != Some(&self.settings.extra_args) {
+                // Changed elsewhere, e.g. by loading a profile
+                self.extra_args_text = join_args(&self.settings.extra_args);
+            }
+            conflict_marker(ui, &conflicts, Control::ExtraArgs);
+        });
+        if shlex::split(&self.extra_args_text).is_none() {
+            ui.label(RichText::new("Unterminated quote; the last valid arguments are used").small().color(Color32::YELLOW));
+        }
+        if let Err(error) = self.engine.check_capabilities(&self.settings) {
+            ui.label(RichText::new(error).small().color(Color32::YELLOW));
+        }
+
+        ui.add_space(5.0);
+
+        ui.add_enabled_ui(self.engine.is_available() && self.test_run.is_none(), |ui| {
+            ui.menu_button("🧪 Test on one file…", |ui| {
+                let first = ui
+                    .add_enabled(!self.input_paths.is_empty(), egui::Button::new("First input file"))
+                    .on_hover_text("Convert the first image in the input list with the current settings");
+                if first.clicked() {
+                    ui.close_menu();
+                    match self.first_input_image() {
+                        Some(input) => self.start_test_run(input),
+                        None => self.add_log(LogEntry::Warning("No image among the inputs to test on.".to_string())),
+                    }
+                }
+                if ui.button("Choose file…").clicked() {
+                    ui.close_menu();
+                    if let Some(input) = rfd::FileDialog::new()
+                        .set_title("Choose a Sample Image")
+                        .add_filter("Images", SUPPORTED_IMAGE_EXTENSIONS)
+                        .pick_file()
+                    {
+                        self.start_test_run(input);
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Convert a single file into a temp folder to check the settings before a big batch");
+        });
+
+        if ui
+            .button("📋 Paste Command…")
+            .on_hover_text("Take quality, effort and other options from a cjxl command line")
+            .clicked()
+        {
+            self.paste_command = Some(String::new());
+        }
+
+        if ui
+            .button("Save current settings as sidecar here…")
+            .on_hover_text(format!(
+                "Writes {} into a folder; files under it will use these options regardless of the global settings",
+                SIDECAR_FILE_NAME
+            ))
+            .clicked()
+        {
+            self.save_sidecar();
+        }
+
+        ui.add_space(10.0);
+        ui.separator();
+        ui.add_space(5.0);
+
+        // Command preview
+        ui.label(RichText::new("Command Preview:").strong());
+        if let Some(cjxl) = &self.tools.cjxl {
+            ui.label(RichText::new(format!("cjxl {}", cjxl.describe())).small().color(Color32::GRAY));
+        }
+        ui.add_space(3.0);
+        
+        self.refresh_command_preview();
+        let Some(preview) = &self.command_preview else { return };
+
+        // JPEG example
+        ui.label(RichText::new("JPEG files:").small().color(Color32::LIGHT_GRAY));
+        show_preview_command(ui, &preview.jpeg);
+
+        ui.add_space(3.0);
+
+        // Non-JPEG example
+        ui.label(RichText::new("Other formats:").small().color(Color32::LIGHT_GRAY));
+        show_preview_command(ui, &preview.other);
+
+        ui.add_space(5.0);
+        let can_export = !self.input_paths.is_empty() && self.engine.is_available();
+        if ui
+            .add_enabled(can_export, egui::Button::new("📜 Export Script…"))
+            .on_hover_text("Write the cjxl command for every file in the batch to a .sh, .bat or .ps1 script")
+            .clicked()
+        {
+            self.export_script();
+        }
+    }
+
+    /// The first supported image among the inputs, looking inside folders.
+    fn first_input_image(&self) -> Option<PathBuf> {
+        self.input_paths.iter().filter(|p| url_of(p).is_none()).find_map(|input| {
+            self.engine
+                .expand_paths(std::slice::from_ref(input), &self.settings.scan_options())
+                .files
+                .into_iter()
+                .find(|p| is_supported_image(p))
+        })
+    }
+
+    /// Converts `input` alone with the current settings into a fresh temp
+    /// folder. Hooks, notifications and the output budget are left out.
+    fn start_test_run(&mut self, input: PathBuf) {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+        let dir = std::env::temp_dir()
+            .join("jxl-converter-test")
+            .join(format!("{}-{}", std::process::id(), nanos));
+        let settings = ConversionSettings {
+            output_dir: dir.clone(),
+            keep_structure: false,
+            overwrite: OverwritePolicy::Overwrite,
+            transactional: false,
+            output_budget_bytes: None,
+            ..self.settings.clone()
+        };
+        let tool_settings = ToolSettings {
+            post_hook: String::new(),
+            pre_batch_hook: String::new(),
+            post_batch_hook: String::new(),
+            notify_url: String::new(),
+            ..self.tool_settings.clone()
+        };
+
+        let engine = ConversionEngine::from_tools(&self.tools).with_tool_settings(tool_settings);
+        if let Err(error) = engine.validate_settings(&settings) {
+            self.add_log(LogEntry::Error(error));
+            return;
+        }
+        let output = plan_output_path(&input, &OutputPlan::for_encode(std::slice::from_ref(&input), &settings));
+        let args = engine.cjxl_args(&input, &output, &settings);
+        let command = PreviewCommand {
+            command: Shell::native().command_line("cjxl".as_ref(), &args),
+            note: settings.adaptive_quality.is_some().then(|| "adaptive quality tries several distances".to_string()),
+        };
+
+        let (tx, rx) = channel();
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+        let inputs = vec![input.clone()];
+        let (thread_dir, thread_cancel) = (dir.clone(), Arc::clone(&cancel_flag));
+        thread::spawn(move || {
+            engine.convert_batch(inputs, settings, tx, Arc::clone(&thread_cancel));
+            // The panel was closed before the run finished
+            if thread_cancel.load(Ordering::Relaxed) {
+                let _ = std::fs::remove_dir_all(&thread_dir);
+            }
+        });
+
+        self.test_run = Some(TestRun {
+            input,
+            dir,
+            command,
+            started: Instant::now(),
+            cancel_flag,
+            progress_rx: Some(rx),
+            outcome: None,
+        });
+    }
+
+    fn process_test_run_messages(&mut self) {
+        let Some(run) = &mut self.test_run else { return };
+        let Some(rx) = &run.progress_rx else { return };
+
+        let mut disconnected = false;
+        loop {
+            match rx.try_recv() {
+                Ok(ProgressMessage::Success { output, input_bytes, output_bytes, .. }) => {
+                    let duration = run.started.elapsed();
+                    run.outcome = Some(Ok(TestOutcome { output, input_bytes, output_bytes, duration }));
+                }
+                Ok(ProgressMessage::Error { error, .. }) => run.outcome = Some(Err(error)),
+                Ok(ProgressMessage::Skipped { reason, .. }) => run.outcome = Some(Err(format!("Skipped: {}", reason))),
+                Ok(_) => {}
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    disconnected = true;
+                    break;
+                }
+            }
+        }
+        if disconnected {
+            run.progress_rx = None;
+            if run.outcome.is_none() {
+                run.outcome = Some(Err("The test stopped without converting the file".to_string()));
+            }
+        }
+    }
+
+    fn render_test_run_window(&mut self, ctx: &egui::Context) {
+        let Some(run) = &self.test_run else { return };
+
+        let mut open = true;
+        let mut close = false;
+        let mut open_output = None;
+        let mut reveal = None;
+        let mut compare = None;
+
+        egui::Window::new("Test Conversion")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(460.0)
+            .show(ctx, |ui| {
+                ui.label(RichText::new(run.input.display().to_string()).strong());
+                ui.add_space(5.0);
+
+                match &run.outcome {
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!("Converting… {:.1} s", run.started.elapsed().as_secs_f32()));
+                        });
+                    }
+                    Some(Err(error)) => {
+                        ui.colored_label(Color32::LIGHT_RED, error);
+                    }
+                    Some(Ok(outcome)) => {
+                        const KB: f64 = 1024.0;
+                        egui::Grid::new("test_run_result").num_columns(2).show(ui, |ui| {
+                            ui.label("Input:");
+                            ui.label(format!("{:.1} KB", outcome.input_bytes as f64 / KB));
+                            ui.end_row();
+                            ui.label("Output:");
+                            ui.label(format!("{:.1} KB", outcome.output_bytes as f64 / KB));
+                            ui.end_row();
+                            ui.label("Ratio:");
+                            if outcome.input_bytes > 0 {
+                                let ratio = outcome.output_bytes as f64 / outcome.input_bytes as f64;
+                                ui.label(format!("{:.1}% of the original ({:.2}×)", ratio * 100.0, 1.0 / ratio.max(f64::EPSILON)));
+                            } else {
+                                ui.label("—");
+                            }
+                            ui.end_row();
+                            ui.label("Time:");
+                            ui.label(format!("{:.2} s", outcome.duration.as_secs_f64()));
+                            ui.end_row();
+                        });
+                    }
+                }
+
+                ui.add_space(5.0);
+                ui.label(RichText::new("Command:").small().color(Color32::LIGHT_GRAY));
+                show_preview_command(ui, &run.command);
+
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    let output = run.outcome.as_ref().and_then(|outcome| outcome.as_ref().ok()).map(|o| &o.output);
+                    if ui.add_enabled(output.is_some(), egui::Button::new("Open result")).clicked() {
+                        open_output = output.cloned();
+                    }
+                    if ui.add_enabled(output.is_some(), egui::Button::new("Show in folder")).clicked() {
+                        reveal = output.cloned();
+                    }
+                    if ui
+                        .add_enabled(output.is_some(), egui::Button::new("Compare"))
+                        .on_hover_text("Compare the result with the input in the Compare tab")
+                        .clicked()
+                    {
+                        compare = output.map(|output| (run.input.clone(), output.clone()));
+                    }
+                    if ui.button("Close").on_hover_text("Deletes the test output").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if run.outcome.is_none() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        if let Some(path) = open_output {
+            if let Err(e) = open_with_default_app(&path) {
+                self.add_log(LogEntry::Error(e));
+            }
+        }
+        if let Some(path) = reveal {
+            self.reveal_path(&path);
+        }
+        if let Some((input, output)) = compare {
+            self.compare.a = Some(input);
+            self.compare.b = Some(output);
+            self.active_tab = AppTab::Compare;
+            self.start_compare(ctx);
+        }
+        if close || !open {
+            if let Some(run) = self.test_run.take() {
+                run.discard();
+            }
+        }
+    }
+
+    /// Writes the whole encode batch as a script; the shell is chosen by the
+    /// extension of the file picked.
+    fn export_script(&mut self) {
+        let default_name = if Shell::native() == Shell::Cmd { "convert.bat" } else { "convert.sh" };
+        let Some(path) = rfd::FileDialog::new()
+            .set_title("Export Script")
+            .set_file_name(default_name)
+            .add_filter("Shell script", &["sh"])
+            .add_filter("Batch file", &["bat", "cmd"])
+            .add_filter("PowerShell script", &["ps1"])
+            .save_file()
+        else {
+            return;
         };
 
-        ui.put(
-            drop_area.rect,
-            egui::Label::new(RichText::new(text).size(14.0).color(Color32::LIGHT_GRAY)),
-        );
+        let Some(shell) = Shell::from_path(&path) else {
+            self.add_log(LogEntry::Error(format!(
+                "Can't tell which shell {} is for; use .sh, .bat or .ps1",
+                path.display()
+            )));
+            return;
+        };
+
+        let engine = ConversionEngine::from_tools(&self.tools).with_tool_settings(self.tool_settings.clone());
+        let result = std::fs::File::create(&path).and_then(|file| {
+            write_encode_script(&engine, &self.input_paths, &self.settings, shell, std::io::BufWriter::new(file))
+        });
+        match result {
+            Ok(count) => self.add_log(LogEntry::Info(format!(
+                "Exported {} command(s) to {}",
+                count,
+                path.display()
+            ))),
+            Err(e) => self.add_log(LogEntry::Error(format!("Failed to write {}: {}", path.display(), e))),
+        }
+    }
+
+    fn save_sidecar(&mut self) {
+        let Some(folder) = rfd::FileDialog::new()
+            .set_title("Choose Folder for Sidecar Settings")
+            .pick_folder()
+        else {
+            return;
+        };
+
+        match SidecarSettings::from_settings(&self.settings).save(&folder) {
+            Ok(path) => self.add_log(LogEntry::Info(format!("Saved sidecar settings to {}", path.display()))),
+            Err(e) => self.add_log(LogEntry::Error(e)),
+        }
+    }
+
+    /// Rebuilds the preview when the inputs or settings changed since the
+    /// last frame. Finding sample files touches the filesystem, so that only
+    /// happens when the inputs change.
+    fn refresh_command_preview(&mut self) {
+        if let Some(preview) = &self.command_preview {
+            if preview.inputs == self.input_paths
+                && preview.recursive == self.settings.recursive
+                && preview.settings == self.settings
+                && preview.tool_settings == self.tool_settings
+            {
+                return;
+            }
+        }
+
+        let samples = match self.command_preview.take() {
+            Some(preview) if preview.inputs == self.input_paths && preview.recursive == self.settings.recursive => {
+                preview.samples
+            }
+            _ => find_preview_samples(&self.input_paths, self.settings.recursive),
+        };
+
+        let plan = OutputPlan::for_encode(&self.input_paths, &self.settings);
+        self.command_preview = Some(CommandPreview {
+            jpeg: self.generate_command_preview(samples.0.as_deref(), &plan, true),
+            other: self.generate_command_preview(samples.1.as_deref(), &plan, false),
+            inputs: self.input_paths.clone(),
+            recursive: self.settings.recursive,
+            settings: self.settings.clone(),
+            tool_settings: self.tool_settings.clone(),
+            samples,
+        });
+    }
+
+    /// The cjxl command for `sample`, or for placeholder paths without one.
+    fn generate_command_preview(&self, sample: Option<&Path>, plan: &OutputPlan, is_jpeg: bool) -> PreviewCommand {
+        let placeholder = if is_jpeg { "input.jpg" } else { "input.png" };
+        let (input, output) = match sample {
+            Some(input) => (input.to_path_buf(), plan_output_path(input, plan)),
+            None => (PathBuf::from(placeholder), PathBuf::from("output.jxl")),
+        };
+
+        let engine = ConversionEngine::from_tools(&self.tools).with_tool_settings(self.tool_settings.clone());
+        let args = engine.cjxl_args(&input, &output, &self.settings);
+        let command = Shell::native().command_line("cjxl".as_ref(), &args);
+
+        let uses_quality = !(self.settings.lossless || is_jpeg && self.settings.jpeg_lossless);
+        let note = uses_quality.then(|| {
+            let distance = distance_from_quality(f32::from(self.settings.quality));
+            format!("q {} ≈ d {:.2}", self.settings.quality, distance)
+        });
+        PreviewCommand { command, note }
+    }
+
+    fn render_controls_section(&mut self, ui: &mut egui::Ui) {
+        ui.add_space(10.0);
+        ui.separator();
+        ui.add_space(10.0);
+
+        ui.horizontal(|ui| {
+            let (can_start, button_text) = match self.active_tab {
+                AppTab::Encode => {
+                    let can_start = self.progress_rx.is_none()
+                        && self.engine.is_available() 
+                        && !self.input_paths.is_empty()
+                        && !self.settings.output_dir.as_os_str().is_empty();
+                    (can_start, "▶ Start Encoding")
+                }
+                AppTab::Decode => {
+                    let can_start = self.progress_rx.is_none()
+                        && self.engine.is_decode_available() 
+                        && !self.decode_items.is_empty()
+                        && !self.decode_settings.output_dir.as_os_str().is_empty();
+                    (can_start, "▶ Start Decoding")
+                }
+                AppTab::Compare => (false, "▶ Start"),
+            };
 
-        // Handle drag and drop
-        ui.ctx().input(|i| {
-            if !i.raw.dropped_files.is_empty() {
-                for file in &i.raw.dropped_files {
-                    if let Some(path) = &file.path {
-                        if !self.input_paths.contains(path) {
-                            self.input_paths.push(path.clone());
-                        }
-                    }
+            if let Some(armed) = &self.armed_start {
+                let remaining = (armed.at - Local::now()).to_std().unwrap_or_default();
+                let text = if armed.deferred {
+                    "⏰ Waiting for AC power, click to cancel".to_string()
+                } else {
+                    format!("⏰ Starts in {}, click to cancel", format_countdown(remaining))
+                };
+                if ui.button(text).on_hover_text(armed.at.format("%Y-%m-%d %H:%M").to_string()).clicked() {
+                    self.disarm_schedule();
                 }
+            } else if ui.add_enabled(can_start, egui::Button::new(button_text)).clicked()
+                && self.confirm_output_dir()
+                && self.confirm_conflicts()
+                && self.confirm_animation_loss()
+                && self.confirm_large_batch()
+            {
+                self.start_or_arm(ui.ctx());
+            }
+
+            // Also stops a post-batch hook still running
+            if ui.add_enabled(self.progress_rx.is_some(), egui::Button::new("⬛ Cancel")).clicked() {
+                self.cancel_conversion();
+            }
+
+            if self.tool_settings.has_hooks() {
+                let hooks: Vec<String> = [
+                    ("Before the batch", &self.tool_settings.pre_batch_hook),
+                    ("After each file", &self.tool_settings.post_hook),
+                    ("After the batch", &self.tool_settings.post_batch_hook),
+                ]
+                .into_iter()
+                .filter(|(_, hook)| !hook.trim().is_empty())
+                .map(|(when, hook)| format!("{}: {}", when, hook.trim()))
+                .collect();
+                ui.add_space(10.0);
+                ui.add_enabled(!self.is_converting, egui::Checkbox::new(&mut self.run_hooks, "Run hooks"))
+                    .on_hover_text(hooks.join("\n"));
             }
+
+            ui.add_space(10.0);
+            ui.add_enabled_ui(self.armed_start.is_none() && !self.is_converting, |ui| {
+                ui.checkbox(&mut self.schedule_enabled, "Start at…");
+                if self.schedule_enabled {
+                    ui.add(egui::DragValue::new(&mut self.schedule_hour).range(0..=23).custom_formatter(|v, _| format!("{:02}", v)));
+                    ui.label(":");
+                    ui.add(egui::DragValue::new(&mut self.schedule_minute).range(0..=59).custom_formatter(|v, _| format!("{:02}", v)));
+                    egui::ComboBox::from_id_salt("schedule_day")
+                        .selected_text(if self.schedule_tomorrow { "tomorrow" } else { "today" })
+                        .width(90.0)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.schedule_tomorrow, false, "today");
+                            ui.selectable_value(&mut self.schedule_tomorrow, true, "tomorrow");
+                        });
+                }
+            });
         });
 
-        ui.add_space(10.0);
+        if self.is_converting {
+            ui.add_space(10.0);
+            let progress = if self.total_files > 0 {
+                self.current_progress as f32 / self.total_files as f32
+            } else {
+                0.0
+            };
+
+            ui.add(egui::ProgressBar::new(progress).text(format!(
+                "{} / {}",
+                self.current_progress, self.total_files
+            )));
+
+            if self.throttle.state() == ThrottleState::Throttled {
+                ui.label(
+                    RichText::new(format!(
+                        "throttled: 1 job, {:.1} s between files",
+                        self.throttle_settings.delay_ms as f64 / 1000.0
+                    ))
+                    .small()
+                    .color(Color32::GRAY),
+                );
+            }
+
+            let cooling = self.cooling_until.and_then(|until| until.checked_duration_since(Instant::now()));
+            if self.pause_flag.load(Ordering::Relaxed) {
+                ui.label(RichText::new("⏸ Paused while on battery").small().color(Color32::from_rgb(255, 200, 100)));
+            } else if let Some(left) = cooling {
+                let secs = left.as_secs_f64().ceil() as u64;
+                ui.label(RichText::new(format!("cooling down… {}s", secs)).small().italics());
+            } else if !self.current_file.is_empty() {
+                let text = match self.current_frame {
+                    Some((frame, Some(total))) => format!("{} — frame {}/{}", self.current_file, frame, total),
+                    Some((frame, None)) => format!("{} — frame {}", self.current_file, frame),
+                    None => self.current_file.clone(),
+                };
+                ui.label(RichText::new(text).small().italics());
+            }
+        } else if !self.remaining_inputs.is_empty() && self.active_tab == AppTab::Encode {
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                ui.label(
+                    RichText::new(format!("{} file(s) left by the output budget", self.remaining_inputs.len()))
+                        .color(Color32::from_rgb(255, 200, 100)),
+                );
+                if ui.button("Keep only these").on_hover_text("Replace the input list with the files left over").clicked() {
+                    self.input_paths = std::mem::take(&mut self.remaining_inputs);
+                    self.prune_input_selection();
+                }
+                if ui.button("Export list…").clicked() {
+                    let entries =
+                        self.remaining_inputs.iter().map(|path| ListEntry { path: path.clone(), formats: Vec::new() }).collect();
+                    self.save_list(entries);
+                }
+                if ui.small_button("✖").on_hover_text("Dismiss").clicked() {
+                    self.remaining_inputs.clear();
+                }
+            });
+        }
+    }
+
+    fn reveal_path(&mut self, path: &Path) {
+        if let Err(e) = reveal_in_file_manager(path) {
+            self.add_log(LogEntry::Error(format!("Failed to reveal {}: {}", path.display(), e)));
+        }
+    }
 
+    fn render_log_section(&mut self, ui: &mut egui::Ui) {
+        ui.add_space(10.0);
+        ui.separator();
+        ui.add_space(5.0);
         ui.horizontal(|ui| {
-            if ui.button("📁 Add Files").clicked() {
+            ui.heading("Log");
+            if ui
+                .button("🔄 Re-scan tools")
+                .on_hover_text("Look for cjxl and djxl again, e.g. after installing them")
+                .clicked()
+            {
+                self.rescan_tools();
+            }
+        });
+        ui.add_space(5.0);
+
+        let scroll_area = ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .stick_to_bottom(true);
+
+        let mut reveal_request = None;
+
+        scroll_area.show(ui, |ui| {
+            for entry in &self.log_entries {
+                let (color, text, output) = match entry {
+                    LogEntry::Info(s) | LogEntry::Table(s) => (Color32::LIGHT_GRAY, s, None),
+                    LogEntry::Success(s, output) => (Color32::from_rgb(100, 255, 100), s, output.as_ref()),
+                    LogEntry::Error(s) => (Color32::from_rgb(255, 100, 100), s, None),
+                    LogEntry::Warning(s) => (Color32::from_rgb(255, 200, 100), s, None),
+                };
+
+                if let Some(output) = output {
+                    ui.add(egui::Label::new(RichText::new(text).color(color).small()).sense(egui::Sense::click()))
+                        .context_menu(|ui| {
+                            if ui.button("Reveal in file manager").clicked() {
+                                reveal_request = Some(output.clone());
+                                ui.close_menu();
+                            }
+                        });
+                } else if matches!(entry, LogEntry::Table(_)) {
+                    ui.label(RichText::new(text).color(color).small().monospace());
+                } else {
+                    ui.label(RichText::new(text).color(color).small());
+                }
+            }
+
+            if self.scroll_to_bottom {
+                ui.scroll_to_cursor(Some(egui::Align::BOTTOM));
+                self.scroll_to_bottom = false;
+            }
+        });
+
+        if let Some(path) = reveal_request {
+            self.reveal_path(&path);
+        }
+    }
+
+    fn render_decode_input_section(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Input JXL Files");
+        ui.add_space(5.0);
+
+        ui.horizontal_wrapped(|ui| {
+            if ui.button("📁 Add JXL Files").clicked() {
                 if let Some(files) = rfd::FileDialog::new()
-                    .set_title("Select Image Files")
-                    .add_filter("Images", &["jpg", "jpeg", "png", "gif", "bmp", "tiff", "tif", "webp", "ppm", "pgm", "pnm"])
+                    .set_title("Select JXL Files")
+                    .add_filter("JPEG XL", &["jxl"])
                     .pick_files()
                 {
+                    let mut known = self.decode_item_keys();
                     for file in files {
-                        if !self.input_paths.contains(&file) {
-                            self.input_paths.push(file);
+                        if known.insert(path_key(&file)) {
+                            self.decode_items.push(DecodeItem {
+                                path: file,
+                                output_formats: vec![self.decode_settings.output_format],
+                                folder: None,
+                            });
                         }
                     }
                 }
@@ -280,293 +3168,490 @@ impl JxlConverterApp {
                     .set_title("Select Folder")
                     .pick_folder()
                 {
-                    if !self.input_paths.contains(&folder) {
-                        self.input_paths.push(folder);
-                    }
+                    let mut known = self.decode_item_keys();
+                    self.add_jxl_files_from_folder(&folder, &mut known);
                 }
             }
 
-            if ui.button("Clear").clicked() {
-                self.input_paths.clear();
+            if ui.button("📋 Paste").on_hover_text("Add file paths from the clipboard (Ctrl+V)").clicked() {
+                self.paste_paths_from_clipboard();
             }
-        });
 
-        ui.add_space(5.0);
-        ui.checkbox(&mut self.settings.recursive, "Recursive (scan subfolders)");
-    }
+            if ui.add_enabled(self.import_rx.is_none(), egui::Button::new("📄 Import list…")).clicked() {
+                self.start_list_import(ListTarget::Decode);
+            }
 
-    fn render_output_section(&mut self, ui: &mut egui::Ui) {
-        ui.heading("Output");
-        ui.add_space(5.0);
+            if ui.add_enabled(!self.decode_items.is_empty(), egui::Button::new("💾 Export list…")).clicked() {
+                self.export_list(ListTarget::Decode);
+            }
 
-        ui.horizontal(|ui| {
-            ui.label("Directory:");
-            ui.add(
-                egui::TextEdit::singleline(&mut self.settings.output_dir.to_string_lossy().to_string())
-                    .desired_width(ui.available_width() - 80.0)
-                    .interactive(false),
-            );
-            if ui.button("Browse").clicked() {
-                if let Some(folder) = rfd::FileDialog::new()
-                    .set_title("Select Output Directory")
-                    .pick_folder()
-                {
-                    self.settings.output_dir = folder;
-                }
+            if ui.button("Clear").clicked() {
+                self.decode_items.clear();
             }
         });
 
+        self.render_import_progress(ui, ListTarget::Decode);
+
         ui.add_space(5.0);
-        ui.checkbox(&mut self.settings.keep_structure, "Keep input folder structure");
+        ui.checkbox(&mut self.decode_settings.recursive, "Recursive (scan subfolders)");
+        ui.checkbox(&mut self.decode_settings.respect_ignore_files, "Skip .nomedia and .jxlignore folders")
+            .on_hover_text("Leave out folders containing a .nomedia file and paths matching patterns in a .jxlignore file");
+        
+        ui.add_space(10.0);
+        ui.label(format!("{} file(s) selected", self.decode_items.len()));
     }
 
-    fn render_options_section(&mut self, ui: &mut egui::Ui) {
-        ui.heading("Conversion Options");
-        ui.add_space(5.0);
+    /// Handles Ctrl+V and Alt+V when no text field has focus.
+    fn handle_paste_events(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
 
-        ui.checkbox(&mut self.settings.lossless, "Lossless (all formats)");
-        ui.add_space(3.0);
-        
-        ui.horizontal(|ui| {
-            ui.checkbox(&mut self.settings.jpeg_lossless, "JPEG Lossless");
-            ui.label(RichText::new("(uses --lossless_jpeg=1)").small().color(Color32::GRAY));
-        });
-        ui.add_space(5.0);
+        // Not Ctrl+Shift+V: egui-winit turns that into a paste of the
+        // clipboard's text, and sends nothing when it only holds an image.
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::ALT, egui::Key::V)) {
+            self.active_tab = AppTab::Encode;
+            self.paste_image_from_clipboard();
+        }
 
-        ui.horizontal(|ui| {
-            ui.label("Quality:");
-            ui.add_enabled(
-                !self.settings.lossless,
-                Slider::new(&mut self.settings.quality, 1..=100),
-            );
+        let pasted: Vec<String> = ctx.input(|i| {
+            i.events
+                .iter()
+                .filter_map(|e| match e {
+                    egui::Event::Paste(text) => Some(text.clone()),
+                    _ => None,
+                })
+                .collect()
         });
 
-        ui.add_space(5.0);
+        for text in pasted {
+            self.add_pasted_paths(parse_path_list(&text));
+        }
+    }
 
-        ui.horizontal(|ui| {
-            ui.label("Effort:");
-            ui.add(Slider::new(&mut self.settings.effort, 1..=9));
-        });
+    /// Reads the clipboard directly, which also picks up files copied in
+    /// Explorer/Finder that carry no text representation.
+    fn paste_paths_from_clipboard(&mut self) {
+        let mut clipboard = match arboard::Clipboard::new() {
+            Ok(c) => c,
+            Err(e) => {
+                self.add_log(LogEntry::Error(format!("Failed to access clipboard: {}", e)));
+                return;
+            }
+        };
 
-        ui.add_space(10.0);
-        ui.separator();
-        ui.add_space(5.0);
+        let paths = match clipboard.get().file_list() {
+            Ok(files) if !files.is_empty() => files,
+            _ => match clipboard.get_text() {
+                Ok(text) => parse_path_list(&text),
+                Err(_) => Vec::new(),
+            },
+        };
 
-        // Command preview
-        ui.label(RichText::new("Command Preview:").strong());
-        ui.add_space(3.0);
-        
-        // JPEG example
-        let jpeg_cmd = self.generate_command_preview(true);
-        ui.label(RichText::new("JPEG files:").small().color(Color32::LIGHT_GRAY));
-        ui.add(
-            egui::TextEdit::multiline(&mut jpeg_cmd.as_str())
-                .font(egui::TextStyle::Monospace)
-                .desired_width(f32::INFINITY)
-                .desired_rows(1)
-                .interactive(false)
-                .frame(true),
-        );
-        
-        ui.add_space(3.0);
-        
-        // Non-JPEG example
-        let other_cmd = self.generate_command_preview(false);
-        ui.label(RichText::new("Other formats:").small().color(Color32::LIGHT_GRAY));
-        ui.add(
-            egui::TextEdit::multiline(&mut other_cmd.as_str())
-                .font(egui::TextStyle::Monospace)
-                .desired_width(f32::INFINITY)
-                .desired_rows(1)
-                .interactive(false)
-                .frame(true),
-        );
+        if paths.is_empty() {
+            self.add_log(LogEntry::Warning("Clipboard doesn't contain any file paths.".to_string()));
+            return;
+        }
+
+        self.add_pasted_paths(paths);
     }
 
-    fn generate_command_preview(&self, is_jpeg: bool) -> String {
-        let mut cmd_parts = vec!["cjxl".to_string()];
-        
-        // Add quality/lossless options
-        if self.settings.lossless {
-            if is_jpeg {
-                cmd_parts.push("--lossless_jpeg=1".to_string());
-            } else {
-                cmd_parts.push("-d".to_string());
-                cmd_parts.push("0".to_string());
+    /// Saves the clipboard's image as a PNG in the temp folder and adds it to
+    /// the encode list, so it comes out as `clipboard-YYYYMMDD-HHMMSS.jxl`.
+    fn paste_image_from_clipboard(&mut self) {
+        let image = match arboard::Clipboard::new().and_then(|mut c| c.get_image()) {
+            Ok(image) => image,
+            Err(arboard::Error::ContentNotAvailable) => {
+                self.add_log(LogEntry::Warning(
+                    "Clipboard doesn't contain an image. Copy one from an image viewer, browser or screenshot tool first.".to_string(),
+                ));
+                return;
             }
-        } else if is_jpeg && self.settings.jpeg_lossless {
-            cmd_parts.push("--lossless_jpeg=1".to_string());
-        } else {
-            cmd_parts.push("-q".to_string());
-            cmd_parts.push(self.settings.quality.to_string());
-        }
-        
-        // Add effort option
-        cmd_parts.push("-e".to_string());
-        cmd_parts.push(self.settings.effort.to_string());
-        
-        // Add placeholder paths
-        if is_jpeg {
-            cmd_parts.push("input.jpg".to_string());
-            cmd_parts.push("output.jxl".to_string());
-        } else {
-            cmd_parts.push("input.png".to_string());
-            cmd_parts.push("output.jxl".to_string());
+            Err(e) => {
+                self.add_log(LogEntry::Error(format!("Failed to read an image from the clipboard: {}", e)));
+                return;
+            }
+        };
+
+        let dir = std::env::temp_dir().join("jxl-converter-clipboard");
+        let stamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let (width, height) = (image.width as u32, image.height as u32);
+        match save_clipboard_image(&dir, &stamp, width, height, image.bytes.into_owned()) {
+            Ok(path) => {
+                self.add_log(LogEntry::Info(format!(
+                    "Pasted a {}×{} image from the clipboard as {}",
+                    width,
+                    height,
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                )));
+                self.pasted_images.insert(path.clone());
+                self.input_paths.push(path);
+            }
+            Err(e) => self.add_log(LogEntry::Error(e)),
         }
-        
-        cmd_parts.join(" ")
     }
 
-    fn render_controls_section(&mut self, ui: &mut egui::Ui) {
-        ui.add_space(10.0);
-        ui.separator();
-        ui.add_space(10.0);
+    /// Routes pasted paths to the list of the active tab and logs a summary.
+    fn add_pasted_paths(&mut self, paths: Vec<PathBuf>) {
+        let mut added = 0;
+        let mut missing = 0;
+        let mut unsupported = 0;
+        let mut known_inputs = self.input_keys();
+        let mut known_decode_items = self.decode_item_keys();
 
-        ui.horizontal(|ui| {
-            let (can_start, button_text) = match self.active_tab {
+        for path in paths {
+            // URLs are downloaded when the batch runs
+            if url_of(&path).is_some() {
+                if self.active_tab != AppTab::Encode {
+                    unsupported += 1;
+                } else if known_inputs.insert(path_key(&path)) {
+                    self.input_paths.push(path);
+                    added += 1;
+                }
+                continue;
+            }
+
+            if !path.exists() {
+                missing += 1;
+                continue;
+            }
+
+            match self.active_tab {
                 AppTab::Encode => {
-                    let can_start = !self.is_converting 
-                        && self.engine.is_available() 
-                        && !self.input_paths.is_empty()
-                        && !self.settings.output_dir.as_os_str().is_empty();
-                    (can_start, "▶ Start Encoding")
+                    if path.is_dir() || is_supported_image(&path) {
+                        if known_inputs.insert(path_key(&path)) {
+                            self.input_paths.push(path);
+                            added += 1;
+                        }
+                    } else {
+                        unsupported += 1;
+                    }
                 }
                 AppTab::Decode => {
-                    let can_start = !self.is_converting 
-                        && self.engine.is_decode_available() 
-                        && !self.decode_items.is_empty()
-                        && !self.decode_settings.output_dir.as_os_str().is_empty();
-                    (can_start, "▶ Start Decoding")
+                    if path.is_dir() {
+                        let before = self.decode_items.len();
+                        self.add_jxl_files_from_folder(&path, &mut known_decode_items);
+                        added += self.decode_items.len() - before;
+                    } else if is_jxl_file(&path) {
+                        if known_decode_items.insert(path_key(&path)) {
+                            self.decode_items.push(DecodeItem {
+                                path,
+                                output_formats: vec![self.decode_settings.output_format],
+                                folder: None,
+                            });
+                            added += 1;
+                        }
+                    } else {
+                        unsupported += 1;
+                    }
                 }
-            };
+                AppTab::Compare => {
+                    if path.is_file() {
+                        self.compare.add(path);
+                        added += 1;
+                    } else {
+                        unsupported += 1;
+                    }
+                }
+            }
+        }
+
+        let mut summary = format!("Added {} file(s) from clipboard", added);
+        if missing > 0 {
+            summary.push_str(&format!(", ignored {} non-existent", missing));
+        }
+        if unsupported > 0 {
+            summary.push_str(&format!(", {} unsupported", unsupported));
+        }
+
+        if added > 0 {
+            self.add_log(LogEntry::Info(summary));
+        } else {
+            self.add_log(LogEntry::Warning(summary));
+        }
+    }
+
+    fn start_list_import(&mut self, target: ListTarget) {
+        let Some(list_file) = rfd::FileDialog::new()
+            .set_title("Import File List")
+            .add_filter("File lists", &["txt", "m3u", "m3u8", "lst"])
+            .add_filter("All files", &["*"])
+            .pick_file()
+        else {
+            return;
+        };
+
+        let (tx, rx) = channel();
+        self.import_rx = Some((target, rx));
+        self.import_progress = (0, 0);
+
+        thread::spawn(move || {
+            import_list(&list_file, target, tx);
+        });
+    }
 
-            if ui.add_enabled(can_start, egui::Button::new(button_text)).clicked() {
-                match self.active_tab {
-                    AppTab::Encode => self.start_conversion(),
-                    AppTab::Decode => self.start_decode(),
-                }
+    fn process_import_messages(&mut self) {
+        let Some((target, rx)) = &self.import_rx else {
+            return;
+        };
+        let target = *target;
+
+        let mut finished = None;
+        while let Ok(msg) = rx.try_recv() {
+            match msg {
+                ImportMessage::Progress { done, total } => self.import_progress = (done, total),
+                ImportMessage::Finished(result) => finished = Some(result),
             }
+        }
 
-            if ui.add_enabled(self.is_converting, egui::Button::new("⬛ Cancel")).clicked() {
-                self.cancel_conversion();
+        let Some(result) = finished else {
+            return;
+        };
+        self.import_rx = None;
+
+        let result = match result {
+            Ok(r) => r,
+            Err(e) => {
+                self.add_log(LogEntry::Error(e));
+                return;
             }
-        });
+        };
 
-        if self.is_converting {
-            ui.add_space(10.0);
-            let progress = if self.total_files > 0 {
-                self.current_progress as f32 / self.total_files as f32
-            } else {
-                0.0
-            };
+        let mut added = 0;
+        match target {
+            ListTarget::Encode => {
+                let mut known = self.input_keys();
+                for entry in result.entries {
+                    if known.insert(path_key(&entry.path)) {
+                        self.input_paths.push(entry.path);
+                        added += 1;
+                    }
+                }
+            }
+            ListTarget::Decode => {
+                let mut known = self.decode_item_keys();
+                for entry in result.entries {
+                    if known.insert(path_key(&entry.path)) {
+                        self.decode_items.push(DecodeItem {
+                            path: entry.path,
+                            output_formats: if entry.formats.is_empty() {
+                                vec![self.decode_settings.output_format]
+                            } else {
+                                entry.formats
+                            },
+                            folder: None,
+                        });
+                        added += 1;
+                    }
+                }
+            }
+        }
 
-            ui.add(egui::ProgressBar::new(progress).text(format!(
-                "{} / {}",
-                self.current_progress, self.total_files
-            )));
+        self.add_log(LogEntry::Info(format!(
+            "Imported list: {} added, {} missing, {} unsupported",
+            added, result.missing, result.unsupported
+        )));
+    }
+
+    fn export_list(&mut self, target: ListTarget) {
+        let entries: Vec<ListEntry> = match target {
+            ListTarget::Encode => {
+                let paths = if self.export_expanded {
+                    self.engine
+                        .expand_paths(&self.input_paths, &self.settings.scan_options())
+                        .files
+                        .into_iter()
+                        .filter(|p| is_supported_image(p))
+                        .collect()
+                } else {
+                    self.input_paths.clone()
+                };
+                paths.into_iter().map(|path| ListEntry { path, formats: Vec::new() }).collect()
+            }
+            ListTarget::Decode => self
+                .decode_items
+                .iter()
+                .map(|item| ListEntry {
+                    path: item.path.clone(),
+                    formats: item.output_formats.clone(),
+                })
+                .collect(),
+        };
+        self.save_list(entries);
+    }
 
-            if !self.current_file.is_empty() {
-                ui.label(RichText::new(&self.current_file).small().italics());
+    /// Asks where to save `entries` as a file list, then writes it.
+    fn save_list(&mut self, entries: Vec<ListEntry>) {
+        let Some(list_file) = rfd::FileDialog::new()
+            .set_title("Export File List")
+            .add_filter("File lists", &["txt"])
+            .set_file_name("file-list.txt")
+            .save_file()
+        else {
+            return;
+        };
+
+        match write_list(&list_file, &entries) {
+            Ok(skipped) => {
+                self.add_log(LogEntry::Info(format!(
+                    "Exported {} entries to {}",
+                    entries.len() - skipped,
+                    list_file.display()
+                )));
+                if skipped > 0 {
+                    self.add_log(LogEntry::Warning(format!(
+                        "{} path(s) left out because they aren't valid UTF-8 or contain line breaks",
+                        skipped
+                    )));
+                }
             }
+            Err(e) => self.add_log(LogEntry::Error(e)),
         }
     }
 
-    fn render_log_section(&mut self, ui: &mut egui::Ui) {
-        ui.add_space(10.0);
-        ui.separator();
-        ui.add_space(5.0);
-        ui.heading("Log");
-        ui.add_space(5.0);
+    fn render_export_dialog(&mut self, ctx: &egui::Context) {
+        if !self.export_dialog_open {
+            return;
+        }
 
-        let scroll_area = ScrollArea::vertical()
-            .auto_shrink([false, false])
-            .stick_to_bottom(true);
+        let mut open = true;
+        let mut export = false;
 
-        scroll_area.show(ui, |ui| {
-            for entry in &self.log_entries {
-                let (color, text) = match entry {
-                    LogEntry::Info(s) => (Color32::LIGHT_GRAY, s),
-                    LogEntry::Success(s) => (Color32::from_rgb(100, 255, 100), s),
-                    LogEntry::Error(s) => (Color32::from_rgb(255, 100, 100), s),
-                    LogEntry::Warning(s) => (Color32::from_rgb(255, 200, 100), s),
-                };
+        egui::Window::new("Export List")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.radio_value(&mut self.export_expanded, false, "Entries as added (folders stay folders)");
+                ui.radio_value(&mut self.export_expanded, true, "Expanded (every image file the batch would convert)");
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Export…").clicked() {
+                        export = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.export_dialog_open = false;
+                    }
+                });
+            });
 
-                ui.label(RichText::new(text).color(color).small());
-            }
+        if !open {
+            self.export_dialog_open = false;
+        }
 
-            if self.scroll_to_bottom {
-                ui.scroll_to_cursor(Some(egui::Align::BOTTOM));
-                self.scroll_to_bottom = false;
-            }
-        });
+        if export {
+            self.export_dialog_open = false;
+            self.export_list(ListTarget::Encode);
+        }
     }
 
-    fn render_decode_input_section(&mut self, ui: &mut egui::Ui) {
-        ui.heading("Input JXL Files");
-        ui.add_space(5.0);
+    fn render_paste_command_dialog(&mut self, ctx: &egui::Context) {
+        let Some(text) = &mut self.paste_command else {
+            return;
+        };
 
-        ui.horizontal(|ui| {
-            if ui.button("📁 Add JXL Files").clicked() {
-                if let Some(files) = rfd::FileDialog::new()
-                    .set_title("Select JXL Files")
-                    .add_filter("JPEG XL", &["jxl"])
-                    .pick_files()
-                {
-                    for file in files {
-                        if !self.decode_items.iter().any(|item| item.path == file) {
-                            self.decode_items.push(DecodeItem {
-                                path: file,
-                                output_format: self.decode_settings.output_format,
-                            });
+        let mut open = true;
+        let mut apply = None;
+        let mut close = false;
+
+        egui::Window::new("Paste cjxl Command")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(460.0)
+            .show(ctx, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(text)
+                        .font(egui::TextStyle::Monospace)
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(3)
+                        .hint_text("cjxl -d 1.0 -e 8 in.png out.jxl"),
+                );
+                ui.add_space(5.0);
+
+                let parsed = (!text.trim().is_empty()).then(|| parse_cjxl_command(text, &self.settings));
+                match &parsed {
+                    None => {}
+                    Some(Err(e)) => {
+                        ui.colored_label(Color32::LIGHT_RED, e);
+                    }
+                    Some(Ok(import)) => {
+                        let changes = describe_changes(&self.settings, &import.settings);
+                        if changes.is_empty() {
+                            ui.label("No changes to the current settings.");
+                        }
+                        for change in &changes {
+                            ui.monospace(change);
+                        }
+                        for note in &import.notes {
+                            ui.label(RichText::new(note).small().color(Color32::GRAY));
                         }
                     }
                 }
-            }
 
-            if ui.button("📂 Add Folder").clicked() {
-                if let Some(folder) = rfd::FileDialog::new()
-                    .set_title("Select Folder")
-                    .pick_folder()
-                {
-                    self.add_jxl_files_from_folder(&folder);
-                }
-            }
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    let import = match parsed {
+                        Some(Ok(import)) if import.settings != self.settings => Some(import),
+                        _ => None,
+                    };
+                    if ui.add_enabled(import.is_some(), egui::Button::new("Apply")).clicked() {
+                        apply = import;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
 
-            if ui.button("Clear").clicked() {
-                self.decode_items.clear();
-            }
-        });
+        if let Some(import) = apply {
+            let changes = describe_changes(&self.settings, &import.settings);
+            self.settings = import.settings;
+            self.add_log(LogEntry::Info(format!("Imported from command: {}", changes.join(", "))));
+            close = true;
+        }
+        if close || !open {
+            self.paste_command = None;
+        }
+    }
+
+    fn render_import_progress(&self, ui: &mut egui::Ui, target: ListTarget) {
+        if !matches!(&self.import_rx, Some((t, _)) if *t == target) {
+            return;
+        }
 
+        let (done, total) = self.import_progress;
+        let fraction = if total > 0 { done as f32 / total as f32 } else { 0.0 };
         ui.add_space(5.0);
-        ui.checkbox(&mut self.decode_settings.recursive, "Recursive (scan subfolders)");
-        
-        ui.add_space(10.0);
-        ui.label(format!("{} file(s) selected", self.decode_items.len()));
+        ui.add(egui::ProgressBar::new(fraction).text(format!("Importing list… {} / {}", done, total)));
     }
 
-    fn add_jxl_files_from_folder(&mut self, folder: &PathBuf) {
-        use walkdir::WalkDir;
-        
-        let walker = if self.decode_settings.recursive {
-            WalkDir::new(folder).follow_links(false).into_iter()
-        } else {
-            WalkDir::new(folder).max_depth(1).follow_links(false).into_iter()
-        };
+    /// The [`path_key`]s of the paths queued for encoding, so adding many
+    /// files can skip duplicates without normalizing the whole list each time.
+    fn input_keys(&self) -> HashSet<PathBuf> {
+        self.input_paths.iter().map(|p| path_key(p)).collect()
+    }
 
-        for entry in walker.filter_map(|e| e.ok()) {
-            if entry.file_type().is_file() {
-                let path = entry.path();
-                if let Some(ext) = path.extension() {
-                    if ext.to_string_lossy().to_lowercase() == "jxl" {
-                        let path_buf = path.to_path_buf();
-                        if !self.decode_items.iter().any(|item| item.path == path_buf) {
-                            self.decode_items.push(DecodeItem {
-                                path: path_buf,
-                                output_format: self.decode_settings.output_format,
-                            });
-                        }
-                    }
-                }
+    /// Like [`Self::input_keys`], for the decode list.
+    fn decode_item_keys(&self) -> HashSet<PathBuf> {
+        self.decode_items.iter().map(|item| path_key(&item.path)).collect()
+    }
+
+    /// Queues the JXLs under `folder` that aren't in `known` yet, adding them to it.
+    fn add_jxl_files_from_folder(&mut self, folder: &Path, known: &mut HashSet<PathBuf>) {
+        let scan = self
+            .engine
+            .expand_paths(&[folder.to_path_buf()], &self.decode_settings.scan_options());
+        if let Some(summary) = scan.summary() {
+            self.add_log(LogEntry::Info(format!("{}: {}", folder.display(), summary)));
+        }
+
+        for path in scan.files {
+            if is_jxl_file(&path) && known.insert(path_key(&path)) {
+                self.decode_items.push(DecodeItem {
+                    path,
+                    output_formats: vec![self.decode_settings.output_format],
+                    folder: Some(folder.to_path_buf()),
+                });
             }
         }
     }
@@ -575,25 +3660,59 @@ impl JxlConverterApp {
         ui.heading("Output");
         ui.add_space(5.0);
 
+        output_dir_field(ui, &mut self.decode_output_dir_text, &mut self.decode_settings.output_dir);
+
+        ui.add_space(5.0);
+        ui.checkbox(&mut self.decode_settings.keep_structure, "Keep input folder structure");
+        ui.checkbox(&mut self.decode_settings.replace_inner_extension, "Replace double extensions")
+            .on_hover_text("scan.tiff.jxl becomes scan.png rather than scan.tiff.png, and photo.jpg.jxl decoded to JPEG gets its original name back");
+        overwrite_controls(
+            ui,
+            "decode_overwrite",
+            &mut self.decode_settings.overwrite,
+            &mut self.decode_settings.transactional,
+        );
+
+        ui.add_space(5.0);
         ui.horizontal(|ui| {
-            ui.label("Directory:");
-            ui.add(
-                egui::TextEdit::singleline(&mut self.decode_settings.output_dir.to_string_lossy().to_string())
-                    .desired_width(ui.available_width() - 80.0)
-                    .interactive(false),
-            );
-            if ui.button("Browse").clicked() {
-                if let Some(folder) = rfd::FileDialog::new()
-                    .set_title("Select Output Directory")
-                    .pick_folder()
-                {
-                    self.decode_settings.output_dir = folder;
-                }
-            }
+            ui.label("Background for transparency:");
+            ui.color_edit_button_srgb(&mut self.decode_settings.flatten_background.0)
+                .on_hover_text("Transparent areas are blended onto this color when decoding to JPEG, PPM, PGM or PBM");
+            ui.label(RichText::new(self.decode_settings.flatten_background.hex()).small().color(Color32::GRAY));
         });
 
         ui.add_space(5.0);
-        ui.checkbox(&mut self.decode_settings.keep_structure, "Keep input folder structure");
+        ui.add_enabled(
+            self.engine.capabilities().djxl_supports_partial_files,
+            egui::Checkbox::new(&mut self.decode_settings.allow_partial_files, "Decode partial files"),
+        )
+        .on_hover_text("Decode truncated JXLs, e.g. unfinished downloads, as far as they go")
+        .on_disabled_hover_text("Needs a djxl with --allow_partial_files");
+
+        ui.add_space(5.0);
+        ui.checkbox(&mut self.decode_settings.verify_reconstruction, "Verify reconstructed JPEGs")
+            .on_hover_text("When a --lossless_jpeg JXL is decoded to JPEG, compare the SHA-256 of the output with the original JPEG");
+        if self.decode_settings.verify_reconstruction {
+            ui.horizontal(|ui| {
+                ui.label("Originals:");
+                let text = match &self.decode_settings.original_root {
+                    Some(root) => root.display().to_string(),
+                    None => "next to each JXL".to_string(),
+                };
+                ui.label(RichText::new(text).small().color(Color32::GRAY));
+                if ui.small_button("Browse").clicked() {
+                    if let Some(folder) = rfd::FileDialog::new()
+                        .set_title("Select Folder With the Original JPEGs")
+                        .pick_folder()
+                    {
+                        self.decode_settings.original_root = Some(folder);
+                    }
+                }
+                if self.decode_settings.original_root.is_some() && ui.small_button("✖").on_hover_text("Look next to each JXL").clicked() {
+                    self.decode_settings.original_root = None;
+                }
+            });
+        }
 
         ui.add_space(10.0);
         ui.separator();
@@ -602,21 +3721,63 @@ impl JxlConverterApp {
         ui.label(RichText::new("Default Output Format:").strong());
         ui.add_space(3.0);
         
+        let exr_supported = self.engine.capabilities().djxl_supports_exr;
         egui::ComboBox::from_id_salt("default_output_format")
             .selected_text(self.decode_settings.output_format.name())
             .show_ui(ui, |ui| {
                 for format in OutputFormat::all() {
-                    if ui.selectable_value(&mut self.decode_settings.output_format, *format, format.name()).clicked() {
+                    let available = *format != OutputFormat::Exr || exr_supported;
+                    let selected = self.decode_settings.output_format == *format;
+                    let response = ui
+                        .add_enabled(available, egui::SelectableLabel::new(selected, format.name()))
+                        .on_disabled_hover_text(EXR_UNSUPPORTED);
+                    if response.clicked() {
+                        self.decode_settings.output_format = *format;
                         // Update all items to use the new default format
                         for item in &mut self.decode_items {
-                            item.output_format = self.decode_settings.output_format;
+                            item.output_formats = vec![self.decode_settings.output_format];
+                        }
+                    }
+                }
+            });
+
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("(applies to all files below)").small().color(Color32::GRAY));
+            ui.menu_button("Add format to all", |ui| {
+                for format in OutputFormat::all() {
+                    let available = *format != OutputFormat::Exr || exr_supported;
+                    let button = ui.add_enabled(available, egui::Button::new(format.name()));
+                    if button.on_disabled_hover_text(EXR_UNSUPPORTED).clicked() {
+                        for item in &mut self.decode_items {
+                            if !item.output_formats.contains(format) {
+                                item.output_formats.push(*format);
+                            }
                         }
+                        ui.close_menu();
                     }
                 }
+            })
+            .response
+            .on_hover_text("Also decode every file below to this format");
+        });
+
+        let makes_gifs = self.decode_settings.output_format == OutputFormat::Gif
+            || self.decode_items.iter().any(|item| item.output_formats.contains(&OutputFormat::Gif));
+        if makes_gifs {
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                ui.label("GIF colors:");
+                ui.add(Slider::new(&mut self.decode_settings.gif_max_colors, 2..=256))
+                    .on_hover_text("Fewer colors make smaller files");
             });
+            ui.checkbox(&mut self.decode_settings.gif_dither, "Dither GIFs")
+                .on_hover_text("Smoother gradients at the cost of larger files");
+        }
 
         ui.add_space(5.0);
-        ui.label(RichText::new("(applies to all files below)").small().color(Color32::GRAY));
+        ui.label("Animated outputs (GIF/APNG):");
+        animation_timing_controls(ui, &mut self.decode_settings.frame_delay_ms, &mut self.decode_settings.loop_count);
     }
 
     fn render_decode_list_section(&mut self, ui: &mut egui::Ui) {
@@ -628,66 +3789,214 @@ impl JxlConverterApp {
             return;
         }
 
+        // Reordering is locked while a batch runs since the worker already
+        // holds its own copy of the list
+        let can_reorder = !self.is_converting;
+        let exr_supported = self.engine.capabilities().djxl_supports_exr;
+
         ScrollArea::vertical()
             .max_height(200.0)
             .auto_shrink([false, false])
             .show(ui, |ui| {
                 let mut items_to_remove = Vec::new();
+                let mut reveal_request = None;
+                // (from index, insertion index before removal)
+                let mut pending_move: Option<(usize, usize)> = None;
+                let item_count = self.decode_items.len();
                 
                 for (idx, item) in self.decode_items.iter_mut().enumerate() {
-                    ui.horizontal(|ui| {
-                        // File name
-                        ui.label(
-                            RichText::new(item.path.file_name().unwrap().to_string_lossy())
-                                .strong()
-                        );
-                        
-                        ui.label("→");
-                        
-                        // Format selector
-                        egui::ComboBox::from_id_salt(format!("format_{}", idx))
-                            .selected_text(item.output_format.name())
-                            .width(80.0)
-                            .show_ui(ui, |ui| {
-                                for format in OutputFormat::all() {
-                                    ui.selectable_value(&mut item.output_format, *format, format.name());
+                    let animated = *self
+                        .animated_jxls
+                        .entry(item.path.clone())
+                        .or_insert_with(|| jxl_is_animated(&item.path) == Some(true));
+                    let row = ui.vertical(|ui| {
+                        ui.horizontal(|ui| {
+                            // Drag handle
+                            if can_reorder {
+                                ui.dnd_drag_source(egui::Id::new(("decode_item_drag", idx)), idx, |ui| {
+                                    ui.label(RichText::new("☰").color(Color32::GRAY));
+                                });
+                            } else {
+                                ui.label(RichText::new("☰").color(Color32::DARK_GRAY));
+                            }
+
+                            // File name
+                            ui.add(
+                                egui::Label::new(
+                                    RichText::new(item.path.file_name().unwrap().to_string_lossy())
+                                        .strong()
+                                )
+                                .sense(egui::Sense::click()),
+                            )
+                            .context_menu(|ui| {
+                                if ui.button("Reveal in file manager").clicked() {
+                                    reveal_request = Some(item.path.clone());
+                                    ui.close_menu();
+                                }
+                                ui.separator();
+                                if ui.add_enabled(can_reorder && idx > 0, egui::Button::new("Move to top")).clicked() {
+                                    pending_move = Some((idx, 0));
+                                    ui.close_menu();
+                                }
+                                if ui.add_enabled(can_reorder && idx + 1 < item_count, egui::Button::new("Move to bottom")).clicked() {
+                                    pending_move = Some((idx, item_count));
+                                    ui.close_menu();
                                 }
                             });
+
+                            if animated {
+                                let stills: Vec<&str> = item
+                                    .output_formats
+                                    .iter()
+                                    .filter(|f| !f.supports_animation())
+                                    .map(|f| f.name())
+                                    .collect();
+                                if stills.is_empty() {
+                                    ui.label("🎞").on_hover_text("Animated");
+                                } else {
+                                    ui.label(RichText::new("⚠ 🎞").color(Color32::YELLOW)).on_hover_text(
+                                        format!("Animated: {} keeps only the first frame", stills.join(", ")),
+                                    );
+                                }
+                            }
+                            
+                            ui.label("→");
+                            
+                            // Format chips; the last one can't be turned off
+                            for format in OutputFormat::all() {
+                                let selected = item.output_formats.contains(format);
+                                // Already chosen chips stay clickable so they can be turned off
+                                let available = selected || *format != OutputFormat::Exr || exr_supported;
+                                let chip = ui
+                                    .add_enabled(available, egui::SelectableLabel::new(selected, format.name()))
+                                    .on_disabled_hover_text(EXR_UNSUPPORTED);
+                                if chip.clicked() {
+                                    if !selected {
+                                        item.output_formats.push(*format);
+                                    } else if item.output_formats.len() > 1 {
+                                        item.output_formats.retain(|f| f != format);
+                                    }
+                                }
+                            }
+                            
+                            // Remove button
+                            if ui.button("✖").clicked() {
+                                items_to_remove.push(idx);
+                            }
+                        });
                         
-                        // Remove button
-                        if ui.button("✖").clicked() {
-                            items_to_remove.push(idx);
-                        }
+                        ui.label(RichText::new(item.path.display().to_string()).small().color(Color32::DARK_GRAY));
                     });
-                    
-                    ui.label(RichText::new(item.path.display().to_string()).small().color(Color32::DARK_GRAY));
                     ui.add_space(3.0);
+
+                    if can_reorder {
+                        // Dropping on the upper half inserts above this row, lower half below
+                        let rect = row.response.rect;
+                        let pointer_y = ui.ctx().pointer_interact_pos().map(|p| p.y);
+                        let insert_at = match pointer_y {
+                            Some(y) if y > rect.center().y => idx + 1,
+                            _ => idx,
+                        };
+
+                        if row.response.dnd_hover_payload::<usize>().is_some() {
+                            let y = if insert_at == idx { rect.top() } else { rect.bottom() };
+                            ui.painter().hline(
+                                rect.x_range(),
+                                y,
+                                egui::Stroke::new(2.0, Color32::from_rgb(100, 150, 255)),
+                            );
+                        }
+
+                        if let Some(from) = row.response.dnd_release_payload::<usize>() {
+                            pending_move = Some((*from, insert_at));
+                        }
+                    }
+                }
+
+                if let Some((from, insert_at)) = pending_move {
+                    self.move_decode_item(from, insert_at);
                 }
 
                 // Remove items in reverse order to preserve indices
                 for idx in items_to_remove.into_iter().rev() {
                     self.decode_items.remove(idx);
                 }
+
+                if let Some(path) = reveal_request {
+                    self.reveal_path(&path);
+                }
             });
     }
+
+    /// Moves the decode item at `from` so it sits before the item that was at
+    /// `insert_at` (or at the end when `insert_at` equals the length).
+    fn move_decode_item(&mut self, from: usize, insert_at: usize) {
+        if from >= self.decode_items.len() {
+            return;
+        }
+
+        let to = if insert_at > from { insert_at - 1 } else { insert_at };
+        if to == from {
+            return;
+        }
+
+        let item = self.decode_items.remove(from);
+        self.decode_items.insert(to.min(self.decode_items.len()), item);
+    }
 }
 
 impl eframe::App for JxlConverterApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.process_progress_messages();
+        self.check_power_state();
+        self.check_throttle(ctx);
+        self.check_scheduled_start();
+        self.process_import_messages();
+        self.handle_paste_events(ctx);
+        self.render_export_dialog(ctx);
+        self.render_paste_command_dialog(ctx);
+        self.process_test_run_messages();
+        self.process_compare_messages(ctx);
+        self.render_test_run_window(ctx);
+        self.render_create_dir_dialog(ctx);
+        self.render_resume_dialog(ctx);
+        self.render_conflicts_dialog(ctx);
+        self.render_animation_loss_dialog(ctx);
+        self.render_large_batch_dialog(ctx);
+        self.render_profile_manager(ctx);
+        self.render_settings_window(ctx);
+        self.render_about_window(ctx);
+        self.update_window_title(ctx);
+
+        if ctx.input(|i| i.viewport().close_requested()) {
+            self.save_config();
+            for path in self.pasted_images.drain() {
+                let _ = std::fs::remove_file(path);
+            }
+            if let Some(run) = self.test_run.take() {
+                run.discard();
+            }
+        }
 
         // Request repaint if converting
-        if self.is_converting {
+        if self.progress_rx.is_some() || self.import_rx.is_some() {
             ctx.request_repaint();
+        } else if self.armed_start.is_some() {
+            // Keep the countdown ticking
+            ctx.request_repaint_after(Duration::from_secs(1));
         }
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.add_space(10.0);
 
+            self.render_profile_bar(ui);
+            ui.add_space(5.0);
+
             // Tab selection
             ui.horizontal(|ui| {
                 ui.selectable_value(&mut self.active_tab, AppTab::Encode, "⚙ Encode (to JXL)");
                 ui.selectable_value(&mut self.active_tab, AppTab::Decode, "📦 Decode (from JXL)");
+                ui.selectable_value(&mut self.active_tab, AppTab::Compare, "🔍 Compare");
             });
 
             ui.add_space(10.0);
@@ -698,12 +4007,15 @@ impl eframe::App for JxlConverterApp {
             match self.active_tab {
                 AppTab::Encode => self.render_encode_tab(ui),
                 AppTab::Decode => self.render_decode_tab(ui),
+                AppTab::Compare => self.render_compare_tab(ui),
             }
 
-            // Controls and log are shared between tabs
-            ui.group(|ui| {
-                self.render_controls_section(ui);
-            });
+            // Controls and log are shared between the batch tabs
+            if self.active_tab != AppTab::Compare {
+                ui.group(|ui| {
+                    self.render_controls_section(ui);
+                });
+            }
 
             ui.group(|ui| {
                 self.render_log_section(ui);
@@ -752,4 +4064,172 @@ impl JxlConverterApp {
             });
         });
     }
+
+    fn render_compare_tab(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Compare Two Images");
+        ui.label(
+            RichText::new("Any supported image or JXL; drop files here to fill A, then B")
+                .small()
+                .color(Color32::GRAY),
+        );
+        ui.add_space(5.0);
+
+        ui.ctx().input(|i| {
+            for file in &i.raw.dropped_files {
+                if let Some(path) = &file.path {
+                    self.compare.add(path.clone());
+                }
+            }
+        });
+
+        let running = self.compare.run.is_some();
+        for (label, slot) in [("A:", &mut self.compare.a), ("B:", &mut self.compare.b)] {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(label).strong());
+                match slot.as_ref() {
+                    Some(path) => ui.label(path.display().to_string()),
+                    None => ui.label(RichText::new("No file chosen").color(Color32::GRAY).italics()),
+                };
+                if ui.add_enabled(!running, egui::Button::new("Choose…")).clicked() {
+                    let mut extensions = SUPPORTED_IMAGE_EXTENSIONS.to_vec();
+                    extensions.push("jxl");
+                    if let Some(path) = rfd::FileDialog::new()
+                        .set_title("Choose an Image")
+                        .add_filter("Images", &extensions)
+                        .pick_file()
+                    {
+                        *slot = Some(path);
+                    }
+                }
+                if slot.is_some() && ui.add_enabled(!running, egui::Button::new("✖").small()).clicked() {
+                    *slot = None;
+                }
+            });
+        }
+
+        let tools = CompareTools::from_tools(&self.tools);
+        let missing: Vec<&str> = [("ssimulacra2", &tools.ssimulacra2), ("butteraugli_main", &tools.butteraugli)]
+            .into_iter()
+            .filter(|(_, path)| path.is_none())
+            .map(|(name, _)| name)
+            .collect();
+        if !missing.is_empty() {
+            ui.label(
+                RichText::new(format!("{} not found; those scores are left out", missing.join(" and ")))
+                    .small()
+                    .color(Color32::GRAY),
+            );
+        }
+
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            let ready = self.compare.a.is_some() && self.compare.b.is_some();
+            if ui.add_enabled(ready && !running, egui::Button::new("🔍 Compare")).clicked() {
+                self.start_compare(ui.ctx());
+            }
+            if let Some(run) = &self.compare.run {
+                if ui.button("Cancel").clicked() {
+                    run.cancel_flag.store(true, Ordering::Relaxed);
+                }
+            }
+        });
+
+        if let Some(run) = &self.compare.run {
+            let (done, total, label) = &run.step;
+            ui.add_space(5.0);
+            ui.add(egui::ProgressBar::new(*done as f32 / (*total).max(1) as f32).text(label.as_str()));
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+
+        ui.add_space(10.0);
+        match &self.compare.result {
+            None => {}
+            Some(Err(error)) => {
+                ui.colored_label(Color32::LIGHT_RED, error);
+            }
+            Some(Ok(comparison)) => {
+                let lines = comparison.describe();
+                ui.group(|ui| {
+                    for line in &lines {
+                        ui.label(RichText::new(line).monospace());
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("📋 Copy results").clicked() {
+                        ui.ctx().copy_text(lines.join("\n"));
+                    }
+                    ui.checkbox(&mut self.compare.show_heatmap, "Show difference heatmap")
+                        .on_hover_text(format!(
+                            "Each pixel's largest difference ×{}: black is none, then red, yellow and white",
+                            HEATMAP_GAIN
+                        ));
+                });
+                if let Some(texture) = self.compare.heatmap.as_ref().filter(|_| self.compare.show_heatmap) {
+                    ScrollArea::both().max_height(300.0).show(ui, |ui| {
+                        ui.add(egui::Image::new(texture).max_width(ui.available_width()).maintain_aspect_ratio(true));
+                    });
+                }
+            }
+        }
+        ui.add_space(10.0);
+    }
+
+    /// Compares the two chosen files on a background thread.
+    fn start_compare(&mut self, ctx: &egui::Context) {
+        let (Some(a), Some(b)) = (self.compare.a.clone(), self.compare.b.clone()) else {
+            return;
+        };
+        if let Some(run) = self.compare.run.take() {
+            run.cancel_flag.store(true, Ordering::Relaxed);
+        }
+
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+        let dir = std::env::temp_dir()
+            .join("jxl-converter-compare")
+            .join(format!("{}-{}", std::process::id(), nanos));
+        let tools = CompareTools::from_tools(&self.tools);
+        let (tx, rx) = channel();
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+        let thread_cancel = Arc::clone(&cancel_flag);
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let step_tx = tx.clone();
+            let result = compare_files(&a, &b, &tools, &dir, thread_cancel, |done, total, label| {
+                let _ = step_tx.send(CompareMessage::Step(done, total, label.to_string()));
+            });
+            let _ = std::fs::remove_dir_all(&dir);
+            let _ = tx.send(CompareMessage::Done(result));
+            ctx.request_repaint();
+        });
+
+        self.compare.result = None;
+        self.compare.heatmap = None;
+        self.compare.run = Some(CompareRun { cancel_flag, rx, step: (0, 1, "Starting".to_string()) });
+    }
+
+    fn process_compare_messages(&mut self, ctx: &egui::Context) {
+        let Some(run) = &mut self.compare.run else { return };
+
+        let mut finished = None;
+        loop {
+            match run.rx.try_recv() {
+                Ok(CompareMessage::Step(done, total, label)) => run.step = (done, total, label),
+                Ok(CompareMessage::Done(result)) => finished = Some(result),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    finished.get_or_insert_with(|| Err("The comparison stopped without a result".to_string()));
+                    break;
+                }
+            }
+        }
+        let Some(result) = finished else { return };
+
+        self.compare.run = None;
+        self.compare.heatmap = result.as_ref().ok().map(|comparison| {
+            let size = [comparison.heatmap.width() as usize, comparison.heatmap.height() as usize];
+            let image = egui::ColorImage::from_rgb(size, comparison.heatmap.as_raw());
+            ctx.load_texture("compare_heatmap", image, egui::TextureOptions::NEAREST)
+        });
+        self.compare.result = Some(result);
+    }
 }
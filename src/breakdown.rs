@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::download::url_of;
+use crate::engine::output_formats;
+use crate::flatten::jxl_is_animated;
+use crate::types::{DecodeItem, DecodeSettings, ToolSettings, DEFAULT_CONFIRM_BATCH_BYTES, DEFAULT_CONFIRM_BATCH_FILES};
+
+/// Files and bytes of one format in a [`BatchBreakdown`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormatCount {
+    /// E.g. `PNG`, or `JXL → PNG` for decodes.
+    pub format: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// What a batch is about to convert, counted before it starts so a
+/// surprisingly large one can be confirmed first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BatchBreakdown {
+    pub files: usize,
+    /// Input bytes; URLs count as nothing until downloaded.
+    pub bytes: u64,
+    /// Most files first.
+    pub formats: Vec<FormatCount>,
+}
+
+impl BatchBreakdown {
+    /// Counts encode inputs, already expanded to files, by extension. URLs
+    /// are counted together.
+    pub fn of_files(files: &[PathBuf]) -> Self {
+        let mut breakdown = Self::default();
+        for file in files {
+            if url_of(file).is_some() {
+                breakdown.add("URL", 0);
+                continue;
+            }
+            let format = file.extension().map_or("(none)".to_string(), |ext| ext.to_string_lossy().to_uppercase());
+            breakdown.add(&format, std::fs::metadata(file).map_or(0, |m| m.len()));
+        }
+        breakdown
+    }
+
+    /// Counts decode outputs by format. Each output counts its input's
+    /// bytes, as each is a decode of its own.
+    pub fn of_decode(items: &[DecodeItem], settings: &DecodeSettings) -> Self {
+        let mut breakdown = Self::default();
+        for item in items {
+            let bytes = std::fs::metadata(&item.path).map_or(0, |m| m.len());
+            for format in output_formats(item, settings) {
+                breakdown.add(&format!("JXL → {}", format.extension().to_uppercase()), bytes);
+            }
+        }
+        breakdown
+    }
+
+    /// Counts one file of `format`.
+    pub fn add(&mut self, format: &str, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+        match self.formats.iter_mut().find(|count| count.format == format) {
+            Some(count) => {
+                count.files += 1;
+                count.bytes += bytes;
+            }
+            None => self.formats.push(FormatCount { format: format.to_string(), files: 1, bytes }),
+        }
+        // Stable, so formats with as many files stay in the order first seen
+        self.formats.sort_by_key(|count| std::cmp::Reverse(count.files));
+    }
+
+    /// Whether the batch is over either of the thresholds in `settings`.
+    pub fn needs_confirmation(&self, settings: &ToolSettings) -> bool {
+        self.files > settings.confirm_batch_files.unwrap_or(DEFAULT_CONFIRM_BATCH_FILES)
+            || self.bytes > settings.confirm_batch_bytes.unwrap_or(DEFAULT_CONFIRM_BATCH_BYTES)
+    }
+
+    /// How long the batch should take at `bytes_per_sec` of input.
+    pub fn estimate(&self, bytes_per_sec: f64) -> Option<Duration> {
+        (bytes_per_sec > 0.0).then(|| Duration::from_secs_f64(self.bytes as f64 / bytes_per_sec))
+    }
+
+    /// The breakdown as lines of text: the totals and destination, then
+    /// one line per format.
+    pub fn describe(&self, destination: &Path) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{} file(s), {} in total, to {}",
+            self.files,
+            format_bytes(self.bytes),
+            destination.display()
+        )];
+        lines.extend(
+            self.formats
+                .iter()
+                .map(|count| format!("  {}: {} file(s), {}", count.format, count.files, format_bytes(count.bytes))),
+        );
+        lines
+    }
+}
+
+/// The inputs of animated JXLs among `items` that decode to a format
+/// keeping only the first frame, such as JPEG.
+pub fn losing_animation<'a>(items: &'a [DecodeItem], settings: &DecodeSettings) -> Vec<&'a Path> {
+    items
+        .iter()
+        .filter(|item| output_formats(item, settings).iter().any(|format| !format.supports_animation()))
+        .filter(|item| jxl_is_animated(&item.path) == Some(true))
+        .map(|item| item.path.as_path())
+        .collect()
+}
+
+/// A byte count in binary units, e.g. `1.5 GB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["bytes", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
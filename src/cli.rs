@@ -0,0 +1,658 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use jxl_converter::animation::MAX_FRAME_DELAY_MS;
+use jxl_converter::breakdown::{losing_animation, BatchBreakdown};
+use jxl_converter::download::url_of;
+use jxl_converter::engine::{is_jxl_file, is_supported_image};
+use jxl_converter::filelist::{parse_list, ListEntry};
+use jxl_converter::summary::{format_table, BatchStatus, BatchSummary};
+use jxl_converter::types::Color;
+use jxl_converter::{
+    ConversionEngine, ConversionSettings, DecodeItem, DecodeSettings, OutputFormat, OverwritePolicy, ProgressMessage,
+    ToolSettings,
+};
+
+const USAGE: &str = "\
+Usage:
+  jxl-converter-gui                          Start the graphical interface
+  jxl-converter-gui encode [OPTIONS] <INPUT>...
+  jxl-converter-gui decode [OPTIONS] <INPUT>...
+  jxl-converter-gui --progress-schema        Print the JSON Schema of --progress=json lines
+
+Options:
+  -o, --output <DIR>        Output directory (required)
+      --file-list <FILE>    Read inputs from a list file, one path per line; when
+                            decoding, a path may end in <TAB>png,jpg to pick formats
+      --progress <MODE>     text (default) or json; json writes one event per line to stdout
+      --no-recursive        Don't descend into subfolders of input folders
+      --no-ignore-files     Also scan folders marked with .nomedia or .jxlignore
+      --keep-structure      Recreate the input folder structure in the output
+      --existing <POLICY>   What to do with outputs that already exist:
+                            overwrite (default), skip or rename
+      --transactional       Delete everything the batch wrote if it is
+                            interrupted; existing outputs default to skip
+      --file-delay <MS>     Idle this long between files so a hot machine can
+                            cool down
+      --stable-wait <SECONDS>
+                            Skip files that change over this many seconds, as
+                            they are still being written
+      --post-hook <COMMAND> Run COMMAND through the shell after each converted
+                            file, with {input}, {output} and {status} replaced
+      --post-hook-always    Run the post hook after failed files too
+      --pre-batch-hook <COMMAND>
+                            Run COMMAND before the batch; if it fails, nothing
+                            is converted
+      --post-batch-hook <COMMAND>
+                            Run COMMAND after the batch, with JXL_CONVERTED,
+                            JXL_FAILED, JXL_SKIPPED, JXL_BYTES_SAVED and
+                            JXL_STATUS set
+      --hook-timeout <SECONDS>
+                            Kill a batch hook after this long (default 300)
+      --notify-url <URL>    POST the batch summary as JSON to URL when it ends
+      --notify-token <TOKEN>
+                            Send TOKEN as a bearer token with the notification
+      --label <TEXT>        Label the batch in its summary and notification
+      --dry-run             List each file's output without converting,
+                            downloading or running hooks
+      --cjxl <PATH>         Use this cjxl instead of the bundled one or PATH
+      --djxl <PATH>         Use this djxl instead of the bundled one or PATH
+  -h, --help                Show this help
+
+Encode options:
+  -q, --quality <1-100>     Quality for lossy encoding (default 90)
+  -e, --effort <1-10>       Encoder effort (default 7; 10 needs cjxl 0.10+)
+      --lossless            Encode losslessly
+      --no-jpeg-lossless    Re-encode JPEGs instead of transcoding them losslessly
+      --override-bitdepth <1-32>
+                            Bits per sample to record in the JXL header
+      --adaptive-quality <MIN-MAX>
+                            Try a few distances per file and keep the largest
+                            whose ssimulacra2 score is in this window, e.g. 80-85
+      --adaptive-distance <MIN-MAX>
+                            Distances adaptive quality stays within (default
+                            0.3-6)
+      --adaptive-attempts <1-10>
+                            Encodes per file adaptive quality may try (default 4)
+      --ssimulacra2 <PATH>  Use this ssimulacra2 instead of the bundled one or
+                            PATH
+      --output-budget <SIZE>
+                            Stop before outputs add up to more than SIZE, in
+                            bytes or with a K, M, G or T suffix (e.g. 28G)
+      --keep-downloads      Keep images downloaded from http(s):// inputs in the
+                            temp folder
+
+Decode options:
+      --format <FORMAT>     png (default), jpg, ppm, pgm, pbm, gif or exr; several,
+                            comma-separated, decode each file to each of them
+      --verify              Compare JPEGs reconstructed from --lossless_jpeg JXLs
+                            with the originals (same name next to the JXL)
+      --originals <DIR>     Look for the originals here instead; implies --verify
+      --frame-delay <MS>    Delay for every frame of animated outputs and GIF/APNG
+                            inputs
+      --loops <N>           Times animations play; 0 loops forever
+      --gif-colors <2-256>  Palette size for GIF output (default 256)
+      --no-dither           Don't dither GIF output
+      --background <COLOR>  What transparency becomes when decoding to formats
+                            without alpha, as #RRGGBB (default #FFFFFF)
+      --allow-partial-files Decode truncated JXLs as far as they go
+      --keep-inner-extension
+                            Decode scan.tiff.jxl to scan.tiff.png rather than
+                            scan.png (and photo.jpg.jxl to photo.jpg.jpg)";
+
+/// Process exit codes of the CLI.
+pub mod exit_code {
+    /// Every file converted.
+    pub const OK: i32 = 0;
+    /// At least one file failed or was left by the output budget, or a
+    /// reconstructed JPEG didn't match its original.
+    pub const PARTIAL_FAILURE: i32 = 1;
+    /// No input file matched.
+    pub const NO_INPUTS: i32 = 2;
+    /// cjxl or djxl could not be found.
+    pub const TOOL_MISSING: i32 = 3;
+    /// Unknown option or bad value.
+    pub const INVALID_ARGUMENTS: i32 = 4;
+    /// Stopped by Ctrl-C.
+    pub const INTERRUPTED: i32 = 130;
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Command {
+    Encode,
+    Decode,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ProgressMode {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+struct CliArgs {
+    command: Command,
+    inputs: Vec<PathBuf>,
+    settings: ConversionSettings,
+    decode_settings: DecodeSettings,
+    /// What each decoded file becomes; empty for the default format.
+    decode_formats: Vec<OutputFormat>,
+    /// Formats a `--file-list` pins for its files, used instead of
+    /// `decode_formats`.
+    list_formats: HashMap<PathBuf, Vec<OutputFormat>>,
+    progress: ProgressMode,
+    tool_settings: ToolSettings,
+    cjxl_path: Option<PathBuf>,
+    djxl_path: Option<PathBuf>,
+    ssimulacra2_path: Option<PathBuf>,
+    dry_run: bool,
+}
+
+/// Totals for a finished batch, written as the last line of the JSON stream.
+#[derive(Debug, Default, Serialize, JsonSchema)]
+#[serde(tag = "type", rename = "summary")]
+pub struct Summary {
+    #[serde(flatten)]
+    pub batch: BatchSummary,
+    pub exit_code: i32,
+}
+
+impl Summary {
+    fn outcome(&self) -> i32 {
+        let batch = &self.batch;
+        if batch.cancelled {
+            exit_code::INTERRUPTED
+        } else if batch.matched == Some(0) {
+            exit_code::NO_INPUTS
+        } else if batch.failed > 0 || batch.mismatched > 0 || batch.remaining > 0 || batch.matched.is_none() {
+            exit_code::PARTIAL_FAILURE
+        } else {
+            exit_code::OK
+        }
+    }
+
+    /// The single line always printed to stderr when the CLI finishes.
+    fn line(&self) -> String {
+        let batch = &self.batch;
+        let mut line = format!(
+            "converted={} failed={} skipped={} bytes_in={} bytes_out={}",
+            batch.converted, batch.failed, batch.skipped, batch.bytes_in, batch.bytes_out
+        );
+        if batch.remaining > 0 {
+            line.push_str(&format!(" remaining={}", batch.remaining));
+        }
+        if batch.verified + batch.mismatched + batch.unverified > 0 {
+            line.push_str(&format!(
+                " verified={} mismatched={} unverified={}",
+                batch.verified, batch.mismatched, batch.unverified
+            ));
+        }
+        line
+    }
+}
+
+/// One line of the `--progress=json` stream.
+#[derive(Serialize, JsonSchema)]
+#[serde(untagged)]
+enum StreamLine {
+    Event(ProgressMessage),
+    Summary(Summary),
+}
+
+/// Runs the headless CLI and returns the process exit code.
+pub fn run(args: Vec<OsString>) -> i32 {
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return exit_code::OK;
+    }
+
+    if args.iter().any(|a| a == "--progress-schema") {
+        let schema = schemars::schema_for!(StreamLine);
+        println!("{}", serde_json::to_string_pretty(&schema).unwrap_or_default());
+        return exit_code::OK;
+    }
+
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return finish(Summary { exit_code: exit_code::INVALID_ARGUMENTS, ..Default::default() }, ProgressMode::Text);
+        }
+    };
+
+    let engine = ConversionEngine::new()
+        .with_tool_settings(args.tool_settings.clone())
+        .with_tool_paths(args.cjxl_path.clone(), args.djxl_path.clone())
+        .with_dry_run(args.dry_run);
+    let engine = match &args.ssimulacra2_path {
+        Some(path) => engine.with_ssimulacra2_path(path.clone()),
+        None => engine,
+    };
+    let (tool_name, explicit_path, tool_error) = match args.command {
+        Command::Encode => ("cjxl", &args.cjxl_path, engine.get_error()),
+        Command::Decode => ("djxl", &args.djxl_path, engine.get_decode_error()),
+    };
+    let tool_error = match explicit_path {
+        Some(path) if !path.is_file() => Some(format!("{} not found at {}", tool_name, path.display())),
+        _ => tool_error,
+    };
+    if let Some(error) = tool_error {
+        eprintln!("error: {}", error);
+        return finish(Summary { exit_code: exit_code::TOOL_MISSING, ..Default::default() }, args.progress);
+    }
+    if args.command == Command::Encode {
+        if let Err(error) = engine.validate_settings(&args.settings) {
+            eprintln!("error: {}", error);
+            return finish(Summary { exit_code: exit_code::INVALID_ARGUMENTS, ..Default::default() }, args.progress);
+        }
+    }
+
+    let label = args.tool_settings.batch_label.trim();
+    let mut summary = Summary { batch: BatchSummary::begin(), ..Default::default() };
+    summary.batch.label = (!label.is_empty()).then(|| label.to_string());
+
+    let (tx, rx) = channel();
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+
+    // Ctrl-C stops the batch after the current file; the engine then reports
+    // Cancelled and we exit once it has cleaned up.
+    let handler_flag = Arc::clone(&cancel_flag);
+    if let Err(e) = ctrlc::set_handler(move || {
+        if !handler_flag.swap(true, Ordering::Relaxed) {
+            eprintln!("Interrupted, stopping after the current file...");
+        }
+    }) {
+        eprintln!("warning: failed to install Ctrl-C handler: {}", e);
+    }
+
+    // Decode inputs are expanded here so each remembers its folder; encode
+    // inputs are only counted, as the engine expands them itself
+    let (breakdown, items) = match args.command {
+        Command::Encode => {
+            let (mut files, paths): (Vec<PathBuf>, Vec<PathBuf>) =
+                args.inputs.iter().cloned().partition(|p| url_of(p).is_some());
+            let scan = engine.expand_paths(&paths, &args.settings.scan_options());
+            files.extend(scan.files.into_iter().filter(|p| is_supported_image(p)));
+            (BatchBreakdown::of_files(&files), Vec::new())
+        }
+        Command::Decode => {
+            let mut items: Vec<DecodeItem> = Vec::new();
+            for input in &args.inputs {
+                let folder = input.is_dir().then(|| input.clone());
+                let scan = engine.expand_paths(std::slice::from_ref(input), &args.decode_settings.scan_options());
+                if let Some(summary) = scan.summary() {
+                    eprintln!("{}: {}", input.display(), summary);
+                }
+                items.extend(scan.files.into_iter().filter(|p| is_jxl_file(p)).map(|path| DecodeItem {
+                    output_formats: args.list_formats.get(&path).unwrap_or(&args.decode_formats).clone(),
+                    path,
+                    folder: folder.clone(),
+                }));
+            }
+            let animated = losing_animation(&items, &args.decode_settings).len();
+            if animated > 0 {
+                eprintln!(
+                    "warning: {} animated file(s) will lose all but the first frame; decode to PNG or GIF to keep them",
+                    animated
+                );
+            }
+            (BatchBreakdown::of_decode(&items, &args.decode_settings), items)
+        }
+    };
+    if args.command == Command::Decode {
+        if let Err(error) = engine.validate_decode(&items, &args.decode_settings) {
+            eprintln!("error: {}", error);
+            return finish(Summary { exit_code: exit_code::INVALID_ARGUMENTS, ..Default::default() }, args.progress);
+        }
+    }
+    // Nobody is there to confirm a large batch, but the log says what it was
+    if args.dry_run || breakdown.needs_confirmation(&args.tool_settings) {
+        for line in breakdown.describe(&args.settings.output_dir) {
+            eprintln!("{}", line);
+        }
+    }
+
+    let worker = match args.command {
+        Command::Encode => {
+            let inputs = args.inputs.clone();
+            let settings = args.settings.clone();
+            let cancel_flag = Arc::clone(&cancel_flag);
+            thread::spawn(move || engine.convert_batch(inputs, settings, tx, cancel_flag))
+        }
+        Command::Decode => {
+            let settings = args.decode_settings.clone();
+            let cancel_flag = Arc::clone(&cancel_flag);
+            thread::spawn(move || engine.decode_batch(items, settings, tx, cancel_flag))
+        }
+    };
+
+    for msg in rx {
+        summary.batch.record(&msg);
+        print_human(&msg);
+        if args.progress == ProgressMode::Json {
+            print_json(&StreamLine::Event(msg));
+        }
+    }
+    let _ = worker.join();
+
+    // The flag may be set after the last file, when the engine no longer checks it
+    if cancel_flag.load(Ordering::Relaxed) {
+        summary.batch.cancelled = true;
+        summary.batch.status = BatchStatus::Cancelled;
+    }
+    summary.batch.end();
+    summary.exit_code = summary.outcome();
+    finish(summary, args.progress)
+}
+
+/// Prints the summary line (and JSON summary when streaming) and returns the
+/// exit code.
+fn finish(summary: Summary, progress: ProgressMode) -> i32 {
+    if !summary.batch.formats.is_empty() {
+        for line in format_table(&summary.batch.formats) {
+            eprintln!("{}", line);
+        }
+    }
+    eprintln!("{}", summary.line());
+    let code = summary.exit_code;
+    if progress == ProgressMode::Json {
+        print_json(&StreamLine::Summary(summary));
+    }
+    code
+}
+
+fn print_json(line: &StreamLine) {
+    match serde_json::to_string(line) {
+        Ok(json) => {
+            let mut stdout = std::io::stdout().lock();
+            let _ = writeln!(stdout, "{}", json);
+        }
+        Err(e) => eprintln!("warning: failed to serialize progress event: {}", e),
+    }
+}
+
+fn print_human(msg: &ProgressMessage) {
+    match msg {
+        ProgressMessage::Started { total } => eprintln!("Processing {} file(s)...", total),
+        ProgressMessage::Progress { current, total, file } => eprintln!("[{}/{}] {}", current, total, file),
+        ProgressMessage::Success { output, .. } => eprintln!("  -> {}", output.display()),
+        ProgressMessage::Error { file, error } if file.is_empty() => eprintln!("error: {}", error),
+        ProgressMessage::Error { file, error } => eprintln!("  failed: {}: {}", file, error),
+        ProgressMessage::Skipped { file, reason } => eprintln!("  skipped: {}: {}", file, reason),
+        ProgressMessage::Verified { file, verification } if verification.is_problem() => {
+            eprintln!("warning: {}: {}", file, verification.describe())
+        }
+        ProgressMessage::Verified { verification, .. } => eprintln!("  {}", verification.describe()),
+        ProgressMessage::Adapted { distance, score, attempts, .. } => {
+            eprintln!("  d {:.2}, ssimulacra2 {:.1} after {} attempt(s)", distance, score, attempts)
+        }
+        // Too chatty for a log; the JSON stream carries it
+        ProgressMessage::FrameProgress { .. } => {}
+        ProgressMessage::Info { message } => eprintln!("{}", message),
+        ProgressMessage::Warning { message } => eprintln!("warning: {}", message),
+        ProgressMessage::Completed => eprintln!("Completed."),
+        ProgressMessage::Stopped { reason, remaining } => {
+            eprintln!("Stopped: {}; {} file(s) left unconverted:", reason, remaining.len());
+            for file in remaining {
+                eprintln!("  {}", file);
+            }
+        }
+        ProgressMessage::Cancelled => eprintln!("Cancelled."),
+    }
+}
+
+fn parse_args(args: Vec<OsString>) -> Result<CliArgs, String> {
+    let mut args = args.into_iter();
+
+    let command = match args.next().as_ref().and_then(|a| a.to_str()) {
+        Some("encode") => Command::Encode,
+        Some("decode") => Command::Decode,
+        Some(other) => return Err(format!("unknown command '{}'", other)),
+        None => return Err("missing command".to_string()),
+    };
+
+    let mut parsed = CliArgs {
+        command,
+        inputs: Vec::new(),
+        settings: ConversionSettings::default(),
+        decode_settings: DecodeSettings::default(),
+        decode_formats: Vec::new(),
+        list_formats: HashMap::new(),
+        progress: ProgressMode::Text,
+        tool_settings: ToolSettings::default(),
+        cjxl_path: None,
+        djxl_path: None,
+        ssimulacra2_path: None,
+        dry_run: false,
+    };
+    let mut output_dir = None;
+    let mut only_inputs = false;
+    let mut existing_given = false;
+
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.to_str().filter(|a| a.starts_with('-') && !only_inputs && *a != "-") else {
+            parsed.inputs.push(PathBuf::from(arg));
+            continue;
+        };
+
+        // Accept both `--flag value` and `--flag=value`
+        let (flag, inline_value) = match flag.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(OsString::from(value))),
+            _ => (flag.to_string(), None),
+        };
+        let mut value = || -> Result<OsString, String> {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+
+        match flag.as_str() {
+            "--" => only_inputs = true,
+            "-o" | "--output" => output_dir = Some(PathBuf::from(value()?)),
+            "--file-list" => {
+                for entry in read_file_list(&PathBuf::from(value()?))? {
+                    if !entry.formats.is_empty() {
+                        parsed.list_formats.insert(entry.path.clone(), entry.formats);
+                    }
+                    parsed.inputs.push(entry.path);
+                }
+            }
+            "--progress" => {
+                parsed.progress = match value()?.to_str() {
+                    Some("text") => ProgressMode::Text,
+                    Some("json") => ProgressMode::Json,
+                    _ => return Err("--progress must be 'text' or 'json'".to_string()),
+                }
+            }
+            "--no-ignore-files" => {
+                parsed.settings.respect_ignore_files = false;
+                parsed.decode_settings.respect_ignore_files = false;
+            }
+            "--no-recursive" => {
+                parsed.settings.recursive = false;
+                parsed.decode_settings.recursive = false;
+            }
+            "--keep-structure" => {
+                parsed.settings.keep_structure = true;
+                parsed.decode_settings.keep_structure = true;
+            }
+            "--file-delay" => {
+                parsed.tool_settings.inter_file_delay_ms = Some(parse_number(&flag, &value()?, 1, 3_600_000)?);
+            }
+            "--stable-wait" => {
+                parsed.tool_settings.stable_wait_secs = Some(parse_number(&flag, &value()?, 1, 3600)?);
+            }
+            "--existing" => {
+                let policy = value()?;
+                let policy = policy
+                    .to_str()
+                    .and_then(OverwritePolicy::from_name)
+                    .ok_or_else(|| format!("unknown --existing policy '{}'", policy.to_string_lossy()))?;
+                parsed.settings.overwrite = policy;
+                parsed.decode_settings.overwrite = policy;
+                existing_given = true;
+            }
+            "--transactional" => {
+                parsed.settings.transactional = true;
+                parsed.decode_settings.transactional = true;
+            }
+            "--post-hook" => parsed.tool_settings.post_hook = value()?.to_string_lossy().into_owned(),
+            "--post-hook-always" => parsed.tool_settings.post_hook_always = true,
+            "--pre-batch-hook" => parsed.tool_settings.pre_batch_hook = value()?.to_string_lossy().into_owned(),
+            "--post-batch-hook" => parsed.tool_settings.post_batch_hook = value()?.to_string_lossy().into_owned(),
+            "--notify-url" => parsed.tool_settings.notify_url = value()?.to_string_lossy().into_owned(),
+            "--notify-token" => parsed.tool_settings.notify_token = value()?.to_string_lossy().into_owned(),
+            "--label" => parsed.tool_settings.batch_label = value()?.to_string_lossy().into_owned(),
+            "--dry-run" => parsed.dry_run = true,
+            "--hook-timeout" => {
+                parsed.tool_settings.batch_hook_timeout_secs = Some(parse_number(&flag, &value()?, 1, 86_400)?);
+            }
+            "--cjxl" => parsed.cjxl_path = Some(PathBuf::from(value()?)),
+            "--djxl" => parsed.djxl_path = Some(PathBuf::from(value()?)),
+            "-q" | "--quality" => parsed.settings.quality = parse_number(&flag, &value()?, 1, 100)?,
+            "-e" | "--effort" => parsed.settings.effort = parse_number(&flag, &value()?, 1, 10)?,
+            "--lossless" => parsed.settings.lossless = true,
+            "--no-jpeg-lossless" => parsed.settings.jpeg_lossless = false,
+            "--keep-downloads" => parsed.settings.keep_downloads = true,
+            "--override-bitdepth" => {
+                parsed.settings.override_bitdepth = Some(parse_number(&flag, &value()?, 1, 32)?);
+            }
+            "--adaptive-quality" => {
+                let adaptive = parsed.settings.adaptive_quality.get_or_insert_with(Default::default);
+                (adaptive.min_score, adaptive.max_score) = parse_range(&flag, &value()?, 0.0, 100.0)?;
+            }
+            "--adaptive-distance" => {
+                let adaptive = parsed.settings.adaptive_quality.get_or_insert_with(Default::default);
+                (adaptive.min_distance, adaptive.max_distance) = parse_range(&flag, &value()?, 0.01, 25.0)?;
+            }
+            "--adaptive-attempts" => {
+                let attempts = parse_number(&flag, &value()?, 1, 10)?;
+                parsed.settings.adaptive_quality.get_or_insert_with(Default::default).max_attempts = attempts;
+            }
+            "--output-budget" => parsed.settings.output_budget_bytes = Some(parse_size(&flag, &value()?)?),
+            "--ssimulacra2" => parsed.ssimulacra2_path = Some(PathBuf::from(value()?)),
+            "--format" => {
+                let formats = value()?;
+                parsed.decode_formats = formats
+                    .to_string_lossy()
+                    .split(',')
+                    .map(|format| {
+                        OutputFormat::from_extension(format.trim())
+                            .ok_or_else(|| format!("unknown output format '{}'", format.trim()))
+                    })
+                    .collect::<Result<_, _>>()?;
+                parsed.decode_settings.output_format = parsed.decode_formats[0];
+            }
+            "--frame-delay" => {
+                let delay = parse_number(&flag, &value()?, 1, MAX_FRAME_DELAY_MS)?;
+                parsed.settings.frame_delay_ms = Some(delay);
+                parsed.decode_settings.frame_delay_ms = Some(delay);
+            }
+            "--loops" => {
+                let plays = parse_number(&flag, &value()?, 0, u16::MAX)?;
+                parsed.settings.loop_count = Some(plays);
+                parsed.decode_settings.loop_count = Some(plays);
+            }
+            "--gif-colors" => {
+                parsed.decode_settings.gif_max_colors = parse_number(&flag, &value()?, 2, 256)?;
+            }
+            "--no-dither" => parsed.decode_settings.gif_dither = false,
+            "--verify" => parsed.decode_settings.verify_reconstruction = true,
+            "--keep-inner-extension" => parsed.decode_settings.replace_inner_extension = false,
+            "--allow-partial-files" => parsed.decode_settings.allow_partial_files = true,
+            "--originals" => {
+                parsed.decode_settings.original_root = Some(PathBuf::from(value()?));
+                parsed.decode_settings.verify_reconstruction = true;
+            }
+            "--background" => {
+                let color = value()?;
+                parsed.decode_settings.flatten_background = color
+                    .to_str()
+                    .and_then(Color::from_hex)
+                    .ok_or_else(|| format!("invalid color '{}', expected #RRGGBB", color.to_string_lossy()))?;
+            }
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+
+    // Transactional batches can't overwrite; an explicit --existing overwrite
+    // is left for validation to reject
+    if parsed.settings.transactional && !existing_given {
+        parsed.settings.overwrite = OverwritePolicy::Skip;
+        parsed.decode_settings.overwrite = OverwritePolicy::Skip;
+    }
+
+    let output_dir = output_dir.ok_or("--output is required")?;
+    parsed.settings.output_dir = output_dir.clone();
+    parsed.decode_settings.output_dir = output_dir;
+
+    if parsed.inputs.is_empty() {
+        return Err("no inputs given".to_string());
+    }
+
+    Ok(parsed)
+}
+
+fn parse_number<T>(flag: &str, value: &OsString, min: T, max: T) -> Result<T, String>
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display + Copy,
+{
+    value
+        .to_str()
+        .and_then(|v| v.parse::<T>().ok())
+        .filter(|v| (min..=max).contains(v))
+        .ok_or_else(|| format!("{} must be a number between {} and {}", flag, min, max))
+}
+
+/// A byte count, optionally with a binary K, M, G or T suffix.
+fn parse_size(flag: &str, value: &OsString) -> Result<u64, String> {
+    let text = value.to_string_lossy();
+    let text = text.trim().trim_end_matches(['B', 'b']);
+    let (number, shift) = match text.char_indices().last() {
+        Some((i, unit)) if unit.is_ascii_alphabetic() => {
+            let shift = match unit.to_ascii_uppercase() {
+                'K' => 10,
+                'M' => 20,
+                'G' => 30,
+                'T' => 40,
+                _ => return Err(format!("{} has an unknown unit '{}'", flag, unit)),
+            };
+            (&text[..i], shift)
+        }
+        _ => (text, 0),
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| *n > 0.0)
+        .map(|n| (n * (1u64 << shift) as f64) as u64)
+        .ok_or_else(|| format!("{} must be a positive size such as 500M or 28G", flag))
+}
+
+/// A `MIN-MAX` pair within `min..=max`, low end first.
+fn parse_range(flag: &str, value: &OsString, min: f32, max: f32) -> Result<(f32, f32), String> {
+    value
+        .to_str()
+        .and_then(|v| v.split_once('-'))
+        .and_then(|(low, high)| Some((low.trim().parse::<f32>().ok()?, high.trim().parse::<f32>().ok()?)))
+        .filter(|&(low, high)| min <= low && low <= high && high <= max)
+        .ok_or_else(|| format!("{} must be MIN-MAX with {} <= MIN <= MAX <= {}", flag, min, max))
+}
+
+fn read_file_list(list_file: &Path) -> Result<Vec<ListEntry>, String> {
+    let content = std::fs::read_to_string(list_file)
+        .map_err(|e| format!("Failed to read {}: {}", list_file.display(), e))?;
+    let base_dir = list_file.parent().unwrap_or(Path::new(""));
+    Ok(parse_list(&content, base_dir))
+}
@@ -0,0 +1,219 @@
+use std::ffi::OsStr;
+use std::path::Path;
+
+use crate::engine::{is_jxl_file, is_supported_image};
+use crate::script::Shell;
+use crate::types::{quality_from_distance, ConversionSettings};
+
+/// The result of reading a pasted cjxl command: the settings it amounts to
+/// and what couldn't be carried over.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandImport {
+    /// `before` with the command's options applied.
+    pub settings: ConversionSettings,
+    /// Parts of the command that were left out or only approximated.
+    pub notes: Vec<String>,
+}
+
+/// Options the engine sets itself from the tool settings, so they aren't
+/// imported.
+const MANAGED_FLAGS: &[&str] = &["-v", "--verbose", "--num_threads"];
+
+/// Reads a cjxl command line such as
+/// `cjxl -d 1.0 -e 8 --photon_noise_iso=800 "in file.png" out.jxl` and
+/// applies its options to `before`.
+///
+/// The command is split like a POSIX shell would. Options may be written as
+/// `--flag=value` or `--flag value`. Quality, distance, effort and JPEG
+/// transcoding map onto their settings; anything else is kept in
+/// `extra_args`. The input and output paths are not imported.
+pub fn parse_cjxl_command(command: &str, before: &ConversionSettings) -> Result<CommandImport, String> {
+    let command = command.trim().trim_start_matches("$ ");
+    // Lines continued with a backslash, as commands are often shared
+    let command = command.replace("\\\r\n", " ").replace("\\\n", " ");
+    let tokens = shlex::split(&command).ok_or("The command has an unterminated quote or trailing backslash")?;
+
+    let Some((program, args)) = tokens.split_first() else {
+        return Err("The command is empty".to_string());
+    };
+    let name = Path::new(program)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match name.as_str() {
+        "cjxl" => {}
+        "djxl" => return Err("That's a djxl (decoding) command; only cjxl commands can be imported".to_string()),
+        _ => {
+            return Err(format!(
+                "Expected a command starting with cjxl, found \"{}\"",
+                program
+            ))
+        }
+    }
+
+    let mut settings = before.clone();
+    settings.extra_args.clear();
+    let mut notes = Vec::new();
+    let mut positional = Vec::new();
+    let mut args = args.iter().peekable();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            positional.push(arg.clone());
+            continue;
+        }
+
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        // Takes the flag's value from after `=` or from the next argument
+        let mut value = |what: &str| -> Result<String, String> {
+            match inline_value.clone().or_else(|| args.next().cloned()) {
+                Some(value) => Ok(value),
+                None => Err(format!("{} needs a {}", flag, what)),
+            }
+        };
+
+        match flag {
+            "-d" | "--distance" => {
+                let text = value("distance")?;
+                let distance: f32 = text.parse().map_err(|_| format!("Invalid distance \"{}\"", text))?;
+                if distance <= 0.0 {
+                    settings.lossless = true;
+                } else {
+                    let quality = quality_from_distance(distance).round().clamp(1.0, 100.0) as u8;
+                    settings.lossless = false;
+                    settings.quality = quality;
+                    notes.push(format!("Distance {} imported as quality {}", text, quality));
+                }
+            }
+            "-q" | "--quality" => {
+                let text = value("quality")?;
+                let quality: f32 = text.parse().map_err(|_| format!("Invalid quality \"{}\"", text))?;
+                if quality >= 100.0 {
+                    settings.lossless = true;
+                } else {
+                    settings.lossless = false;
+                    settings.quality = quality.round().clamp(1.0, 99.0) as u8;
+                }
+            }
+            "-e" | "--effort" => {
+                let text = value("effort")?;
+                settings.effort = match text.parse::<u8>() {
+                    Ok(effort @ 1..=10) => effort,
+                    _ => return Err(format!("Invalid effort \"{}\"; it must be 1 to 10", text)),
+                };
+            }
+            "-j" | "--lossless_jpeg" => {
+                // The value is optional; a bare flag means 1
+                let text = match inline_value {
+                    Some(value) => value,
+                    None if args.peek().is_some_and(|next| *next == "0" || *next == "1") => {
+                        args.next().cloned().unwrap_or_default()
+                    }
+                    None => "1".to_string(),
+                };
+                settings.jpeg_lossless = match text.as_str() {
+                    "1" => true,
+                    "0" => false,
+                    _ => return Err(format!("Invalid {} value \"{}\"; it must be 0 or 1", flag, text)),
+                };
+            }
+            "--override_bitdepth" => {
+                let text = value("bit depth")?;
+                settings.override_bitdepth = match text.parse::<u8>() {
+                    Ok(0) => None,
+                    Ok(bits @ 1..=32) => Some(bits),
+                    _ => return Err(format!("Invalid bit depth \"{}\"; it must be 0 to 32", text)),
+                };
+            }
+            _ if MANAGED_FLAGS.contains(&flag) => {
+                if flag == "--num_threads" && inline_value.is_none() {
+                    args.next();
+                }
+                let note = format!("{} is left out; it comes from Settings", flag);
+                if !notes.contains(&note) {
+                    notes.push(note);
+                }
+            }
+            _ => {
+                settings.extra_args.push(arg.clone());
+                // An unknown flag's value can't be told apart from the paths
+                // except by what it looks like
+                let takes_next = inline_value.is_none()
+                    && args.peek().is_some_and(|next| !next.starts_with('-') && !looks_like_image_path(next));
+                if takes_next {
+                    settings.extra_args.extend(args.next().cloned());
+                }
+            }
+        }
+    }
+
+    match positional.len() {
+        0 => {}
+        2 => notes.push(format!("Input {} and output {} are not imported", positional[0], positional[1])),
+        _ => notes.push(format!("Ignored {}", positional.join(" "))),
+    }
+
+    Ok(CommandImport { settings, notes })
+}
+
+fn looks_like_image_path(arg: &str) -> bool {
+    let path = Path::new(arg);
+    is_supported_image(path) || is_jxl_file(path)
+}
+
+/// `args` as one line that [`parse_cjxl_command`] and `sh` split back into
+/// the same arguments.
+pub fn join_args(args: &[String]) -> String {
+    let quoted: Vec<String> = args.iter().map(|arg| Shell::Sh.quote(OsStr::new(arg))).collect();
+    quoted.join(" ")
+}
+
+/// One line per encoding option that differs between `before` and `after`,
+/// e.g. `Quality: 90 → 85`.
+pub fn describe_changes(before: &ConversionSettings, after: &ConversionSettings) -> Vec<String> {
+    let on_off = |value: bool| if value { "on" } else { "off" };
+    let args = |args: &[String]| {
+        if args.is_empty() {
+            "(none)".to_string()
+        } else {
+            join_args(args)
+        }
+    };
+
+    let mut changes = Vec::new();
+    if before.lossless != after.lossless {
+        changes.push(format!("Lossless: {} → {}", on_off(before.lossless), on_off(after.lossless)));
+    }
+    if before.jpeg_lossless != after.jpeg_lossless {
+        changes.push(format!(
+            "JPEG lossless: {} → {}",
+            on_off(before.jpeg_lossless),
+            on_off(after.jpeg_lossless)
+        ));
+    }
+    if before.quality != after.quality {
+        changes.push(format!("Quality: {} → {}", before.quality, after.quality));
+    }
+    if before.effort != after.effort {
+        changes.push(format!("Effort: {} → {}", before.effort, after.effort));
+    }
+    if before.override_bitdepth != after.override_bitdepth {
+        let bits = |bits: Option<u8>| bits.map_or("(input)".to_string(), |bits| bits.to_string());
+        changes.push(format!(
+            "Bit depth: {} → {}",
+            bits(before.override_bitdepth),
+            bits(after.override_bitdepth)
+        ));
+    }
+    if before.extra_args != after.extra_args {
+        changes.push(format!(
+            "Extra arguments: {} → {}",
+            args(&before.extra_args),
+            args(&after.extra_args)
+        ));
+    }
+    changes
+}
@@ -0,0 +1,276 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use image::{RgbImage, RgbaImage};
+
+use crate::adaptive::parse_ssimulacra2_score;
+use crate::engine::is_jxl_file;
+use crate::process::{run_tool, RunLimits};
+use crate::tools::DiscoveredTools;
+
+/// Differences are multiplied by this in the heatmap, so the small ones a
+/// lossy encode leaves are still visible.
+pub const HEATMAP_GAIN: u16 = 4;
+
+/// The tools a comparison uses. Only djxl is needed, and only for JXLs;
+/// the metrics are skipped when their binary is missing.
+#[derive(Clone, Debug, Default)]
+pub struct CompareTools {
+    pub djxl: Option<PathBuf>,
+    pub ssimulacra2: Option<PathBuf>,
+    pub butteraugli: Option<PathBuf>,
+}
+
+impl CompareTools {
+    pub fn from_tools(tools: &DiscoveredTools) -> Self {
+        Self {
+            djxl: tools.djxl.as_ref().map(|tool| tool.path.clone()),
+            ssimulacra2: tools.ssimulacra2.clone(),
+            butteraugli: tools.butteraugli.clone(),
+        }
+    }
+}
+
+/// How far one channel of two images is apart.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelDiff {
+    /// `R`, `G`, `B` or `A`.
+    pub channel: char,
+    pub max: u8,
+    pub mean: f64,
+}
+
+/// How far two images of the same size are apart, pixel by pixel. Alpha is
+/// only compared when either image has some transparency.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PixelDiff {
+    pub width: u32,
+    pub height: u32,
+    pub channels: Vec<ChannelDiff>,
+    /// Over every compared channel, in dB; `None` when the images are
+    /// identical.
+    pub psnr: Option<f64>,
+}
+
+impl PixelDiff {
+    pub fn is_identical(&self) -> bool {
+        self.channels.iter().all(|channel| channel.max == 0)
+    }
+}
+
+/// Compares `a` and `b`, which must be the same size.
+pub fn pixel_diff(a: &RgbaImage, b: &RgbaImage) -> Result<PixelDiff, String> {
+    check_sizes(a, b)?;
+    let has_alpha = |image: &RgbaImage| image.pixels().any(|pixel| pixel.0[3] < 255);
+    let count = if has_alpha(a) || has_alpha(b) { 4 } else { 3 };
+
+    let mut max = [0u8; 4];
+    let mut sum = [0u64; 4];
+    let mut squares = 0u64;
+    for (pa, pb) in a.pixels().zip(b.pixels()) {
+        for c in 0..count {
+            let d = pa.0[c].abs_diff(pb.0[c]);
+            max[c] = max[c].max(d);
+            sum[c] += u64::from(d);
+            squares += u64::from(d) * u64::from(d);
+        }
+    }
+
+    let pixels = (u64::from(a.width()) * u64::from(a.height())).max(1) as f64;
+    let channels = ['R', 'G', 'B', 'A']
+        .into_iter()
+        .enumerate()
+        .take(count)
+        .map(|(c, channel)| ChannelDiff { channel, max: max[c], mean: sum[c] as f64 / pixels })
+        .collect();
+    let mse = squares as f64 / (pixels * count as f64);
+    let psnr = (mse > 0.0).then(|| 10.0 * (255.0 * 255.0 / mse).log10());
+    Ok(PixelDiff { width: a.width(), height: a.height(), channels, psnr })
+}
+
+/// Each pixel's largest channel difference, times [`HEATMAP_GAIN`], on a
+/// black–red–yellow–white scale.
+pub fn heatmap(a: &RgbaImage, b: &RgbaImage) -> Result<RgbImage, String> {
+    check_sizes(a, b)?;
+    Ok(RgbImage::from_fn(a.width(), a.height(), |x, y| {
+        let (pa, pb) = (a.get_pixel(x, y).0, b.get_pixel(x, y).0);
+        let d = (0..4).map(|c| pa[c].abs_diff(pb[c])).max().unwrap_or(0);
+        image::Rgb(heat_color(d))
+    }))
+}
+
+fn heat_color(difference: u8) -> [u8; 3] {
+    let scaled = u32::from((u16::from(difference) * HEATMAP_GAIN).min(255)) * 3;
+    match scaled {
+        0..=255 => [scaled as u8, 0, 0],
+        256..=510 => [255, (scaled - 255) as u8, 0],
+        _ => [255, 255, (scaled - 510) as u8],
+    }
+}
+
+fn check_sizes(a: &RgbaImage, b: &RgbaImage) -> Result<(), String> {
+    if a.dimensions() != b.dimensions() {
+        return Err(format!(
+            "The images are different sizes: {}×{} and {}×{}",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height()
+        ));
+    }
+    Ok(())
+}
+
+/// An image read for comparing, and the file the metric tools are given.
+struct Loaded {
+    image: RgbaImage,
+    path: PathBuf,
+}
+
+/// Reads `path`, decoding a JXL with djxl into `temp_dir` first.
+fn load(path: &Path, name: &str, tools: &CompareTools, temp_dir: &Path, limits: &RunLimits) -> Result<Loaded, String> {
+    let readable = if is_jxl_file(path) {
+        let djxl = tools.djxl.as_deref().ok_or("djxl not found")?;
+        std::fs::create_dir_all(temp_dir).map_err(|e| format!("Failed to create {}: {}", temp_dir.display(), e))?;
+        let png = temp_dir.join(format!("{}.png", name));
+        let mut cmd = Command::new(djxl);
+        cmd.arg(path).arg(&png);
+        let output = run_tool(&mut cmd, limits).map_err(|e| format!("djxl {}", e))?;
+        if !output.status.success() {
+            return Err(format!("djxl failed: {}", String::from_utf8_lossy(&output.stderr)));
+        }
+        png
+    } else {
+        path.to_path_buf()
+    };
+    let image = image::open(&readable).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(Loaded { image: image.to_rgba8(), path: readable })
+}
+
+/// A metric tool's score, or why there is none.
+pub type Score = Result<f32, String>;
+
+/// Everything a comparison of two files found.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    pub a: PathBuf,
+    pub b: PathBuf,
+    pub diff: PixelDiff,
+    /// `None` when the tool isn't installed.
+    pub ssimulacra2: Option<Score>,
+    pub butteraugli: Option<Score>,
+    pub heatmap: RgbImage,
+}
+
+impl Comparison {
+    /// The results as lines of text, for showing and copying.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("A: {}", self.a.display()),
+            format!("B: {}", self.b.display()),
+            format!("Size: {}×{}", self.diff.width, self.diff.height),
+        ];
+        for channel in &self.diff.channels {
+            lines.push(format!("{}: max {}, mean {:.3}", channel.channel, channel.max, channel.mean));
+        }
+        lines.push(match self.diff.psnr {
+            Some(psnr) => format!("PSNR: {:.2} dB", psnr),
+            None => "PSNR: ∞ (identical pixels)".to_string(),
+        });
+        for (name, score) in [("ssimulacra2", &self.ssimulacra2), ("butteraugli", &self.butteraugli)] {
+            match score {
+                Some(Ok(score)) => lines.push(format!("{}: {:.3}", name, score)),
+                Some(Err(error)) => lines.push(format!("{}: {}", name, error)),
+                None => {}
+            }
+        }
+        lines
+    }
+}
+
+/// Compares files `a` and `b`, calling `on_step` with the number of steps
+/// done, the total and what comes next. JXLs are decoded into `temp_dir`,
+/// which the caller removes afterwards.
+pub fn compare_files(
+    a: &Path,
+    b: &Path,
+    tools: &CompareTools,
+    temp_dir: &Path,
+    cancel_flag: Arc<AtomicBool>,
+    mut on_step: impl FnMut(usize, usize, &str),
+) -> Result<Comparison, String> {
+    let limits = RunLimits { cancel_flag: Some(cancel_flag), ..Default::default() };
+    let metrics: Vec<(Metric, &Path)> = [
+        (Metric::Ssimulacra2, &tools.ssimulacra2),
+        (Metric::Butteraugli, &tools.butteraugli),
+    ]
+    .into_iter()
+    .filter_map(|(metric, tool)| tool.as_deref().map(|tool| (metric, tool)))
+    .collect();
+    let total = 3 + metrics.len();
+
+    on_step(0, total, "Reading A");
+    let loaded_a = load(a, "a", tools, temp_dir, &limits)?;
+    on_step(1, total, "Reading B");
+    let loaded_b = load(b, "b", tools, temp_dir, &limits)?;
+    on_step(2, total, "Comparing pixels");
+    let diff = pixel_diff(&loaded_a.image, &loaded_b.image)?;
+    let heatmap = heatmap(&loaded_a.image, &loaded_b.image)?;
+
+    let mut comparison = Comparison {
+        a: a.to_path_buf(),
+        b: b.to_path_buf(),
+        diff,
+        ssimulacra2: None,
+        butteraugli: None,
+        heatmap,
+    };
+    for (idx, (metric, tool)) in metrics.into_iter().enumerate() {
+        on_step(3 + idx, total, &format!("Running {}", metric.name()));
+        let score = metric.run(tool, &loaded_a.path, &loaded_b.path, &limits);
+        match metric {
+            Metric::Ssimulacra2 => comparison.ssimulacra2 = Some(score),
+            Metric::Butteraugli => comparison.butteraugli = Some(score),
+        }
+    }
+    on_step(total, total, "Done");
+    Ok(comparison)
+}
+
+/// An external perceptual metric.
+#[derive(Clone, Copy)]
+enum Metric {
+    Ssimulacra2,
+    Butteraugli,
+}
+
+impl Metric {
+    fn name(self) -> &'static str {
+        match self {
+            Metric::Ssimulacra2 => "ssimulacra2",
+            Metric::Butteraugli => "butteraugli",
+        }
+    }
+
+    fn run(self, tool: &Path, a: &Path, b: &Path, limits: &RunLimits) -> Score {
+        let mut cmd = Command::new(tool);
+        cmd.arg(a).arg(b);
+        let output = run_tool(&mut cmd, limits).map_err(|e| format!("{} {}", self.name(), e))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let score = match self {
+            Metric::Ssimulacra2 => parse_ssimulacra2_score(&stdout),
+            Metric::Butteraugli => parse_butteraugli_score(&stdout),
+        };
+        // butteraugli_main exits non-zero when the images differ noticeably,
+        // so a score it printed is used either way
+        score.ok_or_else(|| format!("{} failed: {}", self.name(), String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+/// The score in butteraugli_main's output: the first number it prints, as
+/// the 3-norm follows on a later line.
+pub fn parse_butteraugli_score(output: &str) -> Option<f32> {
+    output.split_whitespace().find_map(|word| word.parse::<f32>().ok())
+}
@@ -0,0 +1,341 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use jxl_converter::{ConversionSettings, DecodeSettings, ToolSettings};
+
+use crate::power::PowerSettings;
+use crate::throttle::ThrottleSettings;
+
+const CONFIG_DIR_NAME: &str = "jxl-converter-gui";
+const CONFIG_FILE_NAME: &str = "config.toml";
+const BACKUP_FILE_NAME: &str = "config.toml.bak";
+const JOURNAL_DIR_NAME: &str = "journals";
+
+/// Version written by this build. Bump it and add a migration whenever a
+/// stored field is renamed, moved or changes meaning.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// `MIGRATIONS[n]` upgrades a version `n` config to version `n + 1`.
+const MIGRATIONS: &[fn(&mut toml::Table)] = &[migrate_v0_to_v1];
+
+/// Everything persisted between runs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub version: u32,
+    pub active_profile: Option<String>,
+    pub settings: ConversionSettings,
+    pub decode_settings: DecodeSettings,
+    pub tools: ToolSettings,
+    pub power: PowerSettings,
+    pub throttle: ThrottleSettings,
+    pub profiles: Vec<Profile>,
+    /// Input bytes converted per second, averaged over recent batches; used
+    /// to estimate how long a large batch will take.
+    pub throughput: Option<f64>,
+    /// Keys this build doesn't know, at any depth of table, written back
+    /// unchanged so running an older version doesn't discard a newer one's
+    /// options.
+    #[serde(skip)]
+    pub unknown: toml::Table,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            active_profile: None,
+            settings: ConversionSettings::default(),
+            decode_settings: DecodeSettings::default(),
+            tools: ToolSettings::default(),
+            power: PowerSettings::default(),
+            throttle: ThrottleSettings::default(),
+            profiles: Vec::new(),
+            throughput: None,
+            unknown: toml::Table::new(),
+        }
+    }
+}
+/// A named snapshot of all encode and decode settings.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub settings: ConversionSettings,
+    #[serde(default)]
+    pub decode_settings: DecodeSettings,
+}
+
+impl AppConfig {
+    pub fn config_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+    }
+
+    /// Where batches keep their crash-recovery journals.
+    pub fn journal_dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(CONFIG_DIR_NAME).join(JOURNAL_DIR_NAME))
+    }
+
+    /// Loads the config file, returning defaults when none exists yet. A file
+    /// that can't be parsed is moved aside to `config.toml.bak` and replaced
+    /// with defaults; the returned message says so.
+    pub fn load() -> (Self, Option<String>) {
+        let Some(path) = Self::config_path() else {
+            return (Self::default(), None);
+        };
+
+        if !path.exists() {
+            return (Self::default(), None);
+        }
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                let message = format!("Failed to read {}: {} (using default settings)", path.display(), e);
+                return (Self::default(), Some(message));
+            }
+        };
+
+        match Self::from_toml_str(&content) {
+            Ok(config) => (config, None),
+            Err(e) => {
+                let config = Self::default();
+                let backup = path.with_file_name(BACKUP_FILE_NAME);
+                let message = match std::fs::rename(&path, &backup).map_err(|e| e.to_string()).and_then(|_| config.save()) {
+                    Ok(()) => format!(
+                        "Invalid config {}: {}. The old file was saved as {} and default settings restored.",
+                        path.display(),
+                        e,
+                        backup.display()
+                    ),
+                    Err(save_error) => format!(
+                        "Invalid config {}: {} (using default settings; backup failed: {})",
+                        path.display(),
+                        e,
+                        save_error
+                    ),
+                };
+                (config, Some(message))
+            }
+        }
+    }
+
+    /// Parses a config file, upgrading it from older versions first.
+    pub fn from_toml_str(content: &str) -> Result<Self, String> {
+        let mut table: toml::Table = toml::from_str(content).map_err(|e| e.to_string())?;
+
+        // Files written before versioning have no `version` key
+        let version = match table.get("version") {
+            None => 0,
+            Some(value) => value
+                .as_integer()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or("version must be a non-negative integer")?,
+        };
+
+        for migrate in MIGRATIONS.iter().skip(version as usize) {
+            migrate(&mut table);
+        }
+        table.insert("version".to_string(), toml::Value::Integer(i64::from(version.max(CONFIG_VERSION))));
+
+        let mut config: Self = table.clone().try_into().map_err(|e: toml::de::Error| e.to_string())?;
+        // Whatever doesn't come back out when the parsed config is written is unknown here
+        let known = toml::Table::try_from(&config).map_err(|e| e.to_string())?;
+        config.unknown = unknown_keys(table, &known);
+        Ok(config)
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, String> {
+        let mut config = self.clone();
+        // Never stamp a newer file with an older version, or its migrations would run again
+        config.version = config.version.max(CONFIG_VERSION);
+
+        // Going through a Value lets toml put plain keys captured in `unknown`
+        // ahead of the tables
+        let mut table = toml::Table::try_from(&config).map_err(|e| format!("Failed to serialize config: {}", e))?;
+        merge_unknown(&mut table, &config.unknown);
+        toml::to_string(&toml::Value::Table(table)).map_err(|e| format!("Failed to serialize config: {}", e))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::config_path().ok_or("No config directory available on this system")?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+
+        let content = self.to_toml_string()?;
+        std::fs::write(&path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// Returns `name`, or `name (2)`, `name (3)`… if it's already taken.
+    pub fn unique_profile_name(&self, name: &str) -> String {
+        if self.profile(name).is_none() {
+            return name.to_string();
+        }
+
+        (2..)
+            .map(|n| format!("{} ({})", name, n))
+            .find(|candidate| self.profile(candidate).is_none())
+            .unwrap()
+    }
+}
+
+impl Profile {
+    pub fn export_json(&self, path: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize profile: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn import_json(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid profile {}: {}", path.display(), e))
+    }
+}
+
+/// The keys of `loaded` that aren't in `known`, keeping the tables they sit
+/// in. Arrays are compared whole, so keys inside `profiles` aren't kept.
+fn unknown_keys(loaded: toml::Table, known: &toml::Table) -> toml::Table {
+    let mut unknown = toml::Table::new();
+    for (key, value) in loaded {
+        match (value, known.get(&key)) {
+            (value, None) => {
+                unknown.insert(key, value);
+            }
+            (toml::Value::Table(loaded), Some(toml::Value::Table(known))) => {
+                let nested = unknown_keys(loaded, known);
+                if !nested.is_empty() {
+                    unknown.insert(key, toml::Value::Table(nested));
+                }
+            }
+            _ => {}
+        }
+    }
+    unknown
+}
+
+/// Puts the keys from [`unknown_keys`] back into a serialized config.
+fn merge_unknown(table: &mut toml::Table, unknown: &toml::Table) {
+    for (key, value) in unknown {
+        match (table.get_mut(key), value) {
+            (Some(toml::Value::Table(known)), toml::Value::Table(nested)) => merge_unknown(known, nested),
+            (None, value) => {
+                table.insert(key.clone(), value.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Version 0 is the unversioned layout from before the `version` key
+/// existed. Version 1 stores the same fields, so only the stamp changes.
+fn migrate_v0_to_v1(_config: &mut toml::Table) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_settings_and_profiles() {
+        let mut config = AppConfig::default();
+        config.settings.quality = 72;
+        config.tools.env_overrides = vec![("JXL_DEBUG".to_string(), "1".to_string())];
+        config.profiles.push(Profile {
+            name: "Archive".to_string(),
+            settings: ConversionSettings { lossless: true, ..Default::default() },
+            decode_settings: DecodeSettings::default(),
+        });
+        config.active_profile = Some("Archive".to_string());
+
+        let loaded = AppConfig::from_toml_str(&config.to_toml_string().unwrap()).unwrap();
+
+        assert_eq!(loaded.version, CONFIG_VERSION);
+        assert_eq!(loaded.settings, config.settings);
+        assert_eq!(loaded.tools, config.tools);
+        assert_eq!(loaded.profiles, config.profiles);
+        assert_eq!(loaded.active_profile, config.active_profile);
+    }
+
+    #[test]
+    fn unversioned_file_is_migrated() {
+        let loaded = AppConfig::from_toml_str(
+            r#"
+            active_profile = "Web"
+
+            [settings]
+            quality = 80
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(loaded.version, CONFIG_VERSION);
+        assert_eq!(loaded.active_profile.as_deref(), Some("Web"));
+        assert_eq!(loaded.settings.quality, 80);
+        assert!(loaded.to_toml_string().unwrap().contains(&format!("version = {}", CONFIG_VERSION)));
+    }
+
+    #[test]
+    fn unknown_fields_survive_a_save() {
+        let newer = r#"
+            version = 99
+            future_flag = true
+
+            [settings]
+            quality = 60
+
+            [future_section]
+            mode = "fast"
+        "#;
+
+        let loaded = AppConfig::from_toml_str(newer).unwrap();
+        let saved: toml::Table = toml::from_str(&loaded.to_toml_string().unwrap()).unwrap();
+
+        // A newer version must not be downgraded, or its migrations would rerun
+        assert_eq!(saved["version"].as_integer(), Some(99));
+        assert_eq!(saved["future_flag"].as_bool(), Some(true));
+        assert_eq!(saved["future_section"]["mode"].as_str(), Some("fast"));
+        assert_eq!(saved["settings"]["quality"].as_integer(), Some(60));
+    }
+
+    #[test]
+    fn unknown_fields_inside_tables_survive_a_save() {
+        let newer = r#"
+            [settings]
+            quality = 60
+            future_option = "on"
+
+            [tools]
+            future_tool = { path = "/opt/jxl/bin" }
+        "#;
+
+        let mut loaded = AppConfig::from_toml_str(newer).unwrap();
+        loaded.settings.quality = 70;
+        let saved = AppConfig::from_toml_str(&loaded.to_toml_string().unwrap()).unwrap();
+        let table: toml::Table = toml::from_str(&saved.to_toml_string().unwrap()).unwrap();
+
+        assert_eq!(saved.settings.quality, 70);
+        assert_eq!(table["settings"]["quality"].as_integer(), Some(70));
+        assert_eq!(table["settings"]["future_option"].as_str(), Some("on"));
+        assert_eq!(table["tools"]["future_tool"]["path"].as_str(), Some("/opt/jxl/bin"));
+        // Only the keys this build doesn't know are kept aside
+        assert!(!saved.unknown["settings"].as_table().unwrap().contains_key("quality"));
+    }
+
+    #[test]
+    fn corrupt_file_is_an_error() {
+        assert!(AppConfig::from_toml_str("settings = [").is_err());
+        assert!(AppConfig::from_toml_str("[settings]\nquality = \"high\"").is_err());
+        assert!(AppConfig::from_toml_str("version = -1").is_err());
+    }
+}
@@ -0,0 +1,234 @@
+use crate::rollback::{check_transactional, TRANSACTIONAL_OVERWRITE};
+use crate::types::ConversionSettings;
+
+/// How much a [`Conflict`] matters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Part of the settings will be silently ignored or overridden; the user
+    /// has to confirm before the batch starts.
+    Soft,
+    /// cjxl would fail or produce something other than what the settings
+    /// say; the batch doesn't start.
+    Hard,
+}
+
+/// The encode setting a conflict involves, so the UI can mark its control.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    Lossless,
+    JpegLossless,
+    Quality,
+    Effort,
+    BitDepth,
+    Animation,
+    Overwrite,
+    ExtraArgs,
+}
+
+/// A combination of settings that doesn't do what it looks like it does.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    /// Stable name of the rule, e.g. `lossless_photon_noise`.
+    pub id: &'static str,
+    pub severity: Severity,
+    pub controls: &'static [Control],
+    pub message: &'static str,
+}
+
+struct Rule {
+    conflict: Conflict,
+    applies: fn(&ConversionSettings) -> bool,
+}
+
+const fn rule(
+    id: &'static str,
+    severity: Severity,
+    controls: &'static [Control],
+    message: &'static str,
+    applies: fn(&ConversionSettings) -> bool,
+) -> Rule {
+    Rule { conflict: Conflict { id, severity, controls, message }, applies }
+}
+
+const RULES: &[Rule] = &[
+    rule(
+        "quality_out_of_range",
+        Severity::Hard,
+        &[Control::Quality],
+        "Quality must be between 1 and 100",
+        |s| !(1..=100).contains(&s.quality),
+    ),
+    rule(
+        "effort_out_of_range",
+        Severity::Hard,
+        &[Control::Effort],
+        "Effort must be between 1 and 10",
+        |s| !(1..=10).contains(&s.effort),
+    ),
+    rule(
+        "bitdepth_out_of_range",
+        Severity::Hard,
+        &[Control::BitDepth],
+        "Bit depth override must be between 1 and 32",
+        |s| s.override_bitdepth.is_some_and(|bits| !(1..=32).contains(&bits)),
+    ),
+    rule(
+        "frame_delay_out_of_range",
+        Severity::Hard,
+        &[Control::Animation],
+        "Frame delay must be between 1 and 65535 ms",
+        |s| s.timing().validate().is_err(),
+    ),
+    rule(
+        "transactional_overwrite",
+        Severity::Hard,
+        &[Control::Overwrite],
+        TRANSACTIONAL_OVERWRITE,
+        |s| check_transactional(s.transactional, s.overwrite).is_err(),
+    ),
+    rule(
+        "adaptive_quality_out_of_range",
+        Severity::Hard,
+        &[Control::Quality],
+        "Adaptive quality needs a score window within 0–100 and a distance range within 0.01–25, low ends first, and 1–10 attempts",
+        |s| s.adaptive_quality.as_ref().is_some_and(|adaptive| adaptive.validate().is_err()),
+    ),
+    rule(
+        "adaptive_lossless",
+        Severity::Soft,
+        &[Control::Quality, Control::Lossless],
+        "Adaptive quality is ignored when encoding losslessly",
+        |s| s.lossless && s.adaptive_quality.is_some(),
+    ),
+    rule(
+        "adaptive_quality_override",
+        Severity::Soft,
+        &[Control::Quality, Control::ExtraArgs],
+        "Extra arguments set a quality or distance, which would override every distance adaptive quality tries",
+        |s| !s.lossless && s.adaptive_quality.is_some() && sets_lossy_quality(s),
+    ),
+    rule(
+        "bitdepth_lossless_jpeg",
+        Severity::Soft,
+        &[Control::BitDepth, Control::Lossless, Control::JpegLossless],
+        "JPEGs transcoded losslessly keep their own bit depth; the override applies to other inputs only",
+        |s| s.override_bitdepth.is_some() && (s.jpeg_lossless || s.lossless),
+    ),
+    rule(
+        "lossless_quality_override",
+        Severity::Hard,
+        &[Control::Lossless, Control::ExtraArgs],
+        "Extra arguments set a quality or distance, which would make Lossless lossy",
+        |s| s.lossless && sets_lossy_quality(s),
+    ),
+    rule(
+        "quality_override",
+        Severity::Soft,
+        &[Control::Quality, Control::ExtraArgs],
+        "Extra arguments set a quality or distance, overriding the Quality slider",
+        |s| !s.lossless && sets_lossy_quality(s),
+    ),
+    rule(
+        "effort_override",
+        Severity::Soft,
+        &[Control::Effort, Control::ExtraArgs],
+        "Extra arguments set an effort, overriding the Effort slider",
+        |s| extra_flag(s, &["-e", "--effort"]).is_some(),
+    ),
+    rule(
+        "bitdepth_override",
+        Severity::Soft,
+        &[Control::BitDepth, Control::ExtraArgs],
+        "Extra arguments set --override_bitdepth as well as the Bit depth option",
+        |s| s.override_bitdepth.is_some() && extra_flag(s, &["--override_bitdepth"]).is_some(),
+    ),
+    rule(
+        "resampling_lossless",
+        Severity::Hard,
+        &[Control::Lossless, Control::ExtraArgs],
+        "Resampling throws away resolution, so it can't be combined with Lossless",
+        |s| s.lossless && resamples(s),
+    ),
+    rule(
+        "resampling_lossless_jpeg",
+        Severity::Hard,
+        &[Control::JpegLossless, Control::ExtraArgs],
+        "cjxl rejects resampling with lossless JPEG transcoding, so every JPEG would fail",
+        |s| s.jpeg_lossless && !s.lossless && resamples(s),
+    ),
+    rule(
+        "lossless_photon_noise",
+        Severity::Soft,
+        &[Control::Lossless, Control::ExtraArgs],
+        "Photon noise is ignored when encoding losslessly",
+        |s| s.lossless && adds_photon_noise(s),
+    ),
+    rule(
+        "lossless_jpeg_photon_noise",
+        Severity::Soft,
+        &[Control::JpegLossless, Control::ExtraArgs],
+        "Photon noise is ignored for JPEGs transcoded losslessly",
+        |s| s.jpeg_lossless && !s.lossless && adds_photon_noise(s),
+    ),
+    rule(
+        "lossless_faster_decoding",
+        Severity::Soft,
+        &[Control::Lossless, Control::ExtraArgs],
+        "--faster_decoding is mostly ignored at distance 0",
+        |s| s.lossless && extra_flag(s, &["--faster_decoding"]).is_some_and(|v| v != Some("0")),
+    ),
+    rule(
+        "managed_tool_flags",
+        Severity::Soft,
+        &[Control::ExtraArgs],
+        "Verbosity and thread count come from Settings → Tools; setting them here as well is confusing",
+        |s| extra_flag(s, &["-v", "--verbose", "--num_threads"]).is_some(),
+    ),
+];
+
+/// Every rule `settings` break, hard ones first.
+pub fn check_settings(settings: &ConversionSettings) -> Vec<Conflict> {
+    let mut conflicts: Vec<Conflict> = RULES
+        .iter()
+        .filter(|rule| (rule.applies)(settings))
+        .map(|rule| rule.conflict.clone())
+        .collect();
+    conflicts.sort_by_key(|conflict| std::cmp::Reverse(conflict.severity));
+    conflicts
+}
+
+/// The ids of all rules, in the order they are checked.
+pub fn rule_ids() -> Vec<&'static str> {
+    RULES.iter().map(|rule| rule.conflict.id).collect()
+}
+
+/// Whether the extra arguments contain one of `names`, and its value if it
+/// has one (`--flag=value` or `--flag value`).
+pub(crate) fn extra_flag<'a>(settings: &'a ConversionSettings, names: &[&str]) -> Option<Option<&'a str>> {
+    let args = &settings.extra_args;
+    args.iter().enumerate().find_map(|(i, arg)| {
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value)),
+            None => {
+                let next = args.get(i + 1).map(String::as_str);
+                (arg.as_str(), next.filter(|v| !v.starts_with('-') || v.parse::<f64>().is_ok()))
+            }
+        };
+        names.contains(&flag).then_some(value)
+    })
+}
+
+fn sets_lossy_quality(settings: &ConversionSettings) -> bool {
+    let lossy = |value: Option<&str>, lossless: f32| value.and_then(|v| v.parse::<f32>().ok()) != Some(lossless);
+    extra_flag(settings, &["-d", "--distance"]).is_some_and(|v| lossy(v, 0.0))
+        || extra_flag(settings, &["-q", "--quality"]).is_some_and(|v| lossy(v, 100.0))
+}
+
+fn resamples(settings: &ConversionSettings) -> bool {
+    extra_flag(settings, &["--resampling", "--ec_resampling", "--already_downsampled"])
+        .is_some_and(|v| !matches!(v, Some("1") | Some("-1")))
+}
+
+fn adds_photon_noise(settings: &ConversionSettings) -> bool {
+    extra_flag(settings, &["--photon_noise", "--photon_noise_iso"]).is_some_and(|v| v != Some("0"))
+}
@@ -0,0 +1,154 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::paste::percent_decode;
+
+/// Downloads larger than this are refused.
+pub const MAX_DOWNLOAD_BYTES: u64 = 200 * 1024 * 1024;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+/// How long a download may stall before it fails.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The URL an input path stands for, if it is an `http://` or `https://` URL
+/// rather than a file.
+pub fn url_of(input: &Path) -> Option<&str> {
+    let text = input.to_str()?;
+    let is_url = ["http://", "https://"]
+        .iter()
+        .any(|scheme| text.get(..scheme.len()).is_some_and(|s| s.eq_ignore_ascii_case(scheme)));
+    is_url.then_some(text)
+}
+
+/// Extensions for the image format `data` starts with, the usual one first,
+/// judged by its signature rather than what the server claims.
+pub fn sniff_image_type(data: &[u8]) -> Option<&'static [&'static str]> {
+    let extensions: &[&str] = match data {
+        [0xFF, 0xD8, 0xFF, ..] => &["jpg", "jpeg"],
+        [0x89, b'P', b'N', b'G', ..] => &["png"],
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => &["gif"],
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => &["webp"],
+        [b'B', b'M', ..] => &["bmp"],
+        [b'I', b'I', 0x2A, 0x00, ..] | [b'M', b'M', 0x00, 0x2A, ..] => &["tif", "tiff"],
+        [b'P', b'5', ..] => &["pgm", "pnm"],
+        [b'P', b'6', ..] => &["ppm", "pnm"],
+        _ => return None,
+    };
+    Some(extensions)
+}
+
+/// A file name for what `url` pointed at: its last path segment,
+/// percent-decoded and made safe for any file system, with the first of
+/// `extensions` appended unless it already ends in one of them.
+pub fn file_name_for(url: &str, extensions: &[&str]) -> String {
+    let without_query = url.split(['?', '#']).next().unwrap_or(url);
+    let path = without_query.split_once("://").map_or(without_query, |(_, rest)| rest);
+    let segment = path.split_once('/').map_or("", |(_, path)| path).rsplit('/').next().unwrap_or("");
+
+    let decoded = String::from_utf8_lossy(&percent_decode(segment)).into_owned();
+    let cleaned: String = decoded
+        .chars()
+        .map(|c| if c.is_control() || r#"<>:"/\|?*"#.contains(c) { '_' } else { c })
+        .collect();
+    let name = match cleaned.trim_matches(['.', ' ']) {
+        "" => "download",
+        name => name,
+    };
+
+    let has_extension = Path::new(name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| extensions.contains(&ext.as_str()));
+    match extensions.first() {
+        Some(extension) if !has_extension => format!("{}.{}", name, extension),
+        _ => name.to_string(),
+    }
+}
+
+/// Downloads the image at `url` into `dir`, named by [`file_name_for`].
+/// Fails on HTTP errors, bodies over `max_bytes` and anything that isn't an
+/// image format cjxl reads. `on_progress` gets the bytes received so far
+/// and the total when the server sent one.
+pub fn download(
+    url: &str,
+    dir: &Path,
+    max_bytes: u64,
+    cancel_flag: &AtomicBool,
+    mut on_progress: impl FnMut(u64, Option<u64>),
+) -> Result<PathBuf, String> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .user_agent(concat!("jxl-converter/", env!("CARGO_PKG_VERSION")))
+        .build();
+    let response = agent.get(url).call().map_err(|e| match e {
+        ureq::Error::Status(code, response) => format!("Download failed: HTTP {} {}", code, response.status_text()),
+        ureq::Error::Transport(transport) => format!("Download failed: {}", transport),
+    })?;
+
+    let too_large = || format!("Download is larger than the {} MB limit", max_bytes / (1024 * 1024));
+    let length = response.header("Content-Length").and_then(|v| v.trim().parse::<u64>().ok());
+    if length.is_some_and(|length| length > max_bytes) {
+        return Err(too_large());
+    }
+    let content_type = response.header("Content-Type").unwrap_or("no content type").to_string();
+
+    // One byte past the limit tells a body that is too large from one that fits
+    let mut reader = response.into_reader().take(max_bytes + 1);
+    let mut data = Vec::new();
+    let mut chunk = vec![0u8; 64 * 1024];
+    loop {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err("Download cancelled".to_string());
+        }
+        let read = reader.read(&mut chunk).map_err(|e| format!("Download failed: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        data.extend_from_slice(&chunk[..read]);
+        if data.len() as u64 > max_bytes {
+            return Err(too_large());
+        }
+        on_progress(data.len() as u64, length);
+    }
+
+    let Some(extensions) = sniff_image_type(&data) else {
+        return Err(format!("Not an image cjxl can read (the server sent {})", content_type));
+    };
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(file_name_for(url, extensions));
+    std::fs::write(&path, &data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// A temp folder for one batch's downloads, deleted when dropped unless
+/// the downloads are to be kept.
+pub struct DownloadDir {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl DownloadDir {
+    /// A new, not yet created, folder under the system temp folder.
+    pub fn new(keep: bool) -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+        let path = std::env::temp_dir()
+            .join("jxl-converter-downloads")
+            .join(format!("{}-{}", std::process::id(), nanos));
+        Self { path, keep }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for DownloadDir {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
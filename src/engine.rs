@@ -1,32 +1,465 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::BufReader;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::mpsc::Sender;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use walkdir::WalkDir;
+use std::time::Duration;
 
-use crate::types::{ConversionSettings, DecodeSettings, DecodeItem, OutputFormat, ProgressMessage};
+use crate::adaptive::{parse_ssimulacra2_score, AdaptiveQuality, Candidate, DistanceSearch};
+use crate::animation::{frame_count, parse_frame_line, retime, write_gif, AnimationTiming, GifOptions};
+use crate::conflicts::{check_settings, extra_flag, Severity};
+use crate::download::{download, url_of, DownloadDir, MAX_DOWNLOAD_BYTES};
+use crate::flatten::{jxl_has_alpha, jxl_is_animated, write_flattened};
+use crate::hook::{expand_post_hook, shell_command};
+use crate::journal::{Journal, JournalBatch, Resume};
+use crate::notify::post_summary;
+use crate::paths::{apply_overwrite_policy, plan_output_path, OutputClaims, OutputPlan};
+use crate::scan::{scan_paths, still_being_written, Scan, ScanOptions};
+use crate::tools::{DiscoveredTools, ToolCapabilities, ToolInfo};
+use crate::rollback::{check_transactional, Rollback};
+use crate::process::{affinity_supported, limit_cores, run_tool, run_tool_with, RunError, RunLimits};
+use crate::sidecar::SidecarResolver;
+use crate::summary::{BatchStatus, BatchSummary};
+use crate::types::{
+    distance_from_quality, Color, ConversionSettings, DecodeSettings, DecodeItem, OutputFormat, OverwritePolicy, ProgressMessage, ToolSettings,
+    DEFAULT_BATCH_HOOK_TIMEOUT_SECS,
+};
+use crate::verify::{check_jpeg_structure, find_original, has_jpeg_reconstruction, sha256_file, Verification};
 
+/// GIFs past this size get a warning; chat apps commonly cap uploads
+/// around here.
+const LARGE_GIF_BYTES: u64 = 10 * 1024 * 1024;
+
+/// The reason given for skipping files that changed during the stability wait.
+const STILL_BEING_WRITTEN: &str = "file still being written";
+
+/// How long a failed notification waits before its first retry.
+const NOTIFY_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// How often a paused batch checks whether it may continue.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Input extensions cjxl can read.
+pub const SUPPORTED_IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "gif", "bmp", "tiff", "tif", "webp", "ppm", "pgm", "pnm",
+];
+
+/// Compares a reconstructed JPEG with its original's hash, or checks its
+/// structure when there is no original.
+fn verify_output(output: &Path, original: Option<(PathBuf, String)>) -> Verification {
+    let Some((original, original_sha256)) = original else {
+        return Verification::Unverified { error: check_jpeg_structure(output).err() };
+    };
+    match sha256_file(output) {
+        Ok(sha256) if sha256 == original_sha256 => Verification::Match { original, sha256 },
+        Ok(output_sha256) => Verification::Mismatch { original, output_sha256, original_sha256 },
+        Err(e) => Verification::Unverified { error: Some(format!("Failed to read output: {}", e)) },
+    }
+}
+
+/// What happens to djxl's output before it becomes the output file.
+enum Finish {
+    /// Composite transparency onto a background for a format without alpha.
+    Flatten(OutputFormat, Color),
+    /// Assemble a GIF from the PNG (or APNG, for animations).
+    Gif(GifOptions),
+    /// Rewrite the frame timing of an APNG.
+    Retime(AnimationTiming),
+}
+
+/// Ends a batch as cancelled, first deleting what it wrote if it is
+/// transactional.
+fn send_cancelled(rollback: Rollback, progress_tx: &Sender<ProgressMessage>) {
+    if rollback.is_enabled() {
+        let removed = rollback.roll_back();
+        let _ = progress_tx.send(ProgressMessage::Info { message: format!("Rolled back {} output(s)", removed) });
+    }
+    let _ = progress_tx.send(ProgressMessage::Cancelled);
+}
+
+/// Turns frame numbers in a tool's stderr into `FrameProgress` messages for
+/// `file`, using `total` when the tool doesn't say.
+fn frame_reporter<'a>(
+    file: &'a Path,
+    total: Option<usize>,
+    progress_tx: &'a Sender<ProgressMessage>,
+) -> impl FnMut(&str) + 'a {
+    let mut last = 0;
+    move |line| {
+        if let Some((frame, line_total)) = parse_frame_line(line).filter(|&(frame, _)| frame != last) {
+            last = frame;
+            let _ = progress_tx.send(ProgressMessage::FrameProgress {
+                file: file.display().to_string(),
+                frame,
+                total: line_total.or(total),
+            });
+        }
+    }
+}
+
+/// Applies `timing` to an animated GIF or APNG input, writing the result to
+/// a hidden file next to `output` for cjxl to read. `None` for other inputs.
+fn retime_input(
+    input: &Path,
+    output: &Path,
+    timing: &AnimationTiming,
+    progress_tx: &Sender<ProgressMessage>,
+) -> Result<Option<PathBuf>, String> {
+    let ext = input.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if !timing.is_set() || !(ext == "gif" || ext == "png") {
+        return Ok(None);
+    }
+    let data = std::fs::read(input).map_err(|e| format!("Failed to read input: {}", e))?;
+    let Some(retimed) = retime(&data, timing)? else {
+        return Ok(None);
+    };
+
+    let name = output.file_name().unwrap_or_default().to_string_lossy();
+    let temp = output.with_file_name(format!(".{}.retimed.{}", name, ext));
+    std::fs::write(&temp, &retimed.data).map_err(|e| format!("Failed to write {}: {}", temp.display(), e))?;
+    let _ = progress_tx.send(ProgressMessage::Info {
+        message: format!("{}: {}", input.display(), retimed.after.describe_override(&retimed.before)),
+    });
+    Ok(Some(temp))
+}
+
+/// Size of a file in bytes, or 0 if it can't be read.
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Whether `path` has an extension cjxl accepts as input.
+pub fn is_supported_image(path: &Path) -> bool {
+    if let Some(ext) = path.extension() {
+        let ext_lower = ext.to_string_lossy().to_lowercase();
+        SUPPORTED_IMAGE_EXTENSIONS.contains(&ext_lower.as_str())
+    } else {
+        false
+    }
+}
+
+fn is_jpeg_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"))
+}
+
+/// Whether cjxl encodes `input` lossily under `settings`, so its quality
+/// applies.
+fn is_lossy_encode(input: &Path, settings: &ConversionSettings) -> bool {
+    !(settings.lossless || (settings.jpeg_lossless && is_jpeg_file(input)))
+}
+
+/// The formats `item` decodes to, in order and without repeats.
+pub(crate) fn output_formats(item: &DecodeItem, settings: &DecodeSettings) -> Vec<OutputFormat> {
+    let mut formats = Vec::new();
+    for format in &item.output_formats {
+        if !formats.contains(format) {
+            formats.push(*format);
+        }
+    }
+    if formats.is_empty() {
+        formats.push(settings.output_format);
+    }
+    formats
+}
+
+/// Reports where a dry run would have written `input`'s output, or that it
+/// would have been skipped.
+fn dry_run_output(input: &Path, planned: &Path, overwrite: OverwritePolicy, progress_tx: &Sender<ProgressMessage>) {
+    let msg = match apply_overwrite_policy(planned, overwrite) {
+        Some(output) => ProgressMessage::Info { message: format!("Would convert {} -> {}", input.display(), output.display()) },
+        None => ProgressMessage::Skipped {
+            file: input.display().to_string(),
+            reason: format!("{} already exists", planned.display()),
+        },
+    };
+    let _ = progress_tx.send(msg);
+}
+
+/// Journals a batch from its messages: the journal is written once the
+/// batch starts, and each file as its outcome arrives.
+struct BatchJournal {
+    batch: Option<JournalBatch>,
+    done: Vec<PathBuf>,
+    previous: Option<PathBuf>,
+    journal: Option<Journal>,
+    current: Option<PathBuf>,
+}
+
+impl BatchJournal {
+    fn new(batch: JournalBatch, resume: &Resume) -> Self {
+        Self { batch: Some(batch), done: resume.done.clone(), previous: resume.previous.clone(), journal: None, current: None }
+    }
+
+    /// Journals `msg`. After the first failure the batch goes on
+    /// unjournaled.
+    fn record(&mut self, dir: Option<&Path>, msg: &ProgressMessage) -> Result<(), String> {
+        let result = match msg {
+            ProgressMessage::Started { total } => match (dir, self.batch.take()) {
+                (Some(dir), Some(batch)) => Journal::create(dir, batch, std::mem::take(&mut self.done)).and_then(|mut journal| {
+                    journal.started(*total)?;
+                    // The run being resumed is now carried by this journal
+                    if let Some(previous) = self.previous.take() {
+                        let _ = std::fs::remove_file(previous);
+                    }
+                    self.journal = Some(journal);
+                    Ok(())
+                }),
+                _ => Ok(()),
+            },
+            ProgressMessage::Progress { file, .. } => {
+                self.current = Some(PathBuf::from(file));
+                Ok(())
+            }
+            ProgressMessage::Success { .. } | ProgressMessage::Skipped { .. } | ProgressMessage::Error { .. } => {
+                let status = match msg {
+                    ProgressMessage::Success { .. } => "success",
+                    ProgressMessage::Skipped { .. } => "skipped",
+                    // An error without a file is the batch's, not the current file's
+                    ProgressMessage::Error { file, .. } if file.is_empty() => return Ok(()),
+                    _ => "error",
+                };
+                // Each file is journaled once, by the first outcome it gets
+                match (&mut self.journal, self.current.take()) {
+                    (Some(journal), Some(file)) => journal.record(&file, status),
+                    _ => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        };
+        if result.is_err() {
+            self.journal = None;
+        }
+        result.map_err(|e| format!("Journaling stopped: {}", e))
+    }
+
+    /// Deletes the journal if the batch ended on its own terms; one that
+    /// was cut short keeps it for resuming.
+    fn close(self, status: BatchStatus) {
+        if let Some(journal) = self.journal {
+            if status != BatchStatus::Aborted {
+                journal.finish();
+            }
+        }
+    }
+}
+
+/// Output bytes written so far against
+/// [`ConversionSettings::output_budget_bytes`].
+struct OutputBudget {
+    limit: Option<u64>,
+    written: u64,
+    files: u64,
+}
+
+impl OutputBudget {
+    fn new(limit: Option<u64>) -> Self {
+        Self { limit, written: 0, files: 0 }
+    }
+
+    fn record(&mut self, bytes: u64) {
+        self.written += bytes;
+        self.files += 1;
+    }
+
+    /// Whether another output of the average size so far would go over
+    /// the limit. Outputs vary, so the last one may still overshoot it.
+    fn is_spent(&self) -> bool {
+        self.limit
+            .is_some_and(|limit| self.files > 0 && self.written + self.written / self.files > limit)
+    }
+}
+
+/// Whether `path` has a `.jxl` extension.
+pub fn is_jxl_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase() == "jxl")
+        .unwrap_or(false)
+}
+
+/// Runs batches through cjxl and djxl. Cheap to construct; each batch call
+/// blocks until it finishes, so run it on its own thread.
 pub struct ConversionEngine {
     cjxl_path: Option<PathBuf>,
     djxl_path: Option<PathBuf>,
+    ssimulacra2_path: Option<PathBuf>,
+    cjxl_version: Option<String>,
+    djxl_version: Option<String>,
+    capabilities: ToolCapabilities,
+    tool_settings: ToolSettings,
+    pause_flag: Arc<AtomicBool>,
+    file_delay_ms: Arc<AtomicU64>,
+    journal_dir: Option<PathBuf>,
+    resume: Resume,
+    dry_run: bool,
+}
+
+impl Default for ConversionEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ConversionEngine {
+    /// Locates cjxl and djxl in a `tools/` folder next to the executable,
+    /// falling back to PATH. Check [`Self::get_error`] before encoding.
     pub fn new() -> Self {
-        let cjxl_path = Self::find_cjxl();
-        let djxl_path = Self::find_djxl();
-        Self { cjxl_path, djxl_path }
+        Self::from_tools(&DiscoveredTools::discover())
+    }
+
+    /// Uses tools found earlier by [`DiscoveredTools::discover`].
+    pub fn from_tools(tools: &DiscoveredTools) -> Self {
+        Self {
+            cjxl_path: tools.cjxl.as_ref().map(|tool| tool.path.clone()),
+            djxl_path: tools.djxl.as_ref().map(|tool| tool.path.clone()),
+            ssimulacra2_path: tools.ssimulacra2.clone(),
+            cjxl_version: tools.cjxl.as_ref().and_then(|tool| tool.version.clone()),
+            djxl_version: tools.djxl.as_ref().and_then(|tool| tool.version.clone()),
+            capabilities: tools.capabilities(),
+            tool_settings: ToolSettings::default(),
+            pause_flag: Arc::new(AtomicBool::new(false)),
+            file_delay_ms: Arc::new(AtomicU64::new(0)),
+            journal_dir: None,
+            resume: Resume::default(),
+            dry_run: false,
+        }
+    }
+
+    /// Options applied to every tool invocation.
+    pub fn with_tool_settings(mut self, tool_settings: ToolSettings) -> Self {
+        self.tool_settings = tool_settings;
+        self
+    }
+
+    /// Uses the given tools instead of the ones found next to the executable
+    /// or on PATH. They are probed with the environment overrides of
+    /// [`Self::with_tool_settings`], so set those first.
+    pub fn with_tool_paths(mut self, cjxl_path: Option<PathBuf>, djxl_path: Option<PathBuf>) -> Self {
+        if let Some(path) = cjxl_path {
+            let cjxl = ToolInfo::probe(path, &self.tool_settings);
+            self.capabilities = ToolCapabilities::combine(&cjxl.capabilities, &self.capabilities);
+            self.cjxl_version = cjxl.version;
+            self.cjxl_path = Some(cjxl.path);
+        }
+        if let Some(path) = djxl_path {
+            let djxl = ToolInfo::probe(path, &self.tool_settings);
+            self.capabilities = ToolCapabilities::combine(&self.capabilities, &djxl.capabilities);
+            self.djxl_version = djxl.version;
+            self.djxl_path = Some(djxl.path);
+        }
+        self
+    }
+
+    /// Scores adaptive quality candidates with this ssimulacra2 instead of
+    /// the one found next to the executable or on PATH.
+    pub fn with_ssimulacra2_path(mut self, path: PathBuf) -> Self {
+        self.ssimulacra2_path = Some(path);
+        self
+    }
+
+    /// Milliseconds to wait between files, read live so it can change mid-batch.
+    pub fn with_file_delay(mut self, file_delay_ms: Arc<AtomicU64>) -> Self {
+        self.file_delay_ms = file_delay_ms;
+        self
+    }
+
+    /// While the flag is set the batch holds before starting its next file.
+    pub fn with_pause_flag(mut self, pause_flag: Arc<AtomicBool>) -> Self {
+        self.pause_flag = pause_flag;
+        self
     }
 
+    /// Journals each batch in this folder so an interrupted one can
+    /// [resume](Self::with_resume); see [`crate::journal`].
+    pub fn with_journal_dir(mut self, journal_dir: PathBuf) -> Self {
+        self.journal_dir = Some(journal_dir);
+        self
+    }
+
+    /// Picks up an unfinished batch: the inputs it finished are left out,
+    /// and its journal is replaced by the new batch's.
+    pub fn with_resume(mut self, resume: Resume) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Plans batches without converting anything: each file's output is
+    /// logged, and nothing is downloaded, written or hooked.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Whether cjxl was found.
     pub fn is_available(&self) -> bool {
         self.cjxl_path.is_some()
     }
 
+    /// Whether djxl was found.
     pub fn is_decode_available(&self) -> bool {
         self.djxl_path.is_some()
     }
 
+    /// What the tools in use support.
+    pub fn capabilities(&self) -> &ToolCapabilities {
+        &self.capabilities
+    }
+
+    /// Rejects settings the cjxl in use can't handle, naming the version
+    /// that would, and hard [conflicts](crate::conflicts).
+    pub fn validate_settings(&self, settings: &ConversionSettings) -> Result<(), String> {
+        let hard: Vec<&str> = check_settings(settings)
+            .into_iter()
+            .filter(|conflict| conflict.severity == Severity::Hard)
+            .map(|conflict| conflict.message)
+            .collect();
+        if !hard.is_empty() {
+            return Err(hard.join("; "));
+        }
+        self.check_capabilities(settings)?;
+        if settings.adaptive_quality.is_some() && !settings.lossless && self.ssimulacra2_path.is_none() {
+            return Err(
+                "Adaptive quality needs ssimulacra2. Please place it in the 'tools' folder or ensure it's in PATH."
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Rejects options the cjxl in use doesn't have, naming its version.
+    pub fn check_capabilities(&self, settings: &ConversionSettings) -> Result<(), String> {
+        let found = self.cjxl_version.as_deref().unwrap_or("an unknown version");
+        if settings.effort >= 10 && !self.capabilities.supports_effort_10 {
+            return Err(format!("Effort 10 needs cjxl 0.10 or newer (found {})", found));
+        }
+        if extra_flag(settings, &["--photon_noise_iso"]).is_some() && !self.capabilities.supports_photon_noise {
+            return Err(format!("--photon_noise_iso isn't supported by this cjxl (found {})", found));
+        }
+        if extra_flag(settings, &["--faster_decoding"]).is_some() && !self.capabilities.supports_faster_decoding {
+            return Err(format!("--faster_decoding isn't supported by this cjxl (found {})", found));
+        }
+        Ok(())
+    }
+
+    /// Rejects decode outputs and options the djxl in use can't handle,
+    /// naming its version.
+    pub fn validate_decode(&self, items: &[DecodeItem], settings: &DecodeSettings) -> Result<(), String> {
+        let found = self.djxl_version.as_deref().unwrap_or("an unknown version");
+        let makes_exr = items.iter().any(|item| output_formats(item, settings).contains(&OutputFormat::Exr));
+        if makes_exr && !self.capabilities.djxl_supports_exr {
+            return Err(format!("EXR output needs a djxl built with OpenEXR (found {})", found));
+        }
+        if settings.allow_partial_files && !self.capabilities.djxl_supports_partial_files {
+            return Err(format!("Decoding partial files needs a djxl with --allow_partial_files (found {})", found));
+        }
+        Ok(())
+    }
+
+    /// A user-facing message when cjxl is missing.
     pub fn get_error(&self) -> Option<String> {
         if self.cjxl_path.is_none() {
             Some("cjxl executable not found. Please place it in the 'tools' folder or ensure it's in PATH.".to_string())
@@ -35,6 +468,7 @@ impl ConversionEngine {
         }
     }
 
+    /// A user-facing message when djxl is missing.
     pub fn get_decode_error(&self) -> Option<String> {
         if self.djxl_path.is_none() {
             Some("djxl executable not found. Please place it in the 'tools' folder or ensure it's in PATH.".to_string())
@@ -43,65 +477,31 @@ impl ConversionEngine {
         }
     }
 
-    fn find_cjxl() -> Option<PathBuf> {
-        Self::find_tool("cjxl")
-    }
-
-    fn find_djxl() -> Option<PathBuf> {
-        Self::find_tool("djxl")
-    }
-
-    fn find_tool(tool_name: &str) -> Option<PathBuf> {
-        // First, try tools folder relative to executable
-        if let Ok(exe_path) = std::env::current_exe() {
-            if let Some(exe_dir) = exe_path.parent() {
-                let tool_path = if cfg!(windows) {
-                    exe_dir.join("tools").join(format!("{}.exe", tool_name))
-                } else {
-                    exe_dir.join("tools").join(tool_name)
-                };
-
-                if tool_path.exists() {
-                    // Ensure executable permission on Unix
-                    #[cfg(unix)]
-                    {
-                        use std::os::unix::fs::PermissionsExt;
-                        if let Ok(metadata) = std::fs::metadata(&tool_path) {
-                            let mut perms = metadata.permissions();
-                            perms.set_mode(0o755);
-                            let _ = std::fs::set_permissions(&tool_path, perms);
-                        }
-                    }
-                    return Some(tool_path);
-                }
-            }
-        }
-
-        // Fall back to PATH
-        let tool_exe = if cfg!(windows) { 
-            format!("{}.exe", tool_name)
-        } else { 
-            tool_name.to_string()
-        };
-        
-        if let Ok(output) = Command::new(if cfg!(windows) { "where" } else { "which" })
-            .arg(&tool_exe)
-            .output()
-        {
-            if output.status.success() {
-                if let Ok(path_str) = String::from_utf8(output.stdout) {
-                    let path = PathBuf::from(path_str.trim());
-                    if path.exists() {
-                        return Some(path);
-                    }
-                }
-            }
-        }
-
-        None
+    /// Encodes every supported image in `input_paths` (folders are expanded)
+    /// to JXL. `http(s)://` URLs among them are downloaded first, each
+    /// counting as one file. Sends `Started` first and `Completed` or `Cancelled` last; the
+    /// sender is dropped on return. `cancel_flag` is checked between files.
+    ///
+    /// The pre-batch hook runs before anything is scanned. The post-batch
+    /// hook and the notification come after the last message, and may
+    /// still log.
+    pub fn convert_batch(
+        &self,
+        input_paths: Vec<PathBuf>,
+        settings: ConversionSettings,
+        progress_tx: Sender<ProgressMessage>,
+        cancel_flag: Arc<AtomicBool>,
+    ) {
+        let journal = self
+            .journal_dir
+            .is_some()
+            .then(|| JournalBatch::Encode { inputs: input_paths.clone(), settings: settings.clone() });
+        self.with_batch_end(progress_tx, &cancel_flag, journal, |progress_tx| {
+            self.convert_files(input_paths, settings, progress_tx, Arc::clone(&cancel_flag))
+        });
     }
 
-    pub fn convert_batch(
+    fn convert_files(
         &self,
         input_paths: Vec<PathBuf>,
         settings: ConversionSettings,
@@ -119,17 +519,48 @@ impl ConversionEngine {
             }
         };
 
+        if let Err(error) = self.validate_settings(&settings) {
+            let _ = progress_tx.send(ProgressMessage::Error { file: String::new(), error });
+            return;
+        }
+        if !self.run_pre_batch_hook(&cancel_flag, &progress_tx) {
+            return;
+        }
+
+        // URLs are downloaded when their turn comes; everything else is scanned
+        let (urls, input_paths): (Vec<PathBuf>, Vec<PathBuf>) =
+            input_paths.into_iter().partition(|p| url_of(p).is_some());
+
         // Expand all input paths to individual files
-        let files = self.expand_paths(&input_paths, settings.recursive);
+        let scan = self.expand_paths(&input_paths, &settings.scan_options());
 
         // Filter for supported image formats
-        let image_files: Vec<PathBuf> = files
-            .into_iter()
-            .filter(|p| self.is_supported_image(p))
+        let mut image_files: Vec<PathBuf> = scan
+            .files
+            .iter()
+            .filter(|p| is_supported_image(p))
+            .cloned()
             .collect();
 
-        let total = image_files.len();
+        // A resumed batch leaves out what its last run finished
+        let done = self.resume.done_counts();
+        let mut urls = urls;
+        if !done.is_empty() {
+            let before = urls.len() + image_files.len();
+            urls.retain(|p| !done.contains_key(p.as_path()));
+            image_files.retain(|p| !done.contains_key(p.as_path()));
+            let _ = progress_tx.send(ProgressMessage::Info {
+                message: format!("Resuming: {} file(s) were already done", before - urls.len() - image_files.len()),
+            });
+        }
+
+        let total = urls.len() + image_files.len();
         let _ = progress_tx.send(ProgressMessage::Started { total });
+        self.announce_dry_run(&progress_tx);
+        self.announce_cpu_limit(&progress_tx);
+        if let Some(message) = scan.summary() {
+            let _ = progress_tx.send(ProgressMessage::Info { message });
+        }
 
         if total == 0 {
             let _ = progress_tx.send(ProgressMessage::Completed);
@@ -137,143 +568,209 @@ impl ConversionEngine {
         }
 
         // Find common base path for structure preservation
-        let base_path = if settings.keep_structure {
-            self.find_common_base(&input_paths)
-        } else {
-            None
-        };
+        let plan = OutputPlan::for_encode(&input_paths, &settings);
+
+        let mut sidecars = SidecarResolver::new(&input_paths);
+        let mut claims = OutputClaims::for_output_dir(&settings.output_dir);
+        let unstable = self.unstable_files(&image_files);
+        let downloads = DownloadDir::new(settings.keep_downloads);
+        let mut rollback = Rollback::new(settings.transactional);
+
+        let mut budget = OutputBudget::new(settings.output_budget_bytes);
+
+        for (idx, input) in urls.iter().chain(&image_files).enumerate() {
+            if budget.is_spent() {
+                const MB: f64 = 1024.0 * 1024.0;
+                let _ = progress_tx.send(ProgressMessage::Info {
+                    message: format!(
+                        "Outputs add up to {:.1} MB of the {:.1} MB budget",
+                        budget.written as f64 / MB,
+                        budget.limit.unwrap_or_default() as f64 / MB
+                    ),
+                });
+                let remaining = urls.iter().chain(&image_files).skip(idx).map(|p| p.display().to_string()).collect();
+                let _ = progress_tx.send(ProgressMessage::Stopped {
+                    reason: "output budget reached".to_string(),
+                    remaining,
+                });
+                return;
+            }
+            if idx > 0 && !self.dry_run {
+                self.wait_file_delay(&cancel_flag);
+            }
 
-        for (idx, input_file) in image_files.iter().enumerate() {
-            if cancel_flag.load(Ordering::Relaxed) {
-                let _ = progress_tx.send(ProgressMessage::Cancelled);
+            if self.wait_while_paused(&cancel_flag) {
+                send_cancelled(rollback, &progress_tx);
                 return;
             }
 
             let _ = progress_tx.send(ProgressMessage::Progress {
                 current: idx + 1,
                 total,
-                file: input_file.display().to_string(),
+                file: input.display().to_string(),
             });
 
+            // Each download gets its own folder so same-named files can't clash
+            let downloaded;
+            let input_file = match url_of(input) {
+                Some(url) if self.dry_run => {
+                    let _ = progress_tx.send(ProgressMessage::Info { message: format!("Would download and convert {}", url) });
+                    continue;
+                }
+                Some(url) => match self.download_input(url, &downloads.path().join(idx.to_string()), &cancel_flag, &progress_tx) {
+                    Ok(path) => {
+                        downloaded = path;
+                        &downloaded
+                    }
+                    Err(_) if cancel_flag.load(Ordering::Relaxed) => {
+                        send_cancelled(rollback, &progress_tx);
+                        return;
+                    }
+                    Err(error) => {
+                        let _ = progress_tx.send(ProgressMessage::Error { file: url.to_string(), error });
+                        continue;
+                    }
+                },
+                None if unstable.contains(input) => {
+                    let _ = progress_tx.send(ProgressMessage::Skipped {
+                        file: input.display().to_string(),
+                        reason: STILL_BEING_WRITTEN.to_string(),
+                    });
+                    continue;
+                }
+                None => input,
+            };
+
+            let planned = plan_output_path(input_file, &plan);
+            if let Err(owner) = claims.claim(&planned, input) {
+                let _ = progress_tx.send(ProgressMessage::Skipped {
+                    file: input.display().to_string(),
+                    reason: format!("same output as {}", owner.display()),
+                });
+                continue;
+            }
+            if self.dry_run {
+                dry_run_output(input, &planned, settings.overwrite, &progress_tx);
+                continue;
+            }
+            let output_path = match self.prepare_output(&planned, settings.overwrite, &mut rollback) {
+                Ok(Some(path)) => path,
+                Ok(None) => {
+                    let _ = progress_tx.send(ProgressMessage::Skipped {
+                        file: input.display().to_string(),
+                        reason: format!("{} already exists", planned.display()),
+                    });
+                    continue;
+                }
+                Err(error) => {
+                    let _ = progress_tx.send(ProgressMessage::Error { file: input.display().to_string(), error });
+                    continue;
+                }
+            };
+
+            let file_settings = sidecars.settings_for(input_file, &settings, &progress_tx);
+
             match self.convert_single(
                 &cjxl_path,
                 input_file,
-                &settings,
-                base_path.as_ref(),
+                &file_settings,
+                &output_path,
+                &cancel_flag,
+                &progress_tx,
             ) {
-                Ok(output) => {
+                Ok((output, adapted)) => {
+                    rollback.record_file(&output);
+                    let output_bytes = file_size(&output);
+                    budget.record(output_bytes);
                     let _ = progress_tx.send(ProgressMessage::Success {
-                        file: format!("{} -> {}", input_file.display(), output.display()),
+                        file: format!("{} -> {}", input.display(), output.display()),
+                        input: input.clone(),
+                        input_bytes: file_size(input_file),
+                        output_bytes,
+                        output: output.clone(),
                     });
+                    if let Some((best, attempts)) = adapted {
+                        let _ = progress_tx.send(ProgressMessage::Adapted {
+                            file: input.display().to_string(),
+                            distance: best.distance,
+                            score: best.score,
+                            attempts,
+                        });
+                    }
+                    self.run_post_hook(input_file, &output, true, &progress_tx);
+                }
+                // Only adaptive quality's tool runs watch the flag
+                Err(_) if cancel_flag.load(Ordering::Relaxed) && file_settings.adaptive_quality.is_some() => {
+                    send_cancelled(rollback, &progress_tx);
+                    return;
                 }
                 Err(e) => {
                     let _ = progress_tx.send(ProgressMessage::Error {
-                        file: input_file.display().to_string(),
+                        file: input.display().to_string(),
                         error: e,
                     });
+                    self.run_post_hook(input_file, &output_path, false, &progress_tx);
                 }
             }
         }
 
-        let _ = progress_tx.send(ProgressMessage::Completed);
-    }
-
-    fn expand_paths(&self, paths: &[PathBuf], recursive: bool) -> Vec<PathBuf> {
-        let mut result = Vec::new();
-
-        for path in paths {
-            if path.is_file() {
-                result.push(path.clone());
-            } else if path.is_dir() {
-                if recursive {
-                    for entry in WalkDir::new(path)
-                        .follow_links(false)
-                        .into_iter()
-                        .filter_map(|e| e.ok())
-                    {
-                        if entry.file_type().is_file() {
-                            result.push(entry.path().to_path_buf());
-                        }
-                    }
-                } else {
-                    if let Ok(entries) = std::fs::read_dir(path) {
-                        for entry in entries.filter_map(|e| e.ok()) {
-                            if entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
-                                result.push(entry.path());
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        result
-    }
-
-    fn is_supported_image(&self, path: &Path) -> bool {
-        if let Some(ext) = path.extension() {
-            let ext_lower = ext.to_string_lossy().to_lowercase();
-            matches!(
-                ext_lower.as_str(),
-                "jpg" | "jpeg" | "png" | "gif" | "bmp" | "tiff" | "tif" | "webp" | "ppm" | "pgm" | "pnm"
-            )
-        } else {
-            false
+        if settings.keep_downloads && downloads.path().exists() {
+            let _ = progress_tx.send(ProgressMessage::Info {
+                message: format!("Downloaded images were kept in {}", downloads.path().display()),
+            });
         }
+        let _ = progress_tx.send(ProgressMessage::Completed);
     }
 
-    fn find_common_base(&self, paths: &[PathBuf]) -> Option<PathBuf> {
-        if paths.is_empty() {
-            return None;
-        }
-
-        // Get the first path's parent (if it's a file) or itself (if it's a dir)
-        let mut base = if paths[0].is_file() {
-            paths[0].parent()?.to_path_buf()
-        } else {
-            paths[0].clone()
-        };
+    /// Downloads a URL input into `dir`, logging its progress in quarters
+    /// when it is large enough for that to be worth seeing.
+    fn download_input(
+        &self,
+        url: &str,
+        dir: &Path,
+        cancel_flag: &AtomicBool,
+        progress_tx: &Sender<ProgressMessage>,
+    ) -> Result<PathBuf, String> {
+        const MB: f64 = 1024.0 * 1024.0;
+        let _ = progress_tx.send(ProgressMessage::Info { message: format!("Downloading {}", url) });
 
-        // Find the common ancestor
-        for path in &paths[1..] {
-            let check_path = if path.is_file() {
-                path.parent()?
-            } else {
-                path.as_path()
+        let mut quarters_reported = 0;
+        let path = download(url, dir, MAX_DOWNLOAD_BYTES, cancel_flag, |received, total| {
+            let Some(total) = total.filter(|&total| total as f64 >= MB) else {
+                return;
             };
-
-            while !check_path.starts_with(&base) {
-                base = base.parent()?.to_path_buf();
+            let quarters = received * 4 / total;
+            if quarters > quarters_reported && quarters < 4 {
+                quarters_reported = quarters;
+                let _ = progress_tx.send(ProgressMessage::Info {
+                    message: format!("{}: {}% of {:.1} MB", url, quarters * 25, total as f64 / MB),
+                });
             }
-        }
+        })?;
+
+        let _ = progress_tx.send(ProgressMessage::Info {
+            message: format!("Downloaded {} ({:.1} MB)", url, file_size(&path) as f64 / MB),
+        });
+        Ok(path)
+    }
 
-        Some(base)
+    /// Replaces folders in `paths` with the files inside them; see
+    /// [`scan_paths`].
+    pub fn expand_paths(&self, paths: &[PathBuf], options: &ScanOptions) -> Scan {
+        scan_paths(paths, options)
     }
 
+    /// Encodes one file, returning the output and, when adaptive quality
+    /// searched for its distance, the candidate kept and the attempts made.
     fn convert_single(
         &self,
         cjxl_path: &Path,
         input_file: &Path,
         settings: &ConversionSettings,
-        base_path: Option<&PathBuf>,
-    ) -> Result<PathBuf, String> {
-        // Determine output path
-        let output_path = if settings.keep_structure {
-            if let Some(base) = base_path {
-                if let Ok(rel_path) = input_file.strip_prefix(base) {
-                    settings.output_dir.join(rel_path)
-                } else {
-                    settings.output_dir.join(input_file.file_name().unwrap())
-                }
-            } else {
-                settings.output_dir.join(input_file.file_name().unwrap())
-            }
-        } else {
-            settings.output_dir.join(input_file.file_name().unwrap())
-        };
-
-        // Change extension to .jxl
-        let output_path = output_path.with_extension("jxl");
+        output_path: &Path,
+        cancel_flag: &Arc<AtomicBool>,
+        progress_tx: &Sender<ProgressMessage>,
+    ) -> Result<(PathBuf, Option<(Candidate, usize)>), String> {
 
         // Create parent directory if needed
         if let Some(parent) = output_path.parent() {
@@ -288,7 +785,7 @@ impl ConversionEngine {
         let abs_input = std::fs::canonicalize(input_file)
             .map_err(|e| format!("Failed to resolve input path: {}", e))?;
         let abs_output = if output_path.exists() {
-            std::fs::canonicalize(&output_path)
+            std::fs::canonicalize(output_path)
                 .map_err(|e| format!("Failed to resolve output path: {}", e))?
         } else {
             // For non-existent paths, resolve parent and join filename
@@ -299,53 +796,160 @@ impl ConversionEngine {
                     .map_err(|e| format!("Failed to resolve output directory: {}", e))?;
                 abs_parent.join(output_path.file_name().unwrap())
             } else {
-                output_path.clone()
+                output_path.to_path_buf()
             }
         };
 
-        cmd.arg(&abs_input);
-        cmd.arg(&abs_output);
+        // Animations report the frames cjxl mentions on stderr
+        let is_animation_format = abs_input
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gif") || ext.eq_ignore_ascii_case("png"));
+        let frames = is_animation_format
+            .then(|| File::open(&abs_input).ok().and_then(|file| frame_count(BufReader::new(file))))
+            .flatten();
 
-        // Add quality/lossless options
-        let ext = input_file.extension()
-            .map(|e| e.to_string_lossy().to_lowercase())
-            .unwrap_or_default();
-        let is_jpeg = ext == "jpg" || ext == "jpeg";
-        
-        if settings.lossless {
-            if is_jpeg {
-                cmd.arg("--lossless_jpeg=1");
-            } else {
-                cmd.arg("-d").arg("0");
-            }
-        } else if is_jpeg && settings.jpeg_lossless {
-            // JPEG-specific lossless conversion
-            cmd.arg("--lossless_jpeg=1");
-        } else {
-            cmd.arg("-q").arg(settings.quality.to_string());
+        // ssimulacra2 compares still images, so animations keep the slider's quality
+        let adaptive = settings
+            .adaptive_quality
+            .as_ref()
+            .filter(|_| is_lossy_encode(&abs_input, settings) && frames.is_none_or(|frames| frames <= 1));
+        if let Some(target) = adaptive {
+            let adapted = self.encode_adaptive(cjxl_path, &abs_input, &abs_output, settings, target, cancel_flag, progress_tx)?;
+            return Ok((abs_output, Some(adapted)));
         }
 
-        // Add effort option
-        cmd.arg("-e").arg(settings.effort.to_string());
+        // Animations with a timing override are encoded from a retimed copy
+        let retimed = retime_input(&abs_input, &abs_output, &settings.timing(), progress_tx)?;
+        cmd.args(self.cjxl_args(retimed.as_deref().unwrap_or(&abs_input), &abs_output, settings));
+        self.tool_settings.apply_env(&mut cmd);
 
         // Execute
-        let output = cmd.output()
-            .map_err(|e| format!("Failed to execute cjxl: {}", e))?;
+        let output = run_tool_with(&mut cmd, &self.run_limits(), frame_reporter(input_file, frames, progress_tx));
+        if let Some(retimed) = &retimed {
+            let _ = std::fs::remove_file(retimed);
+        }
+        let output = output.map_err(|e| self.run_error(e, "cjxl", &abs_output))?;
 
         if output.status.success() {
-            Ok(abs_output)
+            self.report_tool_output(&output, progress_tx);
+            Ok((abs_output, None))
         } else {
             let stderr = String::from_utf8_lossy(&output.stderr);
             Err(format!("cjxl failed: {}", stderr))
         }
     }
 
+    /// Encodes `input` at the distances a [`DistanceSearch`] picks, each to
+    /// a hidden file beside `output`, and keeps the best one as `output`.
+    /// The other candidates are removed, whatever happens.
+    #[allow(clippy::too_many_arguments)]
+    fn encode_adaptive(
+        &self,
+        cjxl_path: &Path,
+        input: &Path,
+        output: &Path,
+        settings: &ConversionSettings,
+        target: &AdaptiveQuality,
+        cancel_flag: &Arc<AtomicBool>,
+        progress_tx: &Sender<ProgressMessage>,
+    ) -> Result<(Candidate, usize), String> {
+        let mut search = DistanceSearch::new(target, distance_from_quality(f32::from(settings.quality)));
+        let mut candidates = Vec::new();
+        let searched =
+            self.search_distance(cjxl_path, input, output, settings, &mut search, &mut candidates, cancel_flag, progress_tx);
+
+        let mut result = searched
+            .and_then(|()| search.best().ok_or_else(|| "Adaptive quality encoded nothing".to_string()))
+            .map(|best| (best, search.attempts()));
+        for (distance, candidate) in &candidates {
+            match &result {
+                Ok((best, _)) if best.distance == *distance => {
+                    if let Err(e) = std::fs::rename(candidate, output) {
+                        let _ = std::fs::remove_file(candidate);
+                        result = Err(format!("Failed to write {}: {}", output.display(), e));
+                    }
+                }
+                _ => {
+                    let _ = std::fs::remove_file(candidate);
+                }
+            }
+        }
+        result
+    }
+
+    /// Runs `search` to the end, listing each candidate file it encodes in
+    /// `candidates` so the caller can clean up after it.
+    #[allow(clippy::too_many_arguments)]
+    fn search_distance(
+        &self,
+        cjxl_path: &Path,
+        input: &Path,
+        output: &Path,
+        settings: &ConversionSettings,
+        search: &mut DistanceSearch,
+        candidates: &mut Vec<(f32, PathBuf)>,
+        cancel_flag: &Arc<AtomicBool>,
+        progress_tx: &Sender<ProgressMessage>,
+    ) -> Result<(), String> {
+        let ssimulacra2 = self.ssimulacra2_path.as_deref().ok_or("ssimulacra2 not found")?;
+        let limits = RunLimits { cancel_flag: Some(Arc::clone(cancel_flag)), ..self.run_limits() };
+        let name = output.file_name().unwrap_or_default().to_string_lossy();
+
+        while let Some(distance) = search.next_distance() {
+            let candidate = output.with_file_name(format!(".{}.d{:.3}.jxl", name, distance));
+            candidates.push((distance, candidate.clone()));
+
+            let mut cmd = Command::new(cjxl_path);
+            cmd.args(self.cjxl_args_at(input, &candidate, settings, Some(distance)));
+            self.tool_settings.apply_env(&mut cmd);
+            let encoded = run_tool(&mut cmd, &limits).map_err(|e| self.run_error(e, "cjxl", &candidate))?;
+            if !encoded.status.success() {
+                return Err(format!("cjxl failed: {}", String::from_utf8_lossy(&encoded.stderr)));
+            }
+
+            let mut cmd = Command::new(ssimulacra2);
+            cmd.arg(input).arg(&candidate);
+            self.tool_settings.apply_env(&mut cmd);
+            let scored = run_tool(&mut cmd, &limits).map_err(|e| self.run_error(e, "ssimulacra2", &candidate))?;
+            let score = scored
+                .status
+                .success()
+                .then(|| parse_ssimulacra2_score(&String::from_utf8_lossy(&scored.stdout)))
+                .flatten()
+                .ok_or_else(|| format!("ssimulacra2 failed: {}", String::from_utf8_lossy(&scored.stderr)))?;
+
+            let _ = progress_tx.send(ProgressMessage::Info {
+                message: format!("{}: d {:.2} scored {:.1}", input.display(), distance, score),
+            });
+            search.record(Candidate { distance, score });
+        }
+        Ok(())
+    }
+
+    /// Decodes each item to its own output format. Messages, cancellation
+    /// and hooks work as in [`Self::convert_batch`].
     pub fn decode_batch(
         &self,
         decode_items: Vec<DecodeItem>,
         settings: DecodeSettings,
         progress_tx: Sender<ProgressMessage>,
         cancel_flag: Arc<AtomicBool>,
+    ) {
+        let journal = self
+            .journal_dir
+            .is_some()
+            .then(|| JournalBatch::Decode { items: decode_items.clone(), settings: settings.clone() });
+        self.with_batch_end(progress_tx, &cancel_flag, journal, |progress_tx| {
+            self.decode_files(decode_items, settings, progress_tx, Arc::clone(&cancel_flag))
+        });
+    }
+
+    fn decode_files(
+        &self,
+        decode_items: Vec<DecodeItem>,
+        settings: DecodeSettings,
+        progress_tx: Sender<ProgressMessage>,
+        cancel_flag: Arc<AtomicBool>,
     ) {
         let djxl_path = match &self.djxl_path {
             Some(p) => p.clone(),
@@ -358,8 +962,41 @@ impl ConversionEngine {
             }
         };
 
-        let total = decode_items.len();
+        if let Err(error) = settings.timing().validate() {
+            let _ = progress_tx.send(ProgressMessage::Error { file: String::new(), error });
+            return;
+        }
+        if let Err(error) = check_transactional(settings.transactional, settings.overwrite) {
+            let _ = progress_tx.send(ProgressMessage::Error { file: String::new(), error });
+            return;
+        }
+        if let Err(error) = self.validate_decode(&decode_items, &settings) {
+            let _ = progress_tx.send(ProgressMessage::Error { file: String::new(), error });
+            return;
+        }
+        if !self.run_pre_batch_hook(&cancel_flag, &progress_tx) {
+            return;
+        }
+
+        // Each format an item asks for is one output and one unit of progress
+        // A resumed batch leaves out the outputs its last run finished
+        let done = self.resume.done_counts();
+        let units: Vec<(&DecodeItem, OutputFormat)> = decode_items
+            .iter()
+            .flat_map(|item| {
+                let skip = done.get(item.path.as_path()).copied().unwrap_or(0);
+                output_formats(item, &settings).into_iter().skip(skip).map(move |format| (item, format))
+            })
+            .collect();
+        let total = units.len();
         let _ = progress_tx.send(ProgressMessage::Started { total });
+        self.announce_dry_run(&progress_tx);
+        self.announce_cpu_limit(&progress_tx);
+        if total > decode_items.len() {
+            let _ = progress_tx.send(ProgressMessage::Info {
+                message: format!("Decoding {} file(s) to {} output(s)", decode_items.len(), total),
+            });
+        }
 
         if total == 0 {
             let _ = progress_tx.send(ProgressMessage::Completed);
@@ -367,16 +1004,19 @@ impl ConversionEngine {
         }
 
         // Find common base path for structure preservation
-        let base_path = if settings.keep_structure {
-            let paths: Vec<PathBuf> = decode_items.iter().map(|item| item.path.clone()).collect();
-            self.find_common_base(&paths)
-        } else {
-            None
-        };
+        let base_plan = OutputPlan::for_decode(&decode_items, &settings);
+        let mut claims = OutputClaims::for_output_dir(&settings.output_dir);
+        let paths: Vec<PathBuf> = decode_items.iter().map(|item| item.path.clone()).collect();
+        let unstable = self.unstable_files(&paths);
+        let mut rollback = Rollback::new(settings.transactional);
+
+        for (idx, &(item, format)) in units.iter().enumerate() {
+            if idx > 0 && !self.dry_run {
+                self.wait_file_delay(&cancel_flag);
+            }
 
-        for (idx, item) in decode_items.iter().enumerate() {
-            if cancel_flag.load(Ordering::Relaxed) {
-                let _ = progress_tx.send(ProgressMessage::Cancelled);
+            if self.wait_while_paused(&cancel_flag) {
+                send_cancelled(rollback, &progress_tx);
                 return;
             }
 
@@ -386,23 +1026,95 @@ impl ConversionEngine {
                 file: item.path.display().to_string(),
             });
 
-            match self.decode_single(
-                &djxl_path,
-                &item.path,
-                item.output_format,
-                &settings,
-                base_path.as_ref(),
-            ) {
+            if unstable.contains(&item.path) {
+                let _ = progress_tx.send(ProgressMessage::Skipped {
+                    file: item.path.display().to_string(),
+                    reason: STILL_BEING_WRITTEN.to_string(),
+                });
+                continue;
+            }
+
+            let plan = OutputPlan {
+                extension: format.extension().to_string(),
+                ..base_plan.clone()
+            };
+
+            let planned = plan_output_path(&item.path, &plan);
+            if let Err(owner) = claims.claim(&planned, &item.path) {
+                let _ = progress_tx.send(ProgressMessage::Skipped {
+                    file: item.path.display().to_string(),
+                    reason: format!("same output as {}", owner.display()),
+                });
+                continue;
+            }
+            if self.dry_run {
+                dry_run_output(&item.path, &planned, settings.overwrite, &progress_tx);
+                continue;
+            }
+            let output_path = match self.prepare_output(&planned, settings.overwrite, &mut rollback) {
+                Ok(Some(path)) => path,
+                Ok(None) => {
+                    let _ = progress_tx.send(ProgressMessage::Skipped {
+                        file: item.path.display().to_string(),
+                        reason: format!("{} already exists", planned.display()),
+                    });
+                    continue;
+                }
+                Err(error) => {
+                    let _ = progress_tx.send(ProgressMessage::Error { file: item.path.display().to_string(), error });
+                    continue;
+                }
+            };
+
+            let reconstructs_jpeg =
+                format == OutputFormat::Jpeg && has_jpeg_reconstruction(&item.path).unwrap_or(false);
+
+            // Hashed before decoding, in case the output overwrites it
+            let original = if settings.verify_reconstruction && reconstructs_jpeg {
+                let original = find_original(&item.path, item.folder.as_deref(), settings.original_root.as_deref());
+                Some(original.and_then(|path| sha256_file(&path).ok().map(|hash| (path, hash))))
+            } else {
+                None
+            };
+
+            let finish = if format == OutputFormat::Gif {
+                Some(Finish::Gif(settings.gif_options()))
+            } else if format == OutputFormat::Png {
+                settings.timing().is_set().then_some(Finish::Retime(settings.timing()))
+            } else {
+                // A reconstructed JPEG is the original file, which had no alpha
+                (!format.supports_alpha() && !reconstructs_jpeg)
+                    .then_some(Finish::Flatten(format, settings.flatten_background))
+            };
+
+            match self.decode_single(&djxl_path, &item.path, &output_path, &settings, finish, &progress_tx) {
                 Ok(output) => {
+                    rollback.record_file(&output);
+                    let mut file = format!("{} -> {}", item.path.display(), output.display());
+                    if !format.supports_animation() && jxl_is_animated(&item.path) == Some(true) {
+                        file.push_str(" (first frame only)");
+                    }
                     let _ = progress_tx.send(ProgressMessage::Success {
-                        file: format!("{} -> {}", item.path.display(), output.display()),
+                        file,
+                        input: item.path.clone(),
+                        input_bytes: file_size(&item.path),
+                        output_bytes: file_size(&output),
+                        output: output.clone(),
                     });
+                    if let Some(original) = original {
+                        let _ = progress_tx.send(ProgressMessage::Verified {
+                            file: item.path.display().to_string(),
+                            verification: verify_output(&output, original),
+                        });
+                    }
+                    self.run_post_hook(&item.path, &output, true, &progress_tx);
                 }
                 Err(e) => {
                     let _ = progress_tx.send(ProgressMessage::Error {
                         file: item.path.display().to_string(),
                         error: e,
                     });
+                    self.run_post_hook(&item.path, &output_path, false, &progress_tx);
                 }
             }
         }
@@ -414,27 +1126,11 @@ impl ConversionEngine {
         &self,
         djxl_path: &Path,
         input_file: &Path,
-        output_format: OutputFormat,
+        output_path: &Path,
         settings: &DecodeSettings,
-        base_path: Option<&PathBuf>,
+        finish: Option<Finish>,
+        progress_tx: &Sender<ProgressMessage>,
     ) -> Result<PathBuf, String> {
-        // Determine output path
-        let output_path = if settings.keep_structure {
-            if let Some(base) = base_path {
-                if let Ok(rel_path) = input_file.strip_prefix(base) {
-                    settings.output_dir.join(rel_path)
-                } else {
-                    settings.output_dir.join(input_file.file_name().unwrap())
-                }
-            } else {
-                settings.output_dir.join(input_file.file_name().unwrap())
-            }
-        } else {
-            settings.output_dir.join(input_file.file_name().unwrap())
-        };
-
-        // Change extension to the selected output format
-        let output_path = output_path.with_extension(output_format.extension());
 
         // Create parent directory if needed
         if let Some(parent) = output_path.parent() {
@@ -449,7 +1145,7 @@ impl ConversionEngine {
         let abs_input = std::fs::canonicalize(input_file)
             .map_err(|e| format!("Failed to resolve input path: {}", e))?;
         let abs_output = if output_path.exists() {
-            std::fs::canonicalize(&output_path)
+            std::fs::canonicalize(output_path)
                 .map_err(|e| format!("Failed to resolve output path: {}", e))?
         } else {
             // For non-existent paths, resolve parent and join filename
@@ -460,23 +1156,473 @@ impl ConversionEngine {
                     .map_err(|e| format!("Failed to resolve output directory: {}", e))?;
                 abs_parent.join(output_path.file_name().unwrap())
             } else {
-                output_path.clone()
+                output_path.to_path_buf()
+            }
+        };
+
+        // Images that may have alpha go through a PNG so it can be flattened
+        // here; djxl would just drop it, leaving black. GIFs are always
+        // assembled here from djxl's PNG or APNG.
+        let finish = finish.filter(|finish| match finish {
+            Finish::Flatten(..) => jxl_has_alpha(&abs_input) != Some(false),
+            Finish::Gif(_) | Finish::Retime(_) => true,
+        });
+        let stage = match &finish {
+            Some(Finish::Flatten(..)) => Some("flatten"),
+            Some(Finish::Gif(_)) => Some("gif"),
+            Some(Finish::Retime(_)) | None => None,
+        };
+        let decode_to = match stage {
+            Some(stage) => {
+                let name = abs_output.file_name().unwrap_or_default().to_string_lossy();
+                abs_output.with_file_name(format!(".{}.{}.png", name, stage))
             }
+            None => abs_output.clone(),
         };
 
-        cmd.arg(&abs_input);
-        cmd.arg(&abs_output);
+        cmd.args(self.djxl_args(&abs_input, &decode_to, settings));
+        self.tool_settings.apply_env(&mut cmd);
 
         // Execute
-        let output = cmd.output()
-            .map_err(|e| format!("Failed to execute djxl: {}", e))?;
+        let output = run_tool_with(&mut cmd, &self.run_limits(), frame_reporter(input_file, None, progress_tx))
+            .map_err(|e| self.run_error(e, "djxl", &abs_output))?;
 
-        if output.status.success() {
-            Ok(abs_output)
-        } else {
+        if !output.status.success() {
+            if decode_to != abs_output {
+                let _ = std::fs::remove_file(&decode_to);
+            }
             let stderr = String::from_utf8_lossy(&output.stderr);
-            Err(format!("djxl failed: {}", stderr))
+            return Err(format!("djxl failed: {}", stderr));
+        }
+        self.report_tool_output(&output, progress_tx);
+
+        match finish {
+            Some(Finish::Flatten(format, background)) => {
+                let result = write_flattened(&decode_to, &abs_output, format, background);
+                let _ = std::fs::remove_file(&decode_to);
+                if result? {
+                    let _ = progress_tx.send(ProgressMessage::Info {
+                        message: format!("{}: alpha flattened over {}", input_file.display(), background.hex()),
+                    });
+                }
+            }
+            Some(Finish::Gif(options)) => {
+                let result = write_gif(&decode_to, &abs_output, &options, |frame, total| {
+                    if total > 1 {
+                        let _ = progress_tx.send(ProgressMessage::FrameProgress {
+                            file: input_file.display().to_string(),
+                            frame,
+                            total: Some(total),
+                        });
+                    }
+                });
+                let _ = std::fs::remove_file(&decode_to);
+                let summary = result?;
+                if summary.written.frames > 1 {
+                    let rounded = if summary.delays_adjusted { "; delays rounded to 10 ms steps" } else { "" };
+                    let _ = progress_tx.send(ProgressMessage::Info {
+                        message: format!(
+                            "{}: {}{}",
+                            input_file.display(),
+                            summary.written.describe_override(&summary.source),
+                            rounded
+                        ),
+                    });
+                }
+                let size = file_size(&abs_output);
+                if size > LARGE_GIF_BYTES {
+                    let _ = progress_tx.send(ProgressMessage::Warning {
+                        message: format!(
+                            "{}: the GIF is {:.1} MB; many chat apps reject files over {} MB. Fewer colors or no dithering make it smaller",
+                            input_file.display(),
+                            size as f64 / (1024.0 * 1024.0),
+                            LARGE_GIF_BYTES / (1024 * 1024)
+                        ),
+                    });
+                }
+            }
+            Some(Finish::Retime(timing)) => {
+                let data = std::fs::read(&abs_output).map_err(|e| format!("Failed to read decoded image: {}", e))?;
+                if let Some(retimed) = retime(&data, &timing)? {
+                    std::fs::write(&abs_output, &retimed.data)
+                        .map_err(|e| format!("Failed to write {}: {}", abs_output.display(), e))?;
+                    let _ = progress_tx.send(ProgressMessage::Info {
+                        message: format!(
+                            "{}: {}",
+                            input_file.display(),
+                            retimed.after.describe_override(&retimed.before)
+                        ),
+                    });
+                }
+            }
+            None => {}
         }
+        Ok(abs_output)
     }
-}
 
+    /// Where the output planned at `planned` is written under `overwrite`,
+    /// with its folder created; `None` when it exists and is to be left alone.
+    fn prepare_output(
+        &self,
+        planned: &Path,
+        overwrite: OverwritePolicy,
+        rollback: &mut Rollback,
+    ) -> Result<Option<PathBuf>, String> {
+        let Some(output_path) = apply_overwrite_policy(planned, overwrite) else {
+            return Ok(None);
+        };
+        if let Some(parent) = output_path.parent() {
+            rollback
+                .create_dir_all(parent)
+                .map_err(|e| format!("Failed to create output directory: {}", e))?;
+        }
+        Ok(Some(output_path))
+    }
+
+    /// Runs the post hook, if there is one, for a file that was converted
+    /// (`succeeded`) or failed. A hook that fails is only a warning; the
+    /// file's result stands.
+    fn run_post_hook(&self, input: &Path, output: &Path, succeeded: bool, progress_tx: &Sender<ProgressMessage>) {
+        let template = self.tool_settings.post_hook.trim();
+        if template.is_empty() || !(succeeded || self.tool_settings.post_hook_always) {
+            return;
+        }
+
+        let status = if succeeded { "success" } else { "error" };
+        let mut cmd = shell_command(&expand_post_hook(template, input, Some(output), status));
+        self.tool_settings.apply_env(&mut cmd);
+        let message = match run_tool(&mut cmd, &self.run_limits()) {
+            Ok(result) if result.status.success() => {
+                self.report_tool_output(&result, progress_tx);
+                let _ = progress_tx.send(ProgressMessage::Info {
+                    message: format!("{}: post hook exited with status 0", input.display()),
+                });
+                return;
+            }
+            Ok(result) => {
+                let stderr = String::from_utf8_lossy(&result.stderr);
+                let status = result.status.code().map_or("no status".to_string(), |code| format!("status {}", code));
+                match stderr.trim() {
+                    "" => format!("{}: post hook exited with {}", input.display(), status),
+                    stderr => format!("{}: post hook exited with {}: {}", input.display(), status, stderr),
+                }
+            }
+            Err(e) => format!("{}: post hook {}", input.display(), e),
+        };
+        let _ = progress_tx.send(ProgressMessage::Warning { message });
+    }
+
+    /// Runs `batch`, passing its messages on, tallying them and journaling
+    /// `journal` when it starts. Then runs the post-batch hook, if it got
+    /// as far as `Completed`, `Stopped` or `Cancelled`, and sends the
+    /// notification, however it ended. A dry run does none of that.
+    fn with_batch_end(
+        &self,
+        progress_tx: Sender<ProgressMessage>,
+        cancel_flag: &Arc<AtomicBool>,
+        journal: Option<JournalBatch>,
+        batch: impl FnOnce(Sender<ProgressMessage>) + Send,
+    ) {
+        let notify_url = self.tool_settings.notify_url.trim();
+        let quiet = self.tool_settings.post_batch_hook.trim().is_empty() && notify_url.is_empty() && journal.is_none();
+        if quiet || self.dry_run {
+            batch(progress_tx);
+            return;
+        }
+
+        let (batch_tx, batch_rx) = channel();
+        let mut summary = BatchSummary::begin();
+        let mut journaling = journal.map(|batch| BatchJournal::new(batch, &self.resume));
+        std::thread::scope(|scope| {
+            scope.spawn(move || batch(batch_tx));
+            for msg in batch_rx {
+                summary.record(&msg);
+                if let Some(journaling) = &mut journaling {
+                    if let Err(error) = journaling.record(self.journal_dir.as_deref(), &msg) {
+                        let _ = progress_tx.send(ProgressMessage::Warning { message: error });
+                    }
+                }
+                let _ = progress_tx.send(msg);
+            }
+        });
+        summary.end();
+        if let Some(journaling) = journaling {
+            journaling.close(summary.status);
+        }
+        let label = self.tool_settings.batch_label.trim();
+        summary.label = (!label.is_empty()).then(|| label.to_string());
+
+        if summary.status != BatchStatus::Aborted {
+            self.run_post_batch_hook(&summary, cancel_flag, &progress_tx);
+        }
+        if !notify_url.is_empty() {
+            let result = post_summary(
+                notify_url,
+                self.tool_settings.notify_token.trim(),
+                &summary,
+                NOTIFY_RETRY_DELAY,
+                |attempt, error| {
+                    let _ = progress_tx.send(ProgressMessage::Info {
+                        message: format!("Notification attempt {} failed ({}), retrying", attempt, error),
+                    });
+                },
+            );
+            let _ = progress_tx.send(match result {
+                Ok(()) => ProgressMessage::Info { message: format!("Notified {}", notify_url) },
+                Err(error) => ProgressMessage::Warning { message: format!("Failed to notify {}: {}", notify_url, error) },
+            });
+        }
+    }
+
+    /// Runs the post-batch hook, if there is one, with the batch's totals
+    /// in its environment.
+    fn run_post_batch_hook(&self, summary: &BatchSummary, cancel_flag: &Arc<AtomicBool>, progress_tx: &Sender<ProgressMessage>) {
+        if self.tool_settings.post_batch_hook.trim().is_empty() {
+            return;
+        }
+        let mut cmd = shell_command(self.tool_settings.post_batch_hook.trim());
+        cmd.env("JXL_CONVERTED", summary.converted.to_string())
+            .env("JXL_FAILED", summary.failed.to_string())
+            .env("JXL_SKIPPED", summary.skipped.to_string())
+            .env("JXL_BYTES_SAVED", summary.bytes_saved().to_string())
+            .env("JXL_STATUS", summary.status.name());
+        // A cancelled batch has already used up the cancel flag
+        let cancel_flag = (!summary.cancelled).then(|| Arc::clone(cancel_flag));
+        if let Err(message) = self.run_batch_hook("Post-batch hook", cmd, cancel_flag, progress_tx) {
+            let _ = progress_tx.send(ProgressMessage::Warning { message });
+        }
+    }
+
+    /// Runs the pre-batch hook, if there is one. When it fails the batch
+    /// can't start: the failure is sent as an error and `false` returned.
+    fn run_pre_batch_hook(&self, cancel_flag: &Arc<AtomicBool>, progress_tx: &Sender<ProgressMessage>) -> bool {
+        let command = self.tool_settings.pre_batch_hook.trim();
+        if command.is_empty() || self.dry_run {
+            return true;
+        }
+        let result = self.run_batch_hook("Pre-batch hook", shell_command(command), Some(Arc::clone(cancel_flag)), progress_tx);
+        match result {
+            Ok(()) => true,
+            Err(error) => {
+                let _ = progress_tx.send(ProgressMessage::Error { file: String::new(), error });
+                false
+            }
+        }
+    }
+
+    /// Runs a batch hook under the tools' limits plus the hook timeout,
+    /// copying its output into the log. The error says why it failed.
+    fn run_batch_hook(
+        &self,
+        name: &str,
+        mut cmd: Command,
+        cancel_flag: Option<Arc<AtomicBool>>,
+        progress_tx: &Sender<ProgressMessage>,
+    ) -> Result<(), String> {
+        self.tool_settings.apply_env(&mut cmd);
+        let timeout = self.tool_settings.batch_hook_timeout_secs.unwrap_or(DEFAULT_BATCH_HOOK_TIMEOUT_SECS);
+        let limits = RunLimits {
+            timeout: Some(Duration::from_secs(timeout)),
+            cancel_flag,
+            ..self.run_limits()
+        };
+
+        let _ = progress_tx.send(ProgressMessage::Info { message: format!("Running {}", name.to_lowercase()) });
+        let output = run_tool(&mut cmd, &limits).map_err(|e| format!("{} {}", name, e))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        for line in stdout.lines().chain(stderr.lines()).map(str::trim_end).filter(|line| !line.is_empty()) {
+            let _ = progress_tx.send(ProgressMessage::Info { message: format!("    {}", line) });
+        }
+
+        match output.status.code() {
+            Some(0) => Ok(()),
+            Some(code) => Err(format!("{} exited with status {}", name, code)),
+            None => Err(format!("{} was killed", name)),
+        }
+    }
+
+    /// The files that changed over the configured stability wait, if any.
+    fn unstable_files(&self, files: &[PathBuf]) -> HashSet<PathBuf> {
+        match self.tool_settings.stable_wait_secs {
+            Some(secs) if secs > 0 => still_being_written(files, Duration::from_secs(secs)),
+            _ => HashSet::new(),
+        }
+    }
+
+    /// Sleeps between files for the cool-down or the live delay, whichever
+    /// is longer, cut short when the delay is lowered or the batch is
+    /// cancelled.
+    fn wait_file_delay(&self, cancel_flag: &AtomicBool) {
+        let started = std::time::Instant::now();
+        let cool_down = self.tool_settings.inter_file_delay_ms.unwrap_or(0);
+        loop {
+            let delay = Duration::from_millis(self.file_delay_ms.load(Ordering::Relaxed).max(cool_down));
+            if started.elapsed() >= delay || cancel_flag.load(Ordering::Relaxed) {
+                return;
+            }
+            std::thread::sleep(PAUSE_POLL_INTERVAL.min(delay.saturating_sub(started.elapsed())));
+        }
+    }
+
+    /// Blocks while the batch is paused. Returns whether it was cancelled.
+    fn wait_while_paused(&self, cancel_flag: &AtomicBool) -> bool {
+        while self.pause_flag.load(Ordering::Relaxed) && !cancel_flag.load(Ordering::Relaxed) {
+            std::thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+        cancel_flag.load(Ordering::Relaxed)
+    }
+
+    fn run_limits(&self) -> RunLimits {
+        let cpu_cores = self.tool_settings.cpu_limit.filter(|_| affinity_supported()).map(limit_cores);
+
+        RunLimits {
+            max_memory_mb: self.tool_settings.max_child_memory_mb,
+            cpu_cores,
+            ..Default::default()
+        }
+    }
+
+    /// The arguments cjxl gets for one file. Batches, the command preview
+    /// and exported scripts all use this, so they can't disagree.
+    pub fn cjxl_args(&self, input: &Path, output: &Path, settings: &ConversionSettings) -> Vec<OsString> {
+        self.cjxl_args_at(input, output, settings, None)
+    }
+
+    /// [`Self::cjxl_args`], with `-d distance` in place of the quality when
+    /// the encode is lossy.
+    fn cjxl_args_at(
+        &self,
+        input: &Path,
+        output: &Path,
+        settings: &ConversionSettings,
+        distance: Option<f32>,
+    ) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec![input.into(), output.into()];
+
+        // Add quality/lossless options
+        let is_jpeg = is_jpeg_file(input);
+        let transcodes_jpeg = is_jpeg && (settings.lossless || settings.jpeg_lossless);
+
+        if settings.lossless {
+            if is_jpeg {
+                args.push("--lossless_jpeg=1".into());
+            } else {
+                args.extend(["-d".into(), "0".into()]);
+            }
+        } else if is_jpeg && settings.jpeg_lossless {
+            // JPEG-specific lossless conversion
+            args.push("--lossless_jpeg=1".into());
+        } else if let Some(distance) = distance {
+            args.extend(["-d".into(), format!("{:.3}", distance).into()]);
+        } else {
+            args.extend(["-q".into(), settings.quality.to_string().into()]);
+        }
+
+        // Add effort option
+        args.extend(["-e".into(), settings.effort.to_string().into()]);
+        // A transcoded JPEG keeps its own bit depth; cjxl fails on it with the flag
+        if let Some(bits) = settings.override_bitdepth.filter(|_| !transcodes_jpeg) {
+            args.push(format!("--override_bitdepth={}", bits).into());
+        }
+        args.extend(settings.extra_args.iter().map(OsString::from));
+
+        args.extend(self.tool_settings.verbosity_args().into_iter().map(OsString::from));
+        args.extend(self.thread_args().into_iter().map(OsString::from));
+        args
+    }
+
+    /// The arguments djxl gets for one file.
+    pub fn djxl_args(&self, input: &Path, output: &Path, settings: &DecodeSettings) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec![input.into(), output.into()];
+        if settings.allow_partial_files {
+            args.push("--allow_partial_files".into());
+        }
+        args.extend(self.tool_settings.verbosity_args().into_iter().map(OsString::from));
+        args.extend(self.thread_args().into_iter().map(OsString::from));
+        args
+    }
+
+    /// Options applied to every tool invocation.
+    pub fn tool_settings(&self) -> &ToolSettings {
+        &self.tool_settings
+    }
+
+    /// The cjxl in use, if any.
+    pub fn cjxl_path(&self) -> Option<&Path> {
+        self.cjxl_path.as_deref()
+    }
+
+    /// The version the cjxl in use reported.
+    pub fn cjxl_version(&self) -> Option<&str> {
+        self.cjxl_version.as_deref()
+    }
+
+    /// The version the djxl in use reported.
+    pub fn djxl_version(&self) -> Option<&str> {
+        self.djxl_version.as_deref()
+    }
+
+    /// Without affinity support the tools are asked to cap their own thread
+    /// pool instead, which is the best we can do.
+    fn thread_args(&self) -> Vec<String> {
+        match self.tool_settings.cpu_limit {
+            Some(limit) if !affinity_supported() => vec![format!("--num_threads={}", limit)],
+            _ => Vec::new(),
+        }
+    }
+
+    fn announce_dry_run(&self, progress_tx: &Sender<ProgressMessage>) {
+        if self.dry_run {
+            let _ = progress_tx.send(ProgressMessage::Info {
+                message: "Dry run: nothing will be converted or written".to_string(),
+            });
+        }
+    }
+
+    fn announce_cpu_limit(&self, progress_tx: &Sender<ProgressMessage>) {
+        let Some(limit) = self.tool_settings.cpu_limit else {
+            return;
+        };
+
+        let message = if affinity_supported() {
+            format!("Tools pinned to {} logical core(s)", limit_cores(limit).len())
+        } else {
+            format!(
+                "CPU affinity is not supported on this platform; passing --num_threads={} instead",
+                limit
+            )
+        };
+        let _ = progress_tx.send(ProgressMessage::Info { message });
+    }
+
+    /// Turns a failed run into a per-file error, removing whatever the
+    /// killed tool managed to write.
+    fn run_error(&self, error: RunError, tool_name: &str, output_path: &Path) -> String {
+        match error {
+            RunError::Spawn(e) => format!("Failed to execute {}: {}", tool_name, e),
+            RunError::MemoryExceeded { .. } => {
+                let _ = std::fs::remove_file(output_path);
+                error.to_string()
+            }
+            RunError::Wait(_) | RunError::TimedOut(_) | RunError::Cancelled => format!("{} {}", tool_name, error),
+        }
+    }
+
+    /// Copies a successful tool run's output into the log when verbose
+    /// output was requested. Failures always report stderr in the error.
+    fn report_tool_output(&self, output: &std::process::Output, progress_tx: &Sender<ProgressMessage>) {
+        if self.tool_settings.tool_verbosity == 0 {
+            return;
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        for line in stdout.lines().chain(stderr.lines()) {
+            let line = line.trim_end();
+            if !line.is_empty() {
+                let _ = progress_tx.send(ProgressMessage::Info { message: format!("    {}", line) });
+            }
+        }
+    }
+}
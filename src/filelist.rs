@@ -0,0 +1,147 @@
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+use crate::engine::{is_jxl_file, is_supported_image};
+use crate::paste::file_uri_to_path;
+use crate::types::OutputFormat;
+
+/// Which input list an imported file list is destined for.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ListTarget {
+    Encode,
+    Decode,
+}
+
+/// One line of a file list. Decode lists may pin formats per file with a
+/// tab-separated suffix: `path<TAB>png` or `path<TAB>png,jpg`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListEntry {
+    pub path: PathBuf,
+    pub formats: Vec<OutputFormat>,
+}
+
+#[derive(Debug)]
+pub enum ImportMessage {
+    Progress { done: usize, total: usize },
+    Finished(Result<ImportResult, String>),
+}
+
+#[derive(Debug, Default)]
+pub struct ImportResult {
+    pub entries: Vec<ListEntry>,
+    pub missing: usize,
+    pub unsupported: usize,
+}
+
+/// Parses a list file: one path per line, blank lines and `#` comments
+/// (including M3U `#EXT` directives) ignored, relative paths resolved
+/// against `base_dir`.
+pub fn parse_list(content: &str, base_dir: &Path) -> Vec<ListEntry> {
+    content
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (line, formats) = match line.rsplit_once('\t') {
+                Some((path, formats)) => {
+                    match formats.split(',').map(|f| OutputFormat::from_extension(f.trim())).collect() {
+                        Some(formats) => (path.trim_end(), formats),
+                        None => (line, Vec::new()),
+                    }
+                }
+                None => (line, Vec::new()),
+            };
+
+            let is_uri = line.get(..7).is_some_and(|scheme| scheme.eq_ignore_ascii_case("file://"));
+            let path = if is_uri {
+                file_uri_to_path(line)?
+            } else {
+                PathBuf::from(line)
+            };
+
+            let path = if path.is_relative() { base_dir.join(path) } else { path };
+            Some(ListEntry { path, formats })
+        })
+        .collect()
+}
+
+/// Writes entries in the format `parse_list` reads back. Returns how many
+/// entries had to be left out because their path can't be stored as a line
+/// of UTF-8 text.
+pub fn write_list(list_file: &Path, entries: &[ListEntry]) -> Result<usize, String> {
+    let file = std::fs::File::create(list_file)
+        .map_err(|e| format!("Failed to create {}: {}", list_file.display(), e))?;
+    let mut writer = BufWriter::new(file);
+    let mut skipped = 0;
+
+    let write_err = |e: std::io::Error| format!("Failed to write {}: {}", list_file.display(), e);
+
+    writeln!(writer, "# jxl-converter-gui file list").map_err(write_err)?;
+
+    for entry in entries {
+        let Some(path) = entry.path.to_str().filter(|p| !p.contains(['\n', '\r'])) else {
+            skipped += 1;
+            continue;
+        };
+
+        if entry.formats.is_empty() {
+            writeln!(writer, "{}", path)
+        } else {
+            let formats: Vec<&str> = entry.formats.iter().map(|format| format.extension()).collect();
+            writeln!(writer, "{}\t{}", path, formats.join(","))
+        }
+        .map_err(write_err)?;
+    }
+
+    writer.flush().map_err(write_err)?;
+    Ok(skipped)
+}
+
+/// Reads and validates a list file, reporting progress over `tx`.
+/// Meant to run on a background thread since large lists touch the
+/// filesystem once per entry.
+pub fn import_list(list_file: &Path, target: ListTarget, tx: Sender<ImportMessage>) {
+    let content = match std::fs::read_to_string(list_file) {
+        Ok(c) => c,
+        Err(e) => {
+            let _ = tx.send(ImportMessage::Finished(Err(format!(
+                "Failed to read {}: {}",
+                list_file.display(),
+                e
+            ))));
+            return;
+        }
+    };
+
+    let base_dir = list_file.parent().unwrap_or(Path::new(""));
+    let entries = parse_list(&content, base_dir);
+    let total = entries.len();
+    let mut result = ImportResult::default();
+
+    for (idx, entry) in entries.into_iter().enumerate() {
+        let path = &entry.path;
+        if idx % 500 == 0 {
+            let _ = tx.send(ImportMessage::Progress { done: idx, total });
+        }
+
+        if !path.exists() {
+            result.missing += 1;
+            continue;
+        }
+
+        let supported = match target {
+            ListTarget::Encode => path.is_dir() || is_supported_image(path),
+            ListTarget::Decode => path.is_file() && is_jxl_file(path),
+        };
+
+        if supported {
+            result.entries.push(entry);
+        } else {
+            result.unsupported += 1;
+        }
+    }
+
+    let _ = tx.send(ImportMessage::Finished(Ok(result)));
+}
@@ -0,0 +1,258 @@
+use std::fs::File;
+use std::io::{BufWriter, Read};
+use std::path::Path;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::pnm::{PnmEncoder, PnmSubtype, SampleEncoding};
+use image::{DynamicImage, ImageBuffer, ImageEncoder, Rgb};
+
+use crate::types::{Color, OutputFormat};
+use crate::verify::find_box;
+
+/// JPEG quality used when the JPEG is written here rather than by djxl,
+/// matching djxl's default.
+const JPEG_QUALITY: u8 = 95;
+
+/// Whether the image in a JXL has an alpha channel, read from its header
+/// without decoding. `None` when the header couldn't be read or has extra
+/// channels this doesn't look into.
+pub fn jxl_has_alpha(path: &Path) -> Option<bool> {
+    read_header(path)?.alpha
+}
+
+/// Whether a JXL is an animation, read from its header without decoding.
+/// `None` when the header couldn't be read.
+pub fn jxl_is_animated(path: &Path) -> Option<bool> {
+    read_header(path).map(|header| header.animated)
+}
+
+/// What the codestream's headers say about the image.
+struct Header {
+    animated: bool,
+    alpha: Option<bool>,
+}
+
+fn read_header(path: &Path) -> Option<Header> {
+    let mut file = File::open(path).ok()?;
+    let mut header = Vec::new();
+    match find_box(&mut file, &[b"jxlc", b"jxlp"]).ok()? {
+        Some(kind) => {
+            if &kind == b"jxlp" {
+                // Partial codestream boxes start with a sequence number
+                let mut index = [0u8; 4];
+                file.read_exact(&mut index).ok()?;
+            }
+        }
+        None => {
+            // Not a container (or one with no codestream): read from the start
+            file = File::open(path).ok()?;
+        }
+    }
+    // The headers up to the extra channels are a few dozen bytes at most
+    file.take(256).read_to_end(&mut header).ok()?;
+    parse_header(&header)
+}
+
+/// Reads the codestream's SizeHeader and ImageMetadata (ISO/IEC 18181-1)
+/// up to the first extra channel.
+fn parse_header(codestream: &[u8]) -> Option<Header> {
+    if !codestream.starts_with(&[0xFF, 0x0A]) {
+        return None;
+    }
+    let mut bits = BitReader { data: &codestream[2..], pos: 0 };
+
+    // SizeHeader
+    let dims = [Dist::Bits(9, 1), Dist::Bits(13, 1), Dist::Bits(18, 1), Dist::Bits(30, 1)];
+    let small = bits.bool()?;
+    if small {
+        bits.bits(5)?;
+    } else {
+        bits.u32(dims)?;
+    }
+    if bits.bits(3)? == 0 {
+        if small {
+            bits.bits(5)?;
+        } else {
+            bits.u32(dims)?;
+        }
+    }
+
+    // ImageMetadata
+    if bits.bool()? {
+        // all_default: a still image with no extra channels
+        return Some(Header { animated: false, alpha: Some(false) });
+    }
+    let mut animated = false;
+    if bits.bool()? {
+        // extra_fields
+        bits.bits(3)?; // orientation
+        if bits.bool()? {
+            // Intrinsic size, a SizeHeader
+            let small = bits.bool()?;
+            if small {
+                bits.bits(5)?;
+            } else {
+                bits.u32(dims)?;
+            }
+            if bits.bits(3)? == 0 {
+                if small {
+                    bits.bits(5)?;
+                } else {
+                    bits.u32(dims)?;
+                }
+            }
+        }
+        if bits.bool()? {
+            // PreviewHeader
+            let div8 = [Dist::Val(16), Dist::Val(32), Dist::Bits(5, 1), Dist::Bits(9, 33)];
+            let plain = [Dist::Bits(6, 1), Dist::Bits(8, 65), Dist::Bits(10, 321), Dist::Bits(12, 1345)];
+            let preview = if bits.bool()? { div8 } else { plain };
+            bits.u32(preview)?;
+            if bits.bits(3)? == 0 {
+                bits.u32(preview)?;
+            }
+        }
+        animated = bits.bool()?;
+        if animated {
+            // AnimationHeader
+            bits.u32([Dist::Val(100), Dist::Val(1000), Dist::Bits(10, 1), Dist::Bits(30, 1)])?;
+            bits.u32([Dist::Val(1), Dist::Val(1001), Dist::Bits(8, 1), Dist::Bits(10, 1)])?;
+            bits.u32([Dist::Val(0), Dist::Bits(3, 0), Dist::Bits(16, 0), Dist::Bits(32, 0)])?;
+            bits.bool()?; // have_timecodes
+        }
+    }
+    Some(Header { animated, alpha: parse_alpha(&mut bits) })
+}
+
+/// Reads the rest of ImageMetadata up to the first extra channel.
+fn parse_alpha(bits: &mut BitReader) -> Option<bool> {
+    // BitDepth
+    if bits.bool()? {
+        bits.u32([Dist::Val(32), Dist::Val(16), Dist::Val(24), Dist::Bits(6, 1)])?;
+        bits.bits(4)?;
+    } else {
+        bits.u32([Dist::Val(8), Dist::Val(10), Dist::Val(12), Dist::Bits(6, 1)])?;
+    }
+    bits.bool()?; // modular_16_bit_buffer_sufficient
+
+    let extra_channels = bits.u32([Dist::Val(0), Dist::Val(1), Dist::Bits(4, 2), Dist::Bits(12, 1)])?;
+    if extra_channels == 0 {
+        return Some(false);
+    }
+
+    // ExtraChannelInfo: all_default means alpha, otherwise type 0 is alpha
+    if bits.bool()? {
+        return Some(true);
+    }
+    let kind = bits.u32([Dist::Val(0), Dist::Val(1), Dist::Bits(4, 2), Dist::Bits(6, 18)])?;
+    if kind == 0 {
+        Some(true)
+    } else if extra_channels == 1 {
+        Some(false)
+    } else {
+        // Later channels would need the rest of this one parsed
+        None
+    }
+}
+
+/// A U32 distribution: a fixed value, or that many bits plus an offset.
+#[derive(Clone, Copy)]
+enum Dist {
+    Val(u32),
+    Bits(u32, u32),
+}
+
+/// Reads a JXL bitstream, least significant bit first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Option<u32> {
+        let mut value = 0u64;
+        for i in 0..count {
+            let byte = *self.data.get(self.pos / 8)?;
+            value |= u64::from((byte >> (self.pos % 8)) & 1) << i;
+            self.pos += 1;
+        }
+        u32::try_from(value).ok()
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        Some(self.bits(1)? == 1)
+    }
+
+    fn u32(&mut self, dists: [Dist; 4]) -> Option<u32> {
+        match dists[self.bits(2)? as usize] {
+            Dist::Val(value) => Some(value),
+            Dist::Bits(count, offset) => self.bits(count)?.checked_add(offset),
+        }
+    }
+}
+
+/// Writes the image in `png` to `output` as `format`, compositing it over
+/// `background` first if it has alpha. Returns whether it had alpha.
+pub fn write_flattened(png: &Path, output: &Path, format: OutputFormat, background: Color) -> Result<bool, String> {
+    if format == OutputFormat::Gif {
+        return Err("GIFs keep their transparency; see animation::write_gif".to_string());
+    }
+    if format == OutputFormat::Exr {
+        return Err("EXRs keep their transparency; djxl writes them directly".to_string());
+    }
+    let image = image::open(png).map_err(|e| format!("Failed to read decoded image: {}", e))?;
+    let had_alpha = image.color().has_alpha();
+    let high_depth = image.color().bytes_per_pixel() / image.color().channel_count() > 1;
+
+    let flat = if had_alpha {
+        let rgba = image.to_rgba16();
+        let bg = background.0.map(|c| u32::from(c) * 257);
+        let flat = ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let [r, g, b, a] = rgba.get_pixel(x, y).0.map(u32::from);
+            let over = |c: u32, bg: u32| ((c * a + bg * (65535 - a)) / 65535) as u16;
+            Rgb([over(r, bg[0]), over(g, bg[1]), over(b, bg[2])])
+        });
+        DynamicImage::ImageRgb16(flat)
+    } else {
+        image
+    };
+
+    let file = File::create(output).map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+    let writer = BufWriter::new(file);
+    let (w, h) = (flat.width(), flat.height());
+    let result = match format {
+        OutputFormat::Jpeg => {
+            let rgb = flat.to_rgb8();
+            JpegEncoder::new_with_quality(writer, JPEG_QUALITY).write_image(&rgb, w, h, image::ExtendedColorType::Rgb8)
+        }
+        OutputFormat::Ppm | OutputFormat::Pgm => {
+            let gray = format == OutputFormat::Pgm;
+            let subtype = if gray {
+                PnmSubtype::Graymap(SampleEncoding::Binary)
+            } else {
+                PnmSubtype::Pixmap(SampleEncoding::Binary)
+            };
+            let image = match (gray, high_depth) {
+                (true, true) => DynamicImage::ImageLuma16(flat.to_luma16()),
+                (true, false) => DynamicImage::ImageLuma8(flat.to_luma8()),
+                (false, true) => DynamicImage::ImageRgb16(flat.to_rgb16()),
+                (false, false) => DynamicImage::ImageRgb8(flat.to_rgb8()),
+            };
+            PnmEncoder::new(writer)
+                .with_subtype(subtype)
+                .write_image(image.as_bytes(), w, h, image.color().into())
+        }
+        OutputFormat::Pbm => {
+            // PBM stores 1 for black
+            let mut luma = flat.to_luma8();
+            luma.pixels_mut().for_each(|p| p.0[0] = u8::from(p.0[0] < 128));
+            PnmEncoder::new(writer)
+                .with_subtype(PnmSubtype::Bitmap(SampleEncoding::Binary))
+                .write_image(&luma, w, h, image::ExtendedColorType::L8)
+        }
+        OutputFormat::Png => flat.write_with_encoder(image::codecs::png::PngEncoder::new(writer)),
+        OutputFormat::Gif | OutputFormat::Exr => unreachable!("rejected above"),
+    };
+    result.map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+    Ok(had_alpha)
+}
@@ -0,0 +1,63 @@
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::Command;
+
+use crate::script::Shell;
+
+/// `template` with the placeholders replaced by `input`, `output` and
+/// `status`, each quoted as one word for the system shell. `{output}` is
+/// empty when there is no output path.
+pub fn expand_post_hook(template: &str, input: &Path, output: Option<&Path>, status: &str) -> String {
+    let output = output.map(Path::as_os_str).unwrap_or_default();
+    let values = [("{input}", input.as_os_str()), ("{output}", output), ("{status}", OsStr::new(status))];
+
+    // One pass, so a path that happens to contain a placeholder stays as is
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        match values.iter().find(|(placeholder, _)| tail.starts_with(placeholder)) {
+            Some((placeholder, value)) => {
+                expanded.push_str(&quote_for_shell(value));
+                rest = &tail[placeholder.len()..];
+            }
+            None => {
+                expanded.push('{');
+                rest = &tail[1..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// `arg` quoted for the shell [`shell_command`] runs. cmd.exe gets plain
+/// double quotes since `%%` is only an escape inside batch files.
+fn quote_for_shell(arg: &OsStr) -> String {
+    match Shell::native() {
+        Shell::Cmd => format!("\"{}\"", arg.to_string_lossy()),
+        shell => shell.quote(arg),
+    }
+}
+
+/// A command running `command_line` through the system shell: `sh -c` or
+/// `cmd /C`.
+pub fn shell_command(command_line: &str) -> Command {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+
+        // cmd.exe parses its own command line, so it is passed untouched
+        let mut cmd = Command::new("cmd");
+        cmd.raw_arg(format!("/S /C \"{}\"", command_line));
+        cmd
+    }
+
+    #[cfg(not(windows))]
+    {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command_line);
+        cmd
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::summary::format_utc;
+use crate::types::{ConversionSettings, DecodeItem, DecodeSettings};
+
+/// Journals are named `<batch id>.jsonl`.
+const JOURNAL_EXTENSION: &str = "jsonl";
+
+/// What a batch was asked to do, enough to start it again.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum JournalBatch {
+    Encode { inputs: Vec<PathBuf>, settings: ConversionSettings },
+    Decode { items: Vec<DecodeItem>, settings: DecodeSettings },
+}
+
+/// One line of a journal. The header comes first, then `started` once the
+/// batch knows its size, then a line per file as each one finishes.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Line {
+    Header {
+        batch_id: String,
+        started_at: String,
+        batch: JournalBatch,
+        /// Inputs finished by the run this one resumes.
+        #[serde(default)]
+        done: Vec<PathBuf>,
+    },
+    Started {
+        total: usize,
+    },
+    File {
+        file: PathBuf,
+        status: String,
+    },
+}
+
+/// A batch's record of the files it has finished, kept so a batch cut short
+/// by a crash or power loss can resume. Each line is flushed to disk as it
+/// is written, and a torn last line is ignored when reading.
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+}
+
+impl Journal {
+    /// Creates a journal in `dir` for a new batch, carrying over the inputs
+    /// `done` by the run it resumes, if any.
+    pub fn create(dir: &Path, batch: JournalBatch, done: Vec<PathBuf>) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let now = SystemTime::now();
+        let nanos = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+        let batch_id = format!("{}-{}", nanos, std::process::id());
+        let path = dir.join(format!("{}.{}", batch_id, JOURNAL_EXTENSION));
+        let file = File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+
+        let mut journal = Self { path, file };
+        journal.write(&Line::Header { batch_id, started_at: format_utc(now), batch, done })?;
+        Ok(journal)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Notes how many files the batch has to do.
+    pub fn started(&mut self, total: usize) -> Result<(), String> {
+        self.write(&Line::Started { total })
+    }
+
+    /// Notes that `file` is finished, with `status` such as `success`.
+    pub fn record(&mut self, file: &Path, status: &str) -> Result<(), String> {
+        self.write(&Line::File { file: file.to_path_buf(), status: status.to_string() })
+    }
+
+    /// Deletes the journal of a batch that ended on its own terms.
+    pub fn finish(self) {
+        drop(self.file);
+        let _ = std::fs::remove_file(&self.path);
+    }
+
+    fn write(&mut self, line: &Line) -> Result<(), String> {
+        let mut text = serde_json::to_string(line).map_err(|e| format!("Failed to write the journal: {}", e))?;
+        text.push('\n');
+        self.file
+            .write_all(text.as_bytes())
+            .and_then(|()| self.file.sync_data())
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+}
+
+/// A journal left behind by a batch that never ended.
+#[derive(Clone, Debug)]
+pub struct UnfinishedBatch {
+    pub path: PathBuf,
+    pub batch_id: String,
+    /// When the batch began, as RFC 3339 UTC.
+    pub started_at: String,
+    pub batch: JournalBatch,
+    /// Inputs already finished, once per output written for them.
+    pub done: Vec<PathBuf>,
+    /// Files the last run still had to do, if it got as far as counting them.
+    total: Option<usize>,
+    /// Files the last run finished.
+    finished: usize,
+}
+
+impl UnfinishedBatch {
+    /// Reads a journal. Lines after a torn or unreadable one are ignored.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let mut lines = BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .map_while(|line| serde_json::from_str::<Line>(&line).ok());
+
+        let Some(Line::Header { batch_id, started_at, batch, done }) = lines.next() else {
+            return Err(format!("{} has no journal header", path.display()));
+        };
+        let mut unfinished =
+            Self { path: path.to_path_buf(), batch_id, started_at, batch, done, total: None, finished: 0 };
+        for line in lines {
+            match line {
+                Line::Started { total } => unfinished.total = Some(total),
+                Line::File { file, .. } => {
+                    unfinished.done.push(file);
+                    unfinished.finished += 1;
+                }
+                Line::Header { .. } => break,
+            }
+        }
+        Ok(unfinished)
+    }
+
+    /// Files left to do, if the batch got as far as counting them.
+    pub fn remaining(&self) -> Option<usize> {
+        self.total.map(|total| total.saturating_sub(self.finished))
+    }
+
+    /// What the engine needs to pick up where this batch stopped.
+    pub fn resume(&self) -> Resume {
+        Resume { done: self.done.clone(), previous: Some(self.path.clone()) }
+    }
+
+    /// Deletes the journal, giving up on the batch.
+    pub fn discard(self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Every unfinished batch journaled in `dir`, oldest first. Files that
+/// aren't journals are left alone.
+pub fn find_unfinished(dir: &Path) -> Vec<UnfinishedBatch> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut batches: Vec<UnfinishedBatch> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == JOURNAL_EXTENSION))
+        .filter_map(|path| UnfinishedBatch::read(&path).ok())
+        .collect();
+    batches.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.batch_id.cmp(&b.batch_id)));
+    batches
+}
+
+/// Where a resumed batch picks up: the inputs already done are left out,
+/// and the journal it resumes is deleted once the new one is written.
+#[derive(Clone, Debug, Default)]
+pub struct Resume {
+    /// Inputs already finished, once per output written for them.
+    pub done: Vec<PathBuf>,
+    pub previous: Option<PathBuf>,
+}
+
+impl Resume {
+    /// How many outputs each finished input already has.
+    pub fn done_counts(&self) -> HashMap<&Path, usize> {
+        let mut counts = HashMap::new();
+        for path in &self.done {
+            *counts.entry(path.as_path()).or_insert(0) += 1;
+        }
+        counts
+    }
+}
@@ -0,0 +1,97 @@
+//! Batch conversion to and from JPEG XL by driving libjxl's `cjxl` and
+//! `djxl` command line tools.
+//!
+//! A batch runs on a thread of your choosing and reports everything it does
+//! as [`ProgressMessage`]s over a channel:
+//!
+//! ```no_run
+//! use std::sync::atomic::AtomicBool;
+//! use std::sync::{mpsc, Arc};
+//! use std::thread;
+//!
+//! use jxl_converter::{ConversionEngine, ConversionSettings, ProgressMessage};
+//!
+//! let engine = ConversionEngine::new();
+//! if let Some(error) = engine.get_error() {
+//!     panic!("{}", error);
+//! }
+//!
+//! let settings = ConversionSettings {
+//!     output_dir: "converted".into(),
+//!     quality: 85,
+//!     ..Default::default()
+//! };
+//! let (tx, rx) = mpsc::channel();
+//! let cancel = Arc::new(AtomicBool::new(false));
+//!
+//! let worker = thread::spawn(move || {
+//!     engine.convert_batch(vec!["photos".into()], settings, tx, cancel);
+//! });
+//!
+//! // The channel closes when the batch is done
+//! for msg in rx {
+//!     match msg {
+//!         ProgressMessage::Success { output, .. } => println!("wrote {}", output.display()),
+//!         ProgressMessage::Error { file, error } => eprintln!("{}: {}", file, error),
+//!         _ => {}
+//!     }
+//! }
+//! worker.join().unwrap();
+//! ```
+//!
+//! Decoding works the same way with [`ConversionEngine::decode_batch`] and a
+//! list of [`DecodeItem`]s.
+
+/// Searching each file's distance for a target ssimulacra2 score.
+pub mod adaptive;
+/// Writing decoded animations as GIFs.
+pub mod animation;
+/// Counting a batch's inputs by format before it starts.
+pub mod breakdown;
+/// Importing settings from a pasted cjxl command line.
+pub mod command;
+/// Comparing two images pixel by pixel and with perceptual metrics.
+pub mod compare;
+/// Settings combinations that don't do what they look like.
+pub mod conflicts;
+/// Fetching `http(s)://` inputs before they are encoded.
+pub mod download;
+/// The batch runner and input discovery.
+pub mod engine;
+/// Compositing transparent images onto a background for formats without alpha.
+pub mod flatten;
+/// Reading and writing plain-text file lists.
+pub mod filelist;
+/// Running user commands around conversions.
+pub mod hook;
+/// Recording a batch's progress so an interrupted one can resume.
+pub mod journal;
+/// Posting batch summaries to a webhook.
+pub mod notify;
+/// Turning pasted text, `file://` URIs and clipboard images into paths.
+pub mod paste;
+/// Mapping input files to output paths.
+pub mod paths;
+/// Running a tool with resource limits.
+pub mod process;
+/// Undoing what a cancelled transactional batch wrote.
+pub mod rollback;
+/// Finding input files in folders, honouring `.nomedia` and `.jxlignore`.
+pub mod scan;
+/// Exporting a batch as a shell script.
+pub mod script;
+/// Per-folder `.jxl-converter.toml` overrides.
+pub mod sidecar;
+/// Totals for a batch.
+pub mod summary;
+/// Finding cjxl and djxl and their versions.
+pub mod tools;
+/// Settings and progress types shared by the engine and its callers.
+pub mod types;
+/// Checking reconstructed JPEGs against their originals.
+pub mod verify;
+
+pub use engine::ConversionEngine;
+pub use types::{
+    ConversionSettings, DecodeItem, DecodeSettings, OutputFormat, OverwritePolicy, ProgressMessage, ToolSettings,
+};
@@ -1,10 +1,41 @@
+#[cfg(feature = "gui")]
 mod app;
-mod engine;
-mod types;
+mod cli;
+#[cfg(feature = "gui")]
+mod config;
+#[cfg(feature = "gui")]
+mod power;
+#[cfg(feature = "gui")]
+mod reveal;
+#[cfg(feature = "gui")]
+mod schedule;
+#[cfg(feature = "gui")]
+mod stats;
+#[cfg(feature = "gui")]
+mod throttle;
 
-use app::JxlConverterApp;
+fn main() {
+    // Any arguments select the headless CLI
+    let args: Vec<std::ffi::OsString> = std::env::args_os().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(args));
+    }
 
-fn main() -> Result<(), eframe::Error> {
+    #[cfg(feature = "gui")]
+    if let Err(e) = run_gui() {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+
+    #[cfg(not(feature = "gui"))]
+    {
+        eprintln!("This build has no graphical interface; run with --help for command line usage.");
+        std::process::exit(cli::exit_code::INVALID_ARGUMENTS);
+    }
+}
+
+#[cfg(feature = "gui")]
+fn run_gui() -> Result<(), eframe::Error> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([800.0, 600.0])
@@ -16,7 +47,6 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "JPEG XL Converter",
         options,
-        Box::new(|_cc| Ok(Box::new(JxlConverterApp::new()))),
+        Box::new(|cc| Ok(Box::new(app::JxlConverterApp::new(&cc.egui_ctx)))),
     )
 }
-
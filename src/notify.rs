@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::summary::BatchSummary;
+
+/// How many times a notification is sent before giving up.
+pub const NOTIFY_ATTEMPTS: u32 = 3;
+
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// The body of a notification: the CLI's JSON summary line without its
+/// exit code, so one parser reads both.
+#[derive(Serialize)]
+#[serde(tag = "type", rename = "summary")]
+struct Notification<'a> {
+    #[serde(flatten)]
+    summary: &'a BatchSummary,
+}
+
+/// `summary` as the JSON a notification carries.
+pub fn notification_body(summary: &BatchSummary) -> String {
+    serde_json::to_string(&Notification { summary }).unwrap_or_default()
+}
+
+/// POSTs `summary` as JSON to `url`, with `Authorization: Bearer <token>`
+/// unless `token` is empty. Network failures and 5xx responses are tried
+/// again, [`NOTIFY_ATTEMPTS`] times in all, waiting `retry_delay` longer
+/// each time; `on_retry` gets the attempt that failed and why.
+pub fn post_summary(
+    url: &str,
+    token: &str,
+    summary: &BatchSummary,
+    retry_delay: Duration,
+    mut on_retry: impl FnMut(u32, &str),
+) -> Result<(), String> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(NOTIFY_TIMEOUT)
+        .user_agent(concat!("jxl-converter/", env!("CARGO_PKG_VERSION")))
+        .build();
+    let body = notification_body(summary);
+
+    let mut attempt = 1;
+    loop {
+        let mut request = agent.post(url).set("Content-Type", "application/json");
+        if !token.is_empty() {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        let error = match request.send_string(&body) {
+            Ok(_) => return Ok(()),
+            Err(ureq::Error::Status(code, response)) if code < 500 => {
+                return Err(format!("HTTP {} {}", code, response.status_text()));
+            }
+            Err(ureq::Error::Status(code, response)) => format!("HTTP {} {}", code, response.status_text()),
+            Err(ureq::Error::Transport(transport)) => transport.to_string(),
+        };
+        if attempt == NOTIFY_ATTEMPTS {
+            return Err(error);
+        }
+        on_retry(attempt, &error);
+        std::thread::sleep(retry_delay * attempt);
+        attempt += 1;
+    }
+}
@@ -0,0 +1,115 @@
+#[cfg(not(windows))]
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+
+/// Parses clipboard text into paths. Accepts one entry per line, either a
+/// plain path (optionally wrapped in quotes, as "Copy as path" produces) or a
+/// `file://` URI as copied by most Linux and macOS file managers.
+pub fn parse_path_list(text: &str) -> Vec<PathBuf> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| {
+            let line = line
+                .strip_prefix('"')
+                .and_then(|l| l.strip_suffix('"'))
+                .unwrap_or(line);
+
+            let is_uri = line.get(..7).is_some_and(|scheme| scheme.eq_ignore_ascii_case("file://"));
+            if is_uri {
+                file_uri_to_path(line)
+            } else {
+                Some(PathBuf::from(line))
+            }
+        })
+        .collect()
+}
+
+/// Writes an image taken from the clipboard (8-bit RGBA rows) to
+/// `dir/clipboard-{stamp}.png`, or `clipboard-{stamp}-2.png` and so on if
+/// that exists, so it can be encoded like any other input.
+pub fn save_clipboard_image(dir: &Path, stamp: &str, width: u32, height: u32, rgba: Vec<u8>) -> Result<PathBuf, String> {
+    let image = RgbaImage::from_raw(width, height, rgba)
+        .ok_or_else(|| format!("Clipboard image data doesn't match its size ({}×{})", width, height))?;
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let path = (1..)
+        .map(|n| match n {
+            1 => dir.join(format!("clipboard-{}.png", stamp)),
+            n => dir.join(format!("clipboard-{}-{}.png", stamp, n)),
+        })
+        .find(|path| !path.exists())
+        .unwrap_or_default();
+    image.save(&path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Converts a `file://` URI into a local path, percent-decoding it.
+/// Returns `None` for URIs that point at another host.
+pub fn file_uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = &uri[7..];
+
+    // Split the authority from the path: file:///p, file://localhost/p, file://host/p
+    let (host, path) = match rest.find('/') {
+        Some(idx) => (&rest[..idx], &rest[idx..]),
+        None => return None,
+    };
+
+    let bytes = percent_decode(path);
+    let local = host.is_empty() || host.eq_ignore_ascii_case("localhost");
+
+    #[cfg(windows)]
+    {
+        let decoded = String::from_utf8_lossy(&bytes).replace('/', "\\");
+        if local {
+            // file:///C:/dir -> C:\dir
+            Some(PathBuf::from(decoded.trim_start_matches('\\')))
+        } else {
+            // file://server/share -> \\server\share
+            Some(PathBuf::from(format!("\\\\{}{}", host, decoded)))
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        if local {
+            Some(PathBuf::from(bytes_to_os_string(bytes)))
+        } else {
+            None
+        }
+    }
+}
+
+pub(crate) fn percent_decode(input: &str) -> Vec<u8> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(value) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(value);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    out
+}
+
+#[cfg(unix)]
+fn bytes_to_os_string(bytes: Vec<u8>) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn bytes_to_os_string(bytes: Vec<u8>) -> OsString {
+    OsString::from(String::from_utf8_lossy(&bytes).into_owned())
+}
@@ -0,0 +1,384 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::engine::SUPPORTED_IMAGE_EXTENSIONS;
+use crate::types::{ConversionSettings, DecodeItem, DecodeSettings, OutputFormat, OverwritePolicy};
+
+/// How input files map to output files for one batch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputPlan {
+    pub output_dir: PathBuf,
+    /// Folders whose layout is recreated under `output_dir`, at most one per
+    /// drive or share (see [`common_bases`]). Empty to write every file
+    /// directly into `output_dir`.
+    pub bases: Vec<PathBuf>,
+    /// Extension of the output files, without the dot.
+    pub extension: String,
+    /// Treat an image extension before `.jxl` as part of it, so
+    /// `scan.tiff.jxl` becomes `scan.png` rather than `scan.tiff.png`, and
+    /// `photo.jpg.jxl` decoded to JPEG gets its original name back.
+    pub inner_extension: bool,
+}
+
+impl OutputPlan {
+    /// The plan an encode batch over `input_paths` uses.
+    pub fn for_encode(input_paths: &[PathBuf], settings: &ConversionSettings) -> Self {
+        Self {
+            output_dir: settings.output_dir.clone(),
+            bases: if settings.keep_structure {
+                structure_bases(input_paths.iter().map(PathBuf::as_path))
+            } else {
+                Vec::new()
+            },
+            extension: "jxl".to_string(),
+            inner_extension: false,
+        }
+    }
+
+    /// The plan a decode batch uses; set `extension` per item.
+    pub fn for_decode(items: &[DecodeItem], settings: &DecodeSettings) -> Self {
+        Self {
+            output_dir: settings.output_dir.clone(),
+            bases: if settings.keep_structure {
+                structure_bases(items.iter().map(|item| item.folder.as_deref().unwrap_or(&item.path)))
+            } else {
+                Vec::new()
+            },
+            extension: String::new(),
+            inner_extension: settings.replace_inner_extension,
+        }
+    }
+}
+
+/// The folders whose layout keep_structure recreates: the common ancestor of
+/// the inputs' parents on each drive or share. Using the parent for folders
+/// too means an added folder shows up by name in the output instead of its
+/// contents being spilled into the output dir.
+pub fn structure_bases<'a>(inputs: impl Iterator<Item = &'a Path>) -> Vec<PathBuf> {
+    let dirs: Vec<PathBuf> = inputs.map(|input| input.parent().unwrap_or(input).to_path_buf()).collect();
+    common_bases(&dirs)
+}
+
+/// Where the output for `input` goes.
+///
+/// Files under one of `plan.bases` keep their path relative to it; everything
+/// else lands directly in `output_dir`. When the bases are on different
+/// drives or shares, each one's files go into a subfolder named after it
+/// (`C/…`, `D/…`, `server_share/…`) so they can't collide. Only the last
+/// extension is replaced, so `a.tar.png` becomes `a.tar.jxl`, and a file
+/// without one just gains it. Flattening can map different inputs to the same
+/// path; [`OutputClaims`] catches that.
+pub fn plan_output_path(input: &Path, plan: &OutputPlan) -> PathBuf {
+    let base = plan
+        .bases
+        .iter()
+        .filter(|base| input.starts_with(base))
+        .max_by_key(|base| base.components().count());
+
+    let relative = base
+        .and_then(|base| input.strip_prefix(base).ok())
+        .filter(|rel| rel.file_name().is_some());
+
+    let output = match (base, relative) {
+        (Some(base), Some(rel)) => {
+            let roots: HashSet<PathBuf> = plan.bases.iter().map(|b| root_of(b)).collect();
+            if roots.len() > 1 {
+                plan.output_dir.join(root_label(base)).join(rel)
+            } else {
+                plan.output_dir.join(rel)
+            }
+        }
+        // An input without a file name (e.g. `..`) is used as-is rather than panicking
+        _ => plan.output_dir.join(input.file_name().unwrap_or(input.as_os_str())),
+    };
+
+    match inner_image_name(&output).filter(|_| plan.inner_extension) {
+        Some(inner) => {
+            let format = OutputFormat::from_extension(&plan.extension);
+            let same_format = inner.extension().and_then(|ext| ext.to_str()).and_then(OutputFormat::from_extension);
+            if format.is_some() && same_format == format {
+                inner
+            } else {
+                inner.with_extension(&plan.extension)
+            }
+        }
+        None => output.with_extension(&plan.extension),
+    }
+}
+
+/// `path` without its `.jxl`, when what is left still ends in an image
+/// extension, as in `scan.tiff.jxl`.
+fn inner_image_name(path: &Path) -> Option<PathBuf> {
+    if !path.extension()?.eq_ignore_ascii_case("jxl") {
+        return None;
+    }
+    let inner = path.with_extension("");
+    let ext = inner.extension()?.to_str()?.to_lowercase();
+    SUPPORTED_IMAGE_EXTENSIONS.contains(&ext.as_str()).then_some(inner)
+}
+
+/// A typed or pasted path with surrounding quotes and spaces removed, a
+/// leading `~` replaced by the home folder, and `$VAR`, `${VAR}` and
+/// `%VAR%` replaced by environment variables. Unknown variables are left as
+/// written.
+pub fn expand_path(text: &str) -> PathBuf {
+    let text = text.trim();
+    let text = text
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .or_else(|| text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')))
+        .unwrap_or(text);
+
+    let mut out = String::new();
+    let mut rest = text;
+    if let Some(after) = rest.strip_prefix('~').filter(|after| after.is_empty() || after.starts_with(['/', '\\'])) {
+        if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+            out.push_str(&home.to_string_lossy());
+            rest = after;
+        }
+    }
+
+    while let Some(pos) = rest.find(['$', '%']) {
+        out.push_str(&rest[..pos]);
+        let sigil = &rest[pos..pos + 1];
+        let after = &rest[pos + 1..];
+        let variable = if sigil == "%" {
+            after.find('%').map(|end| (&after[..end], &after[end + 1..]))
+        } else if let Some(braced) = after.strip_prefix('{') {
+            braced.find('}').map(|end| (&braced[..end], &braced[end + 1..]))
+        } else {
+            let end = after.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(after.len());
+            Some((&after[..end], &after[end..]))
+        };
+
+        match variable.and_then(|(name, remainder)| Some((std::env::var(name).ok()?, remainder))) {
+            Some((value, remainder)) => {
+                out.push_str(&value);
+                rest = remainder;
+            }
+            None => {
+                out.push_str(sigil);
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    PathBuf::from(out)
+}
+
+/// Whether a folder can take a batch's outputs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutputDirStatus {
+    /// It exists.
+    Ready,
+    /// It doesn't exist yet, but its nearest existing parent is a folder.
+    Missing,
+    /// It can't be used, and why.
+    Invalid(String),
+}
+
+/// Checks an output folder without creating anything.
+pub fn check_output_dir(dir: &Path) -> OutputDirStatus {
+    if dir.as_os_str().is_empty() {
+        return OutputDirStatus::Invalid("No output directory selected".to_string());
+    }
+    if dir.is_dir() {
+        return OutputDirStatus::Ready;
+    }
+    if dir.exists() {
+        return OutputDirStatus::Invalid(format!("{} is a file, not a folder", dir.display()));
+    }
+    match dir.ancestors().skip(1).find(|parent| parent.exists()) {
+        Some(parent) if !parent.is_dir() => {
+            OutputDirStatus::Invalid(format!("{} is a file, so the folder can't be created", parent.display()))
+        }
+        Some(parent) if parent.metadata().is_ok_and(|m| m.permissions().readonly()) => {
+            OutputDirStatus::Invalid(format!("{} is read-only, so the folder can't be created", parent.display()))
+        }
+        Some(_) => OutputDirStatus::Missing,
+        // A relative path below the working directory, or an unplugged drive
+        None if dir.is_relative() => OutputDirStatus::Missing,
+        None => OutputDirStatus::Invalid(format!("{} doesn't exist and can't be created", dir.display())),
+    }
+}
+
+/// Where an output planned at `output` goes when a file is already there:
+/// the same path to overwrite it, `None` to skip the input, or the first
+/// free `name (2).ext`, `name (3).ext`, … to rename.
+pub fn apply_overwrite_policy(output: &Path, policy: OverwritePolicy) -> Option<PathBuf> {
+    if !output.exists() {
+        return Some(output.to_path_buf());
+    }
+    match policy {
+        OverwritePolicy::Overwrite => Some(output.to_path_buf()),
+        OverwritePolicy::Skip => None,
+        OverwritePolicy::Rename => {
+            let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+            let extension = output.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+            (2..)
+                .map(|n| output.with_file_name(format!("{} ({}){}", stem, n, extension)))
+                .find(|candidate| !candidate.exists())
+        }
+    }
+}
+
+/// The deepest common folder of `dirs`, one per drive, share or other path
+/// root, in order of first appearance. Folders on different roots have no
+/// common ancestor, so each root gets its own base instead of giving up on
+/// all of them.
+pub fn common_bases(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut bases: Vec<PathBuf> = Vec::new();
+
+    for dir in dirs {
+        let root = root_of(dir);
+        match bases.iter_mut().find(|base| root_of(base) == root) {
+            Some(base) => *base = common_prefix(base, dir),
+            None => bases.push(dir.clone()),
+        }
+    }
+
+    bases
+}
+
+/// The drive/share prefix and root of a path, e.g. `C:\`, `\\server\share\`
+/// or `/`. Empty for relative paths.
+fn root_of(path: &Path) -> PathBuf {
+    path.components()
+        .take_while(|c| matches!(c, Component::Prefix(_) | Component::RootDir))
+        .collect()
+}
+
+fn common_prefix(a: &Path, b: &Path) -> PathBuf {
+    a.components()
+        .zip(b.components())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x)
+        .collect()
+}
+
+/// A folder name standing for the root of `path`: the drive letter, or
+/// `server_share` for UNC paths.
+fn root_label(path: &Path) -> String {
+    use std::path::Prefix;
+
+    let Some(Component::Prefix(prefix)) = path.components().next() else {
+        return "root".to_string();
+    };
+
+    match prefix.kind() {
+        Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => (letter as char).to_ascii_uppercase().to_string(),
+        Prefix::UNC(server, share) | Prefix::VerbatimUNC(server, share) => {
+            format!("{}_{}", server.to_string_lossy(), share.to_string_lossy())
+        }
+        _ => prefix
+            .as_os_str()
+            .to_string_lossy()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect::<String>()
+            .trim_matches('_')
+            .to_string(),
+    }
+}
+
+/// `path` with every component in Unicode NFC, for telling whether two paths
+/// name the same file. macOS hands out decomposed (NFD) names while most
+/// other sources use NFC, and APFS treats both spellings as one file. Only
+/// use this for comparisons; tools still get the original path.
+pub fn path_key(path: &Path) -> PathBuf {
+    path.components()
+        .map(|component| match component.as_os_str().to_str() {
+            Some(name) => OsString::from(name.nfc().collect::<String>()),
+            // Not valid Unicode, so there's nothing to normalize
+            None => component.as_os_str().to_os_string(),
+        })
+        .collect()
+}
+
+/// Like [`path_key`], but also lowercased when `fold_case` is set, for file
+/// systems where `Photo.jxl` and `photo.jxl` are the same file.
+pub fn output_key(path: &Path, fold_case: bool) -> PathBuf {
+    let key = path_key(path);
+    if !fold_case {
+        return key;
+    }
+    key.components()
+        .map(|component| match component {
+            // Drive letters and roots are compared as they are
+            Component::Normal(name) => match name.to_str() {
+                Some(name) => OsString::from(name.to_lowercase()),
+                None => name.to_os_string(),
+            },
+            other => other.as_os_str().to_os_string(),
+        })
+        .collect()
+}
+
+/// Whether file names are case-insensitive in `dir` (or its nearest
+/// existing ancestor), found by creating a probe file there and looking it
+/// up under another case. `None` when that isn't possible, e.g. read-only.
+pub fn is_case_insensitive(dir: &Path) -> Option<bool> {
+    let existing = dir.ancestors().find(|d| d.is_dir())?;
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let name = format!(".JxlCaseProbe-{}-{}", std::process::id(), nanos);
+    let probe = existing.join(&name);
+    std::fs::File::create_new(&probe).ok()?;
+    let insensitive = existing.join(name.to_lowercase()).exists();
+    let _ = std::fs::remove_file(&probe);
+    Some(insensitive)
+}
+
+/// The output paths already taken in a batch, so two inputs that map to the
+/// same output (flattened folders, NFC/NFD twins, and names differing only
+/// in case where the file system ignores case) don't overwrite each other.
+#[derive(Debug)]
+pub struct OutputClaims {
+    claimed: HashMap<PathBuf, PathBuf>,
+    fold_case: bool,
+}
+
+impl Default for OutputClaims {
+    fn default() -> Self {
+        Self::with_case_folding(cfg!(any(windows, target_os = "macos")))
+    }
+}
+
+impl OutputClaims {
+    /// Claims that fold case on Windows and macOS, whose default file
+    /// systems ignore it.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_case_folding(fold_case: bool) -> Self {
+        Self { claimed: HashMap::new(), fold_case }
+    }
+
+    /// Claims for outputs written under `output_dir`: folding case on
+    /// Windows and macOS, and elsewhere when the volume turns out to ignore
+    /// case (a mounted NTFS or FAT drive, a casefolded ext4 folder).
+    pub fn for_output_dir(output_dir: &Path) -> Self {
+        let claims = Self::new();
+        if claims.fold_case {
+            return claims;
+        }
+        Self::with_case_folding(is_case_insensitive(output_dir).unwrap_or(false))
+    }
+
+    /// Reserves `output` for `input`, or returns the input that already has it.
+    pub fn claim(&mut self, output: &Path, input: &Path) -> Result<(), PathBuf> {
+        let key = output_key(output, self.fold_case);
+        match self.claimed.get(&key) {
+            Some(owner) => Err(owner.clone()),
+            None => {
+                self.claimed.insert(key, input.to_path_buf());
+                Ok(())
+            }
+        }
+    }
+}
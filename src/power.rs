@@ -0,0 +1,159 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// How often the power source is probed. Plugging in a charger doesn't need
+/// to be noticed instantly, and the probe touches the filesystem or spawns.
+const PROBE_INTERVAL: Duration = Duration::from_secs(20);
+
+/// How batches react to running on battery. Ignored on machines without one.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerSettings {
+    pub pause_on_battery: bool,
+    pub defer_scheduled_on_battery: bool,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        Self {
+            pause_on_battery: false,
+            defer_scheduled_on_battery: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PowerState {
+    Ac,
+    Battery,
+    /// No battery found (a desktop) or the platform can't tell.
+    Unknown,
+}
+
+impl PowerState {
+    fn to_u8(self) -> u8 {
+        match self {
+            PowerState::Unknown => 0,
+            PowerState::Ac => 1,
+            PowerState::Battery => 2,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => PowerState::Ac,
+            2 => PowerState::Battery,
+            _ => PowerState::Unknown,
+        }
+    }
+}
+
+/// Polls the power source on a background thread and asks the UI to repaint
+/// whenever it changes.
+pub struct PowerMonitor {
+    state: Arc<AtomicU8>,
+}
+
+impl PowerMonitor {
+    pub fn start(ctx: egui::Context) -> Self {
+        let state = Arc::new(AtomicU8::new(probe_power_state().to_u8()));
+        let shared = Arc::clone(&state);
+
+        thread::spawn(move || loop {
+            thread::sleep(PROBE_INTERVAL);
+            let current = probe_power_state().to_u8();
+            if shared.swap(current, Ordering::Relaxed) != current {
+                ctx.request_repaint();
+            }
+        });
+
+        Self { state }
+    }
+
+    pub fn state(&self) -> PowerState {
+        PowerState::from_u8(self.state.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn probe_power_state() -> PowerState {
+    let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else {
+        return PowerState::Unknown;
+    };
+
+    let read = |dir: &std::path::Path, name: &str| {
+        std::fs::read_to_string(dir.join(name)).map(|s| s.trim().to_string()).unwrap_or_default()
+    };
+
+    let mut has_battery = false;
+    let mut discharging = false;
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        match read(&dir, "type").as_str() {
+            "Mains" | "USB" if read(&dir, "online") == "1" => return PowerState::Ac,
+            // Peripheral batteries (mice, headsets) report scope "Device"
+            "Battery" if read(&dir, "scope") != "Device" => {
+                has_battery = true;
+                discharging |= read(&dir, "status") == "Discharging";
+            }
+            _ => {}
+        }
+    }
+
+    match (has_battery, discharging) {
+        (false, _) => PowerState::Unknown,
+        (true, true) => PowerState::Battery,
+        (true, false) => PowerState::Ac,
+    }
+}
+
+#[cfg(windows)]
+pub fn probe_power_state() -> PowerState {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    const BATTERY_FLAG_NO_BATTERY: u8 = 128;
+
+    // SAFETY: GetSystemPowerStatus only writes into the struct we pass.
+    let status = unsafe {
+        let mut status: SYSTEM_POWER_STATUS = std::mem::zeroed();
+        if GetSystemPowerStatus(&mut status) == 0 {
+            return PowerState::Unknown;
+        }
+        status
+    };
+
+    if status.BatteryFlag & BATTERY_FLAG_NO_BATTERY != 0 {
+        return PowerState::Unknown;
+    }
+
+    match status.ACLineStatus {
+        0 => PowerState::Battery,
+        1 => PowerState::Ac,
+        _ => PowerState::Unknown,
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn probe_power_state() -> PowerState {
+    let Ok(output) = std::process::Command::new("pmset").args(["-g", "batt"]).output() else {
+        return PowerState::Unknown;
+    };
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    if !text.contains("InternalBattery") {
+        PowerState::Unknown
+    } else if text.contains("'Battery Power'") {
+        PowerState::Battery
+    } else {
+        PowerState::Ac
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+pub fn probe_power_state() -> PowerState {
+    PowerState::Unknown
+}
@@ -0,0 +1,244 @@
+use std::io::Read;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+/// How often the child is checked for exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How often the child's memory is sampled.
+const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Limits enforced while a tool runs.
+#[derive(Clone, Debug, Default)]
+pub struct RunLimits {
+    pub max_memory_mb: Option<u64>,
+    /// Logical cores the child is pinned to, where the platform supports it.
+    pub cpu_cores: Option<Vec<usize>>,
+    /// Kill the child if it runs longer than this.
+    pub timeout: Option<Duration>,
+    /// Kill the child once this is set.
+    pub cancel_flag: Option<Arc<AtomicBool>>,
+}
+
+/// Whether `RunLimits::cpu_cores` is honoured on this platform.
+pub fn affinity_supported() -> bool {
+    cfg!(any(target_os = "linux", windows))
+}
+
+/// The first `limit` logical cores, at least one and at most all of them.
+/// Batches run one tool at a time, so that tool gets the whole set.
+pub fn limit_cores(limit: usize) -> Vec<usize> {
+    let available = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    (0..limit.clamp(1, available)).collect()
+}
+
+#[derive(Debug)]
+pub enum RunError {
+    Spawn(std::io::Error),
+    Wait(std::io::Error),
+    MemoryExceeded { limit_mb: u64 },
+    TimedOut(Duration),
+    Cancelled,
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunError::Spawn(e) => write!(f, "failed to start: {}", e),
+            RunError::Wait(e) => write!(f, "failed to wait for process: {}", e),
+            RunError::MemoryExceeded { limit_mb } => write!(f, "killed: exceeded {} MB", limit_mb),
+            RunError::TimedOut(limit) => write!(f, "killed: still running after {} s", limit.as_secs()),
+            RunError::Cancelled => write!(f, "killed: cancelled"),
+        }
+    }
+}
+
+/// Runs `cmd` to completion like `Command::output`, polling the child from
+/// this thread so limits can be enforced without a watchdog thread per file.
+pub fn run_tool(cmd: &mut Command, limits: &RunLimits) -> Result<Output, RunError> {
+    run_tool_with(cmd, limits, |_| {})
+}
+
+/// [`run_tool`], calling `on_stderr_line` from this thread with each line
+/// the tool writes to stderr as it arrives. Lines may end in `\r` as well
+/// as `\n`, since progress output often redraws a single line.
+pub fn run_tool_with(
+    cmd: &mut Command,
+    limits: &RunLimits,
+    mut on_stderr_line: impl FnMut(&str),
+) -> Result<Output, RunError> {
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    #[cfg(target_os = "linux")]
+    if let Some(cores) = &limits.cpu_cores {
+        pin_command(cmd, cores.clone());
+    }
+
+    // Console tools would otherwise flash up a window each when run from the GUI
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(windows_sys::Win32::System::Threading::CREATE_NO_WINDOW);
+    }
+
+    let mut child = cmd.spawn().map_err(RunError::Spawn)?;
+
+    #[cfg(windows)]
+    if let Some(cores) = &limits.cpu_cores {
+        pin_child(&child, cores);
+    }
+
+    // Drain the pipes so a chatty tool can't block on a full buffer
+    let stdout_reader = spawn_reader(child.stdout.take(), None);
+    let (line_tx, lines) = channel();
+    let stderr_reader = spawn_reader(child.stderr.take(), Some(line_tx));
+    let mut forward_lines = |lines: &Receiver<String>| {
+        while let Ok(line) = lines.try_recv() {
+            on_stderr_line(&line);
+        }
+    };
+
+    let mut system = limits.max_memory_mb.map(|_| System::new());
+    let started = Instant::now();
+    let mut last_sample = started;
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => {}
+            Err(e) => {
+                kill(&mut child);
+                return Err(RunError::Wait(e));
+            }
+        }
+
+        if let (Some(limit_mb), Some(system)) = (limits.max_memory_mb, system.as_mut()) {
+            if last_sample.elapsed() >= MEMORY_SAMPLE_INTERVAL {
+                last_sample = Instant::now();
+                if resident_memory_mb(system, child.id()) > limit_mb {
+                    kill(&mut child);
+                    return Err(RunError::MemoryExceeded { limit_mb });
+                }
+            }
+        }
+
+        if let Some(limit) = limits.timeout.filter(|&limit| started.elapsed() >= limit) {
+            kill(&mut child);
+            return Err(RunError::TimedOut(limit));
+        }
+        if limits.cancel_flag.as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed)) {
+            kill(&mut child);
+            return Err(RunError::Cancelled);
+        }
+
+        forward_lines(&lines);
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    let stdout = stdout_reader.join().unwrap_or_default();
+    let stderr = stderr_reader.join().unwrap_or_default();
+    forward_lines(&lines);
+    Ok(Output { status, stdout, stderr })
+}
+
+/// Reads `pipe` to the end on its own thread, sending each complete line to
+/// `lines` along the way if given.
+fn spawn_reader<R: Read + Send + 'static>(pipe: Option<R>, lines: Option<Sender<String>>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let Some(mut pipe) = pipe else {
+            return buf;
+        };
+        let mut chunk = [0u8; 4096];
+        let mut line_start = 0;
+        loop {
+            match pipe.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+            let Some(lines) = &lines else {
+                continue;
+            };
+            while let Some(end) = buf[line_start..].iter().position(|&b| b == b'\n' || b == b'\r') {
+                let line = String::from_utf8_lossy(&buf[line_start..line_start + end]);
+                if !line.trim().is_empty() {
+                    let _ = lines.send(line.into_owned());
+                }
+                line_start += end + 1;
+            }
+        }
+        if let Some(lines) = &lines {
+            let rest = String::from_utf8_lossy(&buf[line_start..]);
+            if !rest.trim().is_empty() {
+                let _ = lines.send(rest.into_owned());
+            }
+        }
+        buf
+    })
+}
+
+fn resident_memory_mb(system: &mut System, pid: u32) -> u64 {
+    let pid = Pid::from_u32(pid);
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing().with_memory(),
+    );
+    system
+        .process(pid)
+        .map(|p| p.memory() / (1024 * 1024))
+        .unwrap_or(0)
+}
+
+/// Applies the affinity in the forked child before exec, so the tool never
+/// runs outside its cores.
+#[cfg(target_os = "linux")]
+fn pin_command(cmd: &mut Command, cores: Vec<usize>) {
+    use std::os::unix::process::CommandExt;
+
+    // SAFETY: the closure only calls async-signal-safe libc functions on
+    // memory allocated before the fork.
+    unsafe {
+        cmd.pre_exec(move || {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            libc::CPU_ZERO(&mut set);
+            for &core in &cores {
+                libc::CPU_SET(core, &mut set);
+            }
+            if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Windows can only change the affinity once the process exists, so it is
+/// applied right after spawning.
+#[cfg(windows)]
+fn pin_child(child: &Child, cores: &[usize]) {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::System::Threading::SetProcessAffinityMask;
+
+    let mask = cores
+        .iter()
+        .filter(|&&core| core < usize::BITS as usize)
+        .fold(0usize, |mask, &core| mask | (1 << core));
+
+    if mask != 0 {
+        // SAFETY: the handle belongs to a child we own and is still open.
+        unsafe {
+            SetProcessAffinityMask(child.as_raw_handle() as _, mask);
+        }
+    }
+}
+
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
@@ -0,0 +1,157 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Opens the system file manager with `path` selected.
+pub fn reveal_in_file_manager(path: &Path) -> Result<(), String> {
+    if !path.exists() {
+        return Err(format!("{} no longer exists", path.display()));
+    }
+
+    let path = std::fs::canonicalize(path)
+        .map_err(|e| format!("Failed to resolve path: {}", e))?;
+
+    reveal_platform(&path)
+}
+
+/// Opens `path` in the application the system associates with it.
+pub fn open_with_default_app(path: &Path) -> Result<(), String> {
+    if !path.exists() {
+        return Err(format!("{} no longer exists", path.display()));
+    }
+
+    #[cfg(windows)]
+    let mut command = {
+        // start's first quoted argument is the window title
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]).arg(path);
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = Command::new("open");
+        command.arg(path);
+        command
+    };
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let mut command = {
+        let mut command = Command::new("xdg-open");
+        command.arg(path);
+        command
+    };
+    #[cfg(not(any(windows, unix)))]
+    return Err("Opening files is not supported on this platform".to_string());
+
+    #[cfg(any(windows, unix))]
+    {
+        let status = command
+            .status()
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("Opening {} exited with {}", path.display(), status))
+        }
+    }
+}
+
+#[cfg(windows)]
+fn reveal_platform(path: &Path) -> Result<(), String> {
+    use std::ffi::OsString;
+    use std::os::windows::process::CommandExt;
+
+    // canonicalize yields a verbatim \\?\ path, which explorer doesn't understand
+    let display = path.as_os_str().to_string_lossy();
+    let plain = display.strip_prefix(r"\\?\UNC\").map(|p| format!(r"\\{}", p))
+        .or_else(|| display.strip_prefix(r"\\?\").map(str::to_string));
+    let plain = match plain {
+        Some(p) => OsString::from(p),
+        None => path.as_os_str().to_os_string(),
+    };
+
+    // explorer parses its own command line, so the path must be quoted by hand
+    let mut arg = OsString::from("/select,\"");
+    arg.push(&plain);
+    arg.push("\"");
+
+    // explorer exits with 1 even when it succeeds, so only spawn errors count
+    Command::new("explorer")
+        .raw_arg(&arg)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to launch explorer: {}", e))
+}
+
+#[cfg(target_os = "macos")]
+fn reveal_platform(path: &Path) -> Result<(), String> {
+    let status = Command::new("open")
+        .arg("-R")
+        .arg(path)
+        .status()
+        .map_err(|e| format!("Failed to launch open: {}", e))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("open -R exited with {}", status))
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn reveal_platform(path: &Path) -> Result<(), String> {
+    // Most desktop file managers implement the FileManager1 interface
+    let uri = path_to_file_uri(path);
+    let dbus = Command::new("dbus-send")
+        .args([
+            "--session",
+            "--print-reply",
+            "--dest=org.freedesktop.FileManager1",
+            "--type=method_call",
+            "/org/freedesktop/FileManager1",
+            "org.freedesktop.FileManager1.ShowItems",
+        ])
+        .arg(format!("array:string:{}", uri))
+        .arg("string:")
+        .output();
+
+    if let Ok(output) = dbus {
+        if output.status.success() {
+            return Ok(());
+        }
+    }
+
+    // Fall back to opening the containing folder without a selection
+    let parent = path.parent().unwrap_or(path);
+    let status = Command::new("xdg-open")
+        .arg(parent)
+        .status()
+        .map_err(|e| format!("Failed to launch xdg-open: {}", e))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("xdg-open exited with {}", status))
+    }
+}
+
+/// Builds a `file://` URI, percent-encoding every byte outside the unreserved
+/// set. dbus-send splits array arguments on commas, so those must be escaped too.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn path_to_file_uri(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+#[cfg(not(any(windows, unix)))]
+fn reveal_platform(_path: &Path) -> Result<(), String> {
+    Err("Revealing files is not supported on this platform".to_string())
+}
@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+
+use crate::types::OverwritePolicy;
+
+/// Why transactional batches can't overwrite: the old file would be gone
+/// for good once the new one is rolled back.
+pub const TRANSACTIONAL_OVERWRITE: &str =
+    "Transactional mode can't restore files it overwrites; set existing outputs to Skip or Rename";
+
+/// Checks that a transactional batch won't overwrite anything.
+pub fn check_transactional(transactional: bool, overwrite: OverwritePolicy) -> Result<(), String> {
+    if transactional && overwrite == OverwritePolicy::Overwrite {
+        Err(TRANSACTIONAL_OVERWRITE.to_string())
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+enum Created {
+    File(PathBuf),
+    Dir(PathBuf),
+}
+
+/// The files and folders a batch created, in order, so a transactional
+/// batch that is cancelled can leave the output folder as it found it.
+/// Records nothing when disabled.
+#[derive(Debug, Default)]
+pub struct Rollback {
+    enabled: bool,
+    created: Vec<Created>,
+}
+
+impl Rollback {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, created: Vec::new() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Creates `dir` and any missing parents, recording the ones that
+    /// didn't exist yet.
+    pub fn create_dir_all(&mut self, dir: &Path) -> std::io::Result<()> {
+        let mut missing: Vec<&Path> = dir.ancestors().take_while(|d| !d.as_os_str().is_empty() && !d.exists()).collect();
+        std::fs::create_dir_all(dir)?;
+        if self.enabled {
+            missing.reverse();
+            self.created.extend(missing.into_iter().map(|d| Created::Dir(d.to_path_buf())));
+        }
+        Ok(())
+    }
+
+    /// Records an output the batch wrote.
+    pub fn record_file(&mut self, path: &Path) {
+        if self.enabled {
+            self.created.push(Created::File(path.to_path_buf()));
+        }
+    }
+
+    /// Deletes everything recorded, newest first. Folders are only removed
+    /// once empty, so anything else put in them survives. Returns how many
+    /// files were deleted.
+    pub fn roll_back(self) -> usize {
+        let mut removed = 0;
+        for created in self.created.into_iter().rev() {
+            match created {
+                Created::File(path) => {
+                    if std::fs::remove_file(&path).is_ok() {
+                        removed += 1;
+                    }
+                }
+                Created::Dir(path) => {
+                    // Fails, as intended, when something else is inside
+                    let _ = std::fs::remove_dir(&path);
+                }
+            }
+        }
+        removed
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use walkdir::WalkDir;
+
+use crate::paths::path_key;
+
+/// A folder containing this file is skipped entirely (the Android convention
+/// for cache and thumbnail folders).
+pub const NOMEDIA_FILE_NAME: &str = ".nomedia";
+
+/// Glob patterns, one per line, for files and folders to skip under the
+/// folder containing it. Patterns without a `/` match names at any depth;
+/// patterns with one match the path relative to that folder. Blank lines and
+/// lines starting with `#` are ignored.
+pub const IGNORE_FILE_NAME: &str = ".jxlignore";
+
+/// Extensions browsers, download managers and copy tools give files they
+/// haven't finished writing.
+pub const TEMPORARY_EXTENSIONS: &[&str] = &["part", "partial", "crdownload", "download", "opdownload", "tmp"];
+
+/// How [`scan_paths`] walks input folders.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScanOptions {
+    /// Descend into subfolders of input folders.
+    pub recursive: bool,
+    /// Honour `.nomedia` and `.jxlignore` files.
+    pub respect_ignore_files: bool,
+    /// Leave this folder out when it turns up inside an input folder, so a
+    /// batch never picks up its own (or a previous run's) outputs.
+    pub exclude_dir: Option<PathBuf>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            recursive: true,
+            respect_ignore_files: true,
+            exclude_dir: None,
+        }
+    }
+}
+
+/// The files found by [`scan_paths`] and what was left out.
+#[derive(Debug, Default)]
+pub struct Scan {
+    pub files: Vec<PathBuf>,
+    /// Folders skipped because of `.nomedia` or a `.jxlignore` pattern.
+    pub pruned_dirs: usize,
+    /// Files skipped because of a `.jxlignore` pattern.
+    pub ignored_files: usize,
+    /// Times the excluded (output) folder was found inside an input folder.
+    pub excluded_dirs: usize,
+    /// Files skipped for a [`TEMPORARY_EXTENSIONS`] extension.
+    pub temporary_files: usize,
+}
+
+impl Scan {
+    /// A one-line note about what the scan left out, if anything.
+    pub fn summary(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if self.pruned_dirs > 0 || self.ignored_files > 0 {
+            parts.push(format!(
+                "{} folder(s) and {} file(s) marked by {} or {}",
+                self.pruned_dirs, self.ignored_files, NOMEDIA_FILE_NAME, IGNORE_FILE_NAME
+            ));
+        }
+        if self.excluded_dirs > 0 {
+            parts.push("the output folder inside the inputs".to_string());
+        }
+        if self.temporary_files > 0 {
+            parts.push(format!("{} unfinished download(s) or temporary file(s)", self.temporary_files));
+        }
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(format!("Skipped {}", parts.join("; ")))
+        }
+    }
+}
+
+/// Replaces folders in `paths` with the files inside them. Files reached
+/// twice, including under differently normalized names, are listed once.
+/// Explicitly listed files are always kept; ignore files only affect what is
+/// found inside folders, including a `.nomedia` in a listed folder itself.
+///
+/// The excluded folder only matters below a listed folder: listing it (or a
+/// folder inside it) directly still scans it.
+pub fn scan_paths(paths: &[PathBuf], options: &ScanOptions) -> Scan {
+    let mut scan = Scan::default();
+    // Missing output folders can't contain anything yet
+    let exclude = options.exclude_dir.as_ref().and_then(|dir| dir.canonicalize().ok());
+
+    for path in paths {
+        if path.is_file() {
+            scan.files.push(path.clone());
+        } else if path.is_dir() {
+            scan_dir(path, options, exclude.as_deref(), &mut scan);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    scan.files.retain(|path| seen.insert(path_key(path)));
+    scan
+}
+
+fn scan_dir(root: &Path, options: &ScanOptions, exclude: Option<&Path>, scan: &mut Scan) {
+    // Links aren't followed, so an entry's canonical path is the root's plus
+    // the entry's path below it
+    let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let mut walker = WalkDir::new(root).follow_links(false);
+    if !options.recursive {
+        walker = walker.max_depth(1);
+    }
+
+    // Ignore files of the folders enclosing the current entry, outermost first
+    let mut rules: Vec<IgnoreRules> = Vec::new();
+    let mut entries = walker.into_iter();
+
+    while let Some(entry) = entries.next() {
+        let Ok(entry) = entry else { continue };
+        let path = entry.path();
+
+        if entry.depth() > 0 && entry.file_type().is_dir() {
+            let is_excluded = exclude.is_some_and(|exclude| {
+                path.strip_prefix(root).is_ok_and(|rel| canonical_root.join(rel) == exclude)
+            });
+            if is_excluded {
+                scan.excluded_dirs += 1;
+                entries.skip_current_dir();
+                continue;
+            }
+        }
+
+        if options.respect_ignore_files {
+            rules.retain(|r| path.starts_with(&r.dir) && path != r.dir);
+
+            if entry.depth() > 0 && rules.iter().any(|r| r.matches(path)) {
+                if entry.file_type().is_dir() {
+                    scan.pruned_dirs += 1;
+                    entries.skip_current_dir();
+                } else {
+                    scan.ignored_files += 1;
+                }
+                continue;
+            }
+
+            if entry.file_type().is_dir() {
+                if path.join(NOMEDIA_FILE_NAME).is_file() {
+                    scan.pruned_dirs += 1;
+                    entries.skip_current_dir();
+                    continue;
+                }
+                if let Some(dir_rules) = IgnoreRules::load(path) {
+                    rules.push(dir_rules);
+                }
+            }
+        }
+
+        if entry.file_type().is_file() {
+            if is_temporary_file(path) {
+                scan.temporary_files += 1;
+            } else {
+                scan.files.push(path.to_path_buf());
+            }
+        }
+    }
+}
+
+/// Whether `path` has one of the [`TEMPORARY_EXTENSIONS`].
+pub fn is_temporary_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| TEMPORARY_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// The files among `paths` that are still being written: modified within
+/// the last `wait`, and with a different size or modification time after
+/// waiting that long. Only waits when some file was modified that recently,
+/// so batches of settled files don't pay for the check.
+pub fn still_being_written(paths: &[PathBuf], wait: Duration) -> HashSet<PathBuf> {
+    let stat = |path: &Path| std::fs::metadata(path).ok().map(|m| (m.len(), m.modified().ok()));
+    let now = SystemTime::now();
+    let recent: Vec<(&PathBuf, (u64, Option<SystemTime>))> = paths
+        .iter()
+        .filter_map(|path| Some((path, stat(path)?)))
+        .filter(|(_, (_, modified))| {
+            // A modification time in the future is as good as recent
+            modified.is_some_and(|modified| now.duration_since(modified).map_or(true, |age| age < wait))
+        })
+        .collect();
+    if recent.is_empty() {
+        return HashSet::new();
+    }
+
+    std::thread::sleep(wait);
+    recent
+        .into_iter()
+        .filter(|(path, before)| stat(path).as_ref() != Some(before))
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+/// The patterns from one `.jxlignore` file.
+struct IgnoreRules {
+    dir: PathBuf,
+    names: GlobSet,
+    paths: GlobSet,
+}
+
+impl IgnoreRules {
+    /// Reads `dir/.jxlignore`. Invalid patterns are skipped; a missing or
+    /// empty file gives `None`.
+    fn load(dir: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(dir.join(IGNORE_FILE_NAME)).ok()?;
+        Self::parse(dir, &content)
+    }
+
+    fn parse(dir: &Path, content: &str) -> Option<Self> {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        let mut any = false;
+
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let pattern = line.trim_end_matches('/');
+            let (builder, pattern) = match pattern.strip_prefix('/') {
+                Some(anchored) => (&mut paths, anchored),
+                None if pattern.contains('/') => (&mut paths, pattern),
+                None => (&mut names, pattern),
+            };
+            if let Ok(glob) = Glob::new(pattern) {
+                builder.add(glob);
+                any = true;
+            }
+        }
+
+        if !any {
+            return None;
+        }
+        Some(Self {
+            dir: dir.to_path_buf(),
+            names: names.build().ok()?,
+            paths: paths.build().ok()?,
+        })
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.dir) else {
+            return false;
+        };
+        path.file_name().is_some_and(|name| self.names.is_match(name)) || self.paths.is_match(relative)
+    }
+}
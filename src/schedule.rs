@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Days, Local, NaiveTime, TimeZone};
+
+/// Longest the timer thread sleeps before re-checking, so cancelling or a
+/// system clock change is noticed promptly.
+const TIMER_TICK: Duration = Duration::from_secs(1);
+
+/// Turns the picker's time of day into a concrete start time, `days_ahead`
+/// days from today. Refuses times that have already passed.
+pub fn resolve_start_time(hour: u32, minute: u32, days_ahead: u64, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    let time = NaiveTime::from_hms_opt(hour, minute, 0)
+        .ok_or_else(|| format!("{:02}:{:02} is not a valid time", hour, minute))?;
+    let date = now
+        .date_naive()
+        .checked_add_days(Days::new(days_ahead))
+        .ok_or_else(|| "Scheduled date is out of range".to_string())?;
+
+    let at = Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .ok_or_else(|| format!("{} {:02}:{:02} does not exist in the local time zone", date, hour, minute))?;
+
+    if at <= now {
+        return Err(format!(
+            "{} has already passed; pick a later time or start tomorrow",
+            at.format("%Y-%m-%d %H:%M")
+        ));
+    }
+
+    Ok(at)
+}
+
+/// Formats the time left until a scheduled start, e.g. "2h 14m" or "42s".
+pub fn format_countdown(remaining: Duration) -> String {
+    let secs = remaining.as_secs();
+    let (hours, minutes) = (secs / 3600, secs / 60 % 60);
+
+    if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Wakes the UI once `at` is reached, even while the window is minimized and
+/// nothing else asks for a repaint. Setting `cancel` stops the timer.
+pub fn arm_timer(at: DateTime<Local>, ctx: egui::Context, cancel: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        while !cancel.load(Ordering::Relaxed) {
+            let Ok(remaining) = (at - Local::now()).to_std() else {
+                ctx.request_repaint();
+                return;
+            };
+            thread::sleep(remaining.min(TIMER_TICK));
+        }
+    });
+}
@@ -0,0 +1,203 @@
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+
+use crate::download::url_of;
+use crate::engine::{is_supported_image, ConversionEngine};
+use crate::paths::{apply_overwrite_policy, plan_output_path, OutputClaims, OutputPlan};
+use crate::sidecar::SidecarResolver;
+use crate::types::ConversionSettings;
+
+/// The shell an exported script is written for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shell {
+    /// POSIX `sh`.
+    Sh,
+    /// Windows `cmd.exe` batch file.
+    Cmd,
+    PowerShell,
+}
+
+impl Shell {
+    /// Picks the shell from a script's extension: `.sh`, `.bat`/`.cmd` or `.ps1`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        match ext.as_str() {
+            "sh" => Some(Shell::Sh),
+            "bat" | "cmd" => Some(Shell::Cmd),
+            "ps1" => Some(Shell::PowerShell),
+            _ => None,
+        }
+    }
+
+    /// The shell commands are usually pasted into on this platform.
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            Shell::Cmd
+        } else {
+            Shell::Sh
+        }
+    }
+
+    /// `arg` as a single word for this shell, quoted only when needed.
+    pub fn quote(self, arg: &OsStr) -> String {
+        let arg = arg.to_string_lossy();
+        let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=+,@".contains(c) || (c == '\\' && self != Shell::Sh);
+        if !arg.is_empty() && arg.chars().all(safe) {
+            return arg.into_owned();
+        }
+
+        match self {
+            Shell::Sh => format!("'{}'", arg.replace('\'', "'\\''")),
+            // Windows paths can't contain `"`; `%` would expand as a variable
+            Shell::Cmd => format!("\"{}\"", arg.replace('%', "%%")),
+            Shell::PowerShell => format!("'{}'", arg.replace('\'', "''")),
+        }
+    }
+
+    /// A whole command line: the program followed by its arguments.
+    pub fn command_line<S: AsRef<OsStr>>(self, program: &OsStr, args: &[S]) -> String {
+        let mut line = match self {
+            // A quoted program name is a string to PowerShell unless invoked with &
+            Shell::PowerShell => format!("& {}", self.quote(program)),
+            _ => self.quote(program),
+        };
+        for arg in args {
+            line.push(' ');
+            line.push_str(&self.quote(arg.as_ref()));
+        }
+        line
+    }
+
+    fn header(self) -> &'static str {
+        match self {
+            Shell::Sh => "#!/bin/sh\nset -e\n",
+            Shell::Cmd => "@echo off\r\nsetlocal\r\n",
+            Shell::PowerShell => "$ErrorActionPreference = 'Stop'\n",
+        }
+    }
+
+    fn newline(self) -> &'static str {
+        match self {
+            Shell::Cmd => "\r\n",
+            _ => "\n",
+        }
+    }
+
+    fn comment(self, text: &str) -> String {
+        match self {
+            Shell::Cmd => format!("rem {}", text),
+            _ => format!("# {}", text),
+        }
+    }
+
+    fn set_env(self, key: &str, value: &str) -> String {
+        match self {
+            Shell::Sh => format!("export {}={}", key, self.quote(OsStr::new(value))),
+            Shell::Cmd => format!("set \"{}={}\"", key, value.replace('%', "%%")),
+            Shell::PowerShell => format!("$env:{} = {}", key, Shell::PowerShell.quote(OsStr::new(value))),
+        }
+    }
+
+    fn mkdir(self, dir: &Path) -> String {
+        let quoted = self.quote(dir.as_os_str());
+        match self {
+            Shell::Sh => format!("mkdir -p {}", quoted),
+            Shell::Cmd => format!("if not exist \"{0}\\\" mkdir \"{0}\"", dir.to_string_lossy().replace('%', "%%")),
+            Shell::PowerShell => format!("New-Item -ItemType Directory -Force -Path {} | Out-Null", quoted),
+        }
+    }
+
+    /// Stops the script when the previous command failed. `sh` has `set -e`.
+    fn check_status(self) -> Option<&'static str> {
+        match self {
+            Shell::Sh => None,
+            Shell::Cmd => Some("if errorlevel 1 exit /b 1"),
+            Shell::PowerShell => Some("if ($LASTEXITCODE -ne 0) { exit $LASTEXITCODE }"),
+        }
+    }
+}
+
+/// Writes the commands an encode batch over `input_paths` would run as a
+/// script for `shell`, one file at a time so large batches don't build up in
+/// memory. Inputs are expanded and outputs planned exactly as
+/// [`ConversionEngine::convert_batch`] does, including per-folder sidecars.
+/// Returns the number of files in the script.
+pub fn write_encode_script<W: Write>(
+    engine: &ConversionEngine,
+    input_paths: &[PathBuf],
+    settings: &ConversionSettings,
+    shell: Shell,
+    mut out: W,
+) -> io::Result<usize> {
+    let nl = shell.newline();
+    let program = engine.cjxl_path().map_or(OsStr::new("cjxl"), Path::as_os_str);
+
+    write!(out, "{}", shell.header())?;
+    if let Some(version) = engine.cjxl_version() {
+        write!(out, "{}{}", shell.comment(&format!("cjxl v{}", version)), nl)?;
+    }
+    for (key, value) in &engine.tool_settings().env_overrides {
+        write!(out, "{}{}", shell.set_env(key, value), nl)?;
+    }
+    if settings.timing().is_set() {
+        let note = "Frame delay and loop overrides aren't applied here; animations keep their own timing";
+        write!(out, "{}{}", shell.comment(note), nl)?;
+    }
+    if settings.output_budget_bytes.is_some() {
+        let note = "The output budget isn't applied here; every file is converted";
+        write!(out, "{}{}", shell.comment(note), nl)?;
+    }
+    if settings.adaptive_quality.is_some() && !settings.lossless {
+        let note = "Adaptive quality isn't applied here; every file uses the Quality setting";
+        write!(out, "{}{}", shell.comment(note), nl)?;
+    }
+    for url in input_paths.iter().filter_map(|p| url_of(p)) {
+        let note = format!("Skipped {}: URLs are only downloaded when the batch runs in the converter", url);
+        write!(out, "{}{}", shell.comment(&note), nl)?;
+    }
+    let input_paths: Vec<PathBuf> = input_paths.iter().filter(|p| url_of(p).is_none()).cloned().collect();
+
+    let scan = engine.expand_paths(&input_paths, &settings.scan_options());
+    let plan = OutputPlan::for_encode(&input_paths, settings);
+    let mut sidecars = SidecarResolver::new(&input_paths);
+    let mut claims = OutputClaims::new();
+    let mut created = HashSet::new();
+    // Sidecar problems are reported when the batch really runs
+    let (tx, _rx) = channel();
+    let mut count = 0;
+
+    for input in scan.files.iter().filter(|p| is_supported_image(p)) {
+        let output = plan_output_path(input, &plan);
+        if let Err(owner) = claims.claim(&output, input) {
+            let note = format!("Skipped {}: same output as {}", input.display(), owner.display());
+            write!(out, "{}{}", shell.comment(&note), nl)?;
+            continue;
+        }
+        // Existing outputs are judged now; the script doesn't check again
+        let Some(output) = apply_overwrite_policy(&output, settings.overwrite) else {
+            let note = format!("Skipped {}: {} already exists", input.display(), output.display());
+            write!(out, "{}{}", shell.comment(&note), nl)?;
+            continue;
+        };
+
+        if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+            if created.insert(parent.to_path_buf()) {
+                write!(out, "{}{}", shell.mkdir(parent), nl)?;
+            }
+        }
+
+        let file_settings = sidecars.settings_for(input, settings, &tx);
+        let args = engine.cjxl_args(input, &output, &file_settings);
+        write!(out, "{}{}", shell.command_line(program, &args), nl)?;
+        if let Some(check) = shell.check_status() {
+            write!(out, "{}{}", check, nl)?;
+        }
+        count += 1;
+    }
+
+    out.flush()?;
+    Ok(count)
+}
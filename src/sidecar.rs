@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{ConversionSettings, ProgressMessage};
+
+pub const SIDECAR_FILE_NAME: &str = ".jxl-converter.toml";
+
+/// Per-directory overrides read from a `.jxl-converter.toml` file. Every
+/// field is optional; unset fields fall through to the global settings.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SidecarSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lossless: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jpeg_lossless: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effort: Option<u8>,
+}
+
+impl SidecarSettings {
+    pub fn from_settings(settings: &ConversionSettings) -> Self {
+        Self {
+            lossless: Some(settings.lossless),
+            jpeg_lossless: Some(settings.jpeg_lossless),
+            quality: Some(settings.quality),
+            effort: Some(settings.effort),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let sidecar: SidecarSettings = toml::from_str(&content)
+            .map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+        sidecar
+            .validate()
+            .map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+        Ok(sidecar)
+    }
+
+    /// Writes the sidecar into `dir`, returning the file path.
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, String> {
+        let path = dir.join(SIDECAR_FILE_NAME);
+        let content = toml::to_string(self)
+            .map_err(|e| format!("Failed to serialize sidecar: {}", e))?;
+        std::fs::write(&path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(quality) = self.quality {
+            if !(1..=100).contains(&quality) {
+                return Err(format!("quality must be between 1 and 100, got {}", quality));
+            }
+        }
+        if let Some(effort) = self.effort {
+            if !(1..=10).contains(&effort) {
+                return Err(format!("effort must be between 1 and 10, got {}", effort));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, settings: &mut ConversionSettings) {
+        if let Some(lossless) = self.lossless {
+            settings.lossless = lossless;
+        }
+        if let Some(jpeg_lossless) = self.jpeg_lossless {
+            settings.jpeg_lossless = jpeg_lossless;
+        }
+        if let Some(quality) = self.quality {
+            settings.quality = quality;
+        }
+        if let Some(effort) = self.effort {
+            settings.effort = effort;
+        }
+    }
+}
+
+/// Looks up sidecars for files during a batch. Each directory is read at most
+/// once, and the override is announced the first time it is used.
+pub struct SidecarResolver {
+    roots: Vec<PathBuf>,
+    cache: HashMap<PathBuf, Option<SidecarSettings>>,
+}
+
+impl SidecarResolver {
+    /// `input_paths` are the paths the user added; sidecars are only looked for
+    /// at or below them (or next to a loose file).
+    pub fn new(input_paths: &[PathBuf]) -> Self {
+        let roots = input_paths
+            .iter()
+            .filter_map(|p| {
+                if p.is_dir() {
+                    Some(p.clone())
+                } else {
+                    p.parent().map(Path::to_path_buf)
+                }
+            })
+            .collect();
+
+        Self { roots, cache: HashMap::new() }
+    }
+
+    /// Returns the settings to use for `file`: the global settings with every
+    /// sidecar between its input root and its own directory applied, deeper
+    /// directories taking precedence.
+    pub fn settings_for(
+        &mut self,
+        file: &Path,
+        global: &ConversionSettings,
+        progress_tx: &Sender<ProgressMessage>,
+    ) -> ConversionSettings {
+        let mut settings = global.clone();
+
+        let Some(parent) = file.parent() else {
+            return settings;
+        };
+
+        // Use the most specific root so nested inputs don't re-read outer sidecars
+        let Some(root) = self
+            .roots
+            .iter()
+            .filter(|r| parent.starts_with(r))
+            .max_by_key(|r| r.components().count())
+            .cloned()
+        else {
+            return settings;
+        };
+
+        let mut dirs: Vec<&Path> = parent
+            .ancestors()
+            .take_while(|d| d.starts_with(&root))
+            .collect();
+        dirs.reverse();
+
+        for dir in dirs {
+            if let Some(sidecar) = self.load_cached(dir, progress_tx) {
+                sidecar.apply(&mut settings);
+            }
+        }
+
+        settings
+    }
+
+    fn load_cached(&mut self, dir: &Path, progress_tx: &Sender<ProgressMessage>) -> Option<&SidecarSettings> {
+        if !self.cache.contains_key(dir) {
+            let path = dir.join(SIDECAR_FILE_NAME);
+            let loaded = if path.is_file() {
+                match SidecarSettings::load(&path) {
+                    Ok(sidecar) => {
+                        let _ = progress_tx.send(ProgressMessage::Info {
+                            message: format!(
+                                "Using settings from {} for files under {}",
+                                path.display(),
+                                dir.display()
+                            ),
+                        });
+                        Some(sidecar)
+                    }
+                    Err(e) => {
+                        let _ = progress_tx.send(ProgressMessage::Warning {
+                            message: format!("{} (ignored, using global settings)", e),
+                        });
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            self.cache.insert(dir.to_path_buf(), loaded);
+        }
+
+        self.cache.get(dir).and_then(Option::as_ref)
+    }
+}
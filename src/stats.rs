@@ -0,0 +1,226 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use jxl_converter::breakdown::format_bytes;
+use jxl_converter::summary::{format_utc, BatchStatus, BatchSummary};
+
+const STATS_DIR_NAME: &str = "jxl-converter-gui";
+const STATS_FILE_NAME: &str = "stats.toml";
+
+/// Version written by this build. Bump it whenever a stored counter is
+/// renamed or changes meaning.
+pub const STATS_VERSION: u32 = 1;
+
+/// Totals for one kind of batch.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Counters {
+    pub batches: u64,
+    /// Outputs written.
+    pub files: u64,
+    pub failed_files: u64,
+    /// Input and output bytes over the files written.
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Time spent converting, in seconds.
+    pub seconds: f64,
+}
+
+impl Counters {
+    fn add(&mut self, summary: &BatchSummary) {
+        self.batches += 1;
+        self.files += summary.converted as u64;
+        self.failed_files += summary.failed as u64;
+        self.bytes_in += summary.bytes_in;
+        self.bytes_out += summary.bytes_out;
+        self.seconds += summary.duration_secs.unwrap_or_default();
+    }
+
+    /// E.g. "184,202 files, 412.0 GB → 261.0 GB, saved 151.0 GB".
+    pub fn describe(&self) -> String {
+        let change = if self.bytes_out <= self.bytes_in {
+            format!("saved {}", format_bytes(self.bytes_in - self.bytes_out))
+        } else {
+            format!("grew by {}", format_bytes(self.bytes_out - self.bytes_in))
+        };
+        format!(
+            "{} files, {} → {}, {}",
+            group_thousands(self.files),
+            format_bytes(self.bytes_in),
+            format_bytes(self.bytes_out),
+            change
+        )
+    }
+}
+
+/// Whether a batch encoded or decoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BatchKind {
+    Encode,
+    Decode,
+}
+
+/// Counters kept across runs, in their own file next to the config so a
+/// reset leaves the settings alone.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub version: u32,
+    /// When counting began, as RFC 3339 UTC.
+    pub since: Option<String>,
+    pub encode: Counters,
+    pub decode: Counters,
+    /// Batches of either kind that failed: they never got going, or every
+    /// file they reached failed. Kept apart so they don't skew the rest.
+    pub failed: Counters,
+    /// Keys this build doesn't know, written back unchanged.
+    #[serde(flatten)]
+    pub unknown: toml::Table,
+}
+
+impl Default for LifetimeStats {
+    fn default() -> Self {
+        Self {
+            version: STATS_VERSION,
+            since: None,
+            encode: Counters::default(),
+            decode: Counters::default(),
+            failed: Counters::default(),
+            unknown: toml::Table::new(),
+        }
+    }
+}
+
+impl LifetimeStats {
+    pub fn stats_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(STATS_DIR_NAME).join(STATS_FILE_NAME))
+    }
+
+    /// Loads the counters, starting from zero when there are none yet or
+    /// the file can't be read.
+    pub fn load() -> (Self, Option<String>) {
+        let Some(path) = Self::stats_path().filter(|path| path.exists()) else {
+            return (Self::default(), None);
+        };
+        match std::fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|text| Self::from_toml_str(&text)) {
+            Ok(stats) => (stats, None),
+            Err(e) => (Self::default(), Some(format!("Invalid statistics {}: {} (starting from zero)", path.display(), e))),
+        }
+    }
+
+    pub fn from_toml_str(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| e.to_string())
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, String> {
+        let mut stats = self.clone();
+        // A newer file keeps its version, so its own reader still trusts it
+        stats.version = stats.version.max(STATS_VERSION);
+        let value = toml::Value::try_from(&stats).map_err(|e| format!("Failed to serialize statistics: {}", e))?;
+        toml::to_string(&value).map_err(|e| format!("Failed to serialize statistics: {}", e))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::stats_path().ok_or("No config directory available on this system")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        std::fs::write(&path, self.to_toml_string()?).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Adds a finished batch to the counters.
+    pub fn record(&mut self, kind: BatchKind, summary: &BatchSummary) {
+        self.since.get_or_insert_with(|| format_utc(std::time::SystemTime::now()));
+        let failed = summary.status == BatchStatus::Aborted || (summary.converted == 0 && summary.failed > 0);
+        let counters = match kind {
+            _ if failed => &mut self.failed,
+            BatchKind::Encode => &mut self.encode,
+            BatchKind::Decode => &mut self.decode,
+        };
+        counters.add(summary);
+    }
+
+    /// Starts counting again from zero.
+    pub fn reset(&mut self) {
+        *self = Self { version: self.version, unknown: std::mem::take(&mut self.unknown), ..Self::default() };
+    }
+}
+
+/// `n` with commas between thousands, e.g. `184,202`.
+pub fn group_thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(status: BatchStatus, converted: usize, failed: usize) -> BatchSummary {
+        let mut summary = BatchSummary::default();
+        summary.status = status;
+        summary.converted = converted;
+        summary.failed = failed;
+        summary.bytes_in = converted as u64 * 300;
+        summary.bytes_out = converted as u64 * 100;
+        summary.duration_secs = Some(2.5);
+        summary
+    }
+
+    #[test]
+    fn batches_are_counted_by_kind() {
+        let mut stats = LifetimeStats::default();
+        stats.record(BatchKind::Encode, &summary(BatchStatus::Completed, 3, 1));
+        stats.record(BatchKind::Encode, &summary(BatchStatus::Cancelled, 1, 0));
+        stats.record(BatchKind::Decode, &summary(BatchStatus::Completed, 2, 0));
+        stats.record(BatchKind::Encode, &summary(BatchStatus::Completed, 0, 4));
+        stats.record(BatchKind::Decode, &summary(BatchStatus::Aborted, 0, 0));
+
+        assert_eq!((stats.encode.batches, stats.encode.files, stats.encode.failed_files), (2, 4, 1));
+        assert_eq!(stats.encode.seconds, 5.0);
+        assert_eq!((stats.decode.batches, stats.decode.files), (1, 2));
+        assert_eq!((stats.failed.batches, stats.failed.failed_files), (2, 4));
+        assert!(stats.since.is_some());
+        assert_eq!(stats.encode.describe(), "4 files, 1.2 KB → 400 bytes, saved 800 bytes");
+    }
+
+    #[test]
+    fn unknown_counters_survive_a_save_and_a_reset() {
+        let newer = r#"
+            version = 7
+            since = "2024-01-01T00:00:00Z"
+            future_total = 12
+
+            [encode]
+            files = 184202
+            frames = 9
+        "#;
+
+        let mut stats = LifetimeStats::from_toml_str(newer).unwrap();
+        assert_eq!(stats.encode.files, 184_202);
+        let saved: toml::Table = toml::from_str(&stats.to_toml_string().unwrap()).unwrap();
+        assert_eq!(saved["version"].as_integer(), Some(7));
+        assert_eq!(saved["future_total"].as_integer(), Some(12));
+
+        stats.reset();
+        assert_eq!(stats.encode, Counters::default());
+        assert_eq!(stats.since, None);
+        assert_eq!(stats.unknown["future_total"].as_integer(), Some(12));
+    }
+
+    #[test]
+    fn thousands_are_grouped() {
+        assert_eq!(group_thousands(0), "0");
+        assert_eq!(group_thousands(999), "999");
+        assert_eq!(group_thousands(184_202), "184,202");
+        assert_eq!(group_thousands(1_000_000), "1,000,000");
+    }
+}
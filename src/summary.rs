@@ -0,0 +1,264 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::types::ProgressMessage;
+use crate::verify::Verification;
+
+/// How a batch ended.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchStatus {
+    Completed,
+    Cancelled,
+    /// Stopped early on purpose, e.g. by the output budget.
+    Stopped,
+    /// Stopped before finishing, e.g. by invalid settings or a failed
+    /// pre-batch hook; also a batch still running.
+    #[default]
+    Aborted,
+}
+
+impl BatchStatus {
+    pub fn name(self) -> &'static str {
+        match self {
+            BatchStatus::Completed => "completed",
+            BatchStatus::Cancelled => "cancelled",
+            BatchStatus::Stopped => "stopped",
+            BatchStatus::Aborted => "aborted",
+        }
+    }
+}
+
+/// Totals for a batch, tallied from its messages as they arrive. This is
+/// the CLI's JSON summary line and the body of batch notifications.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, JsonSchema)]
+pub struct BatchSummary {
+    /// Outputs written; a file decoded to several formats counts once per
+    /// format, so this can exceed the number of inputs.
+    pub converted: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Reconstructed JPEGs identical to their originals.
+    pub verified: usize,
+    /// Reconstructed JPEGs that differ from their originals.
+    pub mismatched: usize,
+    /// Reconstructed JPEGs with no original to compare with.
+    pub unverified: usize,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub cancelled: bool,
+    /// Inputs a stopped batch left unconverted.
+    pub remaining: usize,
+    pub status: BatchStatus,
+    /// When the batch began, as RFC 3339 UTC.
+    pub started_at: Option<String>,
+    /// When it ended, as RFC 3339 UTC.
+    pub finished_at: Option<String>,
+    pub duration_secs: Option<f64>,
+    /// The label the batch was given, if any.
+    pub label: Option<String>,
+    /// Outcomes per input format, most files first; filled in when the
+    /// batch ends.
+    pub formats: Vec<FormatOutcomes>,
+    #[serde(skip)]
+    started: Option<SystemTime>,
+    #[serde(skip)]
+    outcomes: Vec<FileOutcome>,
+    /// Files the batch found to work on; `None` if it never started.
+    #[serde(skip)]
+    pub matched: Option<usize>,
+}
+
+impl BatchSummary {
+    /// A summary for a batch starting now.
+    pub fn begin() -> Self {
+        let now = SystemTime::now();
+        Self { started: Some(now), started_at: Some(format_utc(now)), ..Default::default() }
+    }
+
+    /// Stamps the end time, once. [`Self::record`] does this on `Completed`,
+    /// `Stopped` and `Cancelled`; call it for a batch that stopped without either.
+    pub fn end(&mut self) {
+        if self.finished_at.is_some() {
+            return;
+        }
+        let now = SystemTime::now();
+        self.finished_at = Some(format_utc(now));
+        self.duration_secs = self
+            .started
+            .map(|started| now.duration_since(started).unwrap_or_default().as_secs_f64());
+        self.formats = format_breakdown(&self.outcomes);
+    }
+
+    pub fn record(&mut self, msg: &ProgressMessage) {
+        match msg {
+            ProgressMessage::Started { total } => self.matched = Some(*total),
+            ProgressMessage::Success { input, input_bytes, output_bytes, .. } => {
+                self.converted += 1;
+                self.bytes_in += input_bytes;
+                self.bytes_out += output_bytes;
+                self.outcomes.push(FileOutcome::Succeeded {
+                    input: input.clone(),
+                    bytes_in: *input_bytes,
+                    bytes_out: *output_bytes,
+                });
+            }
+            ProgressMessage::Error { file, .. } => {
+                self.failed += 1;
+                // An error without a file is the batch's, not a format's
+                if !file.is_empty() {
+                    self.outcomes.push(FileOutcome::Failed { input: PathBuf::from(file) });
+                }
+            }
+            ProgressMessage::Skipped { file, .. } => {
+                self.skipped += 1;
+                self.outcomes.push(FileOutcome::Skipped { input: PathBuf::from(file) });
+            }
+            ProgressMessage::Verified { verification, .. } => match verification {
+                Verification::Match { .. } => self.verified += 1,
+                Verification::Mismatch { .. } => self.mismatched += 1,
+                Verification::Unverified { .. } => self.unverified += 1,
+            },
+            ProgressMessage::Completed => {
+                self.status = BatchStatus::Completed;
+                self.end();
+            }
+            ProgressMessage::Stopped { remaining, .. } => {
+                self.remaining = remaining.len();
+                self.status = BatchStatus::Stopped;
+                self.end();
+            }
+            ProgressMessage::Cancelled => {
+                self.cancelled = true;
+                self.status = BatchStatus::Cancelled;
+                self.end();
+            }
+            _ => {}
+        }
+    }
+
+    /// Input bytes minus output bytes over the converted files; negative
+    /// when the outputs came out larger.
+    pub fn bytes_saved(&self) -> i64 {
+        self.bytes_in as i64 - self.bytes_out as i64
+    }
+}
+
+/// How one file of a batch ended, as far as [`format_breakdown`] cares.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileOutcome {
+    Succeeded { input: PathBuf, bytes_in: u64, bytes_out: u64 },
+    Failed { input: PathBuf },
+    Skipped { input: PathBuf },
+}
+
+impl FileOutcome {
+    fn input(&self) -> &Path {
+        match self {
+            FileOutcome::Succeeded { input, .. } | FileOutcome::Failed { input } | FileOutcome::Skipped { input } => input,
+        }
+    }
+}
+
+/// A batch's outcomes for one input format.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, JsonSchema)]
+pub struct FormatOutcomes {
+    /// The inputs' extension, lowercased; empty for files without one.
+    pub format: String,
+    /// Files the batch reached: succeeded, failed and skipped together.
+    pub attempted: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Input and output bytes over the succeeded files.
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// The average of output over input bytes per succeeded file; `None`
+    /// if none succeeded.
+    pub average_ratio: Option<f64>,
+}
+
+/// Buckets `outcomes` by their input's extension, most files first and
+/// then by name.
+pub fn format_breakdown(outcomes: &[FileOutcome]) -> Vec<FormatOutcomes> {
+    let mut rows: Vec<FormatOutcomes> = Vec::new();
+    let mut ratio_sums: Vec<f64> = Vec::new();
+    for outcome in outcomes {
+        let format = outcome.input().extension().map_or(String::new(), |ext| ext.to_string_lossy().to_lowercase());
+        let idx = match rows.iter().position(|row| row.format == format) {
+            Some(idx) => idx,
+            None => {
+                rows.push(FormatOutcomes { format, ..Default::default() });
+                ratio_sums.push(0.0);
+                rows.len() - 1
+            }
+        };
+        let row = &mut rows[idx];
+        row.attempted += 1;
+        match outcome {
+            FileOutcome::Succeeded { bytes_in, bytes_out, .. } => {
+                row.succeeded += 1;
+                row.bytes_in += bytes_in;
+                row.bytes_out += bytes_out;
+                if *bytes_in > 0 {
+                    ratio_sums[idx] += *bytes_out as f64 / *bytes_in as f64;
+                }
+            }
+            FileOutcome::Failed { .. } => row.failed += 1,
+            FileOutcome::Skipped { .. } => row.skipped += 1,
+        }
+    }
+    for (row, sum) in rows.iter_mut().zip(ratio_sums) {
+        row.average_ratio = (row.succeeded > 0).then(|| sum / row.succeeded as f64);
+    }
+    rows.sort_by(|a, b| b.attempted.cmp(&a.attempted).then_with(|| a.format.cmp(&b.format)));
+    rows
+}
+
+/// `rows` as a table of aligned lines for a monospace font, headed by
+/// column names.
+pub fn format_table(rows: &[FormatOutcomes]) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{:<8} {:>9} {:>9} {:>7} {:>7} {:>14} {:>14} {:>7}",
+        "format", "attempted", "succeeded", "failed", "skipped", "bytes in", "bytes out", "ratio"
+    )];
+    for row in rows {
+        let format = if row.format.is_empty() { "(none)" } else { &row.format };
+        let ratio = row.average_ratio.map_or("-".to_string(), |ratio| format!("{:.3}", ratio));
+        lines.push(format!(
+            "{:<8} {:>9} {:>9} {:>7} {:>7} {:>14} {:>14} {:>7}",
+            format, row.attempted, row.succeeded, row.failed, row.skipped, row.bytes_in, row.bytes_out, ratio
+        ));
+    }
+    lines
+}
+
+/// `time` as RFC 3339 in UTC, to the second, e.g. `2024-05-01T09:30:00Z`.
+pub fn format_utc(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs_of_day) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // Howard Hinnant's civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
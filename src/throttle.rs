@@ -0,0 +1,191 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// "Be nice while I'm working": slow a batch down while the user is active.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThrottleSettings {
+    pub enabled: bool,
+    /// Pause between files while throttled.
+    pub delay_ms: u64,
+    /// Idle minutes before running at full speed again.
+    pub idle_minutes: u32,
+}
+
+impl Default for ThrottleSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            delay_ms: 2000,
+            idle_minutes: 2,
+        }
+    }
+}
+
+impl ThrottleSettings {
+    pub fn ramp_up_after(&self) -> Duration {
+        Duration::from_secs(u64::from(self.idle_minutes) * 60)
+    }
+}
+
+/// Input more recent than this counts as the user actively working.
+pub const ACTIVE_THRESHOLD: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ThrottleState {
+    Full,
+    Throttled,
+}
+
+/// Decides whether a batch should back off based on how long the user has
+/// been idle. Throttles as soon as there is recent input and only ramps back
+/// up after `idle_minutes` without any, so brief pauses don't flip it back
+/// and forth.
+#[derive(Debug)]
+pub struct Throttle {
+    state: ThrottleState,
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self { state: ThrottleState::Full }
+    }
+}
+
+impl Throttle {
+    pub fn state(&self) -> ThrottleState {
+        self.state
+    }
+
+    /// Feeds the current idle time in, returning the new state when it changed.
+    pub fn update(&mut self, idle: Duration, settings: &ThrottleSettings) -> Option<ThrottleState> {
+        let next = match self.state {
+            _ if !settings.enabled => ThrottleState::Full,
+            ThrottleState::Full if idle < ACTIVE_THRESHOLD => ThrottleState::Throttled,
+            ThrottleState::Throttled if idle >= settings.ramp_up_after() => ThrottleState::Full,
+            state => state,
+        };
+
+        if next == self.state {
+            None
+        } else {
+            self.state = next;
+            Some(next)
+        }
+    }
+
+    /// Pause inserted between files in the current state.
+    pub fn delay(&self, settings: &ThrottleSettings) -> Duration {
+        match self.state {
+            ThrottleState::Full => Duration::ZERO,
+            ThrottleState::Throttled => Duration::from_millis(settings.delay_ms),
+        }
+    }
+}
+
+/// Time since the last keyboard or mouse input anywhere on the system, where
+/// the platform exposes it.
+#[cfg(windows)]
+pub fn os_idle_time() -> Option<Duration> {
+    use windows_sys::Win32::System::SystemInformation::GetTickCount;
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+    let mut info = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+
+    // SAFETY: both calls only read global state or write into `info`.
+    unsafe {
+        if GetLastInputInfo(&mut info) == 0 {
+            return None;
+        }
+        let elapsed = GetTickCount().wrapping_sub(info.dwTime);
+        Some(Duration::from_millis(u64::from(elapsed)))
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn os_idle_time() -> Option<Duration> {
+    let output = std::process::Command::new("ioreg")
+        .args(["-c", "IOHIDSystem", "-d", "4"])
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    // "HIDIdleTime" = 123456789 (nanoseconds)
+    let line = text.lines().find(|l| l.contains("\"HIDIdleTime\""))?;
+    let nanos: u64 = line.rsplit('=').next()?.trim().parse().ok()?;
+    Some(Duration::from_nanos(nanos))
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn os_idle_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> ThrottleSettings {
+        ThrottleSettings { enabled: true, ..Default::default() }
+    }
+
+    fn throttled(settings: &ThrottleSettings) -> Throttle {
+        let mut throttle = Throttle::default();
+        throttle.update(Duration::ZERO, settings);
+        throttle
+    }
+
+    #[test]
+    fn recent_input_throttles() {
+        let settings = enabled();
+        let mut throttle = Throttle::default();
+
+        assert_eq!(throttle.update(ACTIVE_THRESHOLD, &settings), None);
+        assert_eq!(throttle.update(Duration::from_secs(3), &settings), Some(ThrottleState::Throttled));
+        assert_eq!(throttle.state(), ThrottleState::Throttled);
+    }
+
+    #[test]
+    fn short_idle_stays_throttled() {
+        let settings = enabled();
+        let mut throttle = throttled(&settings);
+
+        let almost = settings.ramp_up_after() - Duration::from_secs(1);
+        assert_eq!(throttle.update(almost, &settings), None);
+        assert_eq!(throttle.state(), ThrottleState::Throttled);
+    }
+
+    #[test]
+    fn long_idle_ramps_back_up() {
+        let settings = ThrottleSettings { idle_minutes: 5, ..enabled() };
+        let mut throttle = throttled(&settings);
+
+        assert_eq!(settings.ramp_up_after(), Duration::from_secs(300));
+        assert_eq!(throttle.update(settings.ramp_up_after(), &settings), Some(ThrottleState::Full));
+        assert_eq!(throttle.state(), ThrottleState::Full);
+    }
+
+    #[test]
+    fn disabling_runs_at_full_speed() {
+        let settings = enabled();
+        let mut throttle = throttled(&settings);
+        let disabled = ThrottleSettings { enabled: false, ..settings };
+
+        assert_eq!(throttle.update(Duration::ZERO, &disabled), Some(ThrottleState::Full));
+        assert_eq!(throttle.update(Duration::ZERO, &disabled), None);
+    }
+
+    #[test]
+    fn delay_follows_the_state() {
+        let settings = ThrottleSettings { delay_ms: 1500, ..enabled() };
+        let mut throttle = Throttle::default();
+        assert_eq!(throttle.delay(&settings), Duration::ZERO);
+
+        throttle.update(Duration::ZERO, &settings);
+        assert_eq!(throttle.delay(&settings), Duration::from_millis(1500));
+    }
+}
@@ -0,0 +1,277 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::process::{run_tool, RunLimits};
+use crate::types::ToolSettings;
+
+/// Probes run on the UI thread, so a tool that hangs is given up on after this.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A cjxl or djxl binary, the version it reports and what it supports.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToolInfo {
+    pub path: PathBuf,
+    /// E.g. `0.10.2`, or `None` if `--version` didn't say.
+    pub version: Option<String>,
+    pub capabilities: ToolCapabilities,
+}
+
+impl ToolInfo {
+    /// Runs the tool with `--version` and `--help` to fill in the version and
+    /// capabilities, with the environment overrides in `settings`. Whether it
+    /// is cjxl or djxl comes from the file name.
+    pub fn probe(path: PathBuf, settings: &ToolSettings) -> Self {
+        let is_djxl = path.file_stem().is_some_and(|stem| stem.to_string_lossy().starts_with("djxl"));
+        let help = tool_help(&path, settings);
+        let version = tool_version(&path, settings).or_else(|| parse_version(&help));
+        let capabilities = if is_djxl {
+            ToolCapabilities::from_djxl_help(&help)
+        } else {
+            ToolCapabilities::from_cjxl_help(&help, version.as_deref())
+        };
+        Self { path, version, capabilities }
+    }
+
+    /// `v0.10.2 (/usr/bin/cjxl)`, for logs and tooltips.
+    pub fn describe(&self) -> String {
+        match &self.version {
+            Some(version) => format!("v{} ({})", version, self.path.display()),
+            None => format!("unknown version ({})", self.path.display()),
+        }
+    }
+}
+
+/// The result of looking for cjxl and djxl. Discovery spawns processes, so
+/// callers keep this around and build engines from it with
+/// [`crate::ConversionEngine::from_tools`] instead of searching every time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiscoveredTools {
+    /// The cjxl batches use.
+    pub cjxl: Option<ToolInfo>,
+    /// The djxl batches use.
+    pub djxl: Option<ToolInfo>,
+    /// Every cjxl found, in search order.
+    pub cjxl_candidates: Vec<ToolInfo>,
+    /// Every djxl found, in search order.
+    pub djxl_candidates: Vec<ToolInfo>,
+    /// libjxl's ssimulacra2, which adaptive quality scores candidates with.
+    pub ssimulacra2: Option<PathBuf>,
+    /// libjxl's butteraugli_main, offered by the Compare tab.
+    pub butteraugli: Option<PathBuf>,
+}
+
+/// Options that only some libjxl versions or builds understand, so the UI can
+/// hide them and batches can refuse them up front instead of failing on
+/// every file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ToolCapabilities {
+    /// cjxl accepts `-e 10` (libjxl 0.10).
+    pub supports_effort_10: bool,
+    /// cjxl has `--photon_noise_iso`.
+    pub supports_photon_noise: bool,
+    /// cjxl has `--faster_decoding`.
+    pub supports_faster_decoding: bool,
+    /// djxl was built with OpenEXR output.
+    pub djxl_supports_exr: bool,
+    /// djxl has `--allow_partial_files`.
+    pub djxl_supports_partial_files: bool,
+}
+
+impl ToolCapabilities {
+    /// Reads the cjxl flags from its `--help -v -v -v -v` output.
+    pub fn from_cjxl_help(help: &str, version: Option<&str>) -> Self {
+        let effort_range = help
+            .lines()
+            .skip_while(|line| !line.contains("--effort"))
+            .take(4)
+            .find_map(|line| line.split_once("Range: 1 .. ").map(|(_, max)| max.trim_end_matches('.').trim().to_string()));
+        let supports_effort_10 = match effort_range {
+            Some(max) => max.parse::<u8>().is_ok_and(|max| max >= 10),
+            None => version.is_some_and(|v| version_at_least(v, (0, 10))),
+        };
+
+        Self {
+            supports_effort_10,
+            supports_photon_noise: help.contains("--photon_noise_iso"),
+            supports_faster_decoding: help.contains("--faster_decoding"),
+            ..Default::default()
+        }
+    }
+
+    /// Reads the djxl flags from its `--help -v -v -v -v` output.
+    pub fn from_djxl_help(help: &str) -> Self {
+        Self {
+            djxl_supports_exr: help.lines().any(|line| {
+                let line = line.to_ascii_lowercase();
+                line.contains("exr") && !line.contains("not supported")
+            }),
+            djxl_supports_partial_files: help.contains("--allow_partial_files"),
+            ..Default::default()
+        }
+    }
+
+    /// cjxl's capabilities with djxl's.
+    pub fn combine(cjxl: &ToolCapabilities, djxl: &ToolCapabilities) -> Self {
+        Self {
+            djxl_supports_exr: djxl.djxl_supports_exr,
+            djxl_supports_partial_files: djxl.djxl_supports_partial_files,
+            ..cjxl.clone()
+        }
+    }
+}
+
+impl DiscoveredTools {
+    /// What the selected cjxl and djxl support.
+    pub fn capabilities(&self) -> ToolCapabilities {
+        let default = ToolCapabilities::default();
+        let caps = |tool: &Option<ToolInfo>| tool.as_ref().map_or(default.clone(), |t| t.capabilities.clone());
+        ToolCapabilities::combine(&caps(&self.cjxl), &caps(&self.djxl))
+    }
+
+    /// Looks in a `tools/` folder next to the executable, then on PATH, and
+    /// picks the first of each.
+    pub fn discover() -> Self {
+        Self::discover_with(&ToolSettings::default())
+    }
+
+    /// Also looks in `settings.extra_tool_paths` and picks the tools chosen
+    /// in `settings`, falling back to the first found when a chosen one has
+    /// gone missing.
+    pub fn discover_with(settings: &ToolSettings) -> Self {
+        let mut tools = Self {
+            cjxl_candidates: find_tool_candidates("cjxl", settings),
+            djxl_candidates: find_tool_candidates("djxl", settings),
+            ssimulacra2: find_tool_paths("ssimulacra2", &settings.extra_tool_paths).into_iter().next(),
+            butteraugli: find_tool_paths("butteraugli_main", &settings.extra_tool_paths).into_iter().next(),
+            ..Default::default()
+        };
+        tools.select(settings);
+        tools
+    }
+
+    /// Re-applies the choice in `settings` without searching again.
+    pub fn select(&mut self, settings: &ToolSettings) {
+        self.cjxl = pick(&self.cjxl_candidates, settings.cjxl_path.as_deref());
+        self.djxl = pick(&self.djxl_candidates, settings.djxl_path.as_deref());
+    }
+}
+
+fn pick(candidates: &[ToolInfo], preferred: Option<&Path>) -> Option<ToolInfo> {
+    preferred
+        .and_then(|preferred| candidates.iter().find(|tool| tool.path == preferred))
+        .or(candidates.first())
+        .cloned()
+}
+
+/// Every `tool_name` binary in a `tools/` folder next to the executable, on
+/// PATH and in `settings.extra_tool_paths` (folders containing the tool, or
+/// the tool itself), in that order and without duplicates.
+pub fn find_tool_candidates(tool_name: &str, settings: &ToolSettings) -> Vec<ToolInfo> {
+    find_tool_paths(tool_name, &settings.extra_tool_paths)
+        .into_iter()
+        .map(|path| ToolInfo::probe(path, settings))
+        .collect()
+}
+
+/// Where [`find_tool_candidates`] finds `tool_name`, without running it.
+pub fn find_tool_paths(tool_name: &str, extra: &[PathBuf]) -> Vec<PathBuf> {
+    let file_name = if cfg!(windows) {
+        format!("{}.exe", tool_name)
+    } else {
+        tool_name.to_string()
+    };
+
+    let mut paths = Vec::new();
+    if let Some(exe_dir) = std::env::current_exe().ok().as_deref().and_then(Path::parent) {
+        let bundled = exe_dir.join("tools").join(&file_name);
+        ensure_executable(&bundled);
+        paths.push(bundled);
+    }
+    if let Some(path_var) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&path_var).map(|dir| dir.join(&file_name)));
+    }
+    for location in extra {
+        if location.is_dir() {
+            paths.push(location.join(&file_name));
+        } else if location.file_stem().is_some_and(|stem| stem == tool_name) {
+            paths.push(location.clone());
+        }
+    }
+
+    let mut seen = Vec::new();
+    let mut found = Vec::new();
+    for path in paths {
+        if !path.is_file() {
+            continue;
+        }
+        // PATH often lists the same folder twice, or a symlink to it
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if seen.contains(&canonical) {
+            continue;
+        }
+        seen.push(canonical);
+        found.push(path);
+    }
+    found
+}
+
+/// Bundled tools can lose their executable bit when unpacked from a zip.
+#[cfg(unix)]
+fn ensure_executable(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Ok(metadata) = std::fs::metadata(path) {
+        let mut perms = metadata.permissions();
+        if perms.mode() & 0o111 == 0 {
+            perms.set_mode(0o755);
+            let _ = std::fs::set_permissions(path, perms);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn ensure_executable(_path: &Path) {}
+
+/// The version printed by `path --version`, e.g. `cjxl v0.10.2 [AVX2]` or
+/// `JPEG XL encoder v0.8.1 1234abc [SSE4]` both give `0.10.2`/`0.8.1`.
+pub fn tool_version(path: &Path, settings: &ToolSettings) -> Option<String> {
+    let output = run_probe(path, &["--version"], settings)?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    parse_version(&text)
+}
+
+/// Everything `--help` prints at maximum verbosity; empty if the tool
+/// can't be run. libjxl exits with an error status after printing help.
+fn tool_help(path: &Path, settings: &ToolSettings) -> String {
+    match run_probe(path, &["--help", "-v", "-v", "-v", "-v"], settings) {
+        Some(output) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            text
+        }
+        None => String::new(),
+    }
+}
+
+/// Runs `path` with `args` and the overrides in `settings`, without a
+/// console window and for at most [`PROBE_TIMEOUT`].
+fn run_probe(path: &Path, args: &[&str], settings: &ToolSettings) -> Option<std::process::Output> {
+    let mut cmd = Command::new(path);
+    cmd.args(args);
+    settings.apply_env(&mut cmd);
+    run_tool(&mut cmd, &RunLimits { timeout: Some(PROBE_TIMEOUT), ..Default::default() }).ok()
+}
+
+/// Whether a `major.minor.patch` version is at least `major.minor`.
+pub fn version_at_least(version: &str, (major, minor): (u32, u32)) -> bool {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit()).map(|part| part.parse::<u32>().unwrap_or(0));
+    let found = (parts.next().unwrap_or(0), parts.next().unwrap_or(0));
+    found >= (major, minor)
+}
+
+fn parse_version(text: &str) -> Option<String> {
+    text.lines().next()?.split_whitespace().find_map(|word| {
+        let version = word.strip_prefix('v')?;
+        version.starts_with(|c: char| c.is_ascii_digit()).then(|| version.to_string())
+    })
+}
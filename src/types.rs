@@ -1,15 +1,29 @@
 use std::path::PathBuf;
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive::AdaptiveQuality;
+use crate::animation::{AnimationTiming, GifOptions};
+use crate::scan::ScanOptions;
+use crate::verify::Verification;
+
+/// Image format djxl decodes to.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OutputFormat {
     Png,
     Jpeg,
     Ppm,
     Pgm,
     Pbm,
+    Gif,
+    /// Only from a djxl built with OpenEXR.
+    Exr,
 }
 
 impl OutputFormat {
+    /// File extension written for this format, without the dot.
     pub fn extension(&self) -> &str {
         match self {
             OutputFormat::Png => "png",
@@ -17,9 +31,12 @@ impl OutputFormat {
             OutputFormat::Ppm => "ppm",
             OutputFormat::Pgm => "pgm",
             OutputFormat::Pbm => "pbm",
+            OutputFormat::Gif => "gif",
+            OutputFormat::Exr => "exr",
         }
     }
 
+    /// Display name, e.g. "PNG".
     pub fn name(&self) -> &str {
         match self {
             OutputFormat::Png => "PNG",
@@ -27,9 +44,39 @@ impl OutputFormat {
             OutputFormat::Ppm => "PPM",
             OutputFormat::Pgm => "PGM",
             OutputFormat::Pbm => "PBM",
+            OutputFormat::Gif => "GIF",
+            OutputFormat::Exr => "EXR",
+        }
+    }
+
+    /// Parses a format from a file extension or format name, case-insensitively.
+    pub fn from_extension(ext: &str) -> Option<OutputFormat> {
+        match ext.to_lowercase().as_str() {
+            "png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            "ppm" => Some(OutputFormat::Ppm),
+            "pgm" => Some(OutputFormat::Pgm),
+            "pbm" => Some(OutputFormat::Pbm),
+            "gif" => Some(OutputFormat::Gif),
+            "exr" => Some(OutputFormat::Exr),
+            _ => None,
         }
     }
 
+    /// Whether the format can store transparency (GIF only fully
+    /// transparent pixels). Images with alpha are flattened onto
+    /// [`DecodeSettings::flatten_background`] otherwise.
+    pub fn supports_alpha(&self) -> bool {
+        matches!(self, OutputFormat::Png | OutputFormat::Gif | OutputFormat::Exr)
+    }
+
+    /// Whether the format keeps every frame of an animation (PNG as an
+    /// APNG). The others get only the first frame.
+    pub fn supports_animation(&self) -> bool {
+        matches!(self, OutputFormat::Png | OutputFormat::Gif)
+    }
+
+    /// Every format, in display order.
     pub fn all() -> &'static [OutputFormat] {
         &[
             OutputFormat::Png,
@@ -37,33 +84,281 @@ impl OutputFormat {
             OutputFormat::Ppm,
             OutputFormat::Pgm,
             OutputFormat::Pbm,
+            OutputFormat::Gif,
+            OutputFormat::Exr,
         ]
     }
 }
 
-#[derive(Clone)]
+/// What a batch does when an output file already exists.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverwritePolicy {
+    /// Replace it.
+    #[default]
+    Overwrite,
+    /// Leave it alone and skip the input.
+    Skip,
+    /// Write `name (2).ext`, or the first such name that is free.
+    Rename,
+}
+
+impl OverwritePolicy {
+    /// Display name, e.g. "Overwrite".
+    pub fn name(&self) -> &str {
+        match self {
+            OverwritePolicy::Overwrite => "Overwrite",
+            OverwritePolicy::Skip => "Skip",
+            OverwritePolicy::Rename => "Rename",
+        }
+    }
+
+    /// Parses `overwrite`, `skip` or `rename`, case-insensitively.
+    pub fn from_name(name: &str) -> Option<OverwritePolicy> {
+        Self::all().iter().copied().find(|policy| policy.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Every policy, in display order.
+    pub fn all() -> &'static [OverwritePolicy] {
+        &[OverwritePolicy::Overwrite, OverwritePolicy::Skip, OverwritePolicy::Rename]
+    }
+}
+
+/// An sRGB color, `[r, g, b]`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Color(pub [u8; 3]);
+
+impl Color {
+    pub const WHITE: Color = Color([255, 255, 255]);
+
+    /// `#RRGGBB`.
+    pub fn hex(&self) -> String {
+        let [r, g, b] = self.0;
+        format!("#{:02X}{:02X}{:02X}", r, g, b)
+    }
+
+    /// Parses `#RRGGBB` or `RRGGBB`.
+    pub fn from_hex(text: &str) -> Option<Color> {
+        let hex = text.strip_prefix('#').unwrap_or(text);
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(Color([channel(0)?, channel(2)?, channel(4)?]))
+    }
+}
+
+/// Options for encoding to JXL.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ConversionSettings {
+    /// Directory the `.jxl` files are written to.
     pub output_dir: PathBuf,
+    /// Mathematically lossless encoding (`-d 0`).
     pub lossless: bool,
+    /// Transcode JPEG inputs losslessly so the original can be restored.
     pub jpeg_lossless: bool,
+    /// Lossy quality, 1–100.
     pub quality: u8,
+    /// Encoder effort, 1–10 (10 needs cjxl 0.10); higher is slower and smaller.
     pub effort: u8,
+    /// Descend into subfolders of input folders.
     pub recursive: bool,
+    /// Skip folders marked with `.nomedia` and paths listed in `.jxlignore`.
+    pub respect_ignore_files: bool,
+    /// Recreate the input folder structure under `output_dir`.
     pub keep_structure: bool,
+    /// Bits per sample recorded in the JXL header (`--override_bitdepth`),
+    /// e.g. 10 for 10-bit data stored in 16-bit PNGs. 1–32; the input's
+    /// depth is kept when unset.
+    pub override_bitdepth: Option<u8>,
+    /// Delay given to every frame of animated GIF and APNG inputs, in ms.
+    pub frame_delay_ms: Option<u32>,
+    /// How many times animated inputs play; 0 is forever.
+    pub loop_count: Option<u16>,
+    /// What happens to outputs that already exist.
+    pub overwrite: OverwritePolicy,
+    /// Delete everything the batch wrote if it is cancelled; needs an
+    /// `overwrite` policy other than Overwrite.
+    pub transactional: bool,
+    /// Leave images downloaded from URL inputs in the temp folder after the
+    /// batch instead of deleting them.
+    pub keep_downloads: bool,
+    /// Search each file's distance for a target ssimulacra2 score instead
+    /// of using `quality`; the quality is the starting point. Lossless
+    /// encodes and JPEG transcodes are unaffected.
+    pub adaptive_quality: Option<AdaptiveQuality>,
+    /// Stop the batch before outputs add up to more than this many bytes.
+    pub output_budget_bytes: Option<u64>,
+    /// Passed to cjxl after the options above, for flags the GUI has no
+    /// control for.
+    pub extra_args: Vec<String>,
 }
 
-#[derive(Clone)]
+/// Options for decoding from JXL.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DecodeSettings {
+    /// Directory the decoded images are written to.
     pub output_dir: PathBuf,
+    /// Default format for newly added files.
     pub output_format: OutputFormat,
+    /// Descend into subfolders of input folders.
     pub recursive: bool,
+    /// Skip folders marked with `.nomedia` and paths listed in `.jxlignore`.
+    pub respect_ignore_files: bool,
+    /// Recreate the input folder structure under `output_dir`.
     pub keep_structure: bool,
+    /// After decoding a `--lossless_jpeg` JXL to JPEG, compare the output
+    /// with the original JPEG, or at least check it parses.
+    pub verify_reconstruction: bool,
+    /// Where to look for the original JPEGs; next to the JXLs otherwise.
+    pub original_root: Option<PathBuf>,
+    /// What transparent areas become in formats without alpha.
+    pub flatten_background: Color,
+    /// Decode truncated JXLs as far as they go rather than failing; needs
+    /// a djxl with `--allow_partial_files`.
+    pub allow_partial_files: bool,
+    /// Name the output of `scan.tiff.jxl` `scan.png`, not `scan.tiff.png`,
+    /// and of `photo.jpg.jxl` decoded to JPEG `photo.jpg`.
+    pub replace_inner_extension: bool,
+    /// GIF palette size, 2–256.
+    pub gif_max_colors: u16,
+    /// Dither GIFs; smoother gradients, larger files.
+    pub gif_dither: bool,
+    /// Delay given to every frame of animated outputs (GIF and APNG), in ms.
+    pub frame_delay_ms: Option<u32>,
+    /// How many times animated outputs play; 0 is forever.
+    pub loop_count: Option<u16>,
+    /// What happens to outputs that already exist.
+    pub overwrite: OverwritePolicy,
+    /// Delete everything the batch wrote if it is cancelled; needs an
+    /// `overwrite` policy other than Overwrite.
+    pub transactional: bool,
+}
+
+/// Options that apply to every cjxl/djxl invocation and every batch,
+/// regardless of tab.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolSettings {
+    /// Number of `-v` flags passed to the tools (0–3). Above 0 the tool's
+    /// output is copied into the log for every file, not just on failure.
+    pub tool_verbosity: u8,
+    /// Extra environment variables set on every spawned tool.
+    pub env_overrides: Vec<(String, String)>,
+    /// Kill a tool whose resident memory grows past this many megabytes.
+    pub max_child_memory_mb: Option<u64>,
+    /// Restrict tools to the first N logical cores.
+    pub cpu_limit: Option<usize>,
+    /// Idle this many milliseconds between files, to let a machine that
+    /// throttles when hot cool down.
+    pub inter_file_delay_ms: Option<u64>,
+    /// The cjxl to use when several are installed; the first one found
+    /// otherwise.
+    pub cjxl_path: Option<PathBuf>,
+    /// The djxl to use when several are installed.
+    pub djxl_path: Option<PathBuf>,
+    /// Extra places to look for tools: folders, or the binaries themselves.
+    pub extra_tool_paths: Vec<PathBuf>,
+    /// Skip files whose size or modification time changes over this many
+    /// seconds at the start of a batch, as they are still being written.
+    pub stable_wait_secs: Option<u64>,
+    /// Run through the system shell after each file, with `{input}`,
+    /// `{output}` and `{status}` (`success` or `error`) replaced. Empty for
+    /// none.
+    pub post_hook: String,
+    /// Run the post hook after failed files too, not just converted ones.
+    pub post_hook_always: bool,
+    /// Run through the system shell as a batch starts, before its inputs
+    /// are scanned; a non-zero exit stops the batch. Empty for none.
+    pub pre_batch_hook: String,
+    /// Run through the system shell once a batch has completed or been
+    /// cancelled, with `JXL_CONVERTED`, `JXL_FAILED`, `JXL_SKIPPED`,
+    /// `JXL_BYTES_SAVED` and `JXL_STATUS` set. Empty for none.
+    pub post_batch_hook: String,
+    /// Kill a batch hook still running after this many seconds;
+    /// [`DEFAULT_BATCH_HOOK_TIMEOUT_SECS`] when unset.
+    pub batch_hook_timeout_secs: Option<u64>,
+    /// POST each batch's summary as JSON here when it ends. Empty for none.
+    pub notify_url: String,
+    /// Sent as `Authorization: Bearer <token>` with notifications, unless
+    /// empty.
+    pub notify_token: String,
+    /// Included in batch summaries and notifications to tell batches apart.
+    pub batch_label: String,
+    /// Ask before starting a batch of more files than this;
+    /// [`DEFAULT_CONFIRM_BATCH_FILES`] when unset.
+    pub confirm_batch_files: Option<usize>,
+    /// Ask before starting a batch of more input bytes than this;
+    /// [`DEFAULT_CONFIRM_BATCH_BYTES`] when unset.
+    pub confirm_batch_bytes: Option<u64>,
 }
 
-#[derive(Clone)]
+/// How long a batch hook may run unless configured otherwise.
+pub const DEFAULT_BATCH_HOOK_TIMEOUT_SECS: u64 = 300;
+
+/// Batches of more files than this are confirmed before they start, unless
+/// configured otherwise.
+pub const DEFAULT_CONFIRM_BATCH_FILES: usize = 5_000;
+
+/// Batches of more input than this (50 GB) are confirmed before they start,
+/// unless configured otherwise.
+pub const DEFAULT_CONFIRM_BATCH_BYTES: u64 = 50 * 1024 * 1024 * 1024;
+
+/// A JXL file to decode and the formats to decode it to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DecodeItem {
     pub path: PathBuf,
-    pub output_format: OutputFormat,
+    /// One output per format, each its own unit of progress. Empty means
+    /// the batch's [`DecodeSettings::output_format`].
+    pub output_formats: Vec<OutputFormat>,
+    /// The folder the file was found in when a whole folder was added.
+    /// keep_structure recreates that folder, by name, in the output.
+    pub folder: Option<PathBuf>,
+}
+
+/// The butteraugli distance cjxl uses for `-q quality`, following libjxl's
+/// `JxlEncoderDistanceFromQuality`: 100 is lossless (0.0), 90 is visually
+/// lossless (1.0), and the curve steepens below 30.
+pub fn distance_from_quality(quality: f32) -> f32 {
+    if quality >= 100.0 {
+        0.0
+    } else if quality >= 30.0 {
+        0.1 + (100.0 - quality) * 0.09
+    } else {
+        53.0 / 3000.0 * quality * quality - 23.0 / 20.0 * quality + 25.0
+    }
+}
+
+/// The inverse of [`distance_from_quality`], for settings given as a
+/// distance. Distances past 25 give 0.
+pub fn quality_from_distance(distance: f32) -> f32 {
+    if distance <= 0.1 {
+        100.0
+    } else if distance <= 6.4 {
+        100.0 - (distance - 0.1) / 0.09
+    } else if distance < 25.0 {
+        // The smaller root of the quadratic below 30
+        let (a, b, c) = (53.0 / 3000.0, -23.0 / 20.0, 25.0 - distance);
+        (-b - (b * b - 4.0 * a * c).sqrt()) / (2.0 * a)
+    } else {
+        0.0
+    }
+}
+
+impl ConversionSettings {
+    pub fn timing(&self) -> AnimationTiming {
+        AnimationTiming { frame_delay_ms: self.frame_delay_ms, loop_count: self.loop_count }
+    }
+
+    pub fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            recursive: self.recursive,
+            respect_ignore_files: self.respect_ignore_files,
+            exclude_dir: Some(self.output_dir.clone()),
+        }
+    }
 }
 
 impl Default for ConversionSettings {
@@ -75,7 +370,113 @@ impl Default for ConversionSettings {
             quality: 90,
             effort: 7,
             recursive: true,
+            respect_ignore_files: true,
             keep_structure: false,
+            override_bitdepth: None,
+            frame_delay_ms: None,
+            loop_count: None,
+            overwrite: OverwritePolicy::Overwrite,
+            transactional: false,
+            keep_downloads: false,
+            adaptive_quality: None,
+            output_budget_bytes: None,
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+impl ToolSettings {
+    /// The repeated `-v` flags for the configured verbosity.
+    pub fn verbosity_args(&self) -> Vec<&'static str> {
+        vec!["-v"; self.tool_verbosity.min(3) as usize]
+    }
+
+    /// Whether any hook is configured.
+    pub fn has_hooks(&self) -> bool {
+        [&self.post_hook, &self.pre_batch_hook, &self.post_batch_hook]
+            .iter()
+            .any(|hook| !hook.trim().is_empty())
+    }
+
+    /// Sets the environment overrides on `cmd`.
+    pub fn apply_env(&self, cmd: &mut std::process::Command) {
+        for (key, value) in &self.env_overrides {
+            cmd.env(key, value);
+        }
+    }
+
+    /// The overrides as `KEY=VALUE` text with secret-looking values masked,
+    /// safe to put in logs that may be shared.
+    pub fn describe_env(&self) -> String {
+        self.env_overrides
+            .iter()
+            .map(|(key, value)| {
+                if is_secret_env_key(key) {
+                    format!("{}=****", key)
+                } else {
+                    format!("{}={}", key, value)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn is_secret_env_key(key: &str) -> bool {
+    let upper = key.to_uppercase();
+    ["TOKEN", "KEY", "SECRET"].iter().any(|word| upper.contains(word))
+}
+
+/// Parses `KEY=VALUE` lines; blank lines and `#` comments are skipped.
+/// Malformed lines are returned as errors naming the line number.
+pub fn parse_env_overrides(text: &str) -> (Vec<(String, String)>, Vec<String>) {
+    let mut overrides = Vec::new();
+    let mut errors = Vec::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((key, value))
+                if !key.trim().is_empty()
+                    && !key.trim().contains(char::is_whitespace)
+                    && !line.contains('\0') =>
+            {
+                overrides.push((key.trim().to_string(), value.to_string()));
+            }
+            _ => errors.push(format!("line {}: expected KEY=VALUE", idx + 1)),
+        }
+    }
+
+    (overrides, errors)
+}
+
+/// The inverse of [`parse_env_overrides`].
+pub fn format_env_overrides(overrides: &[(String, String)]) -> String {
+    overrides
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl DecodeSettings {
+    pub fn timing(&self) -> AnimationTiming {
+        AnimationTiming { frame_delay_ms: self.frame_delay_ms, loop_count: self.loop_count }
+    }
+
+    pub fn gif_options(&self) -> GifOptions {
+        GifOptions { max_colors: self.gif_max_colors, dither: self.gif_dither, timing: self.timing() }
+    }
+
+    pub fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            recursive: self.recursive,
+            respect_ignore_files: self.respect_ignore_files,
+            exclude_dir: Some(self.output_dir.clone()),
         }
     }
 }
@@ -86,29 +487,59 @@ impl Default for DecodeSettings {
             output_dir: PathBuf::new(),
             output_format: OutputFormat::Png,
             recursive: true,
+            respect_ignore_files: true,
             keep_structure: false,
+            verify_reconstruction: false,
+            original_root: None,
+            flatten_background: Color::WHITE,
+            allow_partial_files: false,
+            replace_inner_extension: true,
+            gif_max_colors: 256,
+            gif_dither: true,
+            frame_delay_ms: None,
+            loop_count: None,
+            overwrite: OverwritePolicy::Overwrite,
+            transactional: false,
         }
     }
 }
 
-#[derive(Clone, Debug)]
+/// Events sent from a running batch. The serde form is the CLI's
+/// `--progress=json` line format, so renaming fields is a breaking change.
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum ProgressMessage {
+    /// The batch found `total` files to process.
     Started { total: usize },
+    /// File number `current` (1-based) is about to be processed.
     Progress { current: usize, total: usize, file: String },
-    Success { file: String },
+    /// Frame `frame` of the current file's animation is done; sent while
+    /// it converts, between its `Progress` and its `Success` or `Error`.
+    FrameProgress { file: String, frame: usize, total: Option<usize> },
+    /// `input` was written to `output`.
+    Success { file: String, input: PathBuf, output: PathBuf, input_bytes: u64, output_bytes: u64 },
+    /// A file failed; an empty `file` means the whole batch couldn't start.
     Error { file: String, error: String },
-    #[allow(dead_code)]
+    /// A file was left out on purpose.
     Skipped { file: String, reason: String },
+    /// A JPEG reconstructed from `file` was checked against its original;
+    /// sent after its `Success`.
+    Verified { file: String, verification: Verification },
+    /// Adaptive quality settled on `distance` for `file`, which scored
+    /// `score` in ssimulacra2 after `attempts` encodes; sent after its
+    /// `Success`.
+    Adapted { file: String, distance: f32, score: f32, attempts: usize },
+    Info { message: String },
+    Warning { message: String },
+    /// The batch finished; always the last message unless cancelled or
+    /// stopped.
     Completed,
+    /// The batch stopped early for `reason`, e.g. because its output budget
+    /// was reached, without converting the `remaining` inputs; the last
+    /// message.
+    Stopped { reason: String, remaining: Vec<String> },
+    /// The batch stopped early because the cancel flag was set.
     Cancelled,
 }
 
-#[derive(Clone, Debug)]
-pub enum LogEntry {
-    Info(String),
-    Success(String),
-    Error(String),
-    Warning(String),
-}
-
 
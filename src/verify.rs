@@ -0,0 +1,198 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// The first 12 bytes of a JXL in the ISO BMFF container format.
+const CONTAINER_SIGNATURE: [u8; 12] = [0, 0, 0, 0x0C, b'J', b'X', b'L', b' ', 0x0D, 0x0A, 0x87, 0x0A];
+
+/// Extensions tried, in order, when looking for the JPEG a JXL was made from.
+const ORIGINAL_EXTENSIONS: &[&str] = &["jpg", "jpeg", "JPG", "JPEG"];
+
+/// How a reconstructed JPEG compared with its original.
+#[derive(Clone, Debug, PartialEq, Serialize, JsonSchema)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Verification {
+    /// The output is byte-for-byte the original.
+    Match { original: PathBuf, sha256: String },
+    /// The output differs from the original.
+    Mismatch { original: PathBuf, output_sha256: String, original_sha256: String },
+    /// No original was found. `error` says why the output isn't a
+    /// well-formed JPEG, if it isn't.
+    Unverified { error: Option<String> },
+}
+
+impl Verification {
+    /// A line for logs, including both hashes on a mismatch.
+    pub fn describe(&self) -> String {
+        match self {
+            Verification::Match { original, sha256 } => {
+                format!("identical to {} (SHA-256 {})", original.display(), sha256)
+            }
+            Verification::Mismatch { original, output_sha256, original_sha256 } => format!(
+                "differs from {}: output SHA-256 {}, original SHA-256 {}",
+                original.display(),
+                output_sha256,
+                original_sha256
+            ),
+            Verification::Unverified { error: None } => {
+                "no original found to compare with; the output is a well-formed JPEG".to_string()
+            }
+            Verification::Unverified { error: Some(error) } => {
+                format!("no original found to compare with, and the output isn't a valid JPEG: {}", error)
+            }
+        }
+    }
+
+    /// Whether the output shouldn't be trusted in place of the original.
+    pub fn is_problem(&self) -> bool {
+        matches!(self, Verification::Mismatch { .. } | Verification::Unverified { error: Some(_) })
+    }
+}
+
+/// Whether `path` is a JXL container carrying JPEG reconstruction data (a
+/// `jbrd` box), i.e. was made with `--lossless_jpeg=1` and decodes back to
+/// the original file.
+pub fn has_jpeg_reconstruction(path: &Path) -> io::Result<bool> {
+    let mut file = File::open(path)?;
+    Ok(find_box(&mut file, &[b"jbrd"])?.is_some())
+}
+
+/// Finds the first box of one of `kinds` in a JXL container, returning its
+/// type and leaves `file` at the start of its payload. A bare codestream has
+/// no boxes.
+pub(crate) fn find_box(file: &mut File, kinds: &[&[u8; 4]]) -> io::Result<Option<[u8; 4]>> {
+    let mut signature = [0u8; 12];
+    file.seek(SeekFrom::Start(0))?;
+    if file.read_exact(&mut signature).is_err() || signature != CONTAINER_SIGNATURE {
+        return Ok(None);
+    }
+
+    let len = file.metadata()?.len();
+    let mut pos = 12;
+    while pos + 8 <= len {
+        file.seek(SeekFrom::Start(pos))?;
+        let mut header = [0u8; 8];
+        file.read_exact(&mut header)?;
+        let size = u64::from(u32::from_be_bytes([header[0], header[1], header[2], header[3]]));
+        let (size, header_len) = match size {
+            1 => {
+                let mut large = [0u8; 8];
+                file.read_exact(&mut large)?;
+                (u64::from_be_bytes(large), 16)
+            }
+            size => (size, 8),
+        };
+
+        let kind = [header[4], header[5], header[6], header[7]];
+        if kinds.contains(&&kind) {
+            return Ok(Some(kind));
+        }
+        // 0 means the box runs to the end of the file
+        if size < header_len {
+            return Ok(None);
+        }
+        pos += size;
+    }
+    Ok(None)
+}
+
+/// The SHA-256 of a file's contents as lowercase hex.
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Checks that `path` is a structurally complete JPEG: SOI, well-formed
+/// marker segments, a frame header, at least one scan and EOI. The image
+/// data itself isn't decoded.
+pub fn check_jpeg_structure(path: &Path) -> Result<(), String> {
+    let data = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    check_jpeg_bytes(&data)
+}
+
+fn check_jpeg_bytes(data: &[u8]) -> Result<(), String> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return Err("not a JPEG (no start-of-image marker)".to_string());
+    }
+
+    let mut pos = 2;
+    let mut has_frame = false;
+    let mut has_scan = false;
+    loop {
+        if data.get(pos) != Some(&0xFF) {
+            return Err(format!("expected a marker at byte {}", pos));
+        }
+        // Any number of 0xFF fill bytes may precede a marker
+        while data.get(pos) == Some(&0xFF) {
+            pos += 1;
+        }
+        let Some(&marker) = data.get(pos) else {
+            return Err("truncated before end-of-image".to_string());
+        };
+        pos += 1;
+
+        match marker {
+            0xD9 if has_frame && has_scan => return Ok(()),
+            0xD9 => return Err("end-of-image before any image data".to_string()),
+            0xD8 => return Err(format!("unexpected start-of-image at byte {}", pos - 2)),
+            0x01 | 0xD0..=0xD7 => continue,
+            _ => {}
+        }
+
+        let Some(length) = data.get(pos..pos + 2).map(|b| usize::from(u16::from_be_bytes([b[0], b[1]]))) else {
+            return Err("truncated marker segment".to_string());
+        };
+        if length < 2 || pos + length > data.len() {
+            return Err(format!("marker segment at byte {} runs past the end", pos - 2));
+        }
+        pos += length;
+
+        match marker {
+            // Start-of-frame; C4, C8 and CC are other tables
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => has_frame = true,
+            0xDA => {
+                if !has_frame {
+                    return Err("scan before frame header".to_string());
+                }
+                has_scan = true;
+                // Entropy-coded data ends at the first marker other than a
+                // stuffed zero or a restart marker
+                loop {
+                    match data.get(pos..pos + 2) {
+                        None => return Err("truncated image data".to_string()),
+                        Some([0xFF, next]) if *next != 0 && !(0xD0..=0xD7).contains(next) => break,
+                        Some(_) => pos += 1,
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The JPEG `jxl` was made from, if it can be found: under `original_root`
+/// (at the JXL's path relative to `folder` when it was found in an added
+/// folder, or by file name otherwise), else next to the JXL with the same
+/// stem.
+pub fn find_original(jxl: &Path, folder: Option<&Path>, original_root: Option<&Path>) -> Option<PathBuf> {
+    let mut stems = Vec::new();
+    if let Some(root) = original_root {
+        let relative = folder
+            .and_then(|folder| jxl.strip_prefix(folder).ok())
+            .map(Path::to_path_buf)
+            .or_else(|| jxl.file_name().map(PathBuf::from))?;
+        stems.push(root.join(relative));
+    }
+    stems.push(jxl.to_path_buf());
+
+    stems
+        .iter()
+        .flat_map(|stem| ORIGINAL_EXTENSIONS.iter().map(move |ext| stem.with_extension(ext)))
+        .find(|candidate| candidate.is_file())
+}
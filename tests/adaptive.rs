@@ -0,0 +1,95 @@
+mod support;
+
+use jxl_converter::adaptive::{parse_ssimulacra2_score, AdaptiveQuality, Candidate, DistanceSearch};
+use jxl_converter::{ConversionSettings, ProgressMessage};
+use support::{fake_engine, kinds, run_encode, touch};
+
+/// Runs a search against a made-up score curve, returning the distances it
+/// tried and the candidate it keeps.
+fn search(target: &AdaptiveQuality, initial: f32, score: impl Fn(f32) -> f32) -> (Vec<f32>, Option<Candidate>) {
+    let mut search = DistanceSearch::new(target, initial);
+    let mut tried = Vec::new();
+    while let Some(distance) = search.next_distance() {
+        tried.push(distance);
+        search.record(Candidate { distance, score: score(distance) });
+    }
+    (tried, search.best())
+}
+
+#[test]
+fn stops_as_soon_as_a_score_is_in_the_window() {
+    let (tried, best) = search(&AdaptiveQuality::default(), 1.0, |d| 100.0 - 10.0 * d);
+    assert_eq!(tried, [1.0, 1.6]);
+    assert_eq!(best, Some(Candidate { distance: 1.6, score: 84.0 }));
+}
+
+#[test]
+fn bisects_once_the_window_is_bracketed() {
+    let target = AdaptiveQuality { min_score: 84.5, max_score: 85.0, ..Default::default() };
+    let (tried, best) = search(&target, 1.0, |d| 100.0 - 10.0 * d);
+    // 1.0 scores too well and 1.6 just too badly, so the geometric mean is
+    // next; the window is narrower than the four attempts can find
+    assert_eq!(tried.len(), 4);
+    assert!((tried[2] - 1.6f32.sqrt()).abs() < 1e-6);
+    assert!((tried[3] - (tried[2] * 1.6).sqrt()).abs() < 1e-6);
+    assert_eq!(best.unwrap().distance, tried[3]);
+}
+
+#[test]
+fn falls_back_to_the_largest_acceptable_distance() {
+    // Nothing lands in the window: too good below 2, too bad above
+    let target = AdaptiveQuality { max_attempts: 3, ..Default::default() };
+    let (tried, best) = search(&target, 1.0, |d| if d < 2.0 { 95.0 } else { 60.0 });
+    assert_eq!(tried.len(), 3);
+    assert_eq!(best.unwrap().distance, 1.6);
+}
+
+#[test]
+fn stays_within_the_distance_range() {
+    let target = AdaptiveQuality { max_distance: 2.0, ..Default::default() };
+    let (tried, best) = search(&target, 1.0, |_| 99.0);
+    assert_eq!(tried, [1.0, 1.6, 2.0]);
+    assert_eq!(best.unwrap().distance, 2.0);
+}
+
+#[test]
+fn reads_the_score_from_the_last_number() {
+    assert_eq!(parse_ssimulacra2_score("84.125\n"), Some(84.125));
+    assert_eq!(parse_ssimulacra2_score("SSIMULACRA2 score: -3.5"), Some(-3.5));
+    assert_eq!(parse_ssimulacra2_score("usage: ssimulacra2 orig distorted"), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn batches_keep_the_candidate_in_the_window() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Scores 100 - 10 × the distance in the candidate's name
+    const SSIMULACRA2: &str = r#"#!/bin/sh
+d="${2##*.d}"
+awk "BEGIN { print 100 - 10 * ${d%.jxl} }"
+"#;
+
+    let dir = tempfile::tempdir().unwrap();
+    let ssimulacra2 = dir.path().join("ssimulacra2");
+    std::fs::write(&ssimulacra2, SSIMULACRA2).unwrap();
+    std::fs::set_permissions(&ssimulacra2, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let engine = fake_engine(dir.path(), &[]).with_ssimulacra2_path(ssimulacra2);
+    let out = dir.path().join("out");
+    let settings = ConversionSettings {
+        output_dir: out.clone(),
+        adaptive_quality: Some(AdaptiveQuality::default()),
+        ..Default::default()
+    };
+
+    let messages = run_encode(&engine, vec![touch(dir.path(), "in/a.png")], settings);
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "adapted", "completed"]);
+    assert!(messages.iter().any(|msg| matches!(
+        msg,
+        ProgressMessage::Adapted { distance, score, attempts: 2, .. } if *distance == 1.6 && (*score - 84.0).abs() < 1e-4
+    )));
+    let left: Vec<_> = std::fs::read_dir(&out).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert_eq!(left, ["a.jxl"]);
+}
+
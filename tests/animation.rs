@@ -0,0 +1,380 @@
+mod support;
+
+use std::fs::File;
+use std::path::Path;
+
+use image::{Rgba, RgbaImage};
+use jxl_converter::animation::{
+    frame_count, parse_frame_line, retime, write_gif, AnimationInfo, AnimationTiming, GifOptions,
+};
+use jxl_converter::{DecodeItem, DecodeSettings, OutputFormat, ProgressMessage};
+
+use support::{encode_settings, fake_engine, kinds, outputs, run_decode, run_encode};
+
+const NO_TIMING: AnimationTiming = AnimationTiming { frame_delay_ms: None, loop_count: None };
+const OPTIONS: GifOptions = GifOptions { max_colors: 256, dither: false, timing: NO_TIMING };
+
+/// Writes an APNG with one frame per `(image, delay in ms)`.
+fn write_apng(path: &Path, frames: &[(RgbaImage, u16)], plays: u32) {
+    let (width, height) = frames[0].0.dimensions();
+    let mut encoder = png::Encoder::new(File::create(path).unwrap(), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, plays).unwrap();
+    let mut writer = encoder.write_header().unwrap();
+    for (image, delay) in frames {
+        writer.set_frame_delay(*delay, 1000).unwrap();
+        writer.write_image_data(image).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+/// Writes a GIF with one gray frame per delay (in 10 ms units) and
+/// optionally a loop block.
+fn write_test_gif(path: &Path, delays: &[u16], repeat: Option<gif::Repeat>) {
+    let mut encoder = gif::Encoder::new(File::create(path).unwrap(), 2, 2, &[0, 0, 0, 255, 255, 255]).unwrap();
+    if let Some(repeat) = repeat {
+        encoder.set_repeat(repeat).unwrap();
+    }
+    for (i, delay) in delays.iter().enumerate() {
+        let frame = gif::Frame { width: 2, height: 2, delay: *delay, buffer: vec![(i % 2) as u8; 4].into(), ..Default::default() };
+        encoder.write_frame(&frame).unwrap();
+    }
+}
+
+struct Gif {
+    repeat: gif::Repeat,
+    palette: Vec<u8>,
+    transparent: Option<u8>,
+    delays: Vec<u16>,
+    frames: Vec<Vec<u8>>,
+}
+
+fn read_gif(path: &Path) -> Gif {
+    let mut decoder = gif::DecodeOptions::new().read_info(File::open(path).unwrap()).unwrap();
+    let (mut delays, mut frames, mut transparent) = (Vec::new(), Vec::new(), None);
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        delays.push(frame.delay);
+        frames.push(frame.buffer.to_vec());
+        transparent = frame.transparent;
+    }
+    Gif {
+        repeat: decoder.repeat(),
+        palette: decoder.global_palette().unwrap().to_vec(),
+        transparent,
+        delays,
+        frames,
+    }
+}
+
+fn color(gif: &Gif, index: u8) -> [u8; 3] {
+    let i = usize::from(index) * 3;
+    [gif.palette[i], gif.palette[i + 1], gif.palette[i + 2]]
+}
+
+#[test]
+fn still_images_give_one_frame_keeping_transparency() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = dir.path().join("in.png");
+    let mut image = RgbaImage::from_pixel(2, 1, Rgba([200, 30, 40, 255]));
+    image.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+    image.save(&png).unwrap();
+
+    let out = dir.path().join("out.gif");
+    let summary = write_gif(&png, &out, &OPTIONS, |_, _| {}).unwrap();
+    assert_eq!(summary.written.frames, 1);
+    assert!(!summary.delays_adjusted);
+
+    let gif = read_gif(&out);
+    assert_eq!(gif.frames.len(), 1);
+    assert_eq!(gif.repeat, gif::Repeat::Finite(0));
+    assert_eq!(color(&gif, gif.frames[0][0]), [200, 30, 40]);
+    assert_eq!(Some(gif.frames[0][1]), gif.transparent);
+}
+
+#[test]
+fn delays_round_to_10_ms_without_drifting() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = dir.path().join("in.png");
+    let frames: Vec<(RgbaImage, u16)> = [33, 33, 34, 33, 33, 34]
+        .into_iter()
+        .enumerate()
+        .map(|(i, delay)| (RgbaImage::from_pixel(2, 2, Rgba([i as u8 * 40, 0, 0, 255])), delay))
+        .collect();
+    write_apng(&png, &frames, 0);
+
+    let out = dir.path().join("out.gif");
+    let summary = write_gif(&png, &out, &OPTIONS, |_, _| {}).unwrap();
+    assert_eq!(summary.written.frames, 6);
+    assert_eq!(summary.written.describe(), "6 frames @ 30–40 ms, loop ∞");
+    assert_eq!(summary.source.describe(), "6 frames @ 33–34 ms, loop ∞");
+    assert!(summary.delays_adjusted);
+
+    let gif = read_gif(&out);
+    assert_eq!(gif.delays, [3, 4, 3, 3, 4, 3]);
+    assert_eq!(gif.delays.iter().sum::<u16>(), 20);
+    assert_eq!(gif.repeat, gif::Repeat::Infinite);
+    for (i, frame) in gif.frames.iter().enumerate() {
+        assert_eq!(color(&gif, frame[0]), [i as u8 * 40, 0, 0]);
+    }
+}
+
+#[test]
+fn short_delays_and_play_counts() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = dir.path().join("in.png");
+    let frame = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 255]));
+    write_apng(&png, &[(frame.clone(), 100), (frame.clone(), 0)], 3);
+
+    let out = dir.path().join("out.gif");
+    assert!(write_gif(&png, &out, &OPTIONS, |_, _| {}).unwrap().delays_adjusted);
+    let gif = read_gif(&out);
+    // Browsers would play a 0 delay as 100 ms
+    assert_eq!(gif.delays, [10, 2]);
+    // Three plays is the first one and two repeats
+    assert_eq!(gif.repeat, gif::Repeat::Finite(2));
+
+    write_apng(&png, &[(frame.clone(), 100), (frame, 100)], 1);
+    assert!(!write_gif(&png, &out, &OPTIONS, |_, _| {}).unwrap().delays_adjusted);
+    assert_eq!(read_gif(&out).repeat, gif::Repeat::Finite(0));
+}
+
+#[test]
+fn colors_are_limited_and_dithering_spreads_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = dir.path().join("in.png");
+    RgbaImage::from_fn(64, 8, |x, _| Rgba([(x * 4) as u8, 128, 255 - (x * 4) as u8, 255]))
+        .save(&png)
+        .unwrap();
+
+    let out = dir.path().join("out.gif");
+    let options = GifOptions { max_colors: 4, ..OPTIONS };
+    write_gif(&png, &out, &options, |_, _| {}).unwrap();
+    let plain = read_gif(&out);
+    assert!(plain.palette.len() <= 4 * 3);
+    assert!(plain.frames[0].iter().all(|&i| i < 4));
+    // Without dithering every column is one color
+    assert!(plain.frames[0].chunks(64).all(|row| row == &plain.frames[0][..64]));
+
+    write_gif(&png, &out, &GifOptions { dither: true, ..options }, |_, _| {}).unwrap();
+    let dithered = read_gif(&out);
+    assert!(dithered.frames[0].iter().all(|&i| i < 4));
+    assert!(dithered.frames[0].chunks(64).any(|row| row != &dithered.frames[0][..64]));
+}
+
+#[test]
+fn decode_batch_writes_gifs() {
+    let dir = tempfile::tempdir().unwrap();
+    // The fake djxl copies its input, so an APNG named .jxl "decodes" to itself
+    let anim = dir.path().join("in/anim.jxl");
+    std::fs::create_dir_all(anim.parent().unwrap()).unwrap();
+    let frame = |value| (RgbaImage::from_pixel(4, 4, Rgba([value, value, value, 255])), 50);
+    write_apng(&anim, &[frame(0), frame(128), frame(255)], 0);
+    let out = dir.path().join("out");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let items = vec![DecodeItem { path: anim, output_formats: vec![OutputFormat::Gif], folder: None }];
+    let settings = DecodeSettings { output_dir: out.clone(), ..Default::default() };
+    let messages = run_decode(&engine, items, settings);
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "completed"]);
+    assert!(messages
+        .iter()
+        .any(|msg| matches!(msg, ProgressMessage::Info { message } if message.ends_with("anim.jxl: 3 frames @ 50 ms, loop ∞"))));
+    assert_eq!(read_gif(&out.join("anim.gif")).delays, [5, 5, 5]);
+    // No temporary PNG left behind
+    assert_eq!(std::fs::read_dir(&out).unwrap().count(), 1);
+}
+
+#[test]
+fn timing_overrides_replace_the_gifs_own() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = dir.path().join("in.png");
+    let frame = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 255]));
+    write_apng(&png, &[(frame.clone(), 100), (frame.clone(), 200), (frame, 100)], 1);
+
+    let out = dir.path().join("out.gif");
+    let timing = AnimationTiming { frame_delay_ms: Some(33), loop_count: Some(0) };
+    let summary = write_gif(&png, &out, &GifOptions { timing, ..OPTIONS }, |_, _| {}).unwrap();
+    assert_eq!(
+        summary.written.describe_override(&summary.source),
+        "3 frames @ 30–40 ms, loop ∞ (overridden; the source had 100–200 ms, plays once)"
+    );
+    let gif = read_gif(&out);
+    assert_eq!(gif.delays, [3, 4, 3]);
+    assert_eq!(gif.repeat, gif::Repeat::Infinite);
+}
+
+#[test]
+fn retiming_a_gif_rewrites_delays_and_loops() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.gif");
+    write_test_gif(&path, &[4, 8], None);
+    let data = std::fs::read(&path).unwrap();
+
+    let timing = AnimationTiming { frame_delay_ms: Some(33), loop_count: Some(0) };
+    let retimed = retime(&data, &timing).unwrap().unwrap();
+    assert_eq!(retimed.before, AnimationInfo { frames: 2, delays_ms: vec![40, 80], plays: 1 });
+    assert_eq!(retimed.after, AnimationInfo { frames: 2, delays_ms: vec![30, 30], plays: 0 });
+    std::fs::write(&path, &retimed.data).unwrap();
+    let gif = read_gif(&path);
+    assert_eq!(gif.delays, [3, 3]);
+    assert_eq!(gif.repeat, gif::Repeat::Infinite);
+    // The pixels are untouched
+    assert_eq!(gif.frames, [vec![0; 4], vec![1; 4]]);
+
+    // Playing once drops the loop block; only the loop count changes
+    write_test_gif(&path, &[4, 8], Some(gif::Repeat::Infinite));
+    let data = std::fs::read(&path).unwrap();
+    let once = AnimationTiming { frame_delay_ms: None, loop_count: Some(1) };
+    let retimed = retime(&data, &once).unwrap().unwrap();
+    assert_eq!(retimed.before.plays, 0);
+    std::fs::write(&path, &retimed.data).unwrap();
+    let gif = read_gif(&path);
+    assert_eq!(gif.delays, [4, 8]);
+    assert_eq!(gif.repeat, gif::Repeat::Finite(0));
+
+    let three = AnimationTiming { frame_delay_ms: None, loop_count: Some(3) };
+    std::fs::write(&path, retime(&data, &three).unwrap().unwrap().data).unwrap();
+    assert_eq!(read_gif(&path).repeat, gif::Repeat::Finite(2));
+
+    // Stills aren't animations
+    write_test_gif(&path, &[4], None);
+    assert!(retime(&std::fs::read(&path).unwrap(), &timing).unwrap().is_none());
+}
+
+#[test]
+fn retiming_an_apng_keeps_it_valid() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.png");
+    let frame = |value| (RgbaImage::from_pixel(2, 2, Rgba([value, 0, 0, 255])), 100);
+    write_apng(&path, &[frame(0), frame(255)], 1);
+
+    let timing = AnimationTiming { frame_delay_ms: Some(40), loop_count: Some(0) };
+    let retimed = retime(&std::fs::read(&path).unwrap(), &timing).unwrap().unwrap();
+    assert_eq!(retimed.before.describe(), "2 frames @ 100 ms, plays once");
+    assert_eq!(retimed.after.describe(), "2 frames @ 40 ms, loop ∞");
+    std::fs::write(&path, &retimed.data).unwrap();
+
+    // Read back through a decoder that checks every CRC
+    let out = dir.path().join("out.gif");
+    let summary = write_gif(&path, &out, &OPTIONS, |_, _| {}).unwrap();
+    assert_eq!(summary.source.describe(), "2 frames @ 40 ms, loop ∞");
+    let gif = read_gif(&out);
+    assert_eq!(color(&gif, gif.frames[1][0]), [255, 0, 0]);
+
+    let still = dir.path().join("still.png");
+    RgbaImage::new(1, 1).save(&still).unwrap();
+    assert!(retime(&std::fs::read(&still).unwrap(), &timing).unwrap().is_none());
+}
+
+#[test]
+fn batches_apply_timing_overrides() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = fake_engine(dir.path(), &[]);
+    let timing_note = |messages: &[ProgressMessage]| {
+        messages.iter().find_map(|msg| match msg {
+            ProgressMessage::Info { message } if message.contains("overridden") => Some(message.clone()),
+            _ => None,
+        })
+    };
+
+    // Encoding: the fake cjxl copies the retimed GIF it's given
+    let gif = dir.path().join("in/a.gif");
+    std::fs::create_dir_all(gif.parent().unwrap()).unwrap();
+    write_test_gif(&gif, &[10, 10], None);
+    let out = dir.path().join("encoded");
+    let settings = jxl_converter::ConversionSettings {
+        frame_delay_ms: Some(50),
+        loop_count: Some(0),
+        ..encode_settings(&out)
+    };
+    let messages = run_encode(&engine, vec![gif.clone()], settings);
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "completed"]);
+    assert!(timing_note(&messages).unwrap().ends_with("2 frames @ 50 ms, loop ∞ (overridden; the source had 100 ms, plays once)"));
+    let encoded = read_gif(&outputs(&messages)[0]);
+    assert_eq!((encoded.delays, encoded.repeat), (vec![5, 5], gif::Repeat::Infinite));
+    assert_eq!(std::fs::read_dir(&out).unwrap().count(), 1);
+
+    // Decoding to PNG: the fake djxl copies an APNG named .jxl
+    let anim = dir.path().join("in/anim.jxl");
+    let frame = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 255]));
+    write_apng(&anim, &[(frame.clone(), 100), (frame, 100)], 0);
+    let decoded = dir.path().join("decoded");
+    let items = vec![DecodeItem { path: anim.clone(), output_formats: vec![OutputFormat::Png], folder: None }];
+    let settings = DecodeSettings { output_dir: decoded.clone(), loop_count: Some(2), ..Default::default() };
+    let messages = run_decode(&engine, items.clone(), settings);
+    assert!(timing_note(&messages).unwrap().ends_with("2 frames @ 100 ms, plays 2× (overridden; the source had 100 ms, loop ∞)"));
+    let data = std::fs::read(decoded.join("anim.png")).unwrap();
+    assert_eq!(retime(&data, &NO_TIMING).unwrap().unwrap().before.plays, 2);
+
+    let settings = DecodeSettings { output_dir: decoded, frame_delay_ms: Some(0), ..Default::default() };
+    let messages = run_decode(&engine, items, settings);
+    assert_eq!(kinds(&messages), ["error"]);
+}
+
+#[test]
+fn frame_numbers_are_read_from_tool_output() {
+    assert_eq!(parse_frame_line("Encoding frame 12/500"), Some((12, Some(500))));
+    assert_eq!(parse_frame_line("frame 3 of 40 done"), Some((3, Some(40))));
+    assert_eq!(parse_frame_line("Frame #7: 120 KB"), Some((7, None)));
+    assert_eq!(parse_frame_line("Read 640x480 image, 1 frames"), None);
+    assert_eq!(parse_frame_line("Compressed to 120 kB"), None);
+}
+
+#[test]
+fn animations_report_progress_per_frame() {
+    let dir = tempfile::tempdir().unwrap();
+    let frame_progress = |messages: &[ProgressMessage]| -> Vec<(usize, Option<usize>)> {
+        messages
+            .iter()
+            .filter_map(|msg| match msg {
+                ProgressMessage::FrameProgress { frame, total, .. } => Some((*frame, *total)),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // Assembling a GIF here reports each frame written
+    let anim = dir.path().join("in/anim.jxl");
+    std::fs::create_dir_all(anim.parent().unwrap()).unwrap();
+    let frame = |value| (RgbaImage::from_pixel(1, 1, Rgba([value, 0, 0, 255])), 100);
+    write_apng(&anim, &[frame(0), frame(1), frame(2)], 0);
+    let engine = fake_engine(dir.path(), &[]);
+    let items = vec![DecodeItem { path: anim, output_formats: vec![OutputFormat::Gif], folder: None }];
+    let settings = DecodeSettings { output_dir: dir.path().join("out"), ..Default::default() };
+    let messages = run_decode(&engine, items, settings);
+    assert_eq!(frame_progress(&messages), [(1, Some(3)), (2, Some(3)), (3, Some(3))]);
+
+    // cjxl's own frame lines, with the count taken from the input
+    let gif = dir.path().join("in/a.gif");
+    write_test_gif(&gif, &[10, 10], None);
+    let engine = fake_engine(dir.path(), &[("FAKE_TOOL_STDERR", "frame 1\rframe 2\rdone")]);
+    let messages = run_encode(&engine, vec![gif], encode_settings(&dir.path().join("encoded")));
+    assert_eq!(frame_progress(&messages), [(1, Some(2)), (2, Some(2))]);
+}
+
+#[test]
+fn frames_are_counted_from_the_headers() {
+    let dir = tempfile::tempdir().unwrap();
+    let count = |path: &Path| frame_count(std::io::BufReader::new(File::open(path).unwrap()));
+    let frame = |value: u8| RgbaImage::from_pixel(2, 2, Rgba([value, 0, 0, 255]));
+
+    let gif = dir.path().join("a.gif");
+    write_test_gif(&gif, &[4, 8, 4], Some(gif::Repeat::Infinite));
+    assert_eq!(count(&gif), Some(3));
+    write_test_gif(&gif, &[4], None);
+    assert_eq!(count(&gif), None);
+
+    let apng = dir.path().join("a.png");
+    write_apng(&apng, &[(frame(0), 40), (frame(255), 40)], 0);
+    assert_eq!(count(&apng), Some(2));
+    frame(0).save(&apng).unwrap();
+    assert_eq!(count(&apng), None);
+
+    // A truncated GIF isn't counted
+    write_test_gif(&gif, &[4, 8], None);
+    let data = std::fs::read(&gif).unwrap();
+    assert_eq!(frame_count(&data[..data.len() - 3]), None);
+    assert_eq!(frame_count(&b"not an image"[..]), None);
+}
@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use jxl_converter::breakdown::{format_bytes, BatchBreakdown, FormatCount};
+use jxl_converter::ToolSettings;
+
+#[test]
+fn formats_are_counted_most_files_first() {
+    let mut breakdown = BatchBreakdown::default();
+    breakdown.add("PNG", 10);
+    breakdown.add("JPG", 20);
+    breakdown.add("JPG", 30);
+
+    assert_eq!(breakdown.files, 3);
+    assert_eq!(breakdown.bytes, 60);
+    assert_eq!(
+        breakdown.formats,
+        [
+            FormatCount { format: "JPG".to_string(), files: 2, bytes: 50 },
+            FormatCount { format: "PNG".to_string(), files: 1, bytes: 10 },
+        ]
+    );
+}
+
+#[test]
+fn urls_count_together_and_without_bytes() {
+    let files = [PathBuf::from("https://example.com/a.png"), PathBuf::from("https://example.com/b.jpg")];
+    let breakdown = BatchBreakdown::of_files(&files);
+    assert_eq!(breakdown.formats, [FormatCount { format: "URL".to_string(), files: 2, bytes: 0 }]);
+}
+
+#[test]
+fn either_threshold_asks_for_confirmation() {
+    let settings = ToolSettings { confirm_batch_files: Some(2), confirm_batch_bytes: Some(100), ..Default::default() };
+    let mut breakdown = BatchBreakdown::default();
+    breakdown.add("PNG", 60);
+    breakdown.add("PNG", 40);
+    assert!(!breakdown.needs_confirmation(&settings));
+    breakdown.add("PNG", 0);
+    assert!(breakdown.needs_confirmation(&settings));
+
+    let mut large = BatchBreakdown::default();
+    large.add("TIFF", 101);
+    assert!(large.needs_confirmation(&settings));
+    // The defaults are 5,000 files or 50 GB
+    assert!(!large.needs_confirmation(&ToolSettings::default()));
+}
+
+#[test]
+fn sizes_use_binary_units() {
+    assert_eq!(format_bytes(512), "512 bytes");
+    assert_eq!(format_bytes(1536), "1.5 KB");
+    assert_eq!(format_bytes(50 * 1024 * 1024 * 1024), "50.0 GB");
+}
@@ -0,0 +1,252 @@
+mod support;
+
+use std::process::{Command, Output};
+
+use serde_json::Value;
+
+use support::{fake_tool, touch, touch_jxl};
+
+fn run_cli(args: &[&str]) -> Output {
+    run_cli_with_env(args, &[])
+}
+
+/// Runs the CLI with extra environment variables, which the fake tool inherits.
+fn run_cli_with_env(args: &[&str], env: &[(&str, &str)]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jxl-converter-gui"))
+        .args(args)
+        .envs(env.iter().copied())
+        .output()
+        .expect("failed to run the CLI")
+}
+
+fn json_lines(output: &Output) -> Vec<Value> {
+    String::from_utf8(output.stdout.clone())
+        .expect("stdout is not UTF-8")
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("invalid JSON line {:?}: {}", line, e)))
+        .collect()
+}
+
+#[test]
+fn progress_schema_describes_every_line_type() {
+    let output = run_cli(&["--progress-schema"]);
+    assert!(output.status.success());
+
+    let schema: Value = serde_json::from_slice(&output.stdout).expect("schema is not JSON");
+    let text = schema.to_string();
+    for line_type in ["started", "progress", "success", "error", "completed", "cancelled", "summary"] {
+        assert!(text.contains(&format!("\"{}\"", line_type)), "schema is missing {}", line_type);
+    }
+}
+
+#[test]
+fn json_stream_reports_each_file_and_a_summary() {
+    let dir = tempfile::tempdir().unwrap();
+    let cjxl = fake_tool(dir.path(), "cjxl");
+    let input = dir.path().join("in");
+    let out = dir.path().join("out");
+    touch(&input, "a.png");
+    touch(&input, "b.png");
+
+    let output = run_cli(&[
+        "encode",
+        "--progress=json",
+        "--cjxl",
+        cjxl.to_str().unwrap(),
+        "-o",
+        out.to_str().unwrap(),
+        input.to_str().unwrap(),
+    ]);
+
+    let lines = json_lines(&output);
+    let types: Vec<&str> = lines.iter().map(|l| l["type"].as_str().unwrap()).collect();
+
+    assert_eq!(types.first(), Some(&"started"));
+    assert_eq!(lines[0]["total"], 2);
+    assert_eq!(types.iter().filter(|t| **t == "progress").count(), 2);
+    assert_eq!(types.iter().filter(|t| **t == "success").count(), 2);
+    assert_eq!(&types[types.len() - 2..], &["completed", "summary"]);
+
+    let progress = lines.iter().find(|l| l["type"] == "progress").unwrap();
+    assert_eq!(progress["current"], 1);
+    assert_eq!(progress["total"], 2);
+    assert!(progress["file"].as_str().unwrap().ends_with(".png"));
+
+    let summary = lines.last().unwrap();
+    assert_eq!(summary["converted"], 2);
+    assert_eq!(summary["failed"], 0);
+    assert_eq!(summary["bytes_in"], 10);
+    assert_eq!(summary["status"], "completed");
+    assert!(summary["finished_at"].is_string());
+    assert_eq!(summary["exit_code"], 0);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(out.join("a.jxl").exists());
+}
+
+#[test]
+fn json_stream_reports_tool_failures() {
+    let dir = tempfile::tempdir().unwrap();
+    let cjxl = fake_tool(dir.path(), "cjxl");
+    touch(dir.path(), "a.png");
+
+    let output = run_cli_with_env(
+        &[
+            "encode",
+            "--progress",
+            "json",
+            "--cjxl",
+            cjxl.to_str().unwrap(),
+            "-o",
+            dir.path().join("out").to_str().unwrap(),
+            dir.path().join("a.png").to_str().unwrap(),
+        ],
+        &[("FAKE_TOOL_FAIL", "1"), ("FAKE_TOOL_STDERR", "bad input")],
+    );
+
+    let lines = json_lines(&output);
+    let error = lines.iter().find(|l| l["type"] == "error").expect("no error event");
+    assert!(error["error"].as_str().unwrap().contains("bad input"));
+    assert_eq!(lines.last().unwrap()["failed"], 1);
+    assert_eq!(output.status.code(), Some(1));
+
+    // Human-readable text stays on stderr, ending with the summary line
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.trim_end().ends_with("converted=0 failed=1 skipped=0 bytes_in=0 bytes_out=0"));
+}
+
+#[test]
+fn exit_code_reports_no_matching_inputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let cjxl = fake_tool(dir.path(), "cjxl");
+    touch(dir.path(), "notes.txt");
+
+    let output = run_cli(&[
+        "encode",
+        "--cjxl",
+        cjxl.to_str().unwrap(),
+        "-o",
+        dir.path().join("out").to_str().unwrap(),
+        dir.path().join("notes.txt").to_str().unwrap(),
+    ]);
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn exit_code_reports_missing_tool() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = run_cli(&[
+        "encode",
+        "--progress=json",
+        "--cjxl",
+        dir.path().join("no-such-cjxl").to_str().unwrap(),
+        "-o",
+        dir.path().to_str().unwrap(),
+        dir.path().to_str().unwrap(),
+    ]);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(json_lines(&output).last().unwrap()["exit_code"], 3);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("converted=0 failed=0"));
+}
+
+#[test]
+fn exit_code_reports_invalid_arguments() {
+    let output = run_cli(&["encode", "--quality", "500", "-o", "out", "in.png"]);
+    assert_eq!(output.status.code(), Some(4));
+
+    let output = run_cli(&["transcode"]);
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn a_spent_output_budget_leaves_files_remaining() {
+    let dir = tempfile::tempdir().unwrap();
+    let cjxl = fake_tool(dir.path(), "cjxl");
+    let input = dir.path().join("in");
+    let out = dir.path().join("out");
+    touch(&input, "a.png");
+    touch(&input, "b.png");
+
+    // Each copied output is 5 bytes
+    let output = run_cli(&[
+        "encode",
+        "--progress=json",
+        "--output-budget=9",
+        "--cjxl",
+        cjxl.to_str().unwrap(),
+        "-o",
+        out.to_str().unwrap(),
+        input.to_str().unwrap(),
+    ]);
+
+    let lines = json_lines(&output);
+    let stopped = &lines[lines.len() - 2];
+    assert_eq!(stopped["type"], "stopped");
+    assert_eq!(stopped["remaining"].as_array().unwrap().len(), 1);
+    let summary = lines.last().unwrap();
+    assert_eq!(summary["converted"], 1);
+    assert_eq!(summary["remaining"], 1);
+    assert_eq!(summary["status"], "stopped");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn dry_runs_list_outputs_without_writing_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let cjxl = fake_tool(dir.path(), "cjxl");
+    let input = dir.path().join("in");
+    let out = dir.path().join("out");
+    touch(&input, "a.png");
+    touch(&input, "b.jpg");
+
+    let output = run_cli(&[
+        "encode",
+        "--dry-run",
+        "--cjxl",
+        cjxl.to_str().unwrap(),
+        "-o",
+        out.to_str().unwrap(),
+        input.to_str().unwrap(),
+    ]);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("2 file(s), 10 bytes in total"), "{}", stderr);
+    assert!(stderr.contains("  PNG: 1 file(s), 5 bytes"), "{}", stderr);
+    assert!(stderr.contains(&format!("-> {}", out.join("a.jxl").display())), "{}", stderr);
+    assert!(stderr.contains("converted=0 failed=0"), "{}", stderr);
+    assert!(!out.exists());
+}
+
+#[test]
+fn file_lists_pin_formats_per_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let djxl = fake_tool(dir.path(), "djxl");
+    touch_jxl(dir.path(), "in/a.jxl");
+    touch_jxl(dir.path(), "in/b.jxl");
+    touch_jxl(dir.path(), "in/c.jxl");
+    let list = dir.path().join("in/list.txt");
+    std::fs::write(&list, "a.jxl\tpng\nb.jxl\tjpg\nc.jxl\n").unwrap();
+    let out = dir.path().join("out");
+
+    let output = run_cli(&[
+        "decode",
+        "--djxl",
+        djxl.to_str().unwrap(),
+        "--format",
+        "ppm",
+        "-o",
+        out.to_str().unwrap(),
+        "--file-list",
+        list.to_str().unwrap(),
+    ]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    let mut written: Vec<String> = std::fs::read_dir(&out)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    written.sort();
+    // Lines without a format get the one from --format
+    assert_eq!(written, ["a.png", "b.jpg", "c.ppm"]);
+}
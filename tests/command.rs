@@ -0,0 +1,104 @@
+use jxl_converter::command::{describe_changes, parse_cjxl_command};
+use jxl_converter::types::{distance_from_quality, quality_from_distance};
+use jxl_converter::ConversionSettings;
+
+fn parse(command: &str) -> ConversionSettings {
+    parse_cjxl_command(command, &ConversionSettings::default()).unwrap().settings
+}
+
+#[test]
+fn distance_and_effort_with_unknown_flags() {
+    let settings = parse("cjxl -d 1.0 -e 8 --photon_noise=ISO800 in.png out.jxl");
+    assert!(!settings.lossless);
+    assert_eq!(settings.quality, 90);
+    assert_eq!(settings.effort, 8);
+    assert_eq!(settings.extra_args, ["--photon_noise=ISO800"]);
+}
+
+#[test]
+fn flag_values_may_follow_as_separate_arguments() {
+    let settings = parse("cjxl --quality 75 --effort=9 --photon_noise_iso 400 --brotli_effort 11 a.png a.jxl");
+    assert_eq!(settings.quality, 75);
+    assert_eq!(settings.effort, 9);
+    assert_eq!(settings.extra_args, ["--photon_noise_iso", "400", "--brotli_effort", "11"]);
+}
+
+#[test]
+fn quoted_paths_and_full_program_paths() {
+    let import = parse_cjxl_command(
+        r#"/usr/local/bin/cjxl "My Photos/img 1.png" 'out dir/img 1.jxl' -q 85 --progressive"#,
+        &ConversionSettings::default(),
+    )
+    .unwrap();
+    assert_eq!(import.settings.quality, 85);
+    // A flag followed by a path doesn't swallow it
+    assert_eq!(import.settings.extra_args, ["--progressive"]);
+    assert!(import.notes.iter().any(|note| note.contains("My Photos/img 1.png")));
+
+    let settings = parse(r#""C:/Program Files/libjxl/cjxl.exe" -q 60 in.jpg out.jxl"#);
+    assert_eq!(settings.quality, 60);
+}
+
+#[test]
+fn lossless_forms() {
+    assert!(parse("cjxl -d 0 in.png out.jxl").lossless);
+    assert!(parse("cjxl in.png out.jxl -q 100").lossless);
+    assert!(!parse("cjxl --lossless_jpeg=0 in.jpg out.jxl").jpeg_lossless);
+    assert!(!parse("cjxl -j 0 in.jpg out.jxl").jpeg_lossless);
+
+    let before = ConversionSettings { jpeg_lossless: false, ..Default::default() };
+    let import = parse_cjxl_command("cjxl --lossless_jpeg in.jpg out.jxl", &before).unwrap();
+    assert!(import.settings.jpeg_lossless);
+}
+
+#[test]
+fn continued_lines_and_prompts() {
+    let settings = parse("$ cjxl input.png output.jxl \\\n    -d 2.5 \\\n    -e 7 --num_threads 4 -v -v");
+    assert_eq!(settings.effort, 7);
+    assert_eq!(settings.quality, 73);
+    assert!(settings.extra_args.is_empty());
+}
+
+#[test]
+fn rejects_other_commands() {
+    let defaults = ConversionSettings::default();
+    assert!(parse_cjxl_command("djxl in.jxl out.png", &defaults).unwrap_err().contains("djxl"));
+    assert!(parse_cjxl_command("ffmpeg -i in.mp4 out.gif", &defaults).unwrap_err().contains("ffmpeg"));
+    assert!(parse_cjxl_command("-d 1 in.png out.jxl", &defaults).is_err());
+    assert!(parse_cjxl_command("cjxl 'in.png out.jxl", &defaults).is_err());
+    assert!(parse_cjxl_command("cjxl -e 12 in.png out.jxl", &defaults).is_err());
+    assert!(parse_cjxl_command("cjxl in.png out.jxl -d", &defaults).is_err());
+    assert!(parse_cjxl_command("   ", &defaults).is_err());
+}
+
+#[test]
+fn changes_list_only_what_differs() {
+    let before = ConversionSettings::default();
+    let after = parse_cjxl_command("cjxl -q 80 -e 7 --resampling=2 a.png b.jxl", &before).unwrap().settings;
+    assert_eq!(
+        describe_changes(&before, &after),
+        ["Quality: 90 → 80", "Extra arguments: (none) → --resampling=2"]
+    );
+}
+
+#[test]
+fn bit_depth_override_is_imported() {
+    let before = ConversionSettings::default();
+    let after = parse_cjxl_command("cjxl --override_bitdepth 10 a.png b.jxl", &before).unwrap().settings;
+    assert_eq!(after.override_bitdepth, Some(10));
+    assert!(after.extra_args.is_empty());
+    assert_eq!(describe_changes(&before, &after), ["Bit depth: (input) → 10"]);
+
+    assert_eq!(parse("cjxl --override_bitdepth=0 a.png b.jxl").override_bitdepth, None);
+    assert!(parse_cjxl_command("cjxl --override_bitdepth=33 a.png b.jxl", &before).is_err());
+}
+
+#[test]
+fn quality_from_distance_inverts_the_mapping() {
+    for quality in [5.0, 29.0, 30.0, 50.0, 90.0, 99.0] {
+        let back = quality_from_distance(distance_from_quality(quality));
+        assert!((back - quality).abs() < 0.01, "{} -> {}", quality, back);
+    }
+    assert_eq!(quality_from_distance(0.0), 100.0);
+    assert_eq!(quality_from_distance(30.0), 0.0);
+}
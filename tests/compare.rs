@@ -0,0 +1,122 @@
+mod support;
+
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use image::{Rgba, RgbaImage};
+use jxl_converter::compare::{compare_files, heatmap, parse_butteraugli_score, pixel_diff, CompareTools};
+use support::fake_tool;
+
+fn gradient() -> RgbaImage {
+    RgbaImage::from_fn(4, 4, |x, y| Rgba([(x * 60) as u8, (y * 60) as u8, 100, 255]))
+}
+
+#[test]
+fn identical_images_have_no_difference() {
+    let diff = pixel_diff(&gradient(), &gradient()).unwrap();
+
+    assert!(diff.is_identical());
+    assert_eq!(diff.psnr, None);
+    // Both are opaque, so alpha isn't compared
+    let channels: Vec<char> = diff.channels.iter().map(|c| c.channel).collect();
+    assert_eq!(channels, ['R', 'G', 'B']);
+}
+
+#[test]
+fn differences_are_measured_per_channel() {
+    let a = gradient();
+    let mut b = gradient();
+    // One pixel of sixteen is 16 off in red, and every pixel 1 off in blue
+    b.get_pixel_mut(0, 0).0[0] += 16;
+    for pixel in b.pixels_mut() {
+        pixel.0[2] += 1;
+    }
+
+    let diff = pixel_diff(&a, &b).unwrap();
+
+    assert_eq!((diff.width, diff.height), (4, 4));
+    let stats: Vec<(char, u8, f64)> = diff.channels.iter().map(|c| (c.channel, c.max, c.mean)).collect();
+    assert_eq!(stats, [('R', 16, 1.0), ('G', 0, 0.0), ('B', 1, 1.0)]);
+    // MSE = (256 + 16) / 48
+    let expected = 10.0 * (255.0f64 * 255.0 / (272.0 / 48.0)).log10();
+    assert!((diff.psnr.unwrap() - expected).abs() < 1e-9, "{:?}", diff.psnr);
+}
+
+#[test]
+fn alpha_is_compared_when_either_image_has_it() {
+    let a = gradient();
+    let mut b = gradient();
+    b.get_pixel_mut(3, 3).0[3] = 0;
+
+    let diff = pixel_diff(&a, &b).unwrap();
+
+    let alpha = diff.channels.last().unwrap();
+    assert_eq!((alpha.channel, alpha.max), ('A', 255));
+}
+
+#[test]
+fn different_sizes_are_refused() {
+    let error = pixel_diff(&gradient(), &RgbaImage::new(4, 5)).unwrap_err();
+    assert_eq!(error, "The images are different sizes: 4×4 and 4×5");
+    assert!(heatmap(&gradient(), &RgbaImage::new(3, 4)).is_err());
+}
+
+#[test]
+fn the_heatmap_runs_from_black_to_white() {
+    let a = RgbaImage::from_pixel(4, 1, Rgba([0, 0, 0, 255]));
+    let mut b = a.clone();
+    b.put_pixel(1, 0, Rgba([10, 0, 0, 255]));
+    b.put_pixel(2, 0, Rgba([0, 0, 40, 255]));
+    b.put_pixel(3, 0, Rgba([255, 0, 0, 255]));
+
+    let map = heatmap(&a, &b).unwrap();
+
+    assert_eq!(map.get_pixel(0, 0).0, [0, 0, 0]);
+    // 10 × 4 gain × 3 steps of the scale
+    assert_eq!(map.get_pixel(1, 0).0, [120, 0, 0]);
+    assert_eq!(map.get_pixel(2, 0).0, [255, 225, 0]);
+    assert_eq!(map.get_pixel(3, 0).0, [255, 255, 255]);
+}
+
+#[test]
+fn butteraugli_scores_are_the_first_number() {
+    assert_eq!(parse_butteraugli_score("1.2345678\n3-norm: 0.543210\n"), Some(1.2345678));
+    assert_eq!(parse_butteraugli_score("no score"), None);
+}
+
+#[test]
+fn jxls_are_decoded_before_comparing() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.png");
+    // The fake djxl copies its input, so a PNG named .jxl "decodes" to itself
+    let b = dir.path().join("b.jxl");
+    gradient().save_with_format(&a, image::ImageFormat::Png).unwrap();
+    gradient().save_with_format(&b, image::ImageFormat::Png).unwrap();
+    let tools = CompareTools { djxl: Some(fake_tool(dir.path(), "djxl")), ..Default::default() };
+    let temp = dir.path().join("temp");
+
+    let mut steps = Vec::new();
+    let comparison = compare_files(&a, &b, &tools, &temp, Arc::new(AtomicBool::new(false)), |done, total, label| {
+        steps.push((done, total, label.to_string()));
+    })
+    .unwrap();
+
+    assert!(comparison.diff.is_identical());
+    assert_eq!(comparison.ssimulacra2, None);
+    assert!(temp.join("b.png").is_file());
+    assert_eq!(steps.first(), Some(&(0, 3, "Reading A".to_string())));
+    assert_eq!(steps.last(), Some(&(3, 3, "Done".to_string())));
+    assert!(comparison.describe().contains(&"PSNR: ∞ (identical pixels)".to_string()));
+}
+
+#[test]
+fn jxls_need_djxl() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.jxl");
+    std::fs::write(&a, b"").unwrap();
+
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+    let result = compare_files(&a, &a, &CompareTools::default(), dir.path(), cancel_flag, |_, _, _| {});
+
+    assert_eq!(result.unwrap_err(), "djxl not found");
+}
@@ -0,0 +1,127 @@
+use jxl_converter::adaptive::AdaptiveQuality;
+use jxl_converter::conflicts::{check_settings, rule_ids, Severity};
+use jxl_converter::tools::{DiscoveredTools, ToolCapabilities, ToolInfo};
+use jxl_converter::ConversionSettings;
+
+fn with(lossless: bool, jpeg_lossless: bool, extra_args: &[&str]) -> ConversionSettings {
+    ConversionSettings {
+        lossless,
+        jpeg_lossless,
+        extra_args: extra_args.iter().map(|arg| arg.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+fn ids(settings: &ConversionSettings) -> Vec<&'static str> {
+    check_settings(settings).into_iter().map(|conflict| conflict.id).collect()
+}
+
+#[test]
+fn defaults_are_clean() {
+    assert!(check_settings(&ConversionSettings::default()).is_empty());
+    assert!(check_settings(&with(true, true, &[])).is_empty());
+    assert!(check_settings(&with(false, false, &["--photon_noise_iso=800", "--resampling", "2"])).is_empty());
+}
+
+#[test]
+fn every_rule_fires_for_its_case() {
+    let cases: Vec<(&str, ConversionSettings)> = vec![
+        ("quality_out_of_range", ConversionSettings { quality: 0, ..Default::default() }),
+        ("effort_out_of_range", ConversionSettings { effort: 11, ..Default::default() }),
+        ("frame_delay_out_of_range", ConversionSettings { frame_delay_ms: Some(0), ..Default::default() }),
+        ("transactional_overwrite", ConversionSettings { transactional: true, ..Default::default() }),
+        ("bitdepth_out_of_range", ConversionSettings { override_bitdepth: Some(40), ..with(false, false, &[]) }),
+        (
+            "adaptive_quality_out_of_range",
+            ConversionSettings {
+                adaptive_quality: Some(AdaptiveQuality { min_score: 90.0, max_score: 80.0, ..Default::default() }),
+                ..Default::default()
+            },
+        ),
+        (
+            "adaptive_lossless",
+            ConversionSettings { adaptive_quality: Some(AdaptiveQuality::default()), ..with(true, true, &[]) },
+        ),
+        (
+            "adaptive_quality_override",
+            ConversionSettings { adaptive_quality: Some(AdaptiveQuality::default()), ..with(false, true, &["-d", "2"]) },
+        ),
+        ("lossless_quality_override", with(true, true, &["-d", "1.5"])),
+        ("quality_override", with(false, true, &["--quality=80"])),
+        ("effort_override", with(false, true, &["-e", "3"])),
+        (
+            "bitdepth_override",
+            ConversionSettings { override_bitdepth: Some(10), ..with(false, false, &["--override_bitdepth=12"]) },
+        ),
+        ("bitdepth_lossless_jpeg", ConversionSettings { override_bitdepth: Some(10), ..Default::default() }),
+        ("resampling_lossless", with(true, true, &["--resampling=2"])),
+        ("resampling_lossless_jpeg", with(false, true, &["--ec_resampling", "4"])),
+        ("lossless_photon_noise", with(true, false, &["--photon_noise_iso=800"])),
+        ("lossless_jpeg_photon_noise", with(false, true, &["--photon_noise=ISO800"])),
+        ("lossless_faster_decoding", with(true, false, &["--faster_decoding=2"])),
+        ("managed_tool_flags", with(false, false, &["--num_threads", "4"])),
+    ];
+
+    let mut covered: Vec<&str> = cases.iter().map(|(id, _)| *id).collect();
+    covered.sort();
+    let mut all = rule_ids();
+    all.sort();
+    assert_eq!(covered, all, "every rule needs a case");
+
+    for (id, settings) in &cases {
+        assert!(ids(settings).contains(id), "{} didn't fire for {:?}", id, settings.extra_args);
+    }
+}
+
+#[test]
+fn values_that_change_nothing_are_fine() {
+    assert!(ids(&with(true, true, &["-d", "0"])).is_empty());
+    assert!(ids(&with(true, true, &["-q", "100"])).is_empty());
+    assert!(ids(&with(false, true, &["--resampling=1"])).is_empty());
+    assert!(ids(&with(false, true, &["--resampling", "-1"])).is_empty());
+    assert!(ids(&with(true, false, &["--photon_noise_iso", "0"])).is_empty());
+}
+
+#[test]
+fn hard_conflicts_come_first_and_block_the_batch() {
+    let settings = with(true, true, &["--photon_noise_iso=800", "-d", "2"]);
+    let conflicts = check_settings(&settings);
+    assert_eq!(conflicts[0].severity, Severity::Hard);
+    assert_eq!(conflicts.last().unwrap().severity, Severity::Soft);
+
+    let cjxl = ToolInfo {
+        path: "cjxl".into(),
+        version: Some("0.10.2".to_string()),
+        capabilities: ToolCapabilities { supports_photon_noise: true, ..Default::default() },
+    };
+    let tools = DiscoveredTools { cjxl: Some(cjxl), ..Default::default() };
+    let engine = jxl_converter::ConversionEngine::from_tools(&tools);
+    let error = engine.validate_settings(&settings).unwrap_err();
+    assert!(error.contains("lossy"), "{}", error);
+    assert!(engine.validate_settings(&with(true, false, &["--photon_noise_iso=800"])).is_ok());
+}
+
+#[test]
+fn bit_depth_override_reaches_cjxl_unless_jpegs_are_transcoded() {
+    let engine = jxl_converter::ConversionEngine::from_tools(&Default::default());
+    let has_flag = |input: &str, settings: &ConversionSettings| {
+        let args = engine.cjxl_args(input.as_ref(), "out.jxl".as_ref(), settings);
+        args.iter().any(|arg| arg == "--override_bitdepth=10")
+    };
+
+    // The defaults transcode JPEGs, so a mixed batch is only warned and still starts
+    let mixed = ConversionSettings { override_bitdepth: Some(10), ..Default::default() };
+    assert_eq!(ids(&mixed), ["bitdepth_lossless_jpeg"]);
+    assert_eq!(check_settings(&mixed)[0].severity, Severity::Soft);
+    assert!(engine.validate_settings(&mixed).is_ok());
+    assert!(has_flag("a.png", &mixed));
+    assert!(!has_flag("b.jpg", &mixed));
+
+    let lossless = ConversionSettings { lossless: true, ..mixed.clone() };
+    assert!(has_flag("a.png", &lossless));
+    assert!(!has_flag("b.jpg", &lossless));
+
+    let reencoding = ConversionSettings { jpeg_lossless: false, ..mixed };
+    assert!(ids(&reencoding).is_empty());
+    assert!(has_flag("b.jpg", &reencoding));
+}
@@ -0,0 +1,133 @@
+mod support;
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+
+use jxl_converter::download::{download, file_name_for, sniff_image_type, url_of};
+use jxl_converter::{ConversionSettings, ProgressMessage};
+use support::{encode_settings, fake_engine, kinds, outputs, run_encode};
+
+const JPEG: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F'];
+
+/// Serves `routes` (path, status, content type, body) over HTTP on a local
+/// port and returns its base URL. Unknown paths get a 404.
+fn serve(routes: Vec<(&'static str, &'static str, &'static str, Vec<u8>)>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut request = Vec::new();
+            let mut byte = [0u8; 1];
+            while !request.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap_or(0) == 1 {
+                request.push(byte[0]);
+            }
+            let request = String::from_utf8_lossy(&request);
+            let path = request.split_whitespace().nth(1).unwrap_or("/").split('?').next().unwrap_or("/");
+            let (status, content_type, body) = routes
+                .iter()
+                .find(|(route, ..)| *route == path)
+                .map(|(_, status, content_type, body)| (*status, *content_type, body.clone()))
+                .unwrap_or(("404 Not Found", "text/plain", b"missing".to_vec()));
+            let head = format!(
+                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                content_type,
+                body.len()
+            );
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(&body);
+        }
+    });
+    base
+}
+
+#[test]
+fn urls_are_told_apart_from_paths() {
+    assert_eq!(url_of(&PathBuf::from("https://example.com/a.png")), Some("https://example.com/a.png"));
+    assert!(url_of(&PathBuf::from("HTTP://example.com/a.png")).is_some());
+    assert!(url_of(&PathBuf::from("/tmp/http://a.png")).is_none());
+    assert!(url_of(&PathBuf::from("ftp://example.com/a.png")).is_none());
+}
+
+#[test]
+fn names_come_from_the_last_path_segment() {
+    let jpeg = sniff_image_type(JPEG).unwrap();
+    assert_eq!(file_name_for("https://example.com/img/My%20Photo.JPG?size=large#top", jpeg), "My Photo.JPG");
+    assert_eq!(file_name_for("https://example.com/img/photo", jpeg), "photo.jpg");
+    assert_eq!(file_name_for("https://example.com/render.php?id=3", jpeg), "render.php.jpg");
+    assert_eq!(file_name_for("https://example.com/", jpeg), "download.jpg");
+    assert_eq!(file_name_for("https://example.com/a%2F..%5Cb.png", &["png"]), "a_.._b.png");
+
+    assert_eq!(sniff_image_type(b"GIF89a...."), Some(&["gif"][..]));
+    assert_eq!(sniff_image_type(b"<!DOCTYPE html>"), None);
+}
+
+#[test]
+fn downloads_are_checked_before_being_written() {
+    let base = serve(vec![
+        ("/photo", "200 OK", "image/jpeg", JPEG.to_vec()),
+        ("/page.jpg", "200 OK", "text/html", b"<html></html>".to_vec()),
+        ("/big.jpg", "200 OK", "image/jpeg", [JPEG, &[0u8; 4096]].concat()),
+    ]);
+    let dir = tempfile::tempdir().unwrap();
+    let cancel = AtomicBool::new(false);
+
+    let mut progress = Vec::new();
+    let path = download(&format!("{}/photo", base), dir.path(), 1024, &cancel, |received, total| {
+        progress.push((received, total));
+    })
+    .unwrap();
+    assert_eq!(path, dir.path().join("photo.jpg"));
+    assert_eq!(std::fs::read(&path).unwrap(), JPEG);
+    assert_eq!(progress.last(), Some(&(JPEG.len() as u64, Some(JPEG.len() as u64))));
+
+    let missing = download(&format!("{}/gone.png", base), dir.path(), 1024, &cancel, |_, _| {}).unwrap_err();
+    assert!(missing.contains("404"), "{}", missing);
+
+    let page = download(&format!("{}/page.jpg", base), dir.path(), 1024, &cancel, |_, _| {}).unwrap_err();
+    assert!(page.contains("text/html"), "{}", page);
+
+    let big = download(&format!("{}/big.jpg", base), dir.path(), 1024, &cancel, |_, _| {}).unwrap_err();
+    assert!(big.contains("larger"), "{}", big);
+
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn url_inputs_are_downloaded_and_encoded() {
+    let base = serve(vec![("/images/cat.jpg", "200 OK", "image/jpeg", JPEG.to_vec())]);
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let engine = fake_engine(dir.path(), &[]);
+    let local = support::touch(dir.path(), "in/dog.png");
+
+    let inputs = vec![
+        local,
+        PathBuf::from(format!("{}/images/cat.jpg", base)),
+        PathBuf::from(format!("{}/nothing.jpg", base)),
+    ];
+    let settings = ConversionSettings { keep_downloads: true, ..encode_settings(&out) };
+    let messages = run_encode(&engine, inputs, settings);
+
+    assert_eq!(
+        kinds(&messages),
+        ["started", "progress", "success", "progress", "error", "progress", "success", "completed"]
+    );
+    assert!(matches!(messages[0], ProgressMessage::Started { total: 3 }));
+    assert_eq!(outputs(&messages), [out.join("cat.jxl"), out.join("dog.jxl")]);
+    assert_eq!(std::fs::read(out.join("cat.jxl")).unwrap(), JPEG);
+
+    let kept = messages
+        .iter()
+        .find_map(|msg| match msg {
+            ProgressMessage::Info { message } => message.strip_prefix("Downloaded images were kept in "),
+            _ => None,
+        })
+        .map(PathBuf::from)
+        .unwrap();
+    assert!(kept.join("0").join("cat.jpg").is_file());
+    std::fs::remove_dir_all(kept).unwrap();
+}
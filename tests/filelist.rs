@@ -0,0 +1,60 @@
+use std::path::PathBuf;
+
+use jxl_converter::filelist::{parse_list, write_list, ListEntry};
+use jxl_converter::OutputFormat;
+
+fn entry(path: PathBuf, formats: &[OutputFormat]) -> ListEntry {
+    ListEntry { path, formats: formats.to_vec() }
+}
+
+#[test]
+fn written_lists_read_back_unchanged() {
+    let dir = tempfile::tempdir().unwrap();
+    let entries = vec![
+        entry(dir.path().join("photos/a.png"), &[]),
+        entry(dir.path().join("photos/b c.jxl"), &[OutputFormat::Png]),
+        entry(dir.path().join("scans/d.jxl"), &[OutputFormat::Jpeg, OutputFormat::Gif]),
+        entry(dir.path().join("folder"), &[]),
+    ];
+    let list = dir.path().join("list.txt");
+
+    assert_eq!(write_list(&list, &entries).unwrap(), 0);
+    let content = std::fs::read_to_string(&list).unwrap();
+
+    assert_eq!(parse_list(&content, dir.path()), entries);
+}
+
+#[test]
+fn relative_paths_comments_and_blank_lines() {
+    let base = PathBuf::from("lists");
+    let content = "\u{feff}#EXTM3U\n\
+        # a comment\n\
+        \n\
+        a.png\n\
+        \x20 sub/b.jxl\tpng,jpg \n\
+        c.jxl\tnot-a-format\n";
+
+    let entries = parse_list(content, &base);
+
+    assert_eq!(
+        entries,
+        [
+            entry(base.join("a.png"), &[]),
+            entry(base.join("sub/b.jxl"), &[OutputFormat::Png, OutputFormat::Jpeg]),
+            // An unknown suffix is part of the name rather than a format
+            entry(base.join("c.jxl\tnot-a-format"), &[]),
+        ]
+    );
+}
+
+#[test]
+fn paths_that_cannot_be_a_line_are_skipped() {
+    let dir = tempfile::tempdir().unwrap();
+    let list = dir.path().join("list.txt");
+    let entries = [entry(dir.path().join("a.png"), &[]), entry(dir.path().join("b\nc.png"), &[])];
+
+    assert_eq!(write_list(&list, &entries).unwrap(), 1);
+    let content = std::fs::read_to_string(&list).unwrap();
+
+    assert_eq!(parse_list(&content, dir.path()), entries[..1]);
+}
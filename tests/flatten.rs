@@ -0,0 +1,231 @@
+mod support;
+
+use std::path::Path;
+
+use image::{Rgb, RgbImage, Rgba, RgbaImage};
+use jxl_converter::breakdown::losing_animation;
+use jxl_converter::flatten::{jxl_has_alpha, jxl_is_animated, write_flattened};
+use jxl_converter::types::Color;
+use jxl_converter::{DecodeItem, DecodeSettings, OutputFormat, ProgressMessage};
+
+use support::{fake_engine, kinds, run_decode, JXL_HEADER};
+
+/// Writes a JXL bitstream, least significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn push(&mut self, value: u32, count: usize) -> &mut Self {
+        for i in 0..count {
+            if self.len.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            let bit = ((value >> i) & 1) as u8;
+            *self.bytes.last_mut().unwrap() |= bit << (self.len % 8);
+            self.len += 1;
+        }
+        self
+    }
+
+    fn codestream(&self) -> Vec<u8> {
+        let mut data = vec![0xFF, 0x0A];
+        data.extend(&self.bytes);
+        data
+    }
+}
+
+/// An 8×8 SizeHeader followed by ImageMetadata with every optional header,
+/// then two extra channels, the first of type `first_type` (0 is alpha).
+fn full_header(first_type_selector: u32) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    bits.push(1, 1).push(0, 5).push(1, 3); // small, ysize, ratio 1:1
+    bits.push(0, 1).push(1, 1).push(0, 3); // not all_default, extra_fields, orientation
+    bits.push(0, 1); // no intrinsic size
+    bits.push(1, 1).push(1, 1).push(0, 2).push(1, 3); // preview: div8, ysize 16, ratio
+    bits.push(1, 1).push(0, 2).push(0, 2).push(1, 2).push(5, 3).push(0, 1); // animation
+    bits.push(0, 1).push(3, 2).push(15, 6); // 16-bit integer samples
+    bits.push(0, 1); // modular_16_bit_buffer_sufficient
+    bits.push(2, 2).push(0, 4); // two extra channels
+    bits.push(0, 1).push(first_type_selector, 2); // not default, type Val(0) or Val(1)
+    bits.codestream()
+}
+
+/// An 8×8 animation at 100 ticks per second, with no extra channels.
+fn animated_header() -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    bits.push(1, 1).push(0, 5).push(1, 3); // small, ysize, ratio 1:1
+    bits.push(0, 1).push(1, 1).push(0, 3); // not all_default, extra_fields, orientation
+    bits.push(0, 1).push(0, 1); // no intrinsic size, no preview
+    bits.push(1, 1).push(0, 2).push(0, 2).push(0, 2).push(0, 1); // animation
+    bits.push(0, 1).push(0, 2); // 8-bit integer samples
+    bits.push(1, 1); // modular_16_bit_buffer_sufficient
+    bits.push(0, 2); // no extra channels
+    bits.codestream()
+}
+
+fn write(path: &Path, data: &[u8]) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, data).unwrap();
+}
+
+#[test]
+fn alpha_is_read_from_the_header() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.jxl");
+
+    write(&path, JXL_HEADER);
+    assert_eq!(jxl_has_alpha(&path), Some(false));
+
+    // Default metadata with a single default (alpha) extra channel
+    write(&path, &[0xFF, 0x0A, 0x41, 0xC0, 0x02]);
+    assert_eq!(jxl_has_alpha(&path), Some(true));
+
+    write(&path, &full_header(0));
+    assert_eq!(jxl_has_alpha(&path), Some(true));
+
+    // A depth channel first; the second one isn't looked at
+    write(&path, &full_header(1));
+    assert_eq!(jxl_has_alpha(&path), None);
+
+    write(&path, b"not a jxl");
+    assert_eq!(jxl_has_alpha(&path), None);
+}
+
+#[test]
+fn alpha_is_found_inside_a_container() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.jxl");
+    let mut data = vec![0, 0, 0, 0x0C, b'J', b'X', b'L', b' ', 0x0D, 0x0A, 0x87, 0x0A];
+    data.extend([0, 0, 0, 0x0D, b'j', b'x', b'l', b'p', 0, 0, 0, 0, 0xFF, 0x0A, 0x41, 0xC0, 0x02]);
+    write(&path, &data);
+    assert_eq!(jxl_has_alpha(&path), Some(true));
+}
+
+#[test]
+fn animation_is_read_from_the_header() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.jxl");
+
+    write(&path, &animated_header());
+    assert_eq!(jxl_is_animated(&path), Some(true));
+    assert_eq!(jxl_has_alpha(&path), Some(false));
+
+    write(&path, &full_header(0));
+    assert_eq!(jxl_is_animated(&path), Some(true));
+
+    write(&path, JXL_HEADER);
+    assert_eq!(jxl_is_animated(&path), Some(false));
+
+    write(&path, b"not a jxl");
+    assert_eq!(jxl_is_animated(&path), None);
+}
+
+#[test]
+fn animations_decoded_to_still_formats_are_flagged() {
+    let dir = tempfile::tempdir().unwrap();
+    let animated = dir.path().join("in/animated.jxl");
+    let still = dir.path().join("in/still.jxl");
+    write(&animated, &animated_header());
+    write(&still, JXL_HEADER);
+    let out = dir.path().join("out");
+    let items: Vec<DecodeItem> = [&animated, &still]
+        .into_iter()
+        .map(|path| DecodeItem {
+            path: path.clone(),
+            output_formats: vec![OutputFormat::Png, OutputFormat::Ppm],
+            folder: None,
+        })
+        .collect();
+    let settings = DecodeSettings { output_dir: out.clone(), ..Default::default() };
+
+    assert_eq!(losing_animation(&items, &settings), [animated.as_path()]);
+    let gifs = [DecodeItem { path: animated.clone(), output_formats: vec![OutputFormat::Gif], folder: None }];
+    assert!(losing_animation(&gifs, &settings).is_empty());
+
+    let engine = fake_engine(dir.path(), &[]);
+    let decoded: Vec<String> = run_decode(&engine, items, settings)
+        .into_iter()
+        .filter_map(|msg| match msg {
+            ProgressMessage::Success { file, .. } => Some(file),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(decoded.len(), 4);
+    assert!(!decoded[0].ends_with(")"), "{}", decoded[0]);
+    assert!(decoded[1].ends_with("animated.ppm (first frame only)"), "{}", decoded[1]);
+    assert!(!decoded[3].ends_with(")"), "{}", decoded[3]);
+}
+
+#[test]
+fn transparent_pixels_take_the_background() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = dir.path().join("in.png");
+    let mut image = RgbaImage::new(3, 1);
+    image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+    image.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+    image.put_pixel(2, 0, Rgba([0, 0, 255, 128]));
+    image.save(&png).unwrap();
+
+    let out = dir.path().join("out.ppm");
+    assert!(write_flattened(&png, &out, OutputFormat::Ppm, Color([0, 255, 0])).unwrap());
+
+    let flat = image::open(&out).unwrap().to_rgb8();
+    assert_eq!(flat.get_pixel(0, 0), &Rgb([255, 0, 0]));
+    assert_eq!(flat.get_pixel(1, 0), &Rgb([0, 255, 0]));
+    assert_eq!(flat.get_pixel(2, 0), &Rgb([0, 127, 128]));
+}
+
+#[test]
+fn opaque_images_are_only_converted() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = dir.path().join("in.png");
+    RgbImage::from_pixel(2, 2, Rgb([10, 20, 30])).save(&png).unwrap();
+
+    for format in [OutputFormat::Jpeg, OutputFormat::Ppm, OutputFormat::Pgm, OutputFormat::Pbm] {
+        let out = dir.path().join(format!("out.{}", format.extension()));
+        assert!(!write_flattened(&png, &out, format, Color::WHITE).unwrap());
+        assert!(image::open(&out).is_ok(), "{} didn't round-trip", format.name());
+    }
+}
+
+#[test]
+fn decode_batch_flattens_transparent_images_for_jpeg() {
+    let dir = tempfile::tempdir().unwrap();
+    // The fake djxl copies its input, so a PNG named .jxl "decodes" to itself;
+    // its header isn't a JXL one, so the engine has to look at the pixels
+    let transparent = dir.path().join("in/transparent.jxl");
+    let opaque = dir.path().join("in/opaque.jxl");
+    std::fs::create_dir_all(dir.path().join("in")).unwrap();
+    RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 0])).save_with_format(&transparent, image::ImageFormat::Png).unwrap();
+    RgbImage::from_pixel(2, 2, Rgb([0, 0, 0])).save_with_format(&opaque, image::ImageFormat::Png).unwrap();
+    let out = dir.path().join("out");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let items = [&transparent, &opaque]
+        .into_iter()
+        .map(|path| DecodeItem { path: path.clone(), output_formats: vec![OutputFormat::Jpeg], folder: None })
+        .collect();
+    let settings = DecodeSettings { output_dir: out.clone(), ..Default::default() };
+    let messages = run_decode(&engine, items, settings);
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "progress", "success", "completed"]);
+    let notes: Vec<&String> = messages
+        .iter()
+        .filter_map(|msg| match msg {
+            ProgressMessage::Info { message } if message.contains("flattened") => Some(message),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(notes.len(), 1);
+    assert!(notes[0].contains("transparent.jxl") && notes[0].ends_with("#FFFFFF"));
+
+    let white = image::open(out.join("transparent.jpg")).unwrap().to_rgb8();
+    assert!(white.get_pixel(0, 0).0.iter().all(|&c| c > 250));
+    let black = image::open(out.join("opaque.jpg")).unwrap().to_rgb8();
+    assert!(black.get_pixel(0, 0).0.iter().all(|&c| c < 5));
+    // No temporary PNGs left behind
+    assert_eq!(std::fs::read_dir(&out).unwrap().count(), 2);
+}
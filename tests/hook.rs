@@ -0,0 +1,164 @@
+mod support;
+
+use std::path::Path;
+
+use jxl_converter::hook::expand_post_hook;
+use jxl_converter::{ConversionEngine, ProgressMessage, ToolSettings};
+use support::{encode_settings, fake_engine, kinds, run_encode, touch};
+
+/// The fake engine with a post hook; the fake tools fail on inputs
+/// containing "bad".
+fn engine_with_hook(dir: &Path, post_hook: &str, always: bool) -> ConversionEngine {
+    fake_engine(dir, &[]).with_tool_settings(ToolSettings {
+        env_overrides: vec![("FAKE_TOOL_FAIL_ON".to_string(), "bad".to_string())],
+        post_hook: post_hook.to_string(),
+        post_hook_always: always,
+        ..Default::default()
+    })
+}
+
+fn warnings(messages: &[ProgressMessage]) -> Vec<&str> {
+    messages
+        .iter()
+        .filter_map(|msg| match msg {
+            ProgressMessage::Warning { message } => Some(message.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+#[test]
+fn placeholders_are_quoted_once() {
+    let expanded = expand_post_hook(
+        "tool {input} --to={output} {status} {unknown} {",
+        Path::new("/in/it's {output}.png"),
+        Some(Path::new("/out/a.jxl")),
+        "success",
+    );
+    assert_eq!(expanded, r"tool '/in/it'\''s {output}.png' --to=/out/a.jxl success {unknown} {");
+
+    assert_eq!(expand_post_hook("tool {output}", Path::new("a"), None, "error"), "tool ''");
+}
+
+#[cfg(unix)]
+#[test]
+fn the_hook_runs_after_converted_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let log = dir.path().join("hook.log");
+    let hook = format!("echo {{status}} {{output}} >> '{}'", log.display());
+    let engine = engine_with_hook(dir.path(), &hook, false);
+    let inputs = vec![touch(dir.path(), "in/good.png"), touch(dir.path(), "in/bad.png")];
+
+    let messages = run_encode(&engine, inputs, encode_settings(&out));
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "progress", "error", "completed"]);
+    let logged = std::fs::read_to_string(&log).unwrap();
+    assert_eq!(logged, format!("success {}\n", out.canonicalize().unwrap().join("good.jxl").display()));
+    assert!(warnings(&messages).is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn the_hook_can_run_after_failures_too() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("hook.log");
+    let hook = format!("echo {{status}} >> '{}'", log.display());
+    let engine = engine_with_hook(dir.path(), &hook, true);
+    let inputs = vec![touch(dir.path(), "in/good.png"), touch(dir.path(), "in/bad.png")];
+
+    run_encode(&engine, inputs, encode_settings(&dir.path().join("out")));
+
+    assert_eq!(std::fs::read_to_string(&log).unwrap(), "success\nerror\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn a_failing_hook_is_only_a_warning() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = engine_with_hook(dir.path(), "echo upload refused >&2; exit 3", false);
+
+    let messages = run_encode(&engine, vec![touch(dir.path(), "in/good.png")], encode_settings(&dir.path().join("out")));
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "completed"]);
+    let warnings = warnings(&messages);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].ends_with("post hook exited with status 3: upload refused"), "{}", warnings[0]);
+}
+
+/// The fake engine with batch hooks.
+fn engine_with_batch_hooks(dir: &Path, pre: &str, post: &str, timeout_secs: Option<u64>) -> ConversionEngine {
+    fake_engine(dir, &[]).with_tool_settings(ToolSettings {
+        env_overrides: vec![("FAKE_TOOL_FAIL_ON".to_string(), "bad".to_string())],
+        pre_batch_hook: pre.to_string(),
+        post_batch_hook: post.to_string(),
+        batch_hook_timeout_secs: timeout_secs,
+        ..Default::default()
+    })
+}
+
+fn infos(messages: &[ProgressMessage]) -> Vec<&str> {
+    messages
+        .iter()
+        .filter_map(|msg| match msg {
+            ProgressMessage::Info { message } => Some(message.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+#[test]
+fn a_failing_pre_batch_hook_stops_the_batch() {
+    let dir = tempfile::tempdir().unwrap();
+    let marker = dir.path().join("post-ran");
+    let post = format!("touch '{}'", marker.display());
+    let engine = engine_with_batch_hooks(dir.path(), "echo share not mounted; exit 2", &post, None);
+
+    let messages = run_encode(&engine, vec![touch(dir.path(), "in/good.png")], encode_settings(&dir.path().join("out")));
+
+    assert_eq!(kinds(&messages), ["error"]);
+    assert!(
+        matches!(&messages.last(), Some(ProgressMessage::Error { file, error }) if file.is_empty() && error == "Pre-batch hook exited with status 2")
+    );
+    assert!(infos(&messages).contains(&"    share not mounted"));
+    assert!(!dir.path().join("out").exists());
+    assert!(!marker.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn the_post_batch_hook_sees_the_totals() {
+    let dir = tempfile::tempdir().unwrap();
+    let post = "echo $JXL_STATUS converted=$JXL_CONVERTED failed=$JXL_FAILED skipped=$JXL_SKIPPED saved=$JXL_BYTES_SAVED";
+    let engine = engine_with_batch_hooks(dir.path(), "true", post, None);
+    let inputs = vec![touch(dir.path(), "in/good.png"), touch(dir.path(), "in/bad.png")];
+
+    let messages = run_encode(&engine, inputs, encode_settings(&dir.path().join("out")));
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "progress", "error", "completed"]);
+    // The fake tools copy, so nothing is saved
+    let completed = messages.iter().position(|msg| matches!(msg, ProgressMessage::Completed)).unwrap();
+    assert_eq!(
+        infos(&messages[completed..]),
+        ["Running post-batch hook", "    completed converted=1 failed=1 skipped=0 saved=0"]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn batch_hooks_time_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = engine_with_batch_hooks(dir.path(), "sleep 10", "", Some(1));
+
+    let started = std::time::Instant::now();
+    let messages = run_encode(&engine, vec![touch(dir.path(), "in/good.png")], encode_settings(&dir.path().join("out")));
+
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert!(
+        matches!(&messages.last(), Some(ProgressMessage::Error { error, .. }) if error == "Pre-batch hook killed: still running after 1 s"),
+        "{:?}",
+        messages.last()
+    );
+}
@@ -0,0 +1,79 @@
+mod support;
+
+use std::io::Write;
+
+use jxl_converter::journal::{find_unfinished, Journal, JournalBatch, UnfinishedBatch};
+use jxl_converter::{DecodeItem, DecodeSettings, OutputFormat};
+use support::{encode_settings, fake_engine, kinds, outputs, run_decode, run_encode, touch, touch_jxl};
+
+#[test]
+fn a_torn_last_line_is_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.png");
+    let batch = JournalBatch::Encode { inputs: vec![a.clone()], settings: encode_settings(dir.path()) };
+    let mut journal = Journal::create(dir.path(), batch, Vec::new()).unwrap();
+    journal.started(3).unwrap();
+    journal.record(&a, "success").unwrap();
+    let path = journal.path().to_path_buf();
+    // Power lost halfway through the next line
+    std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"type\":\"file\",\"fi").unwrap();
+
+    let unfinished = UnfinishedBatch::read(&path).unwrap();
+
+    assert_eq!(unfinished.done, [a]);
+    assert_eq!(unfinished.remaining(), Some(2));
+    assert_eq!(find_unfinished(dir.path()).len(), 1);
+}
+
+#[test]
+fn finished_batches_delete_their_journal() {
+    let dir = tempfile::tempdir().unwrap();
+    let journals = dir.path().join("journals");
+    let engine = fake_engine(dir.path(), &[]).with_journal_dir(journals.clone());
+
+    let messages = run_encode(&engine, vec![touch(dir.path(), "in/a.png")], encode_settings(&dir.path().join("out")));
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "completed"]);
+    assert_eq!(std::fs::read_dir(&journals).unwrap().count(), 0);
+}
+
+#[test]
+fn resumed_encodes_leave_out_finished_inputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let journals = dir.path().join("journals");
+    let inputs = vec![touch(dir.path(), "in/a.png"), touch(dir.path(), "in/b.png")];
+    let out = dir.path().join("out");
+    let batch = JournalBatch::Encode { inputs: inputs.clone(), settings: encode_settings(&out) };
+    let mut journal = Journal::create(&journals, batch, Vec::new()).unwrap();
+    journal.started(2).unwrap();
+    journal.record(&inputs[0], "success").unwrap();
+    let unfinished = find_unfinished(&journals).remove(0);
+    assert_eq!(unfinished.remaining(), Some(1));
+
+    let engine = fake_engine(dir.path(), &[]).with_journal_dir(journals.clone()).with_resume(unfinished.resume());
+    let messages = run_encode(&engine, inputs, encode_settings(&out));
+
+    assert_eq!(outputs(&messages), [out.join("b.jxl")]);
+    // The old journal was replaced, and the new one deleted once done
+    assert_eq!(std::fs::read_dir(&journals).unwrap().count(), 0);
+}
+
+#[test]
+fn resumed_decodes_leave_out_finished_formats() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = touch_jxl(dir.path(), "in/a.jxl");
+    let out = dir.path().join("out");
+    let items = vec![DecodeItem { path: input.clone(), output_formats: vec![OutputFormat::Png, OutputFormat::Ppm], folder: None }];
+    let settings = DecodeSettings { output_dir: out.clone(), ..Default::default() };
+    let journals = dir.path().join("journals");
+    let batch = JournalBatch::Decode { items: items.clone(), settings: settings.clone() };
+    let mut journal = Journal::create(&journals, batch, Vec::new()).unwrap();
+    journal.started(2).unwrap();
+    journal.record(&input, "success").unwrap();
+
+    let resume = find_unfinished(&journals).remove(0).resume();
+    let engine = fake_engine(dir.path(), &[]).with_resume(resume);
+    let messages = run_decode(&engine, items, settings);
+
+    assert_eq!(outputs(&messages), [out.join("a.ppm")]);
+}
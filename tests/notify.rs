@@ -0,0 +1,114 @@
+mod support;
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, UNIX_EPOCH};
+
+use jxl_converter::notify::post_summary;
+use jxl_converter::summary::{format_utc, BatchSummary};
+use jxl_converter::{ProgressMessage, ToolSettings};
+use serde_json::Value;
+use support::{encode_settings, fake_engine, run_encode, touch};
+
+/// A request the test server received.
+struct Request {
+    head: String,
+    body: String,
+}
+
+/// Answers one request per entry of `statuses`, in order, and passes each
+/// request on. Returns the server's URL.
+fn serve(statuses: Vec<&'static str>) -> (String, Receiver<Request>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let (tx, rx) = channel();
+    std::thread::spawn(move || {
+        for status in statuses {
+            let Ok((mut stream, _)) = listener.accept() else { return };
+            let mut head = Vec::new();
+            let mut byte = [0u8; 1];
+            while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap_or(0) == 1 {
+                head.push(byte[0]);
+            }
+            let head = String::from_utf8_lossy(&head).into_owned();
+            let length = head
+                .lines()
+                .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0);
+            let mut body = vec![0u8; length];
+            let _ = stream.read_exact(&mut body);
+            let _ = stream.write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).as_bytes());
+            let _ = tx.send(Request { head, body: String::from_utf8_lossy(&body).into_owned() });
+        }
+    });
+    (url, rx)
+}
+
+#[test]
+fn timestamps_are_rfc_3339_utc() {
+    assert_eq!(format_utc(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    assert_eq!(format_utc(UNIX_EPOCH + Duration::from_secs(1_700_000_000)), "2023-11-14T22:13:20Z");
+    assert_eq!(format_utc(UNIX_EPOCH + Duration::from_secs(951_782_400)), "2000-02-29T00:00:00Z");
+}
+
+#[test]
+fn server_errors_are_retried() {
+    let (url, requests) = serve(vec!["503 Service Unavailable", "200 OK"]);
+    let mut summary = BatchSummary::begin();
+    summary.converted = 4;
+
+    let mut retries = Vec::new();
+    post_summary(&url, "s3cret", &summary, Duration::from_millis(10), |attempt, error| {
+        retries.push((attempt, error.to_string()));
+    })
+    .unwrap();
+
+    assert_eq!(retries, [(1, "HTTP 503 Service Unavailable".to_string())]);
+    let first = requests.recv().unwrap();
+    let second = requests.recv().unwrap();
+    assert_eq!(first.body, second.body);
+    assert!(second.head.starts_with("POST /hook "));
+    assert!(second.head.contains("Authorization: Bearer s3cret"), "{}", second.head);
+
+    let body: Value = serde_json::from_str(&second.body).unwrap();
+    assert_eq!(body["type"], "summary");
+    assert_eq!(body["converted"], 4);
+    assert_eq!(body["status"], "aborted");
+    assert!(body["started_at"].as_str().unwrap().ends_with('Z'));
+}
+
+#[test]
+fn client_errors_are_not_retried() {
+    let (url, requests) = serve(vec!["401 Unauthorized", "200 OK"]);
+
+    let error = post_summary(&url, "", &BatchSummary::default(), Duration::from_millis(10), |_, _| {
+        panic!("retried a 401")
+    })
+    .unwrap_err();
+
+    assert_eq!(error, "HTTP 401 Unauthorized");
+    assert!(!requests.recv().unwrap().head.contains("Authorization"));
+}
+
+#[test]
+fn batches_post_their_summary_when_they_end() {
+    let (url, requests) = serve(vec!["204 No Content"]);
+    let dir = tempfile::tempdir().unwrap();
+    let engine = fake_engine(dir.path(), &[]).with_tool_settings(ToolSettings {
+        notify_url: url.clone(),
+        batch_label: "nightly".to_string(),
+        ..Default::default()
+    });
+
+    let messages = run_encode(&engine, vec![touch(dir.path(), "in/a.png")], encode_settings(&dir.path().join("out")));
+
+    assert!(matches!(messages.last(), Some(ProgressMessage::Info { message }) if *message == format!("Notified {}", url)));
+    let body: Value = serde_json::from_str(&requests.recv().unwrap().body).unwrap();
+    assert_eq!(body["converted"], 1);
+    assert_eq!(body["bytes_in"], 8);
+    assert_eq!(body["status"], "completed");
+    assert_eq!(body["label"], "nightly");
+    assert!(body["duration_secs"].as_f64().unwrap() >= 0.0);
+}
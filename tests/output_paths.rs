@@ -0,0 +1,305 @@
+use std::path::{Path, PathBuf};
+
+use jxl_converter::paths::{
+    check_output_dir, common_bases, expand_path, is_case_insensitive, output_key, path_key, plan_output_path,
+    OutputClaims, OutputDirStatus, OutputPlan,
+};
+use jxl_converter::ConversionSettings;
+
+fn plan(base: Option<&str>, extension: &str) -> OutputPlan {
+    OutputPlan {
+        output_dir: PathBuf::from("/out"),
+        bases: base.map(PathBuf::from).into_iter().collect(),
+        extension: extension.to_string(),
+        inner_extension: false,
+    }
+}
+
+fn bases(dirs: &[&str]) -> Vec<PathBuf> {
+    common_bases(&dirs.iter().map(PathBuf::from).collect::<Vec<_>>())
+}
+
+fn output(input: &str, plan: &OutputPlan) -> PathBuf {
+    plan_output_path(Path::new(input), plan)
+}
+
+#[test]
+fn flat_output_uses_the_file_name() {
+    let plan = plan(None, "jxl");
+    assert_eq!(output("/photos/2024/a.png", &plan), Path::new("/out/a.jxl"));
+    assert_eq!(output("a.png", &plan), Path::new("/out/a.jxl"));
+}
+
+#[test]
+fn double_extensions_are_replaced_when_decoding() {
+    let cases = [
+        ("/in/scan.tiff.jxl", "png", "/out/scan.png"),
+        ("/in/photo.jpg.jxl", "jpg", "/out/photo.jpg"),
+        ("/in/photo.JPEG.jxl", "jpg", "/out/photo.JPEG"),
+        ("/in/photo.jpg.JXL", "png", "/out/photo.png"),
+        ("/in/a.b.c.jxl", "png", "/out/a.b.c.png"),
+        ("/in/v1.2.jxl", "png", "/out/v1.2.png"),
+        ("/in/.hidden.jxl", "png", "/out/.hidden.png"),
+        ("/in/.png.jxl", "png", "/out/.png.png"),
+        ("/in/a.png.jxl.jxl", "png", "/out/a.png.jxl.png"),
+        ("/in/noext", "png", "/out/noext.png"),
+    ];
+    for (input, extension, expected) in cases {
+        let plan = OutputPlan { inner_extension: true, ..plan(None, extension) };
+        assert_eq!(output(input, &plan), Path::new(expected), "{}", input);
+    }
+
+    // Off, only the last extension is replaced
+    assert_eq!(output("/in/scan.tiff.jxl", &plan(None, "png")), Path::new("/out/scan.tiff.png"));
+}
+
+#[test]
+fn nested_structure_is_kept_relative_to_the_base() {
+    let plan = plan(Some("/photos"), "jxl");
+    assert_eq!(output("/photos/a.png", &plan), Path::new("/out/a.jxl"));
+    assert_eq!(output("/photos/2024/a.png", &plan), Path::new("/out/2024/a.jxl"));
+    assert_eq!(output("/photos/2024/03/trip/a.png", &plan), Path::new("/out/2024/03/trip/a.jxl"));
+}
+
+#[test]
+fn inputs_outside_the_base_are_flattened() {
+    let plan = plan(Some("/photos"), "jxl");
+    assert_eq!(output("/elsewhere/b.png", &plan), Path::new("/out/b.jxl"));
+    // A sibling whose name merely starts with the base isn't inside it
+    assert_eq!(output("/photos-old/c.png", &plan), Path::new("/out/c.jxl"));
+}
+
+#[test]
+fn single_file_input_lands_in_the_output_dir() {
+    // A lone file's base is its own folder
+    let plan = plan(Some("/photos/2024"), "jxl");
+    assert_eq!(output("/photos/2024/a.png", &plan), Path::new("/out/a.jxl"));
+}
+
+#[test]
+fn input_equal_to_the_base_is_not_written_over_the_output_dir() {
+    // Intentional fix: this used to produce "/out.jxl", next to the output folder
+    let plan = plan(Some("/photos/a.png"), "jxl");
+    assert_eq!(output("/photos/a.png", &plan), Path::new("/out/a.jxl"));
+}
+
+#[test]
+fn files_without_an_extension_gain_one() {
+    let plan = plan(None, "png");
+    assert_eq!(output("/in/README", &plan), Path::new("/out/README.png"));
+}
+
+#[test]
+fn only_the_last_extension_is_replaced() {
+    let plan = plan(None, "jxl");
+    assert_eq!(output("/in/archive.tar.png", &plan), Path::new("/out/archive.tar.jxl"));
+    assert_eq!(output("/in/v1.2.final.JPG", &plan), Path::new("/out/v1.2.final.jxl"));
+}
+
+#[test]
+fn decode_extension_comes_from_the_plan() {
+    let plan = plan(Some("/in"), "jpg");
+    assert_eq!(output("/in/sub/photo.jxl", &plan), Path::new("/out/sub/photo.jpg"));
+}
+
+#[test]
+fn flattening_can_map_different_inputs_to_the_same_output() {
+    // Documented behaviour: callers must detect these collisions
+    let plan = plan(None, "jxl");
+    assert_eq!(output("/in/a.png", &plan), output("/in/sub/a.png", &plan));
+    assert_eq!(output("/in/a.png", &plan), output("/in/a.jpg", &plan));
+
+    // Keeping the structure avoids the first kind but not the second
+    let plan = self::plan(Some("/in"), "jxl");
+    assert_ne!(output("/in/a.png", &plan), output("/in/sub/a.png", &plan));
+    assert_eq!(output("/in/a.png", &plan), output("/in/a.jpg", &plan));
+}
+
+#[test]
+fn common_base_is_the_deepest_shared_folder() {
+    assert_eq!(bases(&["/photos/2024/a", "/photos/2024/b"]), [PathBuf::from("/photos/2024")]);
+    assert_eq!(bases(&["/photos/2024", "/photos/2024/march"]), [PathBuf::from("/photos/2024")]);
+    assert_eq!(bases(&["/photos", "/scans"]), [PathBuf::from("/")]);
+    // Component-wise, so /photos-old isn't treated as inside /photos
+    assert_eq!(bases(&["/photos", "/photos-old"]), [PathBuf::from("/")]);
+    assert!(bases(&[]).is_empty());
+}
+
+// "Café" with a precomposed é (NFC) and with e + combining acute (NFD)
+const CAFE_NFC: &str = "Caf\u{e9}";
+const CAFE_NFD: &str = "Cafe\u{301}";
+
+#[test]
+fn nfc_and_nfd_names_share_a_key() {
+    let nfc = PathBuf::from(format!("/photos/{}/{}.png", CAFE_NFC, CAFE_NFC));
+    let nfd = PathBuf::from(format!("/photos/{}/{}.png", CAFE_NFD, CAFE_NFD));
+
+    assert_ne!(nfc, nfd);
+    assert_eq!(path_key(&nfc), path_key(&nfd));
+    assert_eq!(path_key(&nfd), nfc);
+    assert_ne!(path_key(&nfc), path_key(Path::new("/photos/Cafe/Cafe.png")));
+}
+
+#[test]
+fn output_claims_catch_normalization_twins() {
+    let plan = plan(None, "jxl");
+    let nfc = PathBuf::from(format!("/a/{}.png", CAFE_NFC));
+    let nfd = PathBuf::from(format!("/b/{}.png", CAFE_NFD));
+
+    let mut claims = OutputClaims::new();
+    assert_eq!(claims.claim(&plan_output_path(&nfc, &plan), &nfc), Ok(()));
+    assert_eq!(claims.claim(&plan_output_path(&nfd, &plan), &nfd), Err(nfc.clone()));
+    assert_eq!(claims.claim(&plan_output_path(Path::new("/a/other.png"), &plan), Path::new("/a/other.png")), Ok(()));
+}
+
+#[test]
+fn case_folded_keys_match_names_differing_in_case() {
+    let upper = Path::new("/out/Trip/Photo.JXL");
+    let lower = Path::new("/out/trip/photo.jxl");
+
+    assert_ne!(output_key(upper, false), output_key(lower, false));
+    assert_eq!(output_key(upper, true), output_key(lower, true));
+    // Folding happens after normalization, so case and NFC/NFD twins collide too
+    let nfd = PathBuf::from(format!("/out/trip/{}.jxl", CAFE_NFD.to_uppercase()));
+    assert_eq!(output_key(&nfd, true), PathBuf::from(format!("/out/trip/{}.jxl", CAFE_NFC.to_lowercase())));
+}
+
+#[test]
+fn output_claims_catch_case_twins_when_folding() {
+    let plan = plan(None, "jxl");
+    let upper = PathBuf::from("/a/Photo.JPG");
+    let lower = PathBuf::from("/b/photo.jpg");
+
+    let mut folding = OutputClaims::with_case_folding(true);
+    assert_eq!(folding.claim(&plan_output_path(&upper, &plan), &upper), Ok(()));
+    assert_eq!(folding.claim(&plan_output_path(&lower, &plan), &lower), Err(upper.clone()));
+
+    let mut exact = OutputClaims::with_case_folding(false);
+    assert_eq!(exact.claim(&plan_output_path(&upper, &plan), &upper), Ok(()));
+    assert_eq!(exact.claim(&plan_output_path(&lower, &plan), &lower), Ok(()));
+
+    if cfg!(any(windows, target_os = "macos")) {
+        let mut claims = OutputClaims::new();
+        assert_eq!(claims.claim(&plan_output_path(&upper, &plan), &upper), Ok(()));
+        assert!(claims.claim(&plan_output_path(&lower, &plan), &lower).is_err());
+    }
+}
+
+#[test]
+fn case_sensitivity_probe_cleans_up_after_itself() {
+    let dir = tempfile::tempdir().unwrap();
+
+    // A missing output folder is probed through its nearest existing parent
+    assert!(is_case_insensitive(&dir.path().join("not/yet/created")).is_some());
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn typed_output_folders_are_expanded() {
+    std::env::set_var("JXL_TEST_EXPAND_ROOT", "/srv/media");
+    let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).unwrap();
+
+    assert_eq!(expand_path("  \"/tmp/out\" "), PathBuf::from("/tmp/out"));
+    assert_eq!(expand_path("~/converted"), PathBuf::from(format!("{}/converted", home)));
+    assert_eq!(expand_path("~user/x"), PathBuf::from("~user/x"));
+    assert_eq!(expand_path("$JXL_TEST_EXPAND_ROOT/jxl"), PathBuf::from("/srv/media/jxl"));
+    assert_eq!(expand_path("${JXL_TEST_EXPAND_ROOT}x"), PathBuf::from("/srv/mediax"));
+    assert_eq!(expand_path("%JXL_TEST_EXPAND_ROOT%\\jxl"), PathBuf::from("/srv/media\\jxl"));
+    // Unknown variables and lone signs stay as typed
+    assert_eq!(expand_path("/a/$JXL_TEST_UNSET_VAR/100%/%JXL_TEST_UNSET_VAR%"), PathBuf::from("/a/$JXL_TEST_UNSET_VAR/100%/%JXL_TEST_UNSET_VAR%"));
+}
+
+#[test]
+fn output_folders_are_checked_without_creating_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("file.txt");
+    std::fs::write(&file, b"x").unwrap();
+
+    assert_eq!(check_output_dir(dir.path()), OutputDirStatus::Ready);
+    assert_eq!(check_output_dir(&dir.path().join("new/nested")), OutputDirStatus::Missing);
+    assert!(!dir.path().join("new").exists());
+    assert!(matches!(check_output_dir(&file), OutputDirStatus::Invalid(message) if message.contains("is a file")));
+    assert!(matches!(check_output_dir(&file.join("below")), OutputDirStatus::Invalid(_)));
+    assert!(matches!(check_output_dir(Path::new("")), OutputDirStatus::Invalid(_)));
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::*;
+
+    fn windows_plan(bases: Vec<PathBuf>) -> OutputPlan {
+        OutputPlan {
+            output_dir: PathBuf::from(r"E:\out"),
+            bases,
+            extension: "jxl".to_string(),
+            inner_extension: false,
+        }
+    }
+
+    #[test]
+    fn each_drive_gets_its_own_base() {
+        let bases = bases(&[r"C:\Photos\2024", r"D:\Scans\old", r"C:\Photos\2023", r"D:\Scans\new"]);
+        assert_eq!(bases, [PathBuf::from(r"C:\Photos"), PathBuf::from(r"D:\Scans")]);
+    }
+
+    #[test]
+    fn drives_are_kept_apart_in_the_output() {
+        let plan = windows_plan(bases(&[r"C:\Photos", r"D:\Scans"]));
+        assert_eq!(
+            plan_output_path(Path::new(r"C:\Photos\2024\a.png"), &plan),
+            Path::new(r"E:\out\C\2024\a.jxl")
+        );
+        assert_eq!(
+            plan_output_path(Path::new(r"D:\Scans\a.png"), &plan),
+            Path::new(r"E:\out\D\a.jxl")
+        );
+    }
+
+    #[test]
+    fn unc_shares_are_separate_roots() {
+        let bases = bases(&[r"\\nas\photos\2024", r"\\nas\photos\2023", r"\\nas\scans\x", r"C:\Local"]);
+        assert_eq!(
+            bases,
+            [PathBuf::from(r"\\nas\photos"), PathBuf::from(r"\\nas\scans\x"), PathBuf::from(r"C:\Local")]
+        );
+
+        let plan = windows_plan(bases);
+        assert_eq!(
+            plan_output_path(Path::new(r"\\nas\photos\2024\a.png"), &plan),
+            Path::new(r"E:\out\nas_photos\2024\a.jxl")
+        );
+        assert_eq!(
+            plan_output_path(Path::new(r"C:\Local\b.png"), &plan),
+            Path::new(r"E:\out\C\b.jxl")
+        );
+    }
+
+    #[test]
+    fn a_single_drive_needs_no_drive_folder() {
+        let plan = windows_plan(bases(&[r"C:\Photos\2024", r"C:\Photos\2023"]));
+        assert_eq!(
+            plan_output_path(Path::new(r"C:\Photos\2024\a.png"), &plan),
+            Path::new(r"E:\out\2024\a.jxl")
+        );
+    }
+}
+
+#[test]
+fn encode_plan_matches_the_batch() {
+    let settings = ConversionSettings {
+        output_dir: PathBuf::from("/out"),
+        keep_structure: true,
+        ..Default::default()
+    };
+    let inputs = [PathBuf::from("/photos/Vacation"), PathBuf::from("/photos/loose.png")];
+    let plan = OutputPlan::for_encode(&inputs, &settings);
+
+    assert_eq!(plan.bases, [PathBuf::from("/photos")]);
+    assert_eq!(
+        plan_output_path(Path::new("/photos/Vacation/day 1/a.png"), &plan),
+        Path::new("/out/Vacation/day 1/a.jxl")
+    );
+
+    let flat = OutputPlan::for_encode(&inputs, &ConversionSettings { keep_structure: false, ..settings });
+    assert!(flat.bases.is_empty());
+}
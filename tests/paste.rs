@@ -0,0 +1,17 @@
+use jxl_converter::paste::save_clipboard_image;
+
+#[test]
+fn clipboard_images_get_unique_timestamped_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let pixels = vec![255, 0, 0, 255, 0, 0, 255, 128];
+
+    let first = save_clipboard_image(dir.path(), "20260102-030405", 2, 1, pixels.clone()).unwrap();
+    assert_eq!(first, dir.path().join("clipboard-20260102-030405.png"));
+    let second = save_clipboard_image(dir.path(), "20260102-030405", 2, 1, pixels).unwrap();
+    assert_eq!(second, dir.path().join("clipboard-20260102-030405-2.png"));
+
+    let image = image::open(&first).unwrap().to_rgba8();
+    assert_eq!(image.get_pixel(1, 0).0, [0, 0, 255, 128]);
+
+    assert!(save_clipboard_image(dir.path(), "x", 3, 3, vec![0; 4]).is_err());
+}
@@ -0,0 +1,418 @@
+mod support;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use jxl_converter::{
+    ConversionSettings, DecodeItem, DecodeSettings, OutputFormat, OverwritePolicy, ProgressMessage, ToolSettings,
+};
+
+use support::{encode_settings, fake_engine, kinds, outputs, run_decode, run_encode, touch, touch_jxl};
+
+#[test]
+fn keep_structure_mirrors_input_folders() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("photos");
+    touch(&input, "a.png");
+    touch(&input, "2024/b.jpg");
+    touch(&input, "2024/march/c.png");
+    let out = dir.path().join("out");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let settings = jxl_converter::ConversionSettings { keep_structure: true, ..encode_settings(&out) };
+    let messages = run_encode(&engine, vec![input], settings);
+
+    assert_eq!(outputs(&messages).len(), 3);
+    assert!(out.join("photos/a.jxl").is_file());
+    assert!(out.join("photos/2024/b.jxl").is_file());
+    assert!(out.join("photos/2024/march/c.jxl").is_file());
+}
+
+#[test]
+fn keep_structure_with_folders_and_loose_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let folder = dir.path().join("in/Vacation2023");
+    touch(&folder, "beach/a.png");
+    let loose = touch(dir.path(), "in/other/b.png");
+    let top = touch(dir.path(), "in/c.png");
+    let out = dir.path().join("out");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let settings = jxl_converter::ConversionSettings { keep_structure: true, ..encode_settings(&out) };
+    run_encode(&engine, vec![folder, loose, top], settings);
+
+    assert!(out.join("Vacation2023/beach/a.jxl").is_file());
+    assert!(out.join("other/b.jxl").is_file());
+    assert!(out.join("c.jxl").is_file());
+}
+
+#[test]
+fn keep_structure_keeps_the_added_folder_when_decoding() {
+    let dir = tempfile::tempdir().unwrap();
+    let folder = dir.path().join("Vacation2023");
+    let a = touch(&folder, "a.jxl");
+    let b = touch(&folder, "day2/b.jxl");
+    let out = dir.path().join("out");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let items = [a, b]
+        .into_iter()
+        .map(|path| DecodeItem { path, output_formats: vec![OutputFormat::Png], folder: Some(folder.clone()) })
+        .collect();
+    let settings = DecodeSettings { output_dir: out.clone(), keep_structure: true, ..Default::default() };
+    run_decode(&engine, items, settings);
+
+    assert!(out.join("Vacation2023/a.png").is_file());
+    assert!(out.join("Vacation2023/day2/b.png").is_file());
+}
+
+#[test]
+fn without_keep_structure_outputs_are_flat() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("photos");
+    touch(&input, "a.png");
+    touch(&input, "2024/b.png");
+    let out = dir.path().join("out");
+
+    let engine = fake_engine(dir.path(), &[]);
+    run_encode(&engine, vec![input], encode_settings(&out));
+
+    assert!(out.join("a.jxl").is_file());
+    assert!(out.join("b.jxl").is_file());
+    assert!(!out.join("2024").exists());
+}
+
+#[test]
+fn inputs_with_the_same_output_are_converted_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = touch(dir.path(), "in/one/a.png");
+    let second = touch(dir.path(), "in/two/a.png");
+    let out = dir.path().join("out");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let messages = run_encode(&engine, vec![first.clone(), second], encode_settings(&out));
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "progress", "skipped", "completed"]);
+    assert_eq!(std::fs::read(out.join("a.jxl")).unwrap(), b"in/one/a.png");
+}
+
+#[test]
+fn existing_outputs_are_overwritten() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = touch(dir.path(), "in/a.png");
+    let out = dir.path().join("out");
+    std::fs::create_dir_all(&out).unwrap();
+    std::fs::write(out.join("a.jxl"), b"stale").unwrap();
+
+    let engine = fake_engine(dir.path(), &[]);
+    let messages = run_encode(&engine, vec![input], encode_settings(&out));
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "completed"]);
+    assert_eq!(std::fs::read(out.join("a.jxl")).unwrap(), b"in/a.png");
+}
+
+#[test]
+fn existing_outputs_can_be_skipped_or_renamed() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = touch(dir.path(), "in/a.png");
+    let out = dir.path().join("out");
+    std::fs::create_dir_all(&out).unwrap();
+    std::fs::write(out.join("a.jxl"), b"stale").unwrap();
+    std::fs::write(out.join("a (2).jxl"), b"stale").unwrap();
+    let engine = fake_engine(dir.path(), &[]);
+
+    let skip = ConversionSettings { overwrite: OverwritePolicy::Skip, ..encode_settings(&out) };
+    let messages = run_encode(&engine, vec![input.clone()], skip);
+    assert_eq!(kinds(&messages), ["started", "progress", "skipped", "completed"]);
+    assert_eq!(std::fs::read(out.join("a.jxl")).unwrap(), b"stale");
+
+    let rename = ConversionSettings { overwrite: OverwritePolicy::Rename, ..encode_settings(&out) };
+    let messages = run_encode(&engine, vec![input], rename);
+    assert_eq!(outputs(&messages), [out.join("a (3).jxl")]);
+    assert_eq!(std::fs::read(out.join("a.jxl")).unwrap(), b"stale");
+}
+
+/// Runs an encode batch and cancels it as soon as the first file succeeds.
+fn encode_and_cancel_after_first(dir: &std::path::Path, inputs: Vec<std::path::PathBuf>, settings: ConversionSettings) -> Vec<ProgressMessage> {
+    let engine = fake_engine(dir, &[("FAKE_TOOL_SLEEP", "0.2")]);
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = channel();
+    let batch_cancel = Arc::clone(&cancel);
+    let worker = thread::spawn(move || engine.convert_batch(inputs, settings, tx, batch_cancel));
+
+    let mut messages = Vec::new();
+    for msg in rx {
+        if matches!(msg, ProgressMessage::Success { .. }) {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        messages.push(msg);
+    }
+    worker.join().unwrap();
+    messages
+}
+
+#[test]
+fn cancelled_transactional_batches_remove_what_they_wrote() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    for name in ["sub/a.png", "sub/b.png", "sub/c.png"] {
+        touch(&input, name);
+    }
+    let out = dir.path().join("out");
+    std::fs::create_dir_all(&out).unwrap();
+    std::fs::write(out.join("unrelated.txt"), b"keep me").unwrap();
+
+    let settings = ConversionSettings {
+        keep_structure: true,
+        transactional: true,
+        overwrite: OverwritePolicy::Skip,
+        ..encode_settings(&out)
+    };
+    let messages = encode_and_cancel_after_first(dir.path(), vec![input], settings);
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "cancelled"]);
+    assert!(messages.iter().any(|msg| matches!(msg, ProgressMessage::Info { message } if message == "Rolled back 1 output(s)")));
+    // The folders the batch created are gone; the output folder itself and its old contents stay
+    let left: Vec<_> = std::fs::read_dir(&out).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(left, ["unrelated.txt"]);
+}
+
+#[test]
+fn transactional_batches_keep_outputs_when_they_finish() {
+    let dir = tempfile::tempdir().unwrap();
+    let good = touch(dir.path(), "in/good.png");
+    let bad = touch(dir.path(), "in/bad.png");
+    let out = dir.path().join("out");
+
+    let engine = fake_engine(dir.path(), &[("FAKE_TOOL_FAIL_ON", "bad")]);
+    let settings = ConversionSettings { transactional: true, overwrite: OverwritePolicy::Rename, ..encode_settings(&out) };
+    let messages = run_encode(&engine, vec![good, bad], settings);
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "progress", "error", "completed"]);
+    assert!(out.join("good.jxl").is_file());
+}
+
+#[test]
+fn transactional_batches_refuse_to_overwrite() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = fake_engine(dir.path(), &[]);
+    let out = dir.path().join("out");
+
+    let settings = ConversionSettings { transactional: true, ..encode_settings(&out) };
+    assert!(engine.validate_settings(&settings).unwrap_err().contains("Skip or Rename"));
+
+    let settings = DecodeSettings { output_dir: out, transactional: true, ..Default::default() };
+    let messages = run_decode(&engine, vec![], settings);
+    assert_eq!(kinds(&messages), ["error"]);
+}
+
+#[test]
+fn cancelling_stops_after_the_current_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    for name in ["a.png", "b.png", "c.png", "d.png"] {
+        touch(&input, name);
+    }
+    let out = dir.path().join("out");
+
+    let engine = fake_engine(dir.path(), &[("FAKE_TOOL_SLEEP", "0.3")]);
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = channel();
+    let batch_cancel = Arc::clone(&cancel);
+    let worker = thread::spawn(move || engine.convert_batch(vec![input], encode_settings(&out), tx, batch_cancel));
+
+    let mut messages = Vec::new();
+    for msg in rx {
+        if matches!(msg, ProgressMessage::Progress { .. }) {
+            // The first file is already running; it should finish, nothing after it
+            cancel.store(true, Ordering::Relaxed);
+        }
+        messages.push(msg);
+    }
+    worker.join().unwrap();
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "cancelled"]);
+}
+
+#[test]
+fn unsupported_files_are_not_counted() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    touch(&input, "a.png");
+    touch(&input, "notes.txt");
+    touch(&input, "b.webp");
+    touch(&input, "no_extension");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let messages = run_encode(&engine, vec![input], encode_settings(&dir.path().join("out")));
+
+    assert!(matches!(messages[0], ProgressMessage::Started { total: 2 }));
+    assert_eq!(outputs(&messages).len(), 2);
+    assert!(!kinds(&messages).contains(&"skipped"));
+}
+
+#[test]
+fn no_matching_inputs_completes_immediately() {
+    let dir = tempfile::tempdir().unwrap();
+    let notes = touch(dir.path(), "in/notes.txt");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let messages = run_encode(&engine, vec![notes], encode_settings(&dir.path().join("out")));
+
+    assert_eq!(kinds(&messages), ["started", "completed"]);
+}
+
+#[test]
+fn tool_failures_are_reported_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    for name in ["a.png", "b.png", "c.png"] {
+        touch(&input, name);
+    }
+    let out = dir.path().join("out");
+
+    let engine = fake_engine(dir.path(), &[("FAKE_TOOL_FAIL_ON", "b.png"), ("FAKE_TOOL_STDERR", "decoder exploded")]);
+    let messages = run_encode(&engine, vec![input], encode_settings(&out));
+    let kinds = kinds(&messages);
+
+    assert_eq!(kinds.first(), Some(&"started"));
+    assert_eq!(kinds.last(), Some(&"completed"));
+    // Every file gets a progress message followed by exactly one outcome
+    for pair in kinds[1..kinds.len() - 1].chunks(2) {
+        assert_eq!(pair[0], "progress");
+        assert!(pair[1] == "success" || pair[1] == "error", "unexpected {:?}", pair);
+    }
+
+    let errors: Vec<_> = messages
+        .iter()
+        .filter_map(|msg| match msg {
+            ProgressMessage::Error { file, error } => Some((file, error)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].0.ends_with("b.png"));
+    assert!(errors[0].1.contains("decoder exploded"));
+    assert!(!out.join("b.jxl").exists());
+    assert!(out.join("a.jxl").is_file() && out.join("c.jxl").is_file());
+}
+
+#[test]
+fn the_output_budget_stops_the_batch_early() {
+    let dir = tempfile::tempdir().unwrap();
+    // The fake cjxl copies, so each output is 8 bytes
+    let inputs = vec![touch(dir.path(), "in/a.png"), touch(dir.path(), "in/b.png"), touch(dir.path(), "in/c.png")];
+
+    let engine = fake_engine(dir.path(), &[]);
+    let settings = ConversionSettings { output_budget_bytes: Some(20), ..encode_settings(&dir.path().join("out")) };
+    let messages = run_encode(&engine, inputs.clone(), settings);
+
+    // A third 8-byte output would make 24
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "progress", "success", "stopped"]);
+    assert!(matches!(
+        messages.last(),
+        Some(ProgressMessage::Stopped { reason, remaining })
+            if reason == "output budget reached" && *remaining == [inputs[2].display().to_string()]
+    ));
+}
+
+#[test]
+fn decode_uses_each_items_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = touch(dir.path(), "in/a.jxl");
+    let b = touch_jxl(dir.path(), "in/b.jxl");
+    let out = dir.path().join("out");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let items = vec![
+        DecodeItem { path: a, output_formats: vec![OutputFormat::Png], folder: None },
+        DecodeItem { path: b, output_formats: vec![OutputFormat::Jpeg], folder: None },
+    ];
+    let settings = DecodeSettings { output_dir: out.clone(), ..Default::default() };
+    let messages = run_decode(&engine, items, settings);
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "progress", "success", "completed"]);
+    assert!(out.join("a.png").is_file());
+    assert!(out.join("b.jpg").is_file());
+}
+
+#[test]
+fn decode_writes_one_output_per_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let jxl = touch_jxl(dir.path(), "in/a.jxl");
+    let out = dir.path().join("out");
+    touch(&out, "a.png");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let formats = vec![OutputFormat::Png, OutputFormat::Jpeg, OutputFormat::Ppm, OutputFormat::Png];
+    let items = vec![DecodeItem { path: jxl, output_formats: formats, folder: None }];
+    let settings = DecodeSettings { output_dir: out.clone(), overwrite: OverwritePolicy::Skip, ..Default::default() };
+    let messages = run_decode(&engine, items, settings);
+
+    // The repeated PNG is dropped, and the existing one is skipped on its own
+    assert_eq!(kinds(&messages), ["started", "progress", "skipped", "progress", "success", "progress", "success", "completed"]);
+    assert!(matches!(messages[0], ProgressMessage::Started { total: 3 }));
+    assert_eq!(outputs(&messages), [out.join("a.jpg").canonicalize().unwrap(), out.join("a.ppm").canonicalize().unwrap()]);
+}
+
+#[test]
+fn outputs_inside_an_input_folder_are_not_picked_up_again() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("photos");
+    touch(&input, "a.png");
+    let out = input.join("converted");
+    touch(&out, "previous.png");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let messages = run_encode(&engine, vec![input], encode_settings(&out));
+
+    assert_eq!(outputs(&messages).len(), 1);
+    assert!(messages.iter().any(|m| matches!(m, ProgressMessage::Info { message } if message.contains("output folder"))));
+}
+
+#[test]
+fn the_cool_down_idles_between_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = fake_engine(dir.path(), &[]).with_tool_settings(ToolSettings {
+        inter_file_delay_ms: Some(400),
+        ..Default::default()
+    });
+    let inputs = vec![touch(dir.path(), "in/a.png"), touch(dir.path(), "in/b.png")];
+
+    let started = Instant::now();
+    let messages = run_encode(&engine, inputs, encode_settings(&dir.path().join("out")));
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "progress", "success", "completed"]);
+    assert!(started.elapsed() >= Duration::from_millis(400));
+}
+
+#[test]
+fn cancelling_cuts_the_cool_down_short() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = fake_engine(dir.path(), &[]).with_tool_settings(ToolSettings {
+        inter_file_delay_ms: Some(60_000),
+        ..Default::default()
+    });
+    let inputs = vec![touch(dir.path(), "in/a.png"), touch(dir.path(), "in/b.png")];
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = channel();
+    let batch_cancel = Arc::clone(&cancel);
+    let out = dir.path().join("out");
+    let started = Instant::now();
+    let worker = thread::spawn(move || engine.convert_batch(inputs, encode_settings(&out), tx, batch_cancel));
+
+    let mut messages = Vec::new();
+    for msg in rx {
+        if matches!(msg, ProgressMessage::Success { .. }) {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        messages.push(msg);
+    }
+    worker.join().unwrap();
+
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "cancelled"]);
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
@@ -0,0 +1,27 @@
+use jxl_converter::process::limit_cores;
+
+fn available() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+#[test]
+fn a_limit_above_the_core_count_uses_every_core() {
+    let cores = limit_cores(available() + 8);
+    assert_eq!(cores, (0..available()).collect::<Vec<_>>());
+}
+
+#[test]
+fn the_first_cores_are_used_once_each() {
+    let cores = limit_cores(1);
+    assert_eq!(cores, [0]);
+
+    let limit = available().min(2);
+    let cores = limit_cores(limit);
+    assert_eq!(cores.len(), limit);
+    assert!(cores.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", cores);
+}
+
+#[test]
+fn a_zero_limit_still_leaves_one_core() {
+    assert_eq!(limit_cores(0), [0]);
+}
@@ -0,0 +1,26 @@
+use jxl_converter::types::distance_from_quality;
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 1e-4, "expected {}, got {}", expected, actual);
+}
+
+#[test]
+fn quality_maps_to_libjxl_distances() {
+    // Reference points from libjxl's JxlEncoderDistanceFromQuality
+    assert_close(distance_from_quality(100.0), 0.0);
+    assert_close(distance_from_quality(95.0), 0.55);
+    assert_close(distance_from_quality(90.0), 1.0);
+    assert_close(distance_from_quality(75.0), 2.35);
+    assert_close(distance_from_quality(30.0), 6.4);
+    assert_close(distance_from_quality(10.0), 15.266_667);
+    assert_close(distance_from_quality(0.0), 25.0);
+}
+
+#[test]
+fn the_curve_is_continuous_and_decreasing() {
+    // Both branches agree at q 30
+    assert_close(distance_from_quality(29.999_9), distance_from_quality(30.0));
+    for q in 1..=100 {
+        assert!(distance_from_quality(q as f32) < distance_from_quality(q as f32 - 1.0));
+    }
+}
@@ -0,0 +1,203 @@
+mod support;
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use jxl_converter::scan::{scan_paths, still_being_written, Scan, ScanOptions};
+use jxl_converter::{ProgressMessage, ToolSettings};
+
+use support::{encode_settings, fake_engine, kinds, run_encode, touch};
+
+fn scan(root: &Path, options: &ScanOptions) -> Scan {
+    scan_paths(&[root.to_path_buf()], options)
+}
+
+/// The scanned files relative to `root`, sorted, with `/` separators.
+fn names(scan: &Scan, root: &Path) -> Vec<String> {
+    let mut names: Vec<String> = scan
+        .files
+        .iter()
+        .map(|p| p.strip_prefix(root).unwrap().components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/"))
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn nomedia_prunes_the_whole_subtree() {
+    let dir = tempfile::tempdir().unwrap();
+    touch(dir.path(), "a.png");
+    touch(dir.path(), "thumbs/.nomedia");
+    touch(dir.path(), "thumbs/t.png");
+    touch(dir.path(), "thumbs/deeper/t2.png");
+
+    let scan = scan(dir.path(), &ScanOptions::default());
+
+    assert_eq!(names(&scan, dir.path()), ["a.png"]);
+    assert_eq!(scan.pruned_dirs, 1);
+    assert!(scan.summary().unwrap().contains("1 folder(s)"));
+}
+
+#[test]
+fn jxlignore_patterns_are_relative_to_their_folder() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("archive")).unwrap();
+    std::fs::write(dir.path().join("archive/.jxlignore"), "# caches\n*.tmp.png\ncache\n/2020/raw\n\n").unwrap();
+    for name in [
+        "top.tmp.png",
+        "archive/a.png",
+        "archive/b.tmp.png",
+        "archive/x/c.tmp.png",
+        "archive/cache/d.png",
+        "archive/x/cache/e.png",
+        "archive/2020/f.png",
+        "archive/2020/raw/g.png",
+    ] {
+        touch(dir.path(), name);
+    }
+
+    let scan = scan(dir.path(), &ScanOptions::default());
+
+    assert_eq!(
+        names(&scan, dir.path()),
+        ["archive/.jxlignore", "archive/2020/f.png", "archive/a.png", "top.tmp.png"]
+    );
+    assert_eq!(scan.pruned_dirs, 3);
+    assert_eq!(scan.ignored_files, 2);
+}
+
+#[test]
+fn ignore_files_can_be_turned_off() {
+    let dir = tempfile::tempdir().unwrap();
+    touch(dir.path(), "thumbs/.nomedia");
+    touch(dir.path(), "thumbs/t.png");
+
+    let options = ScanOptions { respect_ignore_files: false, ..Default::default() };
+    let scan = scan(dir.path(), &options);
+
+    assert_eq!(names(&scan, dir.path()), ["thumbs/.nomedia", "thumbs/t.png"]);
+    assert!(scan.summary().is_none());
+}
+
+#[test]
+fn listed_files_are_kept_and_listed_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = touch(dir.path(), "a.png");
+    touch(dir.path(), "sub/b.png");
+
+    let options = ScanOptions { recursive: false, ..Default::default() };
+    let scan = scan_paths(&[file.clone(), dir.path().to_path_buf(), PathBuf::from("/no/such/path")], &options);
+
+    assert_eq!(scan.files, [file]);
+}
+
+#[test]
+fn output_folder_inside_the_inputs_is_skipped() {
+    let dir = tempfile::tempdir().unwrap();
+    touch(dir.path(), "a.png");
+    touch(dir.path(), "converted/a.jxl");
+    touch(dir.path(), "converted/nested/b.png");
+
+    let options = ScanOptions { exclude_dir: Some(dir.path().join("converted")), ..Default::default() };
+    let scan = scan(dir.path(), &options);
+
+    assert_eq!(names(&scan, dir.path()), ["a.png"]);
+    assert_eq!(scan.excluded_dirs, 1);
+    assert_eq!(scan.summary().unwrap(), "Skipped the output folder inside the inputs");
+
+    // Listing the output folder itself is deliberate, so it is still scanned
+    let converted = dir.path().join("converted");
+    assert_eq!(scan_paths(&[converted], &options).files.len(), 2);
+}
+
+#[test]
+fn output_folder_is_matched_through_relative_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    touch(dir.path(), "in/a.png");
+    touch(dir.path(), "in/out/a.jxl");
+
+    // Same folder, spelled differently from the input path
+    let options = ScanOptions {
+        exclude_dir: Some(dir.path().join("in/out/../out")),
+        ..Default::default()
+    };
+    let scan = scan(&dir.path().join("in"), &options);
+
+    assert_eq!(names(&scan, &dir.path().join("in")), ["a.png"]);
+}
+
+#[test]
+fn unfinished_downloads_are_left_out() {
+    let dir = tempfile::tempdir().unwrap();
+    touch(dir.path(), "a.png");
+    touch(dir.path(), "b.jpg.part");
+    touch(dir.path(), "c.png.crdownload");
+    touch(dir.path(), "nested/d.TMP");
+
+    let scan = scan(dir.path(), &ScanOptions::default());
+
+    assert_eq!(names(&scan, dir.path()), ["a.png"]);
+    assert_eq!(scan.temporary_files, 3);
+    assert!(scan.summary().unwrap().contains("3 unfinished download(s)"));
+}
+
+/// Appends to `path` every 50 ms until `stop` is set.
+fn keep_appending(path: PathBuf, stop: Arc<AtomicBool>) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut file = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+        while !stop.load(Ordering::Relaxed) {
+            file.write_all(b"more").unwrap();
+            file.flush().unwrap();
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    })
+}
+
+#[test]
+fn files_being_appended_to_are_not_stable() {
+    let dir = tempfile::tempdir().unwrap();
+    let growing = touch(dir.path(), "growing.png");
+    let settled = touch(dir.path(), "settled.png");
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let writer = keep_appending(growing.clone(), Arc::clone(&stop));
+    let unstable = still_being_written(&[growing.clone(), settled.clone()], Duration::from_millis(400));
+    stop.store(true, Ordering::Relaxed);
+    writer.join().unwrap();
+
+    assert_eq!(unstable.into_iter().collect::<Vec<_>>(), [growing]);
+
+    // Nothing modified recently: no waiting at all
+    let old = SystemTime::now() - Duration::from_secs(3600);
+    std::fs::File::options().write(true).open(&settled).unwrap().set_modified(old).unwrap();
+    let started = Instant::now();
+    assert!(still_being_written(&[settled], Duration::from_secs(5)).is_empty());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn batches_skip_files_still_being_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let growing = touch(dir.path(), "in/growing.png");
+    touch(dir.path(), "in/done.png");
+    let engine = fake_engine(dir.path(), &[]);
+    let tool_settings = ToolSettings { stable_wait_secs: Some(1), ..engine.tool_settings().clone() };
+    let engine = engine.with_tool_settings(tool_settings);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let writer = keep_appending(growing.clone(), Arc::clone(&stop));
+    let messages = run_encode(&engine, vec![dir.path().join("in")], encode_settings(&dir.path().join("out")));
+    stop.store(true, Ordering::Relaxed);
+    writer.join().unwrap();
+
+    let mut kinds = kinds(&messages);
+    kinds.sort();
+    assert_eq!(kinds, ["completed", "progress", "progress", "skipped", "started", "success"]);
+    assert!(messages.iter().any(|msg| matches!(
+        msg,
+        ProgressMessage::Skipped { file, reason } if file.ends_with("growing.png") && reason == "file still being written"
+    )));
+}
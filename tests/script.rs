@@ -0,0 +1,80 @@
+mod support;
+
+use std::ffi::OsStr;
+use std::path::Path;
+
+use jxl_converter::script::{write_encode_script, Shell};
+
+use support::{encode_settings, fake_engine, touch};
+
+#[test]
+fn shell_is_chosen_by_extension() {
+    assert_eq!(Shell::from_path(Path::new("run.sh")), Some(Shell::Sh));
+    assert_eq!(Shell::from_path(Path::new("RUN.BAT")), Some(Shell::Cmd));
+    assert_eq!(Shell::from_path(Path::new("run.cmd")), Some(Shell::Cmd));
+    assert_eq!(Shell::from_path(Path::new("run.ps1")), Some(Shell::PowerShell));
+    assert_eq!(Shell::from_path(Path::new("run.txt")), None);
+}
+
+#[test]
+fn quoting_follows_each_shell() {
+    let plain = OsStr::new("photos/a.png");
+    for shell in [Shell::Sh, Shell::Cmd, Shell::PowerShell] {
+        assert_eq!(shell.quote(plain), "photos/a.png");
+    }
+
+    let tricky = OsStr::new("Bob's 100% photo.png");
+    assert_eq!(Shell::Sh.quote(tricky), r"'Bob'\''s 100% photo.png'");
+    assert_eq!(Shell::Cmd.quote(tricky), r#""Bob's 100%% photo.png""#);
+    assert_eq!(Shell::PowerShell.quote(tricky), "'Bob''s 100% photo.png'");
+
+    assert_eq!(Shell::Sh.quote(OsStr::new("")), "''");
+    assert_eq!(Shell::Sh.quote(OsStr::new(r"C:\a")), r"'C:\a'");
+    assert_eq!(Shell::Cmd.quote(OsStr::new(r"C:\a")), r"C:\a");
+}
+
+#[test]
+fn script_has_a_command_per_file_built_like_the_engine() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("my photos");
+    let a = touch(&input, "a.png");
+    touch(&input, "nested/b.jpg");
+    touch(&input, "notes.txt");
+    let out = dir.path().join("out");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let settings = jxl_converter::ConversionSettings { keep_structure: true, ..encode_settings(&out) };
+    let mut script = Vec::new();
+    let count = write_encode_script(&engine, std::slice::from_ref(&input), &settings, Shell::Sh, &mut script).unwrap();
+    let script = String::from_utf8(script).unwrap();
+
+    assert_eq!(count, 2);
+    assert!(script.starts_with("#!/bin/sh\nset -e\n"));
+    assert!(script.contains(&format!("mkdir -p {}", Shell::Sh.quote(out.join("my photos").as_os_str()))));
+    assert!(script.contains(&format!("mkdir -p {}", Shell::Sh.quote(out.join("my photos/nested").as_os_str()))));
+
+    let args = engine.cjxl_args(&a, &out.join("my photos/a.jxl"), &settings);
+    let cjxl = engine.cjxl_path().unwrap().as_os_str();
+    assert!(script.lines().any(|line| line == Shell::Sh.command_line(cjxl, &args)));
+}
+
+#[cfg(unix)]
+#[test]
+fn exported_sh_script_runs() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    touch(&input, "it's here/a.png");
+    touch(&input, "b.jpg");
+    let out = dir.path().join("out dir");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let settings = jxl_converter::ConversionSettings { keep_structure: true, ..encode_settings(&out) };
+    let path = dir.path().join("convert.sh");
+    let file = std::fs::File::create(&path).unwrap();
+    write_encode_script(&engine, &[input], &settings, Shell::Sh, file).unwrap();
+
+    let status = std::process::Command::new("sh").arg(&path).status().unwrap();
+    assert!(status.success());
+    assert!(out.join("in/it's here/a.jxl").is_file());
+    assert!(out.join("in/b.jxl").is_file());
+}
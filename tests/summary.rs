@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+
+use jxl_converter::summary::{format_breakdown, format_table, BatchSummary, FileOutcome, FormatOutcomes};
+use jxl_converter::ProgressMessage;
+
+fn succeeded(input: &str, bytes_in: u64, bytes_out: u64) -> FileOutcome {
+    FileOutcome::Succeeded { input: PathBuf::from(input), bytes_in, bytes_out }
+}
+
+#[test]
+fn outcomes_are_bucketed_by_input_extension() {
+    let outcomes = [
+        succeeded("a.png", 100, 50),
+        FileOutcome::Failed { input: PathBuf::from("b.webp") },
+        succeeded("c.PNG", 100, 25),
+        FileOutcome::Failed { input: PathBuf::from("d.webp") },
+        FileOutcome::Skipped { input: PathBuf::from("e.png") },
+        FileOutcome::Failed { input: PathBuf::from("f.webp") },
+    ];
+
+    let rows = format_breakdown(&outcomes);
+
+    assert_eq!(
+        rows,
+        [
+            FormatOutcomes {
+                format: "png".to_string(),
+                attempted: 3,
+                succeeded: 2,
+                skipped: 1,
+                bytes_in: 200,
+                bytes_out: 75,
+                average_ratio: Some(0.375),
+                ..Default::default()
+            },
+            FormatOutcomes { format: "webp".to_string(), attempted: 3, failed: 3, ..Default::default() },
+        ]
+    );
+}
+
+#[test]
+fn files_without_an_extension_get_their_own_row() {
+    let rows = format_breakdown(&[succeeded("https://example.com/photo", 10, 5), succeeded("x.jpg", 10, 10)]);
+    assert_eq!(rows.iter().map(|row| row.format.as_str()).collect::<Vec<_>>(), ["", "jpg"]);
+
+    let table = format_table(&rows);
+    assert_eq!(table.len(), 3);
+    assert!(table[0].starts_with("format "));
+    assert!(table[1].starts_with("(none) "));
+    assert!(table[1].ends_with("  0.500"), "{}", table[1]);
+}
+
+#[test]
+fn summaries_break_their_files_down_when_the_batch_ends() {
+    let mut summary = BatchSummary::begin();
+    summary.record(&ProgressMessage::Success {
+        file: "in/a.gif -> out/a.jxl".to_string(),
+        input: PathBuf::from("in/a.gif"),
+        output: PathBuf::from("out/a.jxl"),
+        input_bytes: 40,
+        output_bytes: 10,
+    });
+    summary.record(&ProgressMessage::Error { file: String::new(), error: "cjxl not found".to_string() });
+    summary.record(&ProgressMessage::Skipped { file: "in/b.gif".to_string(), reason: "exists".to_string() });
+    assert!(summary.formats.is_empty());
+
+    summary.record(&ProgressMessage::Completed);
+
+    assert_eq!(summary.formats.len(), 1);
+    assert_eq!((summary.formats[0].attempted, summary.formats[0].succeeded), (2, 1));
+    let json = serde_json::to_value(&summary).unwrap();
+    assert_eq!(json["formats"][0]["format"], "gif");
+    assert_eq!(json["formats"][0]["average_ratio"], 0.25);
+}
@@ -0,0 +1,162 @@
+//! Shared helpers for integration tests: a fake cjxl/djxl that copies its
+//! input to its output, and a way to run a batch and collect its messages.
+//!
+//! The fake tool reads these environment variables:
+//! - `FAKE_TOOL_FAIL=1` exits with status 1 without writing anything
+//! - `FAKE_TOOL_FAIL_ON=<text>` fails only for inputs whose path contains `<text>`
+//! - `FAKE_TOOL_SLEEP=<seconds>` sleeps before doing anything
+//! - `FAKE_TOOL_STDERR=<text>` prints `<text>` to stderr
+//! - `FAKE_TOOL_LOG=<file>` appends every invocation's input path to `<file>`
+//! - `FAKE_TOOL_VERSION=<version>` is what `--version` reports
+//! - `FAKE_TOOL_HELP=<text>` is what `--help` prints
+//!
+//! `--version` prints `fake v0.0.0` unless told otherwise and exits; `--help` prints nothing unless
+//! told otherwise, so by default the fake supports no optional features.
+
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+
+use jxl_converter::{
+    ConversionEngine, ConversionSettings, DecodeItem, DecodeSettings, ProgressMessage, ToolSettings,
+};
+
+#[cfg(unix)]
+const SCRIPT: &str = r#"#!/bin/sh
+[ "$1" = "--version" ] && echo "fake v${FAKE_TOOL_VERSION:-0.0.0} [test]" && exit 0
+[ "$1" = "--help" ] && printf '%s\n' "$FAKE_TOOL_HELP" && exit 0
+in="$1"
+out="$2"
+[ -n "$FAKE_TOOL_LOG" ] && printf '%s\n' "$in" >> "$FAKE_TOOL_LOG"
+[ -n "$FAKE_TOOL_SLEEP" ] && sleep "$FAKE_TOOL_SLEEP"
+[ -n "$FAKE_TOOL_STDERR" ] && printf '%s\n' "$FAKE_TOOL_STDERR" >&2
+[ "$FAKE_TOOL_FAIL" = "1" ] && exit 1
+if [ -n "$FAKE_TOOL_FAIL_ON" ]; then
+    case "$in" in *"$FAKE_TOOL_FAIL_ON"*) exit 1 ;; esac
+fi
+cp "$in" "$out"
+"#;
+
+#[cfg(windows)]
+const SCRIPT: &str = "@echo off\r
+if not defined FAKE_TOOL_VERSION set FAKE_TOOL_VERSION=0.0.0\r
+if \"%~1\"==\"--version\" (echo fake v%FAKE_TOOL_VERSION% [test]& exit /b 0)\r
+if \"%~1\"==\"--help\" (if defined FAKE_TOOL_HELP (echo %FAKE_TOOL_HELP%)& exit /b 0)\r
+if defined FAKE_TOOL_LOG echo %~1>>\"%FAKE_TOOL_LOG%\"\r
+if defined FAKE_TOOL_SLEEP powershell -NoProfile -Command \"Start-Sleep -Seconds %FAKE_TOOL_SLEEP%\"\r
+if defined FAKE_TOOL_STDERR echo %FAKE_TOOL_STDERR% 1>&2\r
+if \"%FAKE_TOOL_FAIL%\"==\"1\" exit /b 1\r
+if defined FAKE_TOOL_FAIL_ON (\r
+    echo %~1| findstr /L /C:\"%FAKE_TOOL_FAIL_ON%\" >nul && exit /b 1\r
+)\r
+copy /Y \"%~1\" \"%~2\" >nul\r
+";
+
+/// Writes the fake tool into `dir` as `name` (plus `.cmd` on Windows).
+pub fn fake_tool(dir: &Path, name: &str) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join(name);
+        std::fs::write(&path, SCRIPT).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[cfg(windows)]
+    {
+        let path = dir.join(format!("{}.cmd", name));
+        std::fs::write(&path, SCRIPT).unwrap();
+        path
+    }
+}
+
+/// An engine using fake cjxl and djxl from `dir`, with `env` passed to them.
+pub fn fake_engine(dir: &Path, env: &[(&str, &str)]) -> ConversionEngine {
+    let tools = dir.join("fake-tools");
+    std::fs::create_dir_all(&tools).unwrap();
+    let cjxl = fake_tool(&tools, "cjxl");
+    let djxl = fake_tool(&tools, "djxl");
+
+    let tool_settings = ToolSettings {
+        env_overrides: env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        ..Default::default()
+    };
+    ConversionEngine::new()
+        .with_tool_settings(tool_settings)
+        .with_tool_paths(Some(cjxl), Some(djxl))
+}
+
+/// Creates `dir/relative` (and its parents) with some content.
+pub fn touch(dir: &Path, relative: &str) -> PathBuf {
+    let path = dir.join(relative);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, relative.as_bytes()).unwrap();
+    path
+}
+
+/// The header of an 8×8 JXL codestream without alpha: enough for the engine
+/// to tell it needs no flattening, and copied through by the fake djxl.
+pub const JXL_HEADER: &[u8] = &[0xFF, 0x0A, 0x41, 0x02];
+
+/// Like [`touch`], but the file starts with [`JXL_HEADER`].
+pub fn touch_jxl(dir: &Path, relative: &str) -> PathBuf {
+    let path = touch(dir, relative);
+    std::fs::write(&path, JXL_HEADER).unwrap();
+    path
+}
+
+pub fn encode_settings(output_dir: &Path) -> ConversionSettings {
+    ConversionSettings {
+        output_dir: output_dir.to_path_buf(),
+        ..Default::default()
+    }
+}
+
+/// Runs an encode batch to completion and returns every message it sent.
+pub fn run_encode(engine: &ConversionEngine, inputs: Vec<PathBuf>, settings: ConversionSettings) -> Vec<ProgressMessage> {
+    let (tx, rx) = channel();
+    engine.convert_batch(inputs, settings, tx, Arc::new(AtomicBool::new(false)));
+    rx.into_iter().collect()
+}
+
+pub fn run_decode(engine: &ConversionEngine, items: Vec<DecodeItem>, settings: DecodeSettings) -> Vec<ProgressMessage> {
+    let (tx, rx) = channel();
+    engine.decode_batch(items, settings, tx, Arc::new(AtomicBool::new(false)));
+    rx.into_iter().collect()
+}
+
+/// A compact description of a message stream, e.g. `["started", "progress", "success", "completed"]`.
+pub fn kinds(messages: &[ProgressMessage]) -> Vec<&'static str> {
+    messages
+        .iter()
+        .filter_map(|msg| match msg {
+            ProgressMessage::Started { .. } => Some("started"),
+            ProgressMessage::Progress { .. } => Some("progress"),
+            ProgressMessage::Success { .. } => Some("success"),
+            ProgressMessage::Error { .. } => Some("error"),
+            ProgressMessage::Skipped { .. } => Some("skipped"),
+            ProgressMessage::Verified { .. } => Some("verified"),
+            ProgressMessage::Adapted { .. } => Some("adapted"),
+            ProgressMessage::Completed => Some("completed"),
+            ProgressMessage::Stopped { .. } => Some("stopped"),
+            ProgressMessage::Cancelled => Some("cancelled"),
+            ProgressMessage::FrameProgress { .. } | ProgressMessage::Info { .. } | ProgressMessage::Warning { .. } => None,
+        })
+        .collect()
+}
+
+/// Output paths of every successful file, in order.
+pub fn outputs(messages: &[ProgressMessage]) -> Vec<PathBuf> {
+    messages
+        .iter()
+        .filter_map(|msg| match msg {
+            ProgressMessage::Success { output, .. } => Some(output.clone()),
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,211 @@
+mod support;
+
+use jxl_converter::tools::{version_at_least, DiscoveredTools, ToolCapabilities, ToolInfo};
+use jxl_converter::{ConversionEngine, ConversionSettings, DecodeItem, DecodeSettings, OutputFormat, ToolSettings};
+
+use support::{encode_settings, fake_engine, fake_tool, kinds, run_decode, run_encode, touch, touch_jxl};
+
+#[test]
+fn probe_reads_the_version() {
+    let dir = tempfile::tempdir().unwrap();
+    let cjxl = fake_tool(dir.path(), "cjxl");
+
+    let info = ToolInfo::probe(cjxl.clone(), &ToolSettings::default());
+    assert_eq!(info.version.as_deref(), Some("0.0.0"));
+    assert_eq!(info.describe(), format!("v0.0.0 ({})", cjxl.display()));
+
+    let missing = ToolInfo::probe(dir.path().join("no-such-tool"), &ToolSettings::default());
+    assert_eq!(missing.version, None);
+}
+
+#[test]
+fn probes_use_the_environment_overrides() {
+    let dir = tempfile::tempdir().unwrap();
+    let settings = ToolSettings {
+        env_overrides: vec![("FAKE_TOOL_VERSION".to_string(), "0.11.1".to_string())],
+        ..Default::default()
+    };
+
+    let info = ToolInfo::probe(fake_tool(dir.path(), "cjxl"), &settings);
+    assert_eq!(info.version.as_deref(), Some("0.11.1"));
+
+    let engine = ConversionEngine::new()
+        .with_tool_settings(settings)
+        .with_tool_paths(Some(fake_tool(dir.path(), "cjxl")), None);
+    assert_eq!(engine.cjxl_version(), Some("0.11.1"));
+}
+
+#[test]
+fn engine_uses_the_discovered_tools() {
+    let dir = tempfile::tempdir().unwrap();
+    let tools = DiscoveredTools {
+        cjxl: Some(ToolInfo::probe(fake_tool(dir.path(), "cjxl"), &ToolSettings::default())),
+        ..Default::default()
+    };
+
+    let engine = ConversionEngine::from_tools(&tools);
+    assert!(engine.is_available());
+    assert!(!engine.is_decode_available());
+    assert!(engine.get_decode_error().is_some());
+}
+
+#[test]
+fn extra_locations_are_candidates_and_can_be_chosen() {
+    let dir = tempfile::tempdir().unwrap();
+    let stable = dir.path().join("stable");
+    let nightly = dir.path().join("nightly");
+    std::fs::create_dir_all(&stable).unwrap();
+    std::fs::create_dir_all(&nightly).unwrap();
+    let stable_cjxl = fake_tool(&stable, "cjxl");
+    let nightly_cjxl = fake_tool(&nightly, "cjxl");
+
+    let mut settings = ToolSettings {
+        // Listed twice: still one candidate
+        extra_tool_paths: vec![stable_cjxl.clone(), nightly_cjxl.clone(), stable_cjxl.clone()],
+        cjxl_path: Some(nightly_cjxl.clone()),
+        ..Default::default()
+    };
+    let mut tools = DiscoveredTools::discover_with(&settings);
+
+    let ours: Vec<_> = tools.cjxl_candidates.iter().filter(|t| t.path.starts_with(dir.path())).collect();
+    assert_eq!(ours.len(), 2);
+    assert!(ours.iter().all(|t| t.version.as_deref() == Some("0.0.0")));
+    assert_eq!(tools.cjxl.as_ref().unwrap().path, nightly_cjxl);
+
+    settings.cjxl_path = Some(stable_cjxl.clone());
+    tools.select(&settings);
+    assert_eq!(tools.cjxl.as_ref().unwrap().path, stable_cjxl);
+}
+
+#[test]
+fn a_missing_choice_falls_back_to_the_first_candidate() {
+    let dir = tempfile::tempdir().unwrap();
+    let settings = ToolSettings {
+        extra_tool_paths: vec![fake_tool(dir.path(), "cjxl")],
+        cjxl_path: Some(dir.path().join("gone/cjxl")),
+        ..Default::default()
+    };
+    let tools = DiscoveredTools::discover_with(&settings);
+
+    assert_eq!(tools.cjxl.as_ref(), tools.cjxl_candidates.first());
+    assert!(tools.cjxl.is_some());
+}
+
+#[test]
+fn capabilities_of_libjxl_0_8() {
+    let cjxl = ToolCapabilities::from_cjxl_help(include_str!("data/cjxl-0.8.2-help.txt"), Some("0.8.2"));
+    assert!(!cjxl.supports_effort_10);
+    // 0.8 only has the older --photon_noise spelling
+    assert!(!cjxl.supports_photon_noise);
+    assert!(cjxl.supports_faster_decoding);
+
+    let djxl = ToolCapabilities::from_djxl_help(include_str!("data/djxl-0.8.2-help.txt"));
+    assert!(!djxl.djxl_supports_exr);
+    assert!(djxl.djxl_supports_partial_files);
+}
+
+#[test]
+fn capabilities_of_libjxl_0_10() {
+    let cjxl = ToolCapabilities::from_cjxl_help(include_str!("data/cjxl-0.10.2-help.txt"), Some("0.10.2"));
+    assert!(cjxl.supports_effort_10);
+    assert!(cjxl.supports_photon_noise);
+    assert!(cjxl.supports_faster_decoding);
+    // Input formats in cjxl's help say nothing about djxl
+    assert!(!cjxl.djxl_supports_exr);
+
+    let djxl = ToolCapabilities::from_djxl_help(include_str!("data/djxl-0.10.2-help.txt"));
+    assert!(djxl.djxl_supports_exr);
+
+    let combined = ToolCapabilities::combine(&cjxl, &djxl);
+    assert!(combined.supports_effort_10 && combined.djxl_supports_exr);
+}
+
+#[test]
+fn effort_10_falls_back_to_the_version_without_help() {
+    assert!(ToolCapabilities::from_cjxl_help("", Some("0.11.0")).supports_effort_10);
+    assert!(!ToolCapabilities::from_cjxl_help("", Some("0.9.1")).supports_effort_10);
+    assert!(!ToolCapabilities::from_cjxl_help("", None).supports_effort_10);
+    assert!(version_at_least("1.0.0", (0, 10)));
+}
+
+#[test]
+fn unsupported_effort_is_refused_before_starting() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = support::fake_engine(dir.path(), &[]);
+    touch(dir.path(), "in/a.png");
+
+    let settings = ConversionSettings { effort: 10, ..encode_settings(&dir.path().join("out")) };
+    let error = engine.validate_settings(&settings).unwrap_err();
+    assert_eq!(error, "Effort 10 needs cjxl 0.10 or newer (found 0.0.0)");
+
+    let messages = run_encode(&engine, vec![dir.path().join("in")], settings);
+    assert_eq!(kinds(&messages), ["error"]);
+}
+
+#[test]
+fn photon_noise_needs_a_cjxl_that_has_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let settings = ConversionSettings {
+        extra_args: vec!["--photon_noise_iso=800".to_string()],
+        ..encode_settings(&dir.path().join("out"))
+    };
+
+    let error = fake_engine(dir.path(), &[]).validate_settings(&settings).unwrap_err();
+    assert_eq!(error, "--photon_noise_iso isn't supported by this cjxl (found 0.0.0)");
+
+    let engine = fake_engine(dir.path(), &[("FAKE_TOOL_HELP", "  --photon_noise_iso=ISO_FILM_SPEED")]);
+    assert!(engine.capabilities().supports_photon_noise);
+    assert!(engine.validate_settings(&settings).is_ok());
+}
+
+#[test]
+fn faster_decoding_needs_a_cjxl_that_has_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let settings = ConversionSettings {
+        extra_args: vec!["--faster_decoding".to_string(), "2".to_string()],
+        ..encode_settings(&dir.path().join("out"))
+    };
+
+    let error = fake_engine(dir.path(), &[]).validate_settings(&settings).unwrap_err();
+    assert_eq!(error, "--faster_decoding isn't supported by this cjxl (found 0.0.0)");
+
+    let engine = fake_engine(dir.path(), &[("FAKE_TOOL_HELP", "  --faster_decoding=0|1|2|3|4")]);
+    assert!(engine.validate_settings(&settings).is_ok());
+}
+
+#[test]
+fn exr_output_needs_a_djxl_with_openexr() {
+    let dir = tempfile::tempdir().unwrap();
+    let jxl = touch_jxl(dir.path(), "in/a.jxl");
+    let items = vec![DecodeItem { path: jxl, output_formats: vec![OutputFormat::Exr], folder: None }];
+    let settings = DecodeSettings { output_dir: dir.path().join("out"), ..Default::default() };
+
+    let engine = fake_engine(dir.path(), &[]);
+    let error = engine.validate_decode(&items, &settings).unwrap_err();
+    assert_eq!(error, "EXR output needs a djxl built with OpenEXR (found 0.0.0)");
+    assert_eq!(kinds(&run_decode(&engine, items.clone(), settings.clone())), ["error"]);
+
+    let engine = fake_engine(dir.path(), &[("FAKE_TOOL_HELP", "  EXR via OpenEXR")]);
+    assert!(engine.validate_decode(&items, &settings).is_ok());
+    let messages = run_decode(&engine, items, settings);
+    assert_eq!(kinds(&messages), ["started", "progress", "success", "completed"]);
+    assert!(dir.path().join("out/a.exr").is_file());
+}
+
+#[test]
+fn partial_files_need_a_djxl_that_allows_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let jxl = touch_jxl(dir.path(), "in/a.jxl");
+    let items = vec![DecodeItem { path: jxl, output_formats: Vec::new(), folder: None }];
+    let settings = DecodeSettings { output_dir: dir.path().join("out"), allow_partial_files: true, ..Default::default() };
+
+    let engine = fake_engine(dir.path(), &[]);
+    let error = engine.validate_decode(&items, &settings).unwrap_err();
+    assert_eq!(error, "Decoding partial files needs a djxl with --allow_partial_files (found 0.0.0)");
+    assert!(engine.validate_decode(&items, &DecodeSettings::default()).is_ok());
+
+    let engine = fake_engine(dir.path(), &[("FAKE_TOOL_HELP", "  --allow_partial_files")]);
+    assert!(engine.validate_decode(&items, &settings).is_ok());
+    let args = engine.djxl_args("a.jxl".as_ref(), "a.png".as_ref(), &settings);
+    assert!(args.iter().any(|arg| arg == "--allow_partial_files"), "{:?}", args);
+}
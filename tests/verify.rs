@@ -0,0 +1,178 @@
+mod support;
+
+use std::path::Path;
+
+use jxl_converter::verify::{check_jpeg_structure, find_original, has_jpeg_reconstruction, sha256_file, Verification};
+use jxl_converter::{DecodeItem, DecodeSettings, OutputFormat, ProgressMessage};
+
+use support::{fake_engine, kinds, run_decode, JXL_HEADER};
+
+/// A tiny but structurally complete baseline JPEG: SOI, DQT, SOF0, DHT, SOS,
+/// entropy data with a stuffed 0xFF and a restart marker, EOI.
+fn minimal_jpeg() -> Vec<u8> {
+    let mut jpeg = vec![0xFF, 0xD8];
+    jpeg.extend([0xFF, 0xDB, 0x00, 0x04, 0x00, 0x01]);
+    jpeg.extend([0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x01, 0x00, 0x01, 0x01, 0x01, 0x11, 0x00]);
+    jpeg.extend([0xFF, 0xC4, 0x00, 0x03, 0x00]);
+    jpeg.extend([0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00]);
+    jpeg.extend([0x12, 0xFF, 0x00, 0x34, 0xFF, 0xD0, 0x56]);
+    jpeg.extend([0xFF, 0xD9]);
+    jpeg
+}
+
+/// A JXL container with the given boxes after the signature. Every box holds
+/// a codestream header, which is only meaningful in `jxlc`.
+fn container(boxes: &[&[u8; 4]]) -> Vec<u8> {
+    let mut data = vec![0, 0, 0, 0x0C, b'J', b'X', b'L', b' ', 0x0D, 0x0A, 0x87, 0x0A];
+    data.extend([0, 0, 0, 0x14, b'f', b't', b'y', b'p', b'j', b'x', b'l', b' ', 0, 0, 0, 0, b'j', b'x', b'l', b' ']);
+    for kind in boxes {
+        data.extend([0, 0, 0, 0x0C]);
+        data.extend(kind.iter());
+        data.extend(JXL_HEADER);
+    }
+    data
+}
+
+fn write(path: &Path, data: &[u8]) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, data).unwrap();
+}
+
+#[test]
+fn jpeg_structure() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.jpg");
+    let jpeg = minimal_jpeg();
+
+    write(&path, &jpeg);
+    assert_eq!(check_jpeg_structure(&path), Ok(()));
+
+    write(&path, &jpeg[..jpeg.len() - 2]);
+    assert!(check_jpeg_structure(&path).is_err());
+
+    // Scan data without a frame header
+    let mut no_frame = jpeg.clone();
+    no_frame.drain(8..21);
+    write(&path, &no_frame);
+    assert!(check_jpeg_structure(&path).is_err());
+
+    write(&path, b"\x89PNG\r\n\x1a\n");
+    assert!(check_jpeg_structure(&path).is_err());
+}
+
+#[test]
+fn reconstruction_box_is_found() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.jxl");
+
+    write(&path, &container(&[b"jxlp", b"jbrd", b"jxlp"]));
+    assert!(has_jpeg_reconstruction(&path).unwrap());
+
+    write(&path, &container(&[b"Exif", b"jxlc"]));
+    assert!(!has_jpeg_reconstruction(&path).unwrap());
+
+    // Bare codestream
+    write(&path, &[0xFF, 0x0A, 1, 2, 3]);
+    assert!(!has_jpeg_reconstruction(&path).unwrap());
+}
+
+#[test]
+fn sha256_of_a_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("abc");
+    write(&path, b"abc");
+    assert_eq!(
+        sha256_file(&path).unwrap(),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+#[test]
+fn originals_are_found_under_the_root_or_next_to_the_jxl() {
+    let dir = tempfile::tempdir().unwrap();
+    let folder = dir.path().join("jxl");
+    let jxl = folder.join("2024/a.jxl");
+    let root = dir.path().join("originals");
+    write(&jxl, b"");
+
+    assert_eq!(find_original(&jxl, Some(&folder), Some(&root)), None);
+
+    write(&folder.join("2024/a.JPG"), b"");
+    assert_eq!(find_original(&jxl, Some(&folder), Some(&root)), Some(folder.join("2024/a.JPG")));
+
+    write(&root.join("2024/a.jpeg"), b"");
+    assert_eq!(find_original(&jxl, Some(&folder), Some(&root)), Some(root.join("2024/a.jpeg")));
+
+    // A loose file is looked up by name
+    write(&root.join("a.jpg"), b"");
+    assert_eq!(find_original(&jxl, None, Some(&root)), Some(root.join("a.jpg")));
+}
+
+fn verifications(messages: &[ProgressMessage]) -> Vec<Verification> {
+    messages
+        .iter()
+        .filter_map(|msg| match msg {
+            ProgressMessage::Verified { verification, .. } => Some(verification.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn decode_batch_compares_reconstructions_with_originals() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in");
+    let data = container(&[b"jbrd", b"jxlc"]);
+    // The fake djxl copies its input, so "original" JPEGs with the same bytes match
+    write(&input.join("same.jxl"), &data);
+    write(&input.join("same.jpg"), &data);
+    write(&input.join("changed.jxl"), &data);
+    write(&input.join("changed.jpg"), b"something else");
+    write(&input.join("alone.jxl"), &data);
+    write(&input.join("pixels.jxl"), &container(&[b"jxlc"]));
+    write(&input.join("pixels.jpg"), b"not compared");
+
+    let engine = fake_engine(dir.path(), &[]);
+    let items = ["same", "changed", "alone", "pixels"]
+        .iter()
+        .map(|name| DecodeItem {
+            path: input.join(format!("{}.jxl", name)),
+            output_formats: vec![OutputFormat::Jpeg],
+            folder: None,
+        })
+        .collect();
+    let settings = DecodeSettings {
+        output_dir: dir.path().join("out"),
+        verify_reconstruction: true,
+        ..Default::default()
+    };
+    let messages = run_decode(&engine, items, settings);
+
+    assert_eq!(
+        kinds(&messages),
+        [
+            "started", "progress", "success", "verified", "progress", "success", "verified", "progress", "success",
+            "verified", "progress", "success", "completed"
+        ]
+    );
+    let results = verifications(&messages);
+    assert!(matches!(&results[0], Verification::Match { original, .. } if original.ends_with("same.jpg")));
+    assert!(matches!(&results[1], Verification::Mismatch { output_sha256, original_sha256, .. } if output_sha256 != original_sha256));
+    // The output is a JXL container, which doesn't parse as a JPEG
+    assert!(matches!(&results[2], Verification::Unverified { error: Some(_) }));
+    assert!(results[1].is_problem() && results[2].is_problem() && !results[0].is_problem());
+}
+
+#[test]
+fn verification_is_off_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let jxl = dir.path().join("in/a.jxl");
+    write(&jxl, &container(&[b"jbrd"]));
+
+    let engine = fake_engine(dir.path(), &[]);
+    let item = DecodeItem { path: jxl, output_formats: vec![OutputFormat::Jpeg], folder: None };
+    let settings = DecodeSettings { output_dir: dir.path().join("out"), ..Default::default() };
+    let messages = run_decode(&engine, vec![item], settings);
+
+    assert!(verifications(&messages).is_empty());
+}